@@ -8,8 +8,11 @@ include!(concat!(env!("OUT_DIR"), "/nican.rs"));
 
 use isotp_rs::can::CAN_FRAME_MAX_SIZE;
 use isotp_rs::can::{frame::{Direct, Frame}, identifier::Id};
+use crate::constant::*;
 use crate::frame::CanMessage;
+use rs_can::TimeBase;
 use rs_can::error::CanError;
+use rs_can::error_frame::{BusState, ErrorCounters, ErrorEvent, ErrorKind, ProtocolError};
 
 impl Into<NCTYPE_CAN_FRAME> for CanMessage {
     fn into(self) -> NCTYPE_CAN_FRAME {
@@ -44,6 +47,15 @@ impl TryInto<CanMessage> for NCTYPE_CAN_STRUCT {
         let is_extended = (arb_id & NC_FL_CAN_ARBID_XTD) > 0;
         let dlc = self.DataLength;
         let timestamp = (self.Timestamp.HighPart as u64) << 32 | (self.Timestamp.LowPart as u64);
+        // FILETIME is 100ns since 1601-01-01 on the wall-clock
+        let timestamp = timestamp.saturating_sub(116_444_736_000_000_000) * 100;
+
+        if is_error_frame {
+            let event = comm_error_event(arb_id as i32, &self.Data, timestamp);
+            let mut msg = CanMessage::from(&event);
+            msg.set_time_base(TimeBase::WallClock);
+            return Ok(msg);
+        }
 
         let mut msg = if is_remote_frame {
             CanMessage::new_remote(Id::from_bits(arb_id, is_extended), dlc as usize)
@@ -53,11 +65,49 @@ impl TryInto<CanMessage> for NCTYPE_CAN_STRUCT {
             .ok_or(CanError::FrameConvertFailed(format!("length of data is rather than {}", CAN_FRAME_MAX_SIZE)))?;
 
         msg.set_direct(Direct::Receive)
-            .set_timestamp(Some(
-                (1000. * (timestamp as f64 / 10000000. - 11644473600.)) as u64,
-            ))
-            .set_error_frame(is_error_frame);
+            .set_timestamp(Some(timestamp))
+            .set_time_base(TimeBase::WallClock);
 
         Ok(msg)
     }
 }
+
+/// Convert the `NC_FRMTYPE_COMM_ERR` frame, the `ArbitrationId` is the communication status,
+/// and the `Data[0]` is the RX error counter, the `Data[1]` is the TX error counter.
+///
+/// The `CanErrComm*` means bus-off and the `CanWarnComm*` means error passive.
+pub(crate) fn comm_error_event(status: i32, data: &[u8], timestamp: u64) -> ErrorEvent<String> {
+    let mut event = ErrorEvent::new(Default::default(), timestamp);
+    let error = match status {
+        CanWarnCommStuff | CanErrCommStuff => Some(ProtocolError::Stuff),
+        CanWarnCommFormat | CanErrCommFormat => Some(ProtocolError::Form),
+        CanWarnCommNoAck | CanErrCommNoAck => Some(ProtocolError::Ack),
+        // sent recessive but monitored dominant
+        CanWarnCommTx1Rx0 | CanErrCommTx1Rx0 => Some(ProtocolError::Bit1),
+        CanWarnCommTx0Rx1 | CanErrCommTx0Rx1 => Some(ProtocolError::Bit0),
+        CanWarnCommBadCRC | CanErrCommBadCRC => Some(ProtocolError::Crc),
+        CanWarnComm | CanErrComm | CanWarnCommUnknown | CanErrCommUnknown => Some(ProtocolError::Other),
+        _ => None,
+    };
+    if let Some(error) = error {
+        let transmitting = matches!(error, ProtocolError::Ack | ProtocolError::Bit0 | ProtocolError::Bit1);
+        event.add(ErrorKind::Protocol { error, transmitting });
+    }
+    match status {
+        CanErrOverflowCard | CanErrOverflowChip | CanErrOverflowRxQueue => {
+            event.add(ErrorKind::Overrun { transmitting: false });
+        },
+        CanErrOverflowWrite => {
+            event.add(ErrorKind::Overrun { transmitting: true });
+        },
+        _ => {},
+    }
+
+    if error.is_some() {
+        let state = if status & NICAN_ERROR_BASE == NICAN_ERROR_BASE { BusState::BusOff } else { BusState::ErrorPassive };
+        event.add(ErrorKind::State(state));
+    }
+    event.set_counters(Some(ErrorCounters::new(data[1], data[0])));
+
+    event
+}
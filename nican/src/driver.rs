@@ -2,8 +2,9 @@ use std::collections::HashMap;
 use std::ffi::{c_char, c_void, CStr, CString};
 use isotp_rs::can::frame::Frame;
 use isotp_rs::device::Driver;
-use rs_can::CanFilter;
+use rs_can::{CanDevice, CanFilter, Capability, ChannelConfig};
 use rs_can::error::CanError;
+use rs_can::filter;
 use crate::api::*;
 use crate::constant;
 use crate::frame::CanMessage;
@@ -16,6 +17,10 @@ struct NiCanContext {
     log_errors: bool,
 }
 
+/// The async NI-CAN driver, the blocking calls run on dedicated threads.
+#[cfg(feature = "async")]
+pub type AsyncNiCan = rs_can::asyncio::AsyncCan<NiCan>;
+
 #[derive(Debug, Clone)]
 pub struct NiCan {
     channels: HashMap<String, NiCanContext>
@@ -37,25 +42,22 @@ impl NiCan {
         let mut attr_id = vec![NC_ATTR_START_ON_OPEN, NC_ATTR_LOG_COMM_ERRS];
         let mut attr_val = vec![1, if log_errors { 1 } else { 0 }];
 
-        match filters.len() {
-            0 => {
-                attr_id.extend([
-                    NC_ATTR_CAN_COMP_STD,
-                    NC_ATTR_CAN_MASK_STD,
-                    NC_ATTR_CAN_COMP_XTD,
-                    NC_ATTR_CAN_MASK_XTD,
-                ]);
-                attr_val.extend([0; 4])
-            }
-            _ => filters.iter().for_each(|f| {
-                attr_id.extend([NC_ATTR_CAN_COMP_XTD, NC_ATTR_CAN_MASK_XTD]);
-                if f.extended {
-                    attr_val.extend([f.can_id | NC_FL_CAN_ARBID_XTD, f.can_mask]);
-                } else {
-                    attr_val.extend([f.can_id, f.can_mask]);
-                }
-            }),
-        }
+        // only one comparator for each identifier type, the filters are merged and
+        // the frames are matched exactly in software when receiving.
+        let std = CanFilter::merge_all(filters.iter().filter(|f| !f.extended));
+        let xtd = CanFilter::merge_all(filters.iter().filter(|f| f.extended));
+        attr_id.extend([
+            NC_ATTR_CAN_COMP_STD,
+            NC_ATTR_CAN_MASK_STD,
+            NC_ATTR_CAN_COMP_XTD,
+            NC_ATTR_CAN_MASK_XTD,
+        ]);
+        attr_val.extend([
+            std.map_or(0, |f| f.can_id),
+            std.map_or(0, |f| f.can_mask),
+            xtd.map_or(0, |f| f.can_id | NC_FL_CAN_ARBID_XTD),
+            xtd.map_or(0, |f| f.can_mask),
+        ]);
 
         attr_id.push(NC_ATTR_BAUD_RATE);
         attr_val.push(bitrate);
@@ -132,6 +134,9 @@ impl NiCan {
 
     pub fn transmit_can(&self, msg: CanMessage) -> Result<(), CanError> {
         let channel = msg.channel();
+        if msg.is_can_fd() {
+            return Err(CanError::FrameConvertFailed("CAN-FD frame is not supported".into()));
+        }
         match self.channels.get(&channel) {
             Some(ctx) => {
                 let raw_msg = msg.into();
@@ -197,6 +202,9 @@ impl NiCan {
                 }
 
                 let mut msg = <NCTYPE_CAN_STRUCT as TryInto<CanMessage>>::try_into(raw_msg)?;
+                if !filter::is_matched(&ctx.filters, &msg) {
+                    return Ok(vec![]);
+                }
                 msg.set_channel(channel.clone());
 
                 Ok(vec![msg, ])
@@ -311,6 +319,56 @@ impl Driver for NiCan {
     }
 }
 
+impl CanDevice for NiCan {
+    #[inline]
+    fn open_device(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn init_channel(&mut self, channel: Self::C, cfg: &ChannelConfig) -> Result<(), Self::Error> {
+        if cfg.dbitrate.is_some() {
+            return Err(CanError::ChannelInitializeError(Self::channel_info(channel.as_str())));
+        }
+        if cfg.listen_only {
+            return Err(CanError::OperationError(
+                format!("{} listen only is not supported", Self::channel_info(channel.as_str()))
+            ));
+        }
+
+        let log_errors = self.is_log_errors(channel.clone()).unwrap_or(true);
+        if self.channels.contains_key(&channel) {
+            self.close(channel.clone())?;
+        }
+
+        self.open(channel.as_str(), cfg.filters.clone(), cfg.bitrate, log_errors)
+    }
+
+    #[inline]
+    fn reset_channel(&mut self, channel: Self::C) -> Result<(), Self::Error> {
+        self.reset(channel)
+    }
+
+    #[inline]
+    fn close_device(&mut self) -> Result<(), Self::Error> {
+        self.shutdown();
+        Ok(())
+    }
+
+    fn capability(&self) -> Result<Capability, Self::Error> {
+        Ok(Capability {
+            // the interfaces are configured by NI-MAX, not queried
+            channels: None,
+            canfd: false,
+            resistance: false,
+            hardware_filter: true,
+            // one comparator for each identifier type
+            filter_count: Some(2),
+            auto_send: false,
+            listen_only: false,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::NiCan;
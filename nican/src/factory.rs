@@ -0,0 +1,25 @@
+//! Register `nican://CAN0?bitrate=500000` to the factory of `rs-can`.
+
+use rs_can::CanDevice;
+use rs_can::error::CanError;
+use rs_can::factory::{self, AnyBus, BusUrl};
+use crate::driver::NiCan;
+
+pub const SCHEME: &str = "nican";
+
+/// Register the NI-CAN backend to factory.
+#[inline]
+pub fn register() {
+    factory::register(SCHEME, open);
+}
+
+/// Open the channels by descriptor, the channel is the interface name such as `CAN0`.
+pub fn open(url: &BusUrl) -> Result<AnyBus, CanError> {
+    let mut device = NiCan::new();
+    for channel in url.channels(url.host.as_str()) {
+        let cfg = url.channel_config_of(&channel)?;
+        device.init_channel(channel, &cfg)?;
+    }
+
+    Ok(Box::new(device))
+}
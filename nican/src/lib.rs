@@ -4,5 +4,7 @@ mod constant;
 mod driver;
 pub use driver::*;
 
+pub mod factory;
+
 mod frame;
 pub use frame::*;
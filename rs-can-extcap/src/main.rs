@@ -0,0 +1,304 @@
+//! **`rs-can-extcap`**, the Wireshark extcap of rs-can, the interfaces are the bus profiles of configuration file.
+//!
+//! Copy the binary into the personal extcap folder of Wireshark(`Help > About Wireshark > Folders`),
+//! the configuration file is located as [`Config::locate`].
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+use rs_can::config::{BusProfile, Config};
+use rs_can::error::CanError;
+use rs_can::io::pcap::{PcapFormat, PcapWriter, LINKTYPE_CAN_SOCKETCAN};
+use rs_can::utils::system_timestamp;
+
+/// The prefix of interface names, such as `rs-can-bench` of profile `bench`.
+const INTERFACE_PREFIX: &str = "rs-can-";
+/// The receive timeout(ms) of each channel.
+const RECEIVE_TIMEOUT: u32 = 10;
+/// The delay after a receiving error, the capture is stopped when all channels failed `MAX_ERRORS` times in a row.
+const ERROR_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_ERRORS: usize = 50;
+/// The FIFO is probed by a statistics block at this interval when the bus is idle.
+const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+const DEFAULT_BITRATE: u32 = 500_000;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Action {
+    Interfaces,
+    Dlts,
+    Config,
+    Capture,
+}
+
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+struct Args {
+    action: Option<Action>,
+    interface: Option<String>,
+    fifo: Option<String>,
+    bitrate: Option<u32>,
+    /// `0` is classic CAN.
+    dbitrate: Option<u32>,
+    listen_only: bool,
+}
+
+impl Args {
+    /// Parse the arguments of `--key value` or `--key=value`, the unknown arguments are ignored.
+    fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, CanError> {
+        let mut result = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (key, inline) = match arg.split_once('=') {
+                Some((k, v)) => (k.to_owned(), Some(v.to_owned())),
+                None => (arg, None),
+            };
+            let mut value = |key: &str| inline.clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| CanError::OtherError(format!("the value of `{}` is required", key)));
+
+            match key.as_str() {
+                "--extcap-interfaces" => result.action = Some(Action::Interfaces),
+                "--extcap-dlts" => result.action = Some(Action::Dlts),
+                "--extcap-config" => result.action = Some(Action::Config),
+                "--capture" => result.action = Some(Action::Capture),
+                "--extcap-interface" => result.interface = Some(value(&key)?),
+                "--fifo" => result.fifo = Some(value(&key)?),
+                "--bitrate" => result.bitrate = Some(parse_number(&key, &value(&key)?)?),
+                "--dbitrate" => result.dbitrate = Some(parse_number(&key, &value(&key)?)?),
+                "--listen-only" => result.listen_only = inline.as_deref().is_none_or(|v| v != "false"),
+                // such as `--extcap-capture-filter` and the control pipes
+                "--extcap-capture-filter" | "--extcap-control-in" | "--extcap-control-out" => { value(&key)?; },
+                _ => {},
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// Register the backends of the driver crates, which are not built in rs-can.
+fn register_backends() {
+    #[cfg(feature = "zlgcan")]
+    zlgcan::factory::register();
+    #[cfg(all(windows, feature = "nican"))]
+    nican::factory::register();
+}
+
+fn main() -> ExitCode {
+    register_backends();
+
+    match run(std::env::args().skip(1)) {
+        Ok(output) => {
+            print!("{}", output);
+            ExitCode::SUCCESS
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        },
+    }
+}
+
+fn run<I: IntoIterator<Item = String>>(args: I) -> Result<String, CanError> {
+    let args = Args::parse(args)?;
+    let config = Config::load;
+    match args.action {
+        Some(Action::Interfaces) => Ok(interfaces(&config().unwrap_or_default())),
+        Some(Action::Dlts) => {
+            profile(&config()?, &args)?;
+            Ok(format!("dlt {{number={}}}{{name=CAN_SOCKETCAN}}{{display=SocketCAN}}\n", LINKTYPE_CAN_SOCKETCAN))
+        },
+        Some(Action::Config) => Ok(options(profile(&config()?, &args)?)),
+        Some(Action::Capture) => {
+            capture(profile(&config()?, &args)?, &args)?;
+            Ok(Default::default())
+        },
+        None => Err(CanError::OtherError("one of `--extcap-interfaces`, `--extcap-dlts`, \
+            `--extcap-config` and `--capture` is required".into())),
+    }
+}
+
+/// The interfaces of all profiles, which are sorted by name.
+fn interfaces(config: &Config) -> String {
+    let mut result = format!("extcap {{version={}}}{{help={}}}\n", env!("CARGO_PKG_VERSION"), env!("CARGO_PKG_HOMEPAGE"));
+    let mut profiles = config.profiles.iter().collect::<Vec<_>>();
+    profiles.sort_by_key(|(name, _)| *name);
+    for (name, profile) in profiles {
+        result.push_str(&format!("interface {{value={}{}}}{{display=rs-can {} ({})}}\n",
+                                 INTERFACE_PREFIX, name, name, profile.to_url()));
+    }
+    result
+}
+
+/// The options of interface, the defaults are the configuration of first channel.
+fn options(profile: &BusProfile) -> String {
+    let first = profile.channels.first();
+    let bitrate = first.map(|c| c.bitrate).unwrap_or(DEFAULT_BITRATE);
+    let dbitrate = first.and_then(|c| c.dbitrate).unwrap_or_default();
+    let listen_only = first.is_some_and(|c| c.listen_only);
+
+    [
+        format!("arg {{number=0}}{{call=--bitrate}}{{display=Bitrate}}{{type=integer}}{{range=5000,1000000}}\
+            {{default={}}}{{tooltip=The nominal bitrate of all channels}}", bitrate),
+        format!("arg {{number=1}}{{call=--dbitrate}}{{display=FD data bitrate}}{{type=integer}}{{range=0,10000000}}\
+            {{default={}}}{{tooltip=The data bitrate of CAN-FD, 0 is classic CAN}}", dbitrate),
+        format!("arg {{number=2}}{{call=--listen-only}}{{display=Listen only}}{{type=boolflag}}\
+            {{default={}}}{{tooltip=No acknowledge and frame is transmitted}}", listen_only),
+    ].join("\n") + "\n"
+}
+
+fn profile<'a>(config: &'a Config, args: &Args) -> Result<&'a BusProfile, CanError> {
+    let interface = args.interface.as_deref()
+        .ok_or(CanError::OtherError("`--extcap-interface` is required".into()))?;
+    let name = interface.strip_prefix(INTERFACE_PREFIX)
+        .ok_or_else(|| CanError::OtherError(format!("unknown interface: `{}`", interface)))?;
+    config.profile(Some(name))
+}
+
+/// Stream the frames of all channels into FIFO until it is closed by Wireshark.
+fn capture(profile: &BusProfile, args: &Args) -> Result<(), CanError> {
+    let fifo = args.fifo.as_deref()
+        .ok_or(CanError::OtherError("`--fifo` is required".into()))?;
+    let mut profile = profile.clone();
+    for channel in profile.channels.iter_mut() {
+        if let Some(bitrate) = args.bitrate {
+            channel.bitrate = bitrate;
+        }
+        if let Some(dbitrate) = args.dbitrate {
+            channel.dbitrate = (dbitrate > 0).then_some(dbitrate);
+        }
+        channel.listen_only = args.listen_only;
+    }
+
+    let file = File::create(fifo)
+        .map_err(|e| CanError::OtherError(format!("Unable to open `{}`: {}", fifo, e)))?;
+    let mut writer = PcapWriter::new(BufWriter::new(file), PcapFormat::Pcapng)?;
+    writer.flush()?;
+
+    let mut bus = profile.open()?;
+    let channels = bus.opened_channels();
+    for channel in &channels {
+        writer.add_interface(channel)?;
+    }
+    writer.flush()?;
+
+    let mut errors = 0;
+    let mut probed = Instant::now();
+    let result = 'capture: loop {
+        let mut count = 0;
+        let mut failed = Vec::new();
+        for channel in &channels {
+            match bus.receive(channel.clone(), Some(RECEIVE_TIMEOUT)) {
+                Ok(frames) => for frame in frames {
+                    if writer.write(&frame).is_err() {
+                        break 'capture Ok(());
+                    }
+                    count += 1;
+                },
+                Err(CanError::TimeoutError(_)) => {},
+                Err(e) => {
+                    log::warn!("RUST-CAN - receive from channel: {} failed: {}", channel, e);
+                    failed.push(e);
+                },
+            }
+        }
+        if !failed.is_empty() {
+            errors = if failed.len() == channels.len() { errors + 1 } else { 0 };
+            if errors >= MAX_ERRORS {
+                break failed.pop().map_or(Ok(()), Err);
+            }
+            std::thread::sleep(ERROR_BACKOFF);
+        }
+        else {
+            errors = 0;
+        }
+
+        // the FIFO is closed when the capture is stopped, it's probed when nothing is written
+        if count == 0 && probed.elapsed() >= PROBE_INTERVAL {
+            probed = Instant::now();
+            if writer.write_statistics(system_timestamp()).is_err() {
+                break Ok(());
+            }
+        }
+        if writer.flush().is_err() {
+            break Ok(());
+        }
+    };
+    bus.shutdown();
+
+    result
+}
+
+#[inline]
+fn parse_number(key: &str, value: &str) -> Result<u32, CanError> {
+    value.parse()
+        .map_err(|_| CanError::OtherError(format!("invalid parameter `{}={}`", key, value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use rs_can::config::Config;
+    use super::{interfaces, options, profile, run, Action, Args};
+
+    const CONFIG: &str = r#"
+default = "bench"
+
+[profiles.bench]
+backend = "zlgcan"
+device = "USBCANFD_200U"
+
+[[profiles.bench.channels]]
+channel = "0"
+bitrate = 500000
+dbitrate = 2000000
+
+[profiles.sim]
+backend = "virtual"
+device = "extcap"
+"#;
+
+    fn args<'a>(values: &'a [&'a str]) -> impl Iterator<Item = String> + 'a {
+        values.iter().map(|v| v.to_string())
+    }
+
+    #[test]
+    fn test_args() -> anyhow::Result<()> {
+        let result = Args::parse(args(&["--capture", "--extcap-interface=rs-can-bench", "--fifo", "/tmp/fifo",
+            "--bitrate", "250000", "--dbitrate=0", "--listen-only", "--extcap-capture-filter", "can.id == 1"]))?;
+        assert_eq!(result, Args {
+            action: Some(Action::Capture),
+            interface: Some("rs-can-bench".into()),
+            fifo: Some("/tmp/fifo".into()),
+            bitrate: Some(250_000),
+            dbitrate: Some(0),
+            listen_only: true,
+        });
+        assert!(Args::parse(args(&["--bitrate", "fast"])).is_err());
+        assert!(Args::parse(args(&["--fifo"])).is_err());
+        assert!(run(args(&["--extcap-version=4.2"])).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extcap() -> anyhow::Result<()> {
+        let config = Config::from_toml(CONFIG)?;
+        let result = interfaces(&config);
+        let lines = result.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("extcap {version="));
+        assert!(lines[1].starts_with("interface {value=rs-can-bench}{display=rs-can bench (zlgcan://USBCANFD_200U/0?"));
+        assert!(lines[2].starts_with("interface {value=rs-can-sim}"));
+
+        let args = Args { interface: Some("rs-can-bench".into()), ..Default::default() };
+        let result = options(profile(&config, &args)?);
+        assert!(result.contains("{call=--bitrate}{display=Bitrate}{type=integer}{range=5000,1000000}{default=500000}"));
+        assert!(result.contains("{call=--dbitrate}{display=FD data bitrate}{type=integer}{range=0,10000000}{default=2000000}"));
+        assert!(result.contains("{call=--listen-only}{display=Listen only}{type=boolflag}{default=false}"));
+
+        let args = Args { interface: Some("bench".into()), ..Default::default() };
+        assert!(profile(&config, &args).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,243 @@
+//! The async API based on tokio, the blocking calls of driver run on dedicated threads
+//! with the clones of driver, just like `SyncCan` of `isotp-rs`.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::pin::Pin;
+use std::sync::{mpsc as std_mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::task::{Context, Poll};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use futures_core::Stream;
+use isotp_rs::can::frame::Frame;
+use isotp_rs::device::Driver;
+use tokio::sync::{mpsc, oneshot};
+use crate::error::CanError;
+
+/// The timeout(ms) of receiving in receiver threads, it is also the delay of stopping.
+const POLL_TIMEOUT: u32 = 10;
+
+type Request<F> = (F, Option<u32>, oneshot::Sender<Result<(), CanError>>);
+/// The senders of streams of each channel.
+type Streams<F> = Arc<Mutex<HashMap<String, Vec<mpsc::UnboundedSender<F>>>>>;
+
+/// The frames received from a channel.
+#[derive(Debug)]
+pub struct FrameStream<F> {
+    receiver: mpsc::UnboundedReceiver<F>,
+}
+
+impl<F> FrameStream<F> {
+    /// Receive the next frame, `None` if the device is stopped.
+    #[inline]
+    pub async fn recv(&mut self) -> Option<F> {
+        self.receiver.recv().await
+    }
+}
+
+impl<F> Stream for FrameStream<F> {
+    type Item = F;
+
+    #[inline]
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+pub struct AsyncCan<D: Driver> {
+    device: D,
+    sender: Option<std_mpsc::Sender<Request<D::F>>>,
+    streams: Streams<D::F>,
+    stop: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl<D> AsyncCan<D>
+where
+    D: Driver + Clone + 'static,
+    D::C: Clone + Display + Send + 'static,
+    D::F: Frame<Channel = D::C> + Clone + 'static,
+    D::Error: Into<CanError>,
+{
+    /// Wrap the opened device, the transmitting thread is started.
+    pub fn new(device: D) -> Self {
+        let (sender, receiver) = std_mpsc::channel::<Request<D::F>>();
+        let tx_device = device.clone();
+        // stopped when the sender is dropped.
+        let handle = std::thread::spawn(move || {
+            for (msg, timeout, result) in receiver {
+                let _ = result.send(tx_device.transmit(msg, timeout).map_err(Into::into));
+            }
+        });
+
+        Self {
+            device,
+            sender: Some(sender),
+            streams: Default::default(),
+            stop: Default::default(),
+            threads: vec![handle],
+        }
+    }
+
+    #[inline]
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    /// Transmit a frame, the `timeout` is also passed to driver.
+    pub async fn send(&self, msg: D::F, timeout: Option<Duration>) -> Result<(), CanError> {
+        let channel = msg.channel().to_string();
+        let stopped = || CanError::OperationError(format!("the transmitting thread of channel: {} is stopped", channel));
+        let sender = self.sender.as_ref()
+            .ok_or_else(stopped)?;
+
+        let (tx, rx) = oneshot::channel();
+        sender.send((msg, timeout.map(|v| v.as_millis() as u32), tx))
+            .map_err(|_| stopped())?;
+        let result = match timeout {
+            Some(v) => tokio::time::timeout(v, rx).await
+                .map_err(|_| CanError::TimeoutError(channel.clone()))?,
+            None => rx.await,
+        };
+
+        result.map_err(|_| stopped())?
+    }
+
+    /// The frames received from channel, every stream gets all the frames.
+    /// The streams of a channel share one receiving thread, it is stopped when all of them are dropped.
+    pub fn stream(&mut self, channel: D::C) -> FrameStream<D::F> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let key = channel.to_string();
+        let mut streams = self.streams.lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if let Some(senders) = streams.get_mut(&key) {
+            senders.push(sender);
+            return FrameStream { receiver };
+        }
+        streams.insert(key.clone(), vec![sender]);
+        drop(streams);
+
+        let device = self.device.clone();
+        let streams = Arc::clone(&self.streams);
+        let stop = Arc::clone(&self.stop);
+        let handle = std::thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                let frames = match device.receive(channel.clone(), Some(POLL_TIMEOUT)) {
+                    Ok(frames) => {
+                        if frames.is_empty() {
+                            std::thread::sleep(Duration::from_millis(1));
+                        }
+                        frames
+                    },
+                    Err(e) => {
+                        match e.into() {
+                            CanError::TimeoutError(_) => {},
+                            e => {
+                                log::warn!("RUST-CAN - {} when receiving from channel: {}", e, channel);
+                                std::thread::sleep(Duration::from_millis(POLL_TIMEOUT as u64));
+                            },
+                        }
+                        Vec::new()
+                    },
+                };
+
+                // the dropped streams are removed, the channel is released by the last one.
+                let Ok(mut streams) = streams.lock() else { return; };
+                let Some(senders) = streams.get_mut(&key) else { return; };
+                senders.retain(|s| !s.is_closed() && frames.iter().all(|f| s.send(f.clone()).is_ok()));
+                if senders.is_empty() {
+                    streams.remove(&key);
+                    return;
+                }
+            }
+
+            if let Ok(mut streams) = streams.lock() {
+                streams.remove(&key);
+            }
+        });
+        self.threads.push(handle);
+
+        FrameStream { receiver }
+    }
+
+    /// Stop all threads and shutdown the device.
+    pub fn shutdown(&mut self) {
+        self.stop();
+        self.device.shutdown();
+    }
+}
+
+impl<D: Driver> AsyncCan<D> {
+    /// Stop all threads, the streams are ended.
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.sender.take();
+        for handle in self.threads.drain(..) {
+            if handle.join().is_err() {
+                log::warn!("RUST-CAN - the thread of async device is panicked");
+            }
+        }
+        if let Ok(mut v) = self.streams.lock() {
+            v.clear();
+        }
+    }
+}
+
+impl<D: Driver> Drop for AsyncCan<D> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use isotp_rs::can::{frame::Frame, identifier::Id};
+    use crate::CanMessage;
+    use crate::interfaces::virtual_bus::VirtualCan;
+    use super::AsyncCan;
+
+    #[tokio::test]
+    async fn test_async() -> anyhow::Result<()> {
+        let channel = "test_async";
+        let mut driver = VirtualCan::new();
+        driver.open(channel, vec![], false)?;
+        let mut other = VirtualCan::new();
+        other.open(channel, vec![], false)?;
+
+        let device = AsyncCan::new(driver);
+        let mut other = AsyncCan::new(other);
+        let mut stream = other.stream(channel.into());
+
+        let mut msg = CanMessage::new(Id::from(0x7DF), &[0x02, 0x10, 0x01]).unwrap();
+        msg.set_channel(channel.into());
+        device.send(msg.clone(), Some(Duration::from_millis(100))).await?;
+        let recv = tokio::time::timeout(Duration::from_secs(1), stream.recv()).await?;
+        assert_eq!(recv, Some(msg));
+
+        // the streams of a channel receive the same frames.
+        let mut first = other.stream(channel.into());
+        let mut second = other.stream(channel.into());
+        let mut msg = CanMessage::new(Id::from(0x7E0), &[0x02, 0x3E, 0x00]).unwrap();
+        msg.set_channel(channel.into());
+        device.send(msg.clone(), Some(Duration::from_millis(100))).await?;
+        for s in [&mut stream, &mut first, &mut second] {
+            let recv = tokio::time::timeout(Duration::from_secs(1), s.recv()).await?;
+            assert_eq!(recv, Some(msg.clone()));
+        }
+
+        drop(first);
+        device.send(msg.clone(), Some(Duration::from_millis(100))).await?;
+        for s in [&mut stream, &mut second] {
+            let recv = tokio::time::timeout(Duration::from_secs(1), s.recv()).await?;
+            assert_eq!(recv, Some(msg.clone()));
+        }
+
+        other.stop();
+        assert_eq!(second.recv().await, None);
+        assert_eq!(stream.recv().await, None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,341 @@
+//! The bus load meter, the on-wire bits of each frame are calculated exactly(including the stuff bits and CRC),
+//! and the utilisation of each channel is aggregated over a sliding window.
+//!
+//! The bits of a frame are from SOF to the intermission(3 bits), the bits of CAN FD data phase
+//! are from ESI to the end of CRC sequence when the bitrate is switched.
+
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::time::Duration;
+use isotp_rs::can::frame::Frame;
+use isotp_rs::device::Listener;
+use crate::ChannelConfig;
+
+/// The CRC delimiter, ACK slot, ACK delimiter, EOF and intermission.
+const TRAILER_BITS: u32 = 1 + 1 + 1 + 7 + 3;
+/// The error flag, error delimiter and intermission, the superposition of error flags is ignored.
+const ERROR_FRAME_BITS: u32 = 6 + 8 + 3;
+const CRC15_POLY: u16 = 0x4599;
+const FD_DLC: [usize; 7] = [12, 16, 20, 24, 32, 48, 64];
+
+/// The bits of frame on wire.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct FrameBits {
+    /// The bits at nominal bitrate.
+    pub nominal: u32,
+    /// The bits at data bitrate, which is `0` if the bitrate is not switched.
+    pub data: u32,
+}
+
+impl FrameBits {
+    /// Calculate the bits of frame.
+    pub fn new<F: Frame>(frame: &F) -> Self {
+        if frame.is_error_frame() {
+            return Self { nominal: ERROR_FRAME_BITS, data: 0 };
+        }
+
+        let length = frame.length();
+        let (dlc, size) = match FD_DLC.iter().position(|&v| length <= v) {
+            Some(i) if length > 8 => (9 + i as u64, FD_DLC[i]),
+            _ => (length.min(8) as u64, length.min(8)),
+        };
+        let mut data = frame.data().to_vec();
+        data.resize(size, 0);
+
+        let id = frame.id().as_raw() as u64;
+        let extended = frame.is_extended();
+        let mut bits = Vec::with_capacity(128 + size * 8);
+        push_bits(&mut bits, 0, 1);     // SOF
+        if extended {
+            push_bits(&mut bits, id >> 18, 11);
+            push_bits(&mut bits, 0b11, 2);  // SRR and IDE
+            push_bits(&mut bits, id, 18);
+        }
+        else {
+            push_bits(&mut bits, id, 11);
+        }
+
+        if frame.is_can_fd() {
+            // RRS, IDE(standard), FDF and res
+            push_bits(&mut bits, 0b0010, if extended { 3 } else { 4 });
+            push_bits(&mut bits, frame.is_bitrate_switch() as u64, 1);
+            let split = bits.len();
+            push_bits(&mut bits, frame.is_esi() as u64, 1);
+            push_bits(&mut bits, dlc, 4);
+            data.iter().for_each(|&b| push_bits(&mut bits, b as u64, 8));
+
+            let (before, after) = stuff_bits(&bits, split);
+            // the stuff count(4), CRC and the fixed stuff bits before them and every 4 bits
+            let fixed = if size > 16 { 4 + 21 + 7 } else { 4 + 17 + 6 };
+            let data_bits = (bits.len() - split) as u32 + after + fixed;
+            if frame.is_bitrate_switch() {
+                Self { nominal: split as u32 + before + TRAILER_BITS, data: data_bits }
+            }
+            else {
+                Self { nominal: split as u32 + before + data_bits + TRAILER_BITS, data: 0 }
+            }
+        }
+        else {
+            let remote = frame.is_remote();
+            push_bits(&mut bits, remote as u64, 1);         // RTR
+            push_bits(&mut bits, 0, 2);     // r1 and r0, or IDE and r0
+            push_bits(&mut bits, dlc, 4);
+            if !remote {
+                data.iter().for_each(|&b| push_bits(&mut bits, b as u64, 8));
+            }
+            let crc = crc15(&bits);
+            push_bits(&mut bits, crc as u64, 15);
+
+            let (stuffed, _) = stuff_bits(&bits, bits.len());
+            Self { nominal: bits.len() as u32 + stuffed + TRAILER_BITS, data: 0 }
+        }
+    }
+
+    /// The time of frame on bus, the nominal bitrate is used when `dbitrate` is `None`.
+    pub fn duration(&self, bitrate: u32, dbitrate: Option<u32>) -> Duration {
+        let bitrate = bitrate.max(1) as f64;
+        let dbitrate = dbitrate.map_or(bitrate, |v| v.max(1) as f64);
+        Duration::from_secs_f64(self.nominal as f64 / bitrate + self.data as f64 / dbitrate)
+    }
+
+    #[inline]
+    pub fn total(&self) -> u32 {
+        self.nominal + self.data
+    }
+}
+
+/// Push the lowest `count` bits of `value` from MSB.
+#[inline]
+fn push_bits(bits: &mut Vec<bool>, value: u64, count: u32) {
+    bits.extend((0..count).rev().map(|i| value >> i & 1 == 1));
+}
+
+/// The stuff bits inserted after 5 identical bits, which are counted before and since `split`.
+fn stuff_bits(bits: &[bool], split: usize) -> (u32, u32) {
+    let (mut before, mut after) = (0, 0);
+    let (mut last, mut run) = (None, 0);
+    for (i, &bit) in bits.iter().enumerate() {
+        if last == Some(bit) {
+            run += 1;
+        }
+        else {
+            (last, run) = (Some(bit), 1);
+        }
+
+        if run == 5 {
+            if i < split { before += 1; } else { after += 1; }
+            // the stuff bit is the first of next run.
+            (last, run) = (Some(!bit), 1);
+        }
+    }
+    (before, after)
+}
+
+fn crc15(bits: &[bool]) -> u16 {
+    bits.iter()
+        .fold(0u16, |crc, &bit| {
+            let next = bit ^ (crc >> 14 & 1 == 1);
+            let crc = (crc << 1) & 0x7FFF;
+            if next { crc ^ CRC15_POLY } else { crc }
+        })
+}
+
+/// The bus load of a channel.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BusLoad {
+    /// The utilisation(0.0 ..= 1.0) of last window.
+    pub load: f64,
+    /// The maximum load since started.
+    pub peak: f64,
+    /// The frames of last window.
+    pub frames: usize,
+    /// The bits of last window.
+    pub bits: u64,
+}
+
+#[derive(Debug, Default)]
+struct ChannelLoad {
+    bitrate: u32,
+    dbitrate: Option<u32>,
+    /// The timestamp, busy time and bits of frames in window.
+    records: VecDeque<(Duration, Duration, u32)>,
+    busy: Duration,
+    bits: u64,
+    peak: f64,
+}
+
+/// The bus load meter of channels, the frames are aggregated by timestamp.
+///
+/// It's a [`Listener`] of [`Notifier`], and only the received frames are counted,
+/// so the own frames should be received to measure the transmitted traffic.
+///
+/// [`Notifier`]: crate::notifier::Notifier
+#[derive(Debug)]
+pub struct BusLoadMeter<C> {
+    window: Duration,
+    channels: HashMap<C, ChannelLoad>,
+}
+
+impl<C: Hash + Eq> BusLoadMeter<C> {
+    pub fn new(window: Duration) -> Self {
+        Self { window, channels: Default::default() }
+    }
+
+    #[inline]
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Measure the channel with the nominal and data bitrate of `config`.
+    pub fn add_channel(&mut self, channel: C, config: &ChannelConfig) -> &mut Self {
+        self.channels.insert(channel, ChannelLoad {
+            bitrate: config.bitrate,
+            dbitrate: config.dbitrate,
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Aggregate the frame, the frames of channels not added are ignored.
+    pub fn on_frame<F: Frame<Channel = C>>(&mut self, frame: &F) {
+        let Some(ctx) = self.channels.get_mut(&frame.channel()) else { return; };
+        let bits = FrameBits::new(frame);
+        let busy = bits.duration(ctx.bitrate, ctx.dbitrate);
+        let timestamp = Duration::from_nanos(frame.timestamp());
+
+        ctx.records.push_back((timestamp, busy, bits.total()));
+        ctx.busy += busy;
+        ctx.bits += bits.total() as u64;
+        while let Some(&(ts, busy, bits)) = ctx.records.front() {
+            if ts + self.window > timestamp {
+                break;
+            }
+            ctx.records.pop_front();
+            ctx.busy = ctx.busy.saturating_sub(busy);
+            ctx.bits -= bits as u64;
+        }
+
+        let load = Self::ratio(ctx.busy, self.window);
+        ctx.peak = ctx.peak.max(load);
+    }
+
+    /// The load of channel, `None` if the channel is not added.
+    pub fn load(&self, channel: &C) -> Option<BusLoad> {
+        let ctx = self.channels.get(channel)?;
+        Some(BusLoad {
+            load: Self::ratio(ctx.busy, self.window),
+            peak: ctx.peak,
+            frames: ctx.records.len(),
+            bits: ctx.bits,
+        })
+    }
+
+    /// Clear the frames and peak of all channels.
+    pub fn reset(&mut self) {
+        for ctx in self.channels.values_mut() {
+            ctx.records.clear();
+            ctx.busy = Duration::ZERO;
+            ctx.bits = 0;
+            ctx.peak = 0.;
+        }
+    }
+
+    #[inline]
+    fn ratio(busy: Duration, window: Duration) -> f64 {
+        if window.is_zero() {
+            return 0.;
+        }
+        (busy.as_secs_f64() / window.as_secs_f64()).min(1.)
+    }
+}
+
+impl<C, F> Listener<C, u32, F> for BusLoadMeter<C>
+where
+    C: Hash + Eq + Send + 'static,
+    F: Frame<Channel = C> + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn on_frame_transmitting(&mut self, _: C, _: &F) {}
+
+    fn on_frame_transmitted(&mut self, _: C, _: u32) {}
+
+    fn on_frame_received(&mut self, _: C, frames: &[F]) {
+        frames.iter()
+            .for_each(|f| self.on_frame(f));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use isotp_rs::can::{frame::Frame, identifier::Id};
+    use crate::{CanMessage, ChannelConfig};
+    use super::{BusLoadMeter, FrameBits};
+
+    fn frame(id: u32, extended: bool, data: &[u8]) -> CanMessage<u8> {
+        CanMessage::new(Id::from_bits(id, extended), data).unwrap()
+    }
+
+    #[test]
+    fn test_frame_bits() {
+        // 111 bits without stuff bits, and a stuff bit in CRC
+        assert_eq!(FrameBits::new(&frame(0x555, false, &[0x55; 8])), FrameBits { nominal: 112, data: 0 });
+        // 98 recessive bits from SOF to CRC are stuffed
+        assert_eq!(FrameBits::new(&frame(0x7FF, false, &[0xFF; 8])).total(), 126);
+        assert_eq!(FrameBits::new(&frame(0x000, false, &[])).total(), 53);
+        assert_eq!(FrameBits::new(&frame(0x123, false, &[0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88])).total(), 112);
+        assert_eq!(FrameBits::new(&frame(0x18DA_F110, true, &[0x02, 0x3E, 0x80, 0, 0, 0, 0, 0])).total(), 143);
+        let mut remote = CanMessage::<u8>::new_remote(Id::from_bits(0x7DF, false), 8).unwrap();
+        assert_eq!(FrameBits::new(&remote).total(), 50);
+        remote.set_error_frame(true);
+        assert_eq!(FrameBits::new(&remote).total(), 17);
+
+        let mut fd = frame(0x555, false, &[0x55; 64]);
+        assert!(fd.is_can_fd());
+        assert_eq!(FrameBits::new(&fd), FrameBits { nominal: 579, data: 0 });
+        fd.set_bitrate_switch(true);
+        let bits = FrameBits::new(&fd);
+        assert_eq!(bits, FrameBits { nominal: 30, data: 549 });
+        assert_eq!(bits.duration(500_000, Some(2_000_000)), Duration::from_nanos(30 * 2000 + 549 * 500));
+        assert_eq!(bits.duration(500_000, None), Duration::from_nanos(579 * 2000));
+        // 10 bytes are padded to 12 with zeros
+        let mut padded = [0x55; 12];
+        padded[10..].fill(0);
+        assert_eq!(FrameBits::new(&frame(0x555, false, &[0x55; 10])), FrameBits::new(&frame(0x555, false, &padded)));
+    }
+
+    #[test]
+    fn test_bus_load() {
+        let mut meter = BusLoadMeter::new(Duration::from_millis(100));
+        meter.add_channel(0u8, &ChannelConfig::new(500_000));
+        // 112 bits(224us) every 1ms
+        for i in 0..200 {
+            let mut msg = frame(0x555, false, &[0x55; 8]);
+            msg.set_timestamp(Some(i * 1_000_000));
+            meter.on_frame(&msg);
+        }
+        let load = meter.load(&0).unwrap();
+        assert_eq!(load.frames, 100);
+        assert_eq!(load.bits, 11_200);
+        assert!((load.load - 0.224).abs() < 1e-9, "load: {}", load.load);
+        assert_eq!(load.peak, load.load);
+
+        let mut msg = frame(0x555, false, &[0x55; 8]);
+        msg.set_timestamp(Some(350_000_000));
+        meter.on_frame(&msg);
+        let load = meter.load(&0).unwrap();
+        assert_eq!((load.frames, load.bits), (1, 112));
+        assert!(load.peak > load.load);
+
+        msg.set_channel(1);
+        meter.on_frame(&msg);
+        assert!(meter.load(&1).is_none());
+
+        meter.reset();
+        assert_eq!(meter.load(&0).unwrap().peak, 0.);
+    }
+}
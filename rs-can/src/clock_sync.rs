@@ -0,0 +1,297 @@
+//! The synchronisation of device hardware clock to the host monotonic clock.
+//!
+//! Each received frame gives a sample of `(device time, host time)`, the host time is always later
+//! than the device time because of the latency of USB/driver. The samples are grouped into buckets,
+//! and only the sample with minimum latency of each bucket is kept, the skew is the least squares
+//! slope of those samples, and the offset is the lower envelope of them.
+//!
+//! The corrected time is mapped to the host monotonic clock, and the timestamp is on the monotonic
+//! time base(see [`crate::utils`]), so the frames of different devices can be correlated.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use crate::utils::instant_timestamp;
+
+/// The samples in a window.
+const BUCKETS: u32 = 32;
+/// The minimum device time span to estimate the skew.
+const MIN_SKEW_SPAN: f64 = 1.;
+/// The skew of crystal is far less than this.
+const MAX_SKEW: f64 = 1e-3;
+/// The device clock is treated as restarted when the prediction is out of this.
+const RESYNC_THRESHOLD: f64 = 1.;
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    /// The host time when the bucket started.
+    start: f64,
+    device: f64,
+    host: f64,
+}
+
+impl Sample {
+    #[inline]
+    fn latency(&self) -> f64 {
+        self.host - self.device
+    }
+}
+
+/// The clock synchronisation of a device.
+#[derive(Debug, Clone)]
+pub struct ClockSync {
+    resolution: Duration,
+    bits: Option<u32>,
+    window: Duration,
+    origin: Instant,
+    last: Option<u64>,
+    wraps: u64,
+    samples: VecDeque<Sample>,
+    skew: f64,
+    offset: f64,
+}
+
+impl ClockSync {
+    /// Create the clock synchronisation with the resolution of a device tick.
+    pub fn new(resolution: Duration) -> Self {
+        Self {
+            resolution,
+            bits: Default::default(),
+            window: Duration::from_secs(60),
+            origin: Instant::now(),
+            last: Default::default(),
+            wraps: Default::default(),
+            samples: Default::default(),
+            skew: 1.,
+            offset: Default::default(),
+        }
+    }
+
+    #[inline]
+    pub fn resolution(&self) -> Duration {
+        self.resolution
+    }
+
+    /// Set the bits of device counter, the counter wraps around to 0 when overflowed.
+    #[inline]
+    pub fn set_bits(&mut self, bits: Option<u32>) -> &mut Self {
+        self.bits = bits;
+        self
+    }
+
+    #[inline]
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Set the window of samples, the skew is estimated over it.
+    #[inline]
+    pub fn set_window(&mut self, window: Duration) -> &mut Self {
+        self.window = window;
+        self
+    }
+
+    /// The rate error of device clock in ppm, positive when the device clock is slower than host.
+    #[inline]
+    pub fn skew(&self) -> f64 {
+        (self.skew - 1.) * 1e6
+    }
+
+    /// The host monotonic time(seconds since the clock is created) when the device time is zero.
+    #[inline]
+    pub fn offset(&self) -> f64 {
+        self.offset
+    }
+
+    #[inline]
+    pub fn is_synchronized(&self) -> bool {
+        !self.samples.is_empty()
+    }
+
+    /// Discard all samples, such as the device is reset.
+    pub fn reset(&mut self) {
+        self.last = None;
+        self.wraps = 0;
+        self.samples.clear();
+        self.skew = 1.;
+        self.offset = 0.;
+    }
+
+    /// Add a sample that the device `ticks` is received at `host`, and get the unwrapped device time.
+    pub fn update(&mut self, ticks: u64, host: Instant) -> Duration {
+        let device = self.unwrap(ticks);
+        let device_secs = device.as_secs_f64();
+        let host_secs = self.host_secs(host);
+
+        if self.is_synchronized()
+            && (self.skew * device_secs + self.offset - host_secs).abs() > RESYNC_THRESHOLD {
+            log::debug!("RUST-CAN - device clock is restarted or jumped, resynchronize");
+            self.samples.clear();
+            self.skew = 1.;
+        }
+
+        let width = self.window.as_secs_f64() / BUCKETS as f64;
+        let sample = Sample { start: host_secs, device: device_secs, host: host_secs };
+        match self.samples.back_mut() {
+            Some(last) if host_secs - last.start < width => {
+                if sample.latency() < last.latency() {
+                    *last = Sample { start: last.start, ..sample };
+                }
+            },
+            _ => self.samples.push_back(sample),
+        }
+        while let Some(first) = self.samples.front() {
+            if host_secs - first.start > self.window.as_secs_f64() && self.samples.len() > 1 {
+                self.samples.pop_front();
+            }
+            else {
+                break;
+            }
+        }
+
+        self.estimate();
+        device
+    }
+
+    /// Map the unwrapped device time to the host monotonic clock.
+    pub fn to_host(&self, device: Duration) -> Instant {
+        let secs = self.skew * device.as_secs_f64() + self.offset;
+        if secs >= 0. {
+            self.origin + Duration::from_secs_f64(secs)
+        }
+        else {
+            self.origin.checked_sub(Duration::from_secs_f64(-secs))
+                .unwrap_or(self.origin)
+        }
+    }
+
+    /// Map the host monotonic clock to the device ticks.
+    pub fn to_device(&self, host: Instant) -> u64 {
+        let secs = ((self.host_secs(host) - self.offset) / self.skew).max(0.);
+        let ticks = (secs / self.resolution.as_secs_f64()) as u64;
+        match self.bits {
+            Some(bits) if bits < u64::BITS => ticks & ((1 << bits) - 1),
+            _ => ticks,
+        }
+    }
+
+    /// Get the timestamp(ns) of the unwrapped device time on the monotonic time base.
+    #[inline]
+    pub fn timestamp(&self, device: Duration) -> u64 {
+        instant_timestamp(self.to_host(device))
+    }
+
+    /// Add a sample and get the corrected timestamp of it.
+    #[inline]
+    pub fn synchronize(&mut self, ticks: u64, host: Instant) -> u64 {
+        let device = self.update(ticks, host);
+        self.timestamp(device)
+    }
+
+    fn unwrap(&mut self, ticks: u64) -> Duration {
+        if let Some(bits) = self.bits.filter(|&b| b < u64::BITS) {
+            let period = 1u64 << bits;
+            let ticks = ticks & (period - 1);
+            if let Some(last) = self.last {
+                // the frames may be a little out of order, a wrap is a large jump backwards
+                if ticks < last && last - ticks > period / 2 {
+                    self.wraps += 1;
+                }
+            }
+            self.last = Some(ticks);
+            self.duration(self.wraps.saturating_mul(period).saturating_add(ticks))
+        }
+        else {
+            self.duration(ticks)
+        }
+    }
+
+    #[inline]
+    fn duration(&self, ticks: u64) -> Duration {
+        let nanos = ticks as u128 * self.resolution.as_nanos();
+        Duration::new((nanos / 1_000_000_000) as u64, (nanos % 1_000_000_000) as u32)
+    }
+
+    #[inline]
+    fn host_secs(&self, host: Instant) -> f64 {
+        match host.checked_duration_since(self.origin) {
+            Some(elapsed) => elapsed.as_secs_f64(),
+            None => -(self.origin - host).as_secs_f64(),
+        }
+    }
+
+    fn estimate(&mut self) {
+        let count = self.samples.len() as f64;
+        let first = self.samples.front().map(|s| s.device).unwrap_or_default();
+        let last = self.samples.back().map(|s| s.device).unwrap_or_default();
+
+        self.skew = if last - first >= MIN_SKEW_SPAN {
+            let (mean_d, mean_h) = self.samples.iter()
+                .fold((0., 0.), |(d, h), s| (d + s.device / count, h + s.host / count));
+            let (cov, var) = self.samples.iter()
+                .fold((0., 0.), |(cov, var), s| {
+                    let d = s.device - mean_d;
+                    (cov + d * (s.host - mean_h), var + d * d)
+                });
+            (cov / var).clamp(1. - MAX_SKEW, 1. + MAX_SKEW)
+        }
+        else {
+            1.
+        };
+
+        let skew = self.skew;
+        self.offset = self.samples.iter()
+            .map(|s| s.host - skew * s.device)
+            .fold(f64::INFINITY, f64::min);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use super::ClockSync;
+
+    #[test]
+    fn test_clock_sync() {
+        let host = Instant::now();
+        let mut clock = ClockSync::new(Duration::from_micros(1));
+        clock.set_bits(Some(32))
+            .set_window(Duration::from_secs(10));
+        assert!(!clock.is_synchronized());
+
+        // the device clock is 100ppm slower and started 5s before host, the latency is 1~3ms
+        let mut expected = Duration::ZERO;
+        for i in 0..20_000u64 {
+            let true_host = Duration::from_millis(i);
+            let device = Duration::from_secs_f64((5. + true_host.as_secs_f64()) * (1. - 100e-6));
+            let latency = Duration::from_micros(1000 + (i * 7919) % 2000);
+            let unwrapped = clock.update(device.as_micros() as u64, host + true_host + latency);
+            assert_eq!(unwrapped, Duration::from_micros(device.as_micros() as u64));
+            expected = true_host;
+        }
+        assert!((clock.skew() - 100.).abs() < 1., "{}", clock.skew());
+
+        let device = Duration::from_secs_f64((5. + expected.as_secs_f64()) * (1. - 100e-6));
+        let corrected = clock.to_host(device);
+        let error = corrected.duration_since(host).as_secs_f64() - expected.as_secs_f64();
+        assert!((0. ..0.0015).contains(&error), "{}", error);
+
+        let ticks = clock.to_device(host + expected);
+        assert!((ticks as i64 - device.as_micros() as i64).abs() < 1500);
+
+        // wraps around
+        let mut clock = ClockSync::new(Duration::from_micros(100));
+        clock.set_bits(Some(8));
+        assert_eq!(clock.update(250, host), Duration::from_micros(25_000));
+        assert_eq!(clock.update(4, host + Duration::from_micros(1000)), Duration::from_micros(26_000));
+
+        // restarted
+        let now = Instant::now();
+        let mut clock = ClockSync::new(Duration::from_micros(1));
+        clock.synchronize(10_000_000, now);
+        clock.synchronize(100, now + Duration::from_millis(1));
+        let corrected = clock.to_host(Duration::from_micros(100)).duration_since(now);
+        assert!(corrected.abs_diff(Duration::from_millis(1)) < Duration::from_micros(1));
+        clock.reset();
+        assert!(!clock.is_synchronized());
+    }
+}
@@ -0,0 +1,329 @@
+//! The named bus profiles loaded from a TOML or YAML file, like `can.ini` of python-can.
+//!
+//! The file is located by:
+//! 1. the environment variable `RS_CAN_CONFIG`;
+//! 2. `rs-can.toml`, `rs-can.yaml` or `rs-can.yml` in current directory;
+//! 3. `$XDG_CONFIG_HOME/rs-can/config.{toml,yaml,yml}`(`~/.config` as default) and `$XDG_CONFIG_DIRS`
+//!    (`/etc/xdg` as default), `%APPDATA%\rs-can\config.*` on Windows.
+//!
+//! ```toml
+//! default = "bench"
+//!
+//! [profiles.bench]
+//! backend = "zlgcan"
+//! device = "USBCANFD_200U"
+//! index = 0
+//!
+//! [[profiles.bench.channels]]
+//! channel = "0"
+//! bitrate = 500000
+//! dbitrate = 2000000
+//! termination = true
+//! filters = [{ id = 0x7DF, mask = 0x7FF }]
+//! ```
+
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::{CanFilter, ChannelConfig};
+use crate::error::CanError;
+use crate::factory::{self, AnyBus, BusUrl};
+
+/// The environment variable of configuration file path.
+pub const CONFIG_ENV: &str = "RS_CAN_CONFIG";
+const FILE_NAMES: [&str; 3] = ["config.toml", "config.yaml", "config.yml"];
+const LOCAL_FILE_NAMES: [&str; 3] = ["rs-can.toml", "rs-can.yaml", "rs-can.yml"];
+
+#[derive(Debug, Default, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct FilterProfile {
+    pub id: u32,
+    pub mask: u32,
+    /// Decided by [`factory::is_extended`] when not present.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extended: Option<bool>,
+}
+
+impl From<&FilterProfile> for CanFilter {
+    fn from(value: &FilterProfile) -> Self {
+        Self {
+            can_id: value.id,
+            can_mask: value.mask,
+            extended: value.extended.unwrap_or_else(|| factory::is_extended(value.id, value.mask)),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct ChannelProfile {
+    pub channel: String,
+    pub bitrate: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dbitrate: Option<u32>,
+    /// The terminal resistance.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub termination: Option<bool>,
+    #[serde(default)]
+    pub listen_only: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub filters: Vec<FilterProfile>,
+}
+
+impl From<&ChannelProfile> for ChannelConfig {
+    fn from(value: &ChannelProfile) -> Self {
+        Self {
+            bitrate: value.bitrate,
+            dbitrate: value.dbitrate,
+            resistance: value.termination,
+            listen_only: value.listen_only,
+            filters: value.filters.iter()
+                .map(CanFilter::from)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct BusProfile {
+    /// The scheme registered to factory, such as `zlgcan`, `nican` or `socketcan`.
+    pub backend: String,
+    /// The device type(`zlgcan`), multicast group(`udp_multicast`) or bus name(`virtual`).
+    #[serde(default)]
+    pub device: String,
+    #[serde(default)]
+    pub index: u32,
+    #[serde(default)]
+    pub channels: Vec<ChannelProfile>,
+    /// The backend specific parameters, such as `receive_own` of virtual bus.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub options: HashMap<String, String>,
+}
+
+impl BusProfile {
+    /// Convert to bus descriptor, the common configuration is the first channel's,
+    /// and the channels with different configuration are written as `<channel>.<key>`.
+    pub fn to_url(&self) -> BusUrl {
+        let mut url = BusUrl {
+            scheme: self.backend.to_lowercase(),
+            host: self.device.clone(),
+            path: vec![self.index.to_string()],
+            params: self.options.clone(),
+        };
+        url.params.insert(
+            "channel".into(),
+            self.channels.iter()
+                .map(|c| c.channel.as_str())
+                .collect::<Vec<_>>()
+                .join(",")
+        );
+        if let Some(first) = self.channels.first() {
+            url.set_channel_config(&first.into());
+            for c in self.channels.iter().skip(1) {
+                if c.bitrate != first.bitrate
+                    || c.dbitrate != first.dbitrate
+                    || c.termination != first.termination
+                    || c.listen_only != first.listen_only
+                    || c.filters != first.filters {
+                    url.set_channel_config_of(&c.channel, &c.into());
+                }
+            }
+        }
+
+        url
+    }
+
+    /// Open the bus, each channel is initialized once with its own configuration.
+    pub fn open(&self) -> Result<AnyBus, CanError> {
+        if self.channels.is_empty() {
+            return Err(CanError::OtherError("no channel is configured in profile".into()));
+        }
+
+        factory::open_url(&self.to_url())
+    }
+}
+
+#[derive(Debug, Default, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Config {
+    /// The profile used when the name is not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(default)]
+    pub profiles: HashMap<String, BusProfile>,
+}
+
+impl Config {
+    /// Load from the located file.
+    pub fn load() -> Result<Self, CanError> {
+        let path = Self::locate()
+            .ok_or(CanError::OtherError("configuration file is not found".into()))?;
+        Self::from_file(path)
+    }
+
+    /// Load from file, the format is decided by extension, YAML is used except `.toml`.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let path = path.as_ref();
+        let data = read_to_string(path)
+            .map_err(|e| CanError::OtherError(format!("Unable to read `{}`: {}", path.display(), e)))?;
+        match path.extension().and_then(|v| v.to_str()) {
+            Some("toml") => Self::from_toml(data.as_str()),
+            _ => Self::from_yaml(data.as_str()),
+        }
+    }
+
+    #[inline]
+    pub fn from_toml(data: &str) -> Result<Self, CanError> {
+        toml::from_str(data)
+            .map_err(|e| CanError::OtherError(format!("Error parsing TOML: {}", e)))
+    }
+
+    #[inline]
+    pub fn from_yaml(data: &str) -> Result<Self, CanError> {
+        serde_yaml::from_str(data)
+            .map_err(|e| CanError::OtherError(format!("Error parsing YAML: {}", e)))
+    }
+
+    /// Get the profile by name, the default profile is used when name is `None`.
+    pub fn profile(&self, name: Option<&str>) -> Result<&BusProfile, CanError> {
+        let name = name.or(self.default.as_deref())
+            .ok_or(CanError::OtherError("default profile is not configured".into()))?;
+        self.profiles.get(name)
+            .ok_or(CanError::OtherError(format!("profile `{}` is not configured", name)))
+    }
+
+    /// The first existing file of search paths.
+    pub fn locate() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os(CONFIG_ENV) {
+            return Some(PathBuf::from(path));
+        }
+
+        Self::search_paths()
+            .into_iter()
+            .find(|p| p.is_file())
+    }
+
+    pub fn search_paths() -> Vec<PathBuf> {
+        let mut dirs = Vec::new();
+        let mut results = LOCAL_FILE_NAMES.iter()
+            .map(PathBuf::from)
+            .collect::<Vec<_>>();
+
+        match std::env::var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()) {
+            Some(v) => dirs.push(PathBuf::from(v)),
+            None => if let Some(home) = std::env::var_os("HOME") {
+                dirs.push(PathBuf::from(home).join(".config"));
+            },
+        }
+        if cfg!(windows) {
+            if let Some(v) = std::env::var_os("APPDATA") {
+                dirs.push(PathBuf::from(v));
+            }
+        }
+        else {
+            match std::env::var_os("XDG_CONFIG_DIRS").filter(|v| !v.is_empty()) {
+                Some(v) => dirs.extend(std::env::split_paths(&v)),
+                None => dirs.push(PathBuf::from("/etc/xdg")),
+            }
+        }
+
+        for dir in dirs {
+            results.extend(FILE_NAMES.iter().map(|f| dir.join("rs-can").join(f)));
+        }
+
+        results
+    }
+}
+
+/// Open the bus by profile name from the located file.
+pub fn open(name: Option<&str>) -> Result<AnyBus, CanError> {
+    Config::load()?
+        .profile(name)?
+        .open()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CanFilter;
+    use super::Config;
+
+    const TOML: &str = r#"
+default = "bench"
+
+[profiles.bench]
+backend = "zlgcan"
+device = "USBCANFD_200U"
+index = 1
+
+[[profiles.bench.channels]]
+channel = "0"
+bitrate = 500000
+dbitrate = 2000000
+termination = true
+filters = [{ id = 0x7DF, mask = 0x7FF }, { id = 0x18DA00F1, mask = 0x1FFFFFFF }]
+
+[[profiles.bench.channels]]
+channel = "1"
+bitrate = 250000
+listen_only = true
+
+[profiles.sim]
+backend = "virtual"
+device = "test_config"
+channels = [{ channel = "test_config", bitrate = 500000 }]
+options = { receive_own = "true" }
+"#;
+
+    const YAML: &str = r#"
+profiles:
+  lab:
+    backend: nican
+    channels:
+      - channel: CAN0
+        bitrate: 500000
+"#;
+
+    #[test]
+    fn test_toml() -> anyhow::Result<()> {
+        let cfg = Config::from_toml(TOML)?;
+        let profile = cfg.profile(None)?;
+        assert_eq!(profile.backend, "zlgcan");
+        assert_eq!(profile.index, 1);
+        assert_eq!(profile.channels.len(), 2);
+
+        let url = profile.to_url();
+        assert_eq!(url.host, "USBCANFD_200U");
+        assert_eq!(url.path, vec!["1"]);
+        assert_eq!(url.channels("0"), vec!["0", "1"]);
+        let chl_cfg = url.channel_config()?;
+        assert_eq!(chl_cfg.dbitrate, Some(2_000_000));
+        assert_eq!(chl_cfg.resistance, Some(true));
+        assert_eq!(chl_cfg.filters[1], CanFilter { can_id: 0x18DA00F1, can_mask: 0x1FFFFFFF, extended: true });
+        assert_eq!(url.channel_config_of("0")?, chl_cfg);
+        let chl_cfg = url.channel_config_of("1")?;
+        assert_eq!(chl_cfg.bitrate, 250_000);
+        assert_eq!(chl_cfg.dbitrate, None);
+        assert!(chl_cfg.listen_only);
+        assert!(chl_cfg.filters.is_empty());
+
+        let bus = cfg.profile(Some("sim"))?.open()?;
+        assert_eq!(bus.opened_channels(), vec!["test_config"]);
+
+        assert!(cfg.profile(Some("unknown")).is_err());
+        let mut profile = cfg.profile(Some("sim"))?.clone();
+        profile.channels.clear();
+        assert!(profile.open().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_yaml() -> anyhow::Result<()> {
+        let cfg = Config::from_yaml(YAML)?;
+        assert!(cfg.profile(None).is_err());
+        let profile = cfg.profile(Some("lab"))?;
+        assert_eq!(profile.backend, "nican");
+        assert_eq!(profile.channels[0].channel, "CAN0");
+        assert!(!profile.channels[0].listen_only);
+
+        Ok(())
+    }
+}
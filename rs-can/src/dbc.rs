@@ -0,0 +1,1318 @@
+//! The DBC database, the physical values of signals are encoded to and decoded from the payload of [`CanMessage`].
+//!
+//! The environment variables, signal groups and relative attributes are ignored.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+use isotp_rs::can::frame::Frame;
+use isotp_rs::can::identifier::Id;
+use crate::CanMessage;
+use crate::error::CanError;
+use crate::io::{io_error, parse_error};
+
+/// The placeholder of no node.
+const NO_NODE: &str = "Vector__XXX";
+/// The bit 31 of message identifier is set when the message is extended.
+const EXTENDED_FLAG: u32 = 0x8000_0000;
+const NEW_SYMBOLS: [&str; 28] = [
+    "NS_DESC_", "CM_", "BA_DEF_", "BA_", "VAL_", "CAT_DEF_", "CAT_", "FILTER", "BA_DEF_DEF_", "EV_DATA_",
+    "ENVVAR_DATA_", "SGTYPE_", "SGTYPE_VAL_", "BA_DEF_SGTYPE_", "BA_SGTYPE_", "SIG_TYPE_REF_", "VAL_TABLE_",
+    "SIG_GROUP_", "SIG_VALTYPE_", "SIGTYPE_VALTYPE_", "BO_TX_BU_", "BA_DEF_REL_", "BA_REL_", "BA_DEF_DEF_REL_",
+    "BU_SG_REL_", "BU_EV_REL_", "BU_BO_REL_", "SG_MUL_VAL_",
+];
+/// The attribute of frame format, the CAN FD formats are ended with `_FD`.
+const FRAME_FORMAT: &str = "VFrameFormat";
+/// The characters of Windows-1252 in `0x80..=0x9F`, the undefined bytes are kept as the C1 controls.
+const WINDOWS_1252: [char; 32] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{8D}', '\u{017D}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{9D}', '\u{017E}', '\u{0178}',
+];
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum ByteOrder {
+    /// Intel, `@1`, the start bit is the LSB.
+    #[default]
+    LittleEndian,
+    /// Motorola, `@0`, the start bit is the MSB.
+    BigEndian,
+}
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum ValueType {
+    #[default]
+    Unsigned,
+    Signed,
+    /// IEEE 754 single precision, `SIG_VALTYPE_ 1`.
+    Float,
+    /// IEEE 754 double precision, `SIG_VALTYPE_ 2`.
+    Double,
+}
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum Multiplexer {
+    #[default]
+    Plain,
+    /// `M`
+    Multiplexor,
+    /// `m<value>`, the signal is active when the multiplexor is `value`.
+    Multiplexed(u64),
+    /// `m<value>M` of extended multiplexing.
+    MultiplexedMultiplexor(u64),
+}
+
+impl Multiplexer {
+    #[inline]
+    pub fn value(&self) -> Option<u64> {
+        match self {
+            Self::Multiplexed(v) | Self::MultiplexedMultiplexor(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+/// The extended multiplexing of `SG_MUL_VAL_`,
+/// the signal is active when the value of `switch` is in any of `ranges`.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct MuxValue {
+    pub switch: String,
+    pub ranges: Vec<(u64, u64)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    /// The integer, hex and the index of enum.
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl Display for AttributeValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(v) => write!(f, "{}", v),
+            Self::Float(v) => write!(f, "{:?}", v),
+            Self::String(v) => write!(f, "\"{}\"", escape(v)),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum AttributeObject {
+    #[default]
+    Network,
+    Node,
+    Message,
+    Signal,
+    EnvironmentVariable,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeKind {
+    Int(i64, i64),
+    Hex(i64, i64),
+    Float(f64, f64),
+    String,
+    Enum(Vec<String>),
+}
+
+impl Display for AttributeKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(min, max) => write!(f, "INT {} {}", min, max),
+            Self::Hex(min, max) => write!(f, "HEX {} {}", min, max),
+            Self::Float(min, max) => write!(f, "FLOAT {} {}", min, max),
+            Self::String => write!(f, "STRING "),
+            Self::Enum(values) => write!(f, "ENUM {}", values.iter()
+                .map(|v| format!("\"{}\"", escape(v)))
+                .collect::<Vec<_>>()
+                .join(",")),
+        }
+    }
+}
+
+/// The `BA_DEF_` and `BA_DEF_DEF_`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttributeDefinition {
+    pub name: String,
+    pub object: AttributeObject,
+    pub kind: AttributeKind,
+    pub default: Option<AttributeValue>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Node {
+    pub name: String,
+    pub comment: Option<String>,
+    pub attributes: BTreeMap<String, AttributeValue>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Signal {
+    pub name: String,
+    pub start_bit: u32,
+    /// 1..=64
+    pub size: u32,
+    pub byte_order: ByteOrder,
+    pub value_type: ValueType,
+    pub factor: f64,
+    pub offset: f64,
+    pub min: f64,
+    pub max: f64,
+    pub unit: String,
+    pub receivers: Vec<String>,
+    pub multiplexer: Multiplexer,
+    pub mux_value: Option<MuxValue>,
+    /// The descriptions of raw values, `VAL_`.
+    pub value_descriptions: BTreeMap<i64, String>,
+    pub comment: Option<String>,
+    pub attributes: BTreeMap<String, AttributeValue>,
+}
+
+impl Signal {
+    /// The bit positions from MSB to LSB.
+    fn positions(&self) -> Vec<u32> {
+        match self.byte_order {
+            ByteOrder::LittleEndian => (self.start_bit..self.start_bit + self.size).rev().collect(),
+            ByteOrder::BigEndian => std::iter::successors(Some(self.start_bit), |&pos| match pos % 8 {
+                0 => Some(pos + 15),
+                _ => Some(pos - 1),
+            })
+                .take(self.size as usize)
+                .collect(),
+        }
+    }
+
+    /// The raw bits, `None` if the signal is out of payload or the size is not in `1..=64`.
+    fn raw_bits(&self, data: &[u8]) -> Option<u64> {
+        if !(1..=64).contains(&self.size) {
+            return None;
+        }
+        self.positions()
+            .into_iter()
+            .try_fold(0u64, |raw, pos| {
+                let byte = data.get((pos / 8) as usize)?;
+                Some(raw << 1 | ((byte >> (pos % 8)) & 1) as u64)
+            })
+    }
+
+    /// The raw value, which is sign-extended for signed signal, `None` if the signal is out of payload.
+    pub fn decode_raw(&self, data: &[u8]) -> Option<i64> {
+        let raw = self.raw_bits(data)?;
+        match self.value_type {
+            ValueType::Signed if self.size < 64 => {
+                let shift = 64 - self.size;
+                Some(((raw << shift) as i64) >> shift)
+            },
+            _ => Some(raw as i64),
+        }
+    }
+
+    /// The physical value, `None` if the signal is out of payload.
+    pub fn decode(&self, data: &[u8]) -> Option<f64> {
+        let raw = self.decode_raw(data)?;
+        let value = match self.value_type {
+            ValueType::Unsigned => raw as u64 as f64,
+            ValueType::Signed => raw as f64,
+            ValueType::Float => f32::from_bits(raw as u32) as f64,
+            ValueType::Double => f64::from_bits(raw as u64),
+        };
+        Some(value * self.factor + self.offset)
+    }
+
+    /// The description of raw value in payload.
+    #[inline]
+    pub fn describe(&self, data: &[u8]) -> Option<&str> {
+        self.value_descriptions.get(&self.decode_raw(data)?)
+            .map(|v| v.as_str())
+    }
+
+    /// Encode the physical `value` into payload, the raw value is rounded to the nearest integer.
+    pub fn encode(&self, data: &mut [u8], value: f64) -> Result<(), CanError> {
+        let raw = self.to_raw(value)?;
+        let positions = self.positions();
+        if positions.iter().any(|&pos| (pos / 8) as usize >= data.len()) {
+            return Err(CanError::FrameConvertFailed(format!("signal `{}` is out of payload", self.name)));
+        }
+
+        for (i, pos) in positions.into_iter().rev().enumerate() {
+            let byte = &mut data[(pos / 8) as usize];
+            let mask = 1 << (pos % 8);
+            if raw >> i & 1 == 1 {
+                *byte |= mask;
+            }
+            else {
+                *byte &= !mask;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn to_raw(&self, value: f64) -> Result<u64, CanError> {
+        let factor = if self.factor == 0. { 1. } else { self.factor };
+        let scaled = (value - self.offset) / factor;
+        let out_of_range = || CanError::FrameConvertFailed(format!("the value {} of signal `{}` is out of range", value, self.name));
+        if !(1..=64).contains(&self.size) {
+            return Err(CanError::FrameConvertFailed(format!("invalid size {} of signal `{}`", self.size, self.name)));
+        }
+        match self.value_type {
+            ValueType::Float => Ok((scaled as f32).to_bits() as u64),
+            ValueType::Double => Ok(scaled.to_bits()),
+            ValueType::Unsigned => {
+                let scaled = scaled.round();
+                let max = if self.size >= 64 { u64::MAX as f64 } else { ((1u64 << self.size) - 1) as f64 };
+                if !(0. ..=max).contains(&scaled) {
+                    return Err(out_of_range());
+                }
+                Ok(scaled as u64)
+            },
+            ValueType::Signed => {
+                let scaled = scaled.round();
+                let max = if self.size >= 64 { i64::MAX as f64 } else { ((1u64 << (self.size - 1)) - 1) as f64 };
+                if !(-max - 1. ..=max).contains(&scaled) {
+                    return Err(out_of_range());
+                }
+                let mask = if self.size >= 64 { u64::MAX } else { (1u64 << self.size) - 1 };
+                Ok(scaled as i64 as u64 & mask)
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Message {
+    /// The identifier without the extended flag.
+    pub id: u32,
+    pub extended: bool,
+    pub name: String,
+    /// The payload size, which is greater than 8 for CAN FD.
+    pub size: usize,
+    pub transmitter: Option<String>,
+    /// The CAN FD message, by the payload size or the `VFrameFormat` attribute.
+    pub fd: bool,
+    pub signals: Vec<Signal>,
+    pub comment: Option<String>,
+    pub attributes: BTreeMap<String, AttributeValue>,
+}
+
+impl Message {
+    #[inline]
+    pub fn signal(&self, name: &str) -> Option<&Signal> {
+        self.signals.iter()
+            .find(|s| s.name == name)
+    }
+
+    /// The identifier of DBC, which bit 31 is set when extended.
+    #[inline]
+    fn dbc_id(&self) -> u32 {
+        if self.extended { self.id | EXTENDED_FLAG } else { self.id }
+    }
+
+    /// The multiplexor of signal, `None` if the signal is not multiplexed.
+    fn switch(&self, signal: &Signal) -> Option<&Signal> {
+        signal.multiplexer.value()?;
+        match &signal.mux_value {
+            Some(v) => self.signal(&v.switch),
+            None => self.signals.iter()
+                .find(|s| s.multiplexer == Multiplexer::Multiplexor),
+        }
+    }
+
+    /// Whether the signal is active by the multiplexors in payload.
+    pub fn is_active(&self, signal: &Signal, data: &[u8]) -> bool {
+        let mut signal = signal;
+        // the depth is limited against the circular multiplexing.
+        for _ in 0..=self.signals.len() {
+            let Some(value) = signal.multiplexer.value() else { return true; };
+            let Some(switch) = self.switch(signal) else { return false; };
+            let Some(raw) = switch.decode_raw(data) else { return false; };
+            let raw = raw as u64;
+            let matched = match &signal.mux_value {
+                Some(v) => v.ranges.iter().any(|(min, max)| (*min..=*max).contains(&raw)),
+                None => raw == value,
+            };
+            if !matched {
+                return false;
+            }
+            signal = switch;
+        }
+
+        false
+    }
+
+    /// The physical values of active signals.
+    pub fn decode(&self, data: &[u8]) -> Vec<(&Signal, f64)> {
+        self.signals.iter()
+            .filter(|s| self.is_active(s, data))
+            .filter_map(|s| Some((s, s.decode(data)?)))
+            .collect()
+    }
+
+    /// Encode the physical values by signal names, the multiplexors are encoded before multiplexed signals,
+    /// and the bits of signals not given are zero.
+    pub fn encode<K: AsRef<str>>(&self, values: impl IntoIterator<Item = (K, f64)>) -> Result<Vec<u8>, CanError> {
+        let mut pending = Vec::new();
+        for (name, value) in values {
+            let name = name.as_ref();
+            let signal = self.signal(name)
+                .ok_or_else(|| CanError::FrameConvertFailed(format!("signal `{}` is not in message `{}`", name, self.name)))?;
+            pending.push((signal, value));
+        }
+
+        let mut data = vec![0; self.size];
+        while !pending.is_empty() {
+            // the signals which multiplexor is encoded or not given.
+            let (ready, rest): (Vec<_>, Vec<_>) = pending.iter()
+                .partition(|(s, _)| self.switch(s)
+                    .is_none_or(|switch| pending.iter().all(|(p, _)| p.name != switch.name)));
+            if ready.is_empty() {
+                return Err(CanError::FrameConvertFailed(format!("the multiplexing of message `{}` is circular", self.name)));
+            }
+            for (signal, value) in ready {
+                if !self.is_active(signal, &data) {
+                    return Err(CanError::FrameConvertFailed(format!("signal `{}` is not active by multiplexor", signal.name)));
+                }
+                signal.encode(&mut data, value)?;
+            }
+            pending = rest;
+        }
+
+        Ok(data)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Database {
+    pub version: String,
+    pub nodes: Vec<Node>,
+    /// The global value tables, `VAL_TABLE_`.
+    pub value_tables: BTreeMap<String, BTreeMap<i64, String>>,
+    pub messages: Vec<Message>,
+    pub attribute_definitions: Vec<AttributeDefinition>,
+    /// The attributes of network.
+    pub attributes: BTreeMap<String, AttributeValue>,
+    pub comment: Option<String>,
+}
+
+impl FromStr for Database {
+    type Err = CanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Parser { tokens: tokenize(s)?, pos: 0 }.parse()
+    }
+}
+
+impl Database {
+    /// Load the DBC file, which is decoded as Windows-1252 if it's not UTF-8.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let content = std::fs::read(path).map_err(io_error)?;
+        let content = String::from_utf8(content)
+            .unwrap_or_else(|e| e.into_bytes().into_iter().map(windows_1252).collect());
+        content.parse()
+    }
+
+    #[inline]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CanError> {
+        std::fs::write(path, self.to_string()).map_err(io_error)
+    }
+
+    #[inline]
+    pub fn message(&self, name: &str) -> Option<&Message> {
+        self.messages.iter()
+            .find(|m| m.name == name)
+    }
+
+    #[inline]
+    pub fn message_by_id(&self, id: u32, extended: bool) -> Option<&Message> {
+        self.messages.iter()
+            .find(|m| m.id == id && m.extended == extended)
+    }
+
+    /// The definition of attribute.
+    #[inline]
+    pub fn attribute_definition(&self, name: &str) -> Option<&AttributeDefinition> {
+        self.attribute_definitions.iter()
+            .find(|d| d.name == name)
+    }
+
+    /// The attribute of message, the default is used when it's not set.
+    pub fn message_attribute<'a>(&'a self, message: &'a Message, name: &str) -> Option<&'a AttributeValue> {
+        message.attributes.get(name)
+            .or_else(|| self.attribute_definition(name)?.default.as_ref())
+    }
+
+    /// Decode the frame by the message of same identifier.
+    pub fn decode<'a, C>(&'a self, msg: &CanMessage<C>) -> Option<(&'a Message, Vec<(&'a Signal, f64)>)>
+    where
+        C: Display + Clone + Default + Send + Sync,
+    {
+        if msg.is_error_frame() || msg.is_remote() {
+            return None;
+        }
+        let message = self.message_by_id(msg.id().as_raw(), msg.is_extended())?;
+        Some((message, message.decode(msg.data())))
+    }
+
+    /// Encode the frame of message `name` by the physical values of signals.
+    pub fn encode<C, K>(&self, name: &str, values: impl IntoIterator<Item = (K, f64)>) -> Result<CanMessage<C>, CanError>
+    where
+        C: Display + Clone + Default + Send + Sync,
+        K: AsRef<str>,
+    {
+        let message = self.message(name)
+            .ok_or_else(|| CanError::FrameConvertFailed(format!("message `{}` is not in database", name)))?;
+        let data = message.encode(values)?;
+        let mut msg = CanMessage::new(Id::from_bits(message.id, message.extended), &data)
+            .ok_or_else(|| CanError::FrameConvertFailed(format!("invalid size {} of message `{}`", message.size, name)))?;
+        if message.fd {
+            msg.set_can_fd(true);
+        }
+
+        Ok(msg)
+    }
+
+    /// Resolve the CAN FD of messages.
+    fn resolve_fd(&mut self) {
+        let formats = match self.attribute_definition(FRAME_FORMAT) {
+            Some(AttributeDefinition { kind: AttributeKind::Enum(values), .. }) => values.clone(),
+            _ => Default::default(),
+        };
+        let default = self.attribute_definition(FRAME_FORMAT)
+            .and_then(|d| d.default.clone());
+        for message in self.messages.iter_mut() {
+            let format = match message.attributes.get(FRAME_FORMAT).or(default.as_ref()) {
+                Some(AttributeValue::Int(i)) => usize::try_from(*i).ok()
+                    .and_then(|i| formats.get(i))
+                    .cloned(),
+                Some(AttributeValue::String(v)) => Some(v.clone()),
+                _ => None,
+            };
+            message.fd = message.size > 8 || format.is_some_and(|v| v.ends_with("_FD"));
+        }
+    }
+}
+
+impl Display for Database {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "VERSION \"{}\"\n\n", escape(&self.version))?;
+        writeln!(f, "NS_ :")?;
+        for symbol in NEW_SYMBOLS {
+            writeln!(f, "\t{}", symbol)?;
+        }
+        writeln!(f, "\nBS_:\n")?;
+        write!(f, "BU_:")?;
+        for node in &self.nodes {
+            write!(f, " {}", node.name)?;
+        }
+        writeln!(f, "\n")?;
+
+        for (name, table) in &self.value_tables {
+            write!(f, "VAL_TABLE_ {}", name)?;
+            write_descriptions(f, table)?;
+            writeln!(f, " ;")?;
+        }
+        if !self.value_tables.is_empty() {
+            writeln!(f)?;
+        }
+
+        for message in &self.messages {
+            writeln!(f, "BO_ {} {}: {} {}", message.dbc_id(), message.name, message.size,
+                     message.transmitter.as_deref().unwrap_or(NO_NODE))?;
+            for signal in &message.signals {
+                let multiplexer = match signal.multiplexer {
+                    Multiplexer::Plain => String::new(),
+                    Multiplexer::Multiplexor => " M".into(),
+                    Multiplexer::Multiplexed(v) => format!(" m{}", v),
+                    Multiplexer::MultiplexedMultiplexor(v) => format!(" m{}M", v),
+                };
+                let receivers = match signal.receivers.is_empty() {
+                    true => NO_NODE.into(),
+                    false => signal.receivers.join(","),
+                };
+                writeln!(f, " SG_ {}{} : {}|{}@{}{} ({},{}) [{}|{}] \"{}\" {}",
+                         signal.name, multiplexer, signal.start_bit, signal.size,
+                         if signal.byte_order == ByteOrder::LittleEndian { 1 } else { 0 },
+                         if signal.value_type == ValueType::Unsigned { '+' } else { '-' },
+                         signal.factor, signal.offset, signal.min, signal.max, escape(&signal.unit), receivers)?;
+            }
+            writeln!(f)?;
+        }
+
+        if let Some(comment) = &self.comment {
+            writeln!(f, "CM_ \"{}\";", escape(comment))?;
+        }
+        for node in &self.nodes {
+            if let Some(comment) = &node.comment {
+                writeln!(f, "CM_ BU_ {} \"{}\";", node.name, escape(comment))?;
+            }
+        }
+        for message in &self.messages {
+            if let Some(comment) = &message.comment {
+                writeln!(f, "CM_ BO_ {} \"{}\";", message.dbc_id(), escape(comment))?;
+            }
+            for signal in &message.signals {
+                if let Some(comment) = &signal.comment {
+                    writeln!(f, "CM_ SG_ {} {} \"{}\";", message.dbc_id(), signal.name, escape(comment))?;
+                }
+            }
+        }
+
+        for definition in &self.attribute_definitions {
+            let object = match definition.object {
+                AttributeObject::Network => "",
+                AttributeObject::Node => "BU_ ",
+                AttributeObject::Message => "BO_ ",
+                AttributeObject::Signal => "SG_ ",
+                AttributeObject::EnvironmentVariable => "EV_ ",
+            };
+            writeln!(f, "BA_DEF_ {}\"{}\" {};", object, definition.name, definition.kind)?;
+        }
+        for definition in &self.attribute_definitions {
+            if let Some(default) = &definition.default {
+                writeln!(f, "BA_DEF_DEF_ \"{}\" {};", definition.name, default)?;
+            }
+        }
+        for (name, value) in &self.attributes {
+            writeln!(f, "BA_ \"{}\" {};", name, value)?;
+        }
+        for node in &self.nodes {
+            for (name, value) in &node.attributes {
+                writeln!(f, "BA_ \"{}\" BU_ {} {};", name, node.name, value)?;
+            }
+        }
+        for message in &self.messages {
+            for (name, value) in &message.attributes {
+                writeln!(f, "BA_ \"{}\" BO_ {} {};", name, message.dbc_id(), value)?;
+            }
+            for signal in &message.signals {
+                for (name, value) in &signal.attributes {
+                    writeln!(f, "BA_ \"{}\" SG_ {} {} {};", name, message.dbc_id(), signal.name, value)?;
+                }
+            }
+        }
+
+        for message in &self.messages {
+            for signal in message.signals.iter().filter(|s| !s.value_descriptions.is_empty()) {
+                write!(f, "VAL_ {} {}", message.dbc_id(), signal.name)?;
+                write_descriptions(f, &signal.value_descriptions)?;
+                writeln!(f, " ;")?;
+            }
+        }
+        for message in &self.messages {
+            for signal in &message.signals {
+                match signal.value_type {
+                    ValueType::Float => writeln!(f, "SIG_VALTYPE_ {} {} : 1;", message.dbc_id(), signal.name)?,
+                    ValueType::Double => writeln!(f, "SIG_VALTYPE_ {} {} : 2;", message.dbc_id(), signal.name)?,
+                    _ => {},
+                }
+            }
+        }
+        for message in &self.messages {
+            for signal in &message.signals {
+                if let Some(v) = &signal.mux_value {
+                    let ranges = v.ranges.iter()
+                        .map(|(min, max)| format!("{}-{}", min, max))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    writeln!(f, "SG_MUL_VAL_ {} {} {} {};", message.dbc_id(), signal.name, v.switch, ranges)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[inline]
+fn windows_1252(byte: u8) -> char {
+    match byte {
+        0x80..=0x9F => WINDOWS_1252[(byte - 0x80) as usize],
+        _ => char::from(byte),
+    }
+}
+
+#[inline]
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+}
+
+fn write_descriptions(f: &mut Formatter<'_>, descriptions: &BTreeMap<i64, String>) -> std::fmt::Result {
+    for (value, description) in descriptions.iter().rev() {
+        write!(f, " {} \"{}\"", value, escape(description))?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Punct(char),
+    Newline,
+}
+
+#[derive(Debug)]
+struct Lexeme {
+    token: Token,
+    line: usize,
+    /// The first token of line without indentation.
+    head: bool,
+}
+
+fn tokenize(content: &str) -> Result<Vec<Lexeme>, CanError> {
+    const PUNCTS: &str = ":;,|@()[]";
+    let chars = content.chars().collect::<Vec<_>>();
+    let mut results = Vec::new();
+    let (mut i, mut line) = (0, 1);
+    let (mut first, mut indented) = (true, false);
+    while i < chars.len() {
+        let c = chars[i];
+        let begin = line;
+        let token = match c {
+            '\n' => {
+                results.push(Lexeme { token: Token::Newline, line, head: false });
+                line += 1;
+                (first, indented) = (true, false);
+                i += 1;
+                continue;
+            },
+            _ if c.is_whitespace() => {
+                indented |= first;
+                i += 1;
+                continue;
+            },
+            '"' => {
+                let mut value = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(parse_error(begin, "unterminated string")),
+                        Some('"') => break,
+                        Some('\\') if matches!(chars.get(i + 1), Some('"' | '\\')) => {
+                            i += 1;
+                            value.push(chars[i]);
+                        },
+                        Some(&c) => {
+                            if c == '\n' {
+                                line += 1;
+                            }
+                            value.push(c);
+                        },
+                    }
+                    i += 1;
+                }
+                i += 1;
+                Token::Str(value)
+            },
+            _ if PUNCTS.contains(c) => {
+                i += 1;
+                Token::Punct(c)
+            },
+            _ => {
+                let start = i;
+                while i < chars.len() && !chars[i].is_whitespace() && !PUNCTS.contains(chars[i]) && chars[i] != '"' {
+                    i += 1;
+                }
+                Token::Word(chars[start..i].iter().collect())
+            },
+        };
+        results.push(Lexeme { token, line: begin, head: first && !indented });
+        first = false;
+    }
+
+    Ok(results)
+}
+
+/// The tokens of a statement without the keyword.
+struct Statement {
+    tokens: Vec<Token>,
+    pos: usize,
+    line: usize,
+}
+
+impl Statement {
+    fn error(&self) -> CanError {
+        let content = self.tokens.iter()
+            .map(|t| match t {
+                Token::Word(v) => v.clone(),
+                Token::Str(v) => format!("\"{}\"", v),
+                Token::Punct(c) => c.to_string(),
+                Token::Newline => Default::default(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        parse_error(self.line, &content)
+    }
+
+    #[inline]
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    #[inline]
+    fn is_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    fn next(&mut self) -> Result<Token, CanError> {
+        let token = self.tokens.get(self.pos)
+            .cloned()
+            .ok_or_else(|| self.error())?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn word(&mut self) -> Result<String, CanError> {
+        match self.next()? {
+            Token::Word(v) => Ok(v),
+            _ => Err(self.error()),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, CanError> {
+        match self.next()? {
+            Token::Str(v) => Ok(v),
+            _ => Err(self.error()),
+        }
+    }
+
+    fn number<T: FromStr>(&mut self) -> Result<T, CanError> {
+        self.word()?
+            .parse()
+            .map_err(|_| self.error())
+    }
+
+    fn punct(&mut self, c: char) -> Result<(), CanError> {
+        match self.next()? {
+            Token::Punct(v) if v == c => Ok(()),
+            _ => Err(self.error()),
+        }
+    }
+
+    /// Consume the punctuation if it's next.
+    fn accept(&mut self, c: char) -> bool {
+        let matched = self.peek() == Some(&Token::Punct(c));
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    /// Consume the object keyword, such as `BO_`, if it's next.
+    fn object(&mut self) -> Option<AttributeObject> {
+        let object = match self.peek()? {
+            Token::Word(v) => match v.as_str() {
+                "BU_" => AttributeObject::Node,
+                "BO_" => AttributeObject::Message,
+                "SG_" => AttributeObject::Signal,
+                "EV_" => AttributeObject::EnvironmentVariable,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        self.pos += 1;
+        Some(object)
+    }
+
+    fn attribute_value(&mut self) -> Result<AttributeValue, CanError> {
+        match self.next()? {
+            Token::Str(v) => Ok(AttributeValue::String(v)),
+            Token::Word(v) => v.parse().map(AttributeValue::Int)
+                .or_else(|_| v.parse().map(AttributeValue::Float))
+                .map_err(|_| self.error()),
+            _ => Err(self.error()),
+        }
+    }
+
+    /// The pairs of `value "description"` until the end.
+    fn descriptions(&mut self) -> Result<BTreeMap<i64, String>, CanError> {
+        let mut results = BTreeMap::new();
+        while !self.is_end() {
+            let value = self.number()?;
+            results.insert(value, self.string()?);
+        }
+        Ok(results)
+    }
+}
+
+struct Parser {
+    tokens: Vec<Lexeme>,
+    pos: usize,
+}
+
+impl Parser {
+    fn parse(mut self) -> Result<Database, CanError> {
+        let mut db = Database::default();
+        while let Some(lexeme) = self.tokens.get(self.pos) {
+            let line = lexeme.line;
+            let keyword = match &lexeme.token {
+                Token::Word(v) => Some(v.clone()),
+                Token::Newline => None,
+                _ => return Err(parse_error(line, "unexpected token")),
+            };
+            self.pos += 1;
+            let Some(keyword) = keyword else { continue; };
+
+            match keyword.as_str() {
+                "NS_" => self.skip_new_symbols(),
+                "VERSION" => db.version = self.line_statement(line).string()?,
+                "BS_" => { self.line_statement(line); },
+                "BU_" => {
+                    let mut stmt = self.line_statement(line);
+                    stmt.punct(':')?;
+                    while !stmt.is_end() {
+                        db.nodes.push(Node { name: stmt.word()?, ..Default::default() });
+                    }
+                },
+                "BO_" => db.messages.push(parse_message(&mut self.line_statement(line))?),
+                "SG_" => {
+                    let mut stmt = self.line_statement(line);
+                    let signal = parse_signal(&mut stmt)?;
+                    db.messages.last_mut()
+                        .ok_or_else(|| stmt.error())?
+                        .signals.push(signal);
+                },
+                _ => {
+                    let mut stmt = self.statement(line);
+                    parse_statement(&mut db, &keyword, &mut stmt)?;
+                },
+            }
+        }
+        db.resolve_fd();
+
+        Ok(db)
+    }
+
+    /// Skip the symbols until the next line without indentation.
+    fn skip_new_symbols(&mut self) {
+        while self.tokens.get(self.pos).is_some_and(|v| !v.head) {
+            self.pos += 1;
+        }
+    }
+
+    /// The tokens until the end of line.
+    fn line_statement(&mut self, line: usize) -> Statement {
+        let mut tokens = Vec::new();
+        while let Some(lexeme) = self.tokens.get(self.pos) {
+            self.pos += 1;
+            if lexeme.token == Token::Newline {
+                break;
+            }
+            tokens.push(lexeme.token.clone());
+        }
+        Statement { tokens, pos: 0, line }
+    }
+
+    /// The tokens until `;`.
+    fn statement(&mut self, line: usize) -> Statement {
+        let mut tokens = Vec::new();
+        while let Some(lexeme) = self.tokens.get(self.pos) {
+            self.pos += 1;
+            match &lexeme.token {
+                Token::Punct(';') => break,
+                Token::Newline => {},
+                token => tokens.push(token.clone()),
+            }
+        }
+        Statement { tokens, pos: 0, line }
+    }
+}
+
+fn find_message<'a>(db: &'a mut Database, stmt: &mut Statement) -> Result<&'a mut Message, CanError> {
+    let id: u32 = stmt.number()?;
+    db.messages.iter_mut()
+        .find(|m| m.dbc_id() == id)
+        .ok_or_else(|| stmt.error())
+}
+
+fn find_signal<'a>(db: &'a mut Database, stmt: &mut Statement) -> Result<&'a mut Signal, CanError> {
+    let message = find_message(db, stmt)?;
+    let name = stmt.word()?;
+    message.signals.iter_mut()
+        .find(|s| s.name == name)
+        .ok_or_else(|| stmt.error())
+}
+
+/// `BO_ <id> <name>: <size> <transmitter>`
+fn parse_message(stmt: &mut Statement) -> Result<Message, CanError> {
+    let id: u32 = stmt.number()?;
+    let name = stmt.word()?;
+    stmt.punct(':')?;
+    let size = stmt.number()?;
+    let transmitter = match stmt.is_end() {
+        true => None,
+        false => Some(stmt.word()?).filter(|v| v != NO_NODE),
+    };
+
+    Ok(Message {
+        id: id & !EXTENDED_FLAG,
+        extended: id & EXTENDED_FLAG != 0,
+        name,
+        size,
+        transmitter,
+        ..Default::default()
+    })
+}
+
+/// `SG_ <name> [M|m<value>|m<value>M] : <start>|<size>@<order><sign> (<factor>,<offset>) [<min>|<max>] "<unit>" <receivers>`
+fn parse_signal(stmt: &mut Statement) -> Result<Signal, CanError> {
+    let name = stmt.word()?;
+    let multiplexer = match stmt.peek() {
+        Some(Token::Word(_)) => {
+            let value = stmt.word()?;
+            match value.as_str() {
+                "M" => Multiplexer::Multiplexor,
+                _ => {
+                    let digits = value.strip_prefix('m').ok_or_else(|| stmt.error())?;
+                    match digits.strip_suffix('M') {
+                        Some(v) => Multiplexer::MultiplexedMultiplexor(v.parse().map_err(|_| stmt.error())?),
+                        None => Multiplexer::Multiplexed(digits.parse().map_err(|_| stmt.error())?),
+                    }
+                },
+            }
+        },
+        _ => Multiplexer::Plain,
+    };
+    stmt.punct(':')?;
+    let start_bit = stmt.number()?;
+    stmt.punct('|')?;
+    let size = stmt.number()?;
+    if !(1..=64).contains(&size) {
+        return Err(stmt.error());
+    }
+    stmt.punct('@')?;
+    let mut order = stmt.word()?;
+    if order.len() == 1 {
+        order.push_str(&stmt.word()?);
+    }
+    let byte_order = match &order[..1] {
+        "0" => ByteOrder::BigEndian,
+        "1" => ByteOrder::LittleEndian,
+        _ => return Err(stmt.error()),
+    };
+    let value_type = match &order[1..] {
+        "+" => ValueType::Unsigned,
+        "-" => ValueType::Signed,
+        _ => return Err(stmt.error()),
+    };
+    stmt.punct('(')?;
+    let factor = stmt.number()?;
+    stmt.punct(',')?;
+    let offset = stmt.number()?;
+    stmt.punct(')')?;
+    stmt.punct('[')?;
+    let min = stmt.number()?;
+    stmt.punct('|')?;
+    let max = stmt.number()?;
+    stmt.punct(']')?;
+    let unit = stmt.string()?;
+    let mut receivers = Vec::new();
+    while !stmt.is_end() {
+        let receiver = stmt.word()?;
+        if receiver != NO_NODE {
+            receivers.push(receiver);
+        }
+        stmt.accept(',');
+    }
+
+    Ok(Signal {
+        name,
+        start_bit,
+        size,
+        byte_order,
+        value_type,
+        factor,
+        offset,
+        min,
+        max,
+        unit,
+        receivers,
+        multiplexer,
+        ..Default::default()
+    })
+}
+
+/// The statements ended with `;`, the unknown are ignored.
+fn parse_statement(db: &mut Database, keyword: &str, stmt: &mut Statement) -> Result<(), CanError> {
+    match keyword {
+        "CM_" => {
+            match stmt.object() {
+                None => db.comment = Some(stmt.string()?),
+                Some(AttributeObject::Node) => {
+                    let name = stmt.word()?;
+                    let comment = stmt.string()?;
+                    db.nodes.iter_mut()
+                        .find(|n| n.name == name)
+                        .ok_or_else(|| stmt.error())?
+                        .comment = Some(comment);
+                },
+                Some(AttributeObject::Message) => {
+                    let message = find_message(db, stmt)?;
+                    message.comment = Some(stmt.string()?);
+                },
+                Some(AttributeObject::Signal) => {
+                    let signal = find_signal(db, stmt)?;
+                    signal.comment = Some(stmt.string()?);
+                },
+                _ => {},
+            }
+        },
+        "BA_DEF_" => {
+            let object = stmt.object().unwrap_or_default();
+            let name = stmt.string()?;
+            let kind = match stmt.word()?.as_str() {
+                "INT" => AttributeKind::Int(stmt.number()?, stmt.number()?),
+                "HEX" => AttributeKind::Hex(stmt.number()?, stmt.number()?),
+                "FLOAT" => AttributeKind::Float(stmt.number()?, stmt.number()?),
+                "STRING" => AttributeKind::String,
+                "ENUM" => {
+                    let mut values = Vec::new();
+                    while !stmt.is_end() {
+                        values.push(stmt.string()?);
+                        stmt.accept(',');
+                    }
+                    AttributeKind::Enum(values)
+                },
+                _ => return Err(stmt.error()),
+            };
+            db.attribute_definitions.push(AttributeDefinition { name, object, kind, default: None });
+        },
+        "BA_DEF_DEF_" => {
+            let name = stmt.string()?;
+            let value = stmt.attribute_value()?;
+            if let Some(definition) = db.attribute_definitions.iter_mut().find(|d| d.name == name) {
+                definition.default = Some(value);
+            }
+        },
+        "BA_" => {
+            let name = stmt.string()?;
+            match stmt.object() {
+                None => {
+                    let value = stmt.attribute_value()?;
+                    db.attributes.insert(name, value);
+                },
+                Some(AttributeObject::Node) => {
+                    let node = stmt.word()?;
+                    let value = stmt.attribute_value()?;
+                    db.nodes.iter_mut()
+                        .find(|n| n.name == node)
+                        .ok_or_else(|| stmt.error())?
+                        .attributes.insert(name, value);
+                },
+                Some(AttributeObject::Message) => {
+                    let message = find_message(db, stmt)?;
+                    message.attributes.insert(name, stmt.attribute_value()?);
+                },
+                Some(AttributeObject::Signal) => {
+                    let signal = find_signal(db, stmt)?;
+                    signal.attributes.insert(name, stmt.attribute_value()?);
+                },
+                _ => {},
+            }
+        },
+        "VAL_TABLE_" => {
+            let name = stmt.word()?;
+            let table = stmt.descriptions()?;
+            db.value_tables.insert(name, table);
+        },
+        "VAL_" => {
+            // the value descriptions of environment variable are ignored.
+            if matches!(stmt.peek(), Some(Token::Word(v)) if v.parse::<u32>().is_ok()) {
+                let signal = find_signal(db, stmt)?;
+                signal.value_descriptions = stmt.descriptions()?;
+            }
+        },
+        "SIG_VALTYPE_" => {
+            let signal = find_signal(db, stmt)?;
+            stmt.accept(':');
+            match stmt.number::<u8>()? {
+                0 => {},
+                1 => signal.value_type = ValueType::Float,
+                2 => signal.value_type = ValueType::Double,
+                _ => return Err(stmt.error()),
+            }
+        },
+        "SG_MUL_VAL_" => {
+            let signal = find_signal(db, stmt)?;
+            let switch = stmt.word()?;
+            let mut ranges = Vec::new();
+            while !stmt.is_end() {
+                let range = stmt.word()?;
+                let (min, max) = range.split_once('-')
+                    .and_then(|(min, max)| Some((min.parse().ok()?, max.parse().ok()?)))
+                    .ok_or_else(|| stmt.error())?;
+                ranges.push((min, max));
+                stmt.accept(',');
+            }
+            signal.mux_value = Some(MuxValue { switch, ranges });
+        },
+        _ => {},
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use isotp_rs::can::{frame::Frame, identifier::Id};
+    use crate::CanMessage;
+    use super::{windows_1252, AttributeValue, ByteOrder, Database, Multiplexer, ValueType};
+
+    const DBC: &str = r#"VERSION "1.0"
+
+
+NS_ :
+	NS_DESC_
+	CM_
+	BA_DEF_
+	BA_
+
+BS_:
+
+BU_: ECU Tester
+
+VAL_TABLE_ OnOff 1 "On" 0 "Off" ;
+
+BO_ 256 Engine: 8 ECU
+ SG_ Speed : 0|16@1+ (0.1,0) [0|6553.5] "km/h" Tester
+ SG_ Temperature : 19|12@0- (0.5,-40) [-1064|983.5] "degC" Tester,ECU
+ SG_ Gear : 20|4@1+ (1,0) [0|15] "" Vector__XXX
+ SG_ Ratio : 32|32@1- (1,0) [0|0] "" Tester
+
+BO_ 2147484416 Diag: 8 Tester
+ SG_ Service M : 0|8@1+ (1,0) [0|255] "" ECU
+ SG_ Sub m1M : 8|8@1+ (1,0) [0|255] "" ECU
+ SG_ Session m16 : 8|8@1+ (1,0) [0|255] "" ECU
+ SG_ Data m1 : 16|16@1+ (1,0) [0|65535] "" ECU
+ SG_ Value m2 : 24|8@1+ (1,0) [0|255] "" ECU
+
+BO_ 512 Fd: 64 ECU
+ SG_ Last : 504|8@1+ (1,0) [0|255] "" Tester
+
+CM_ "The \"test\" network";
+CM_ BU_ ECU "The engine
+control unit";
+CM_ BO_ 256 "Engine status";
+CM_ SG_ 256 Speed "Vehicle speed";
+BA_DEF_ BO_ "GenMsgCycleTime" INT 0 65535;
+BA_DEF_ BO_ "VFrameFormat" ENUM "StandardCAN","ExtendedCAN","reserved","J1939PG","StandardCAN_FD","ExtendedCAN_FD";
+BA_DEF_ "BusType" STRING ;
+BA_DEF_ SG_ "GenSigStartValue" FLOAT 0 100000;
+BA_DEF_DEF_ "GenMsgCycleTime" 0;
+BA_DEF_DEF_ "VFrameFormat" "StandardCAN";
+BA_ "BusType" "CAN FD";
+BA_ "GenMsgCycleTime" BO_ 256 100;
+BA_ "VFrameFormat" BO_ 512 4;
+BA_ "GenSigStartValue" SG_ 256 Speed 1.5;
+VAL_ 256 Gear 0 "Neutral" 1 "First" 15 "Invalid" ;
+SIG_VALTYPE_ 256 Ratio : 1;
+SG_MUL_VAL_ 2147484416 Data Sub 1-1, 3-5;
+SG_MUL_VAL_ 2147484416 Sub Service 1-1;
+SG_MUL_VAL_ 2147484416 Value Sub 2-2;
+"#;
+
+    #[test]
+    fn test_parse() -> anyhow::Result<()> {
+        let db: Database = DBC.parse()?;
+        assert_eq!(db.version, "1.0");
+        assert_eq!(db.nodes.len(), 2);
+        assert_eq!(db.nodes[0].comment.as_deref(), Some("The engine\ncontrol unit"));
+        assert_eq!(db.comment.as_deref(), Some("The \"test\" network"));
+        assert_eq!(db.value_tables["OnOff"][&1], "On");
+        assert_eq!(db.attributes["BusType"], AttributeValue::String("CAN FD".into()));
+
+        let engine = db.message("Engine").unwrap();
+        assert_eq!((engine.id, engine.extended, engine.fd), (0x100, false, false));
+        assert_eq!(engine.transmitter.as_deref(), Some("ECU"));
+        assert_eq!(engine.attributes["GenMsgCycleTime"], AttributeValue::Int(100));
+        let temperature = engine.signal("Temperature").unwrap();
+        assert_eq!((temperature.byte_order, temperature.value_type), (ByteOrder::BigEndian, ValueType::Signed));
+        assert_eq!(temperature.receivers, vec!["Tester", "ECU"]);
+        assert_eq!(engine.signal("Gear").unwrap().receivers, Vec::<String>::new());
+        assert_eq!(engine.signal("Ratio").unwrap().value_type, ValueType::Float);
+        assert_eq!(engine.signal("Speed").unwrap().attributes["GenSigStartValue"], AttributeValue::Float(1.5));
+
+        let diag = db.message_by_id(0x300, true).unwrap();
+        assert_eq!(diag.signal("Sub").unwrap().multiplexer, Multiplexer::MultiplexedMultiplexor(1));
+        assert_eq!(diag.signal("Data").unwrap().mux_value.as_ref().unwrap().ranges, vec![(1, 1), (3, 5)]);
+
+        let fd = db.message("Fd").unwrap();
+        assert!(fd.fd);
+        assert_eq!(db.message_attribute(engine, "VFrameFormat"), Some(&AttributeValue::String("StandardCAN".into())));
+
+        assert!("BO_ 1 Bad: 8 ECU\n SG_ Value : 0|65@1+ (1,0) [0|0] \"\" ECU\n".parse::<Database>().is_err());
+        assert!("SG_ Value : 0|8@1+ (1,0) [0|0] \"\" ECU\n".parse::<Database>().is_err());
+        let decoded = [b'A', 0x80, 0x81, 0x9F, 0xE9].map(windows_1252);
+        assert_eq!(decoded, ['A', '\u{20AC}', '\u{81}', '\u{0178}', '\u{E9}']);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_codec() -> anyhow::Result<()> {
+        let db: Database = DBC.parse()?;
+        let engine = db.message("Engine").unwrap();
+
+        // 123.4km/h, -20degC(raw 40, Motorola from bit 19), 3rd gear, 0.5 ratio
+        let msg: CanMessage<u8> = db.encode("Engine", [("Speed", 123.4), ("Temperature", -20.), ("Gear", 3.), ("Ratio", 0.5)])?;
+        assert_eq!(msg.id(), Id::from_bits(0x100, false));
+        assert_eq!(msg.data(), &[0xD2, 0x04, 0x30, 0x28, 0x00, 0x00, 0x00, 0x3F]);
+        let (message, values) = db.decode(&msg).unwrap();
+        assert_eq!(message.name, "Engine");
+        let value = |name: &str| values.iter().find(|(s, _)| s.name == name).map(|(_, v)| *v);
+        assert!((value("Speed").unwrap() - 123.4).abs() < 1e-9);
+        assert_eq!(value("Temperature"), Some(-20.));
+        assert_eq!(value("Ratio"), Some(0.5));
+        assert_eq!(engine.signal("Gear").unwrap().describe(msg.data()), None);
+        assert_eq!(engine.signal("Gear").unwrap().describe(&[0, 0, 0xF0]), Some("Invalid"));
+
+        let temperature = engine.signal("Temperature").unwrap();
+        assert_eq!(temperature.decode(&[0, 0, 0x0F, 0xF0, 0, 0, 0, 0]), Some(-48.));
+        assert!(db.encode::<u8, _>("Engine", [("Gear", 16.)]).is_err());
+        assert!(db.encode::<u8, _>("Engine", [("Unknown", 1.)]).is_err());
+
+        // the extended multiplexing
+        let diag = db.message("Diag").unwrap();
+        let data = diag.encode([("Data", 0x1234 as f64), ("Sub", 4.), ("Service", 1.)])?;
+        assert_eq!(data, vec![0x01, 0x04, 0x34, 0x12, 0, 0, 0, 0]);
+        let names = diag.decode(&data).into_iter().map(|(s, _)| s.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Service", "Sub", "Data"]);
+        let names = diag.decode(&[0x10, 0x03, 0, 0, 0, 0, 0, 0]).into_iter().map(|(s, _)| s.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["Service", "Session"]);
+        assert!(diag.encode([("Value", 1.), ("Sub", 4.), ("Service", 1.)]).is_err());
+
+        let msg: CanMessage<u8> = db.encode("Fd", [("Last", 0xAA as f64)])?;
+        assert!(msg.is_can_fd());
+        assert_eq!(msg.data().len(), 64);
+        assert_eq!(msg.data()[63], 0xAA);
+
+        // the invalid size set by caller
+        let mut signal = temperature.clone();
+        for size in [0, 65] {
+            signal.size = size;
+            assert_eq!(signal.decode(&[0; 8]), None);
+            assert!(signal.encode(&mut [0; 8], 0.).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write() -> anyhow::Result<()> {
+        let db: Database = DBC.parse()?;
+        let content = db.to_string();
+        assert!(content.contains(" SG_ Temperature : 19|12@0- (0.5,-40) [-1064|983.5] \"degC\" Tester,ECU\n"));
+        assert!(content.contains("BO_ 2147484416 Diag: 8 Tester\n"));
+        assert!(content.contains("VAL_ 256 Gear 15 \"Invalid\" 1 \"First\" 0 \"Neutral\" ;\n"));
+        assert!(content.contains("SG_MUL_VAL_ 2147484416 Data Sub 1-1, 3-5;\n"));
+        let parsed: Database = content.parse()?;
+        assert_eq!(parsed, db);
+
+        Ok(())
+    }
+}
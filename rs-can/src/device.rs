@@ -1,3 +1,5 @@
+use isotp_rs::device::Driver;
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct CanFilter {
     pub can_id: u32,
@@ -5,8 +7,76 @@ pub struct CanFilter {
     pub extended: bool
 }
 
-#[derive(Debug, Copy, Clone)]
+/// The common channel configuration used by every backend.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
 pub struct ChannelConfig {
     pub bitrate: u32,
-    pub dbitrate: u32,
+    /// The data phase bitrate, `None` means CAN 2.0 only.
+    pub dbitrate: Option<u32>,
+    /// Enable the terminal resistance, `None` means keep the device default.
+    pub resistance: Option<bool>,
+    pub listen_only: bool,
+    pub filters: Vec<CanFilter>,
+}
+
+impl ChannelConfig {
+    pub fn new(bitrate: u32) -> Self {
+        Self {
+            bitrate,
+            ..Default::default()
+        }
+    }
+    #[inline]
+    pub fn set_data_bitrate(&mut self, dbitrate: u32) -> &mut Self {
+        self.dbitrate = Some(dbitrate);
+        self
+    }
+    #[inline]
+    pub fn set_resistance(&mut self, resistance: bool) -> &mut Self {
+        self.resistance = Some(resistance);
+        self
+    }
+    #[inline]
+    pub fn set_listen_only(&mut self, listen_only: bool) -> &mut Self {
+        self.listen_only = listen_only;
+        self
+    }
+    #[inline]
+    pub fn add_filter(&mut self, filter: CanFilter) -> &mut Self {
+        self.filters.push(filter);
+        self
+    }
+    #[inline]
+    pub fn is_can_fd(&self) -> bool {
+        self.dbitrate.is_some()
+    }
+}
+
+/// The features supported by a device.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct Capability {
+    /// The count of CAN channels, `None` when the device can't tell it.
+    pub channels: Option<usize>,
+    pub canfd: bool,
+    /// The terminal resistance is configurable.
+    pub resistance: bool,
+    /// The acceptance filter can be pushed down to hardware.
+    pub hardware_filter: bool,
+    /// The count of hardware acceptance filters, `None` is unlimited.
+    pub filter_count: Option<usize>,
+    /// The periodic sending can be offloaded to hardware.
+    pub auto_send: bool,
+    pub listen_only: bool,
+}
+
+/// The unified device interface, transmit and receive are inherited from [`Driver`].
+pub trait CanDevice: Driver {
+    /// Open the device, the channels must be initialized by [`CanDevice::init_channel`] after.
+    fn open_device(&mut self) -> Result<(), Self::Error>;
+    /// Initialize(or re-initialize) a channel.
+    fn init_channel(&mut self, channel: Self::C, cfg: &ChannelConfig) -> Result<(), Self::Error>;
+    fn reset_channel(&mut self, channel: Self::C) -> Result<(), Self::Error>;
+    /// Close all channels and the device.
+    fn close_device(&mut self) -> Result<(), Self::Error>;
+    fn capability(&self) -> Result<Capability, Self::Error>;
 }
@@ -0,0 +1,499 @@
+//! The typed error events of CAN controller, such as bus state transitions, protocol errors,
+//! arbitration lost and overrun.
+//!
+//! An [`ErrorEvent`] can be converted from/to an error flagged [`CanMessage`] with the
+//! SocketCAN error frame layout(linux/can/error.h), so the events can be logged or passed by
+//! the drivers as frames.
+
+use std::fmt::{Display, Formatter};
+use isotp_rs::can::{frame::{Direct, Frame}, identifier::Id};
+use crate::{CanMessage, error::CanError};
+
+/// The error classes in the identifier of SocketCAN error frame.
+const CAN_ERR_TX_TIMEOUT: u32 = 0x0000_0001;
+const CAN_ERR_LOSTARB: u32 = 0x0000_0002;
+const CAN_ERR_CRTL: u32 = 0x0000_0004;
+const CAN_ERR_PROT: u32 = 0x0000_0008;
+const CAN_ERR_ACK: u32 = 0x0000_0020;
+const CAN_ERR_BUSOFF: u32 = 0x0000_0040;
+const CAN_ERR_BUSERROR: u32 = 0x0000_0080;
+const CAN_ERR_CNT: u32 = 0x0000_0200;
+const CAN_ERR_DLC: usize = 8;
+
+/// The controller status in `data[1]`.
+const CAN_ERR_CRTL_RX_OVERFLOW: u8 = 0x01;
+const CAN_ERR_CRTL_TX_OVERFLOW: u8 = 0x02;
+const CAN_ERR_CRTL_RX_WARNING: u8 = 0x04;
+const CAN_ERR_CRTL_TX_WARNING: u8 = 0x08;
+const CAN_ERR_CRTL_RX_PASSIVE: u8 = 0x10;
+const CAN_ERR_CRTL_TX_PASSIVE: u8 = 0x20;
+const CAN_ERR_CRTL_ACTIVE: u8 = 0x40;
+
+/// The protocol error type in `data[2]`.
+const CAN_ERR_PROT_BIT: u8 = 0x01;
+const CAN_ERR_PROT_FORM: u8 = 0x02;
+const CAN_ERR_PROT_STUFF: u8 = 0x04;
+const CAN_ERR_PROT_BIT0: u8 = 0x08;
+const CAN_ERR_PROT_BIT1: u8 = 0x10;
+const CAN_ERR_PROT_OVERLOAD: u8 = 0x20;
+const CAN_ERR_PROT_TX: u8 = 0x80;
+
+/// The protocol error location in `data[3]`.
+const CAN_ERR_PROT_LOC_CRC_SEQ: u8 = 0x08;
+const CAN_ERR_PROT_LOC_CRC_DEL: u8 = 0x18;
+const CAN_ERR_PROT_LOC_ACK: u8 = 0x19;
+
+/// The error code in `bit7-6` of SJA1000 error code capture.
+const ECC_BIT: u8 = 0;
+const ECC_FORM: u8 = 1;
+const ECC_STUFF: u8 = 2;
+const ECC_OTHER: u8 = 3;
+/// The direction in `bit5` of SJA1000 error code capture, it is set when receiving.
+const ECC_RX: u8 = 0x20;
+const ECC_SEGMENT: u8 = 0x1F;
+
+/// The error counter thresholds of ISO 11898-1.
+const WARNING_LIMIT: u8 = 96;
+const PASSIVE_LIMIT: u8 = 128;
+
+/// The fault confinement state of CAN node.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum BusState {
+    #[default]
+    ErrorActive,
+    ErrorWarning,
+    ErrorPassive,
+    BusOff,
+}
+
+impl BusState {
+    /// Get the state by error counters, the bus-off can't be detected by the counters.
+    pub fn from_counters(counters: &ErrorCounters) -> Self {
+        let max = counters.tx.max(counters.rx);
+        if max >= PASSIVE_LIMIT {
+            Self::ErrorPassive
+        }
+        else if max >= WARNING_LIMIT {
+            Self::ErrorWarning
+        }
+        else {
+            Self::ErrorActive
+        }
+    }
+}
+
+impl Display for BusState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ErrorActive => write!(f, "error-active"),
+            Self::ErrorWarning => write!(f, "error-warning"),
+            Self::ErrorPassive => write!(f, "error-passive"),
+            Self::BusOff => write!(f, "bus-off"),
+        }
+    }
+}
+
+/// The transmit and receive error counters.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct ErrorCounters {
+    pub tx: u8,
+    pub rx: u8,
+}
+
+impl ErrorCounters {
+    #[inline]
+    pub fn new(tx: u8, rx: u8) -> Self {
+        Self { tx, rx }
+    }
+}
+
+/// The protocol error detected on bus.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ProtocolError {
+    /// The bit error, the sent bit is different from the monitored.
+    Bit,
+    /// Unable to send dominant bit.
+    Bit0,
+    /// Unable to send recessive bit.
+    Bit1,
+    Stuff,
+    Form,
+    Ack,
+    Crc,
+    Overload,
+    Other,
+}
+
+/// The kind of error event.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ErrorKind {
+    /// The bus state is changed.
+    State(BusState),
+    /// The protocol error, `transmitting` is true when the error is detected while transmitting.
+    Protocol { error: ProtocolError, transmitting: bool },
+    /// The arbitration is lost at the bit position if it's known.
+    ArbitrationLost(Option<u8>),
+    /// The buffer of controller or driver overflowed, frames are lost.
+    Overrun { transmitting: bool },
+    TransmitTimeout,
+}
+
+/// The error event of a channel.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ErrorEvent<C> {
+    timestamp: u64,
+    channel: C,
+    kinds: Vec<ErrorKind>,
+    counters: Option<ErrorCounters>,
+}
+
+impl<C> ErrorEvent<C> {
+    pub fn new(channel: C, timestamp: u64) -> Self {
+        Self { timestamp, channel, kinds: Default::default(), counters: Default::default() }
+    }
+
+    #[inline]
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    #[inline]
+    pub fn channel(&self) -> &C {
+        &self.channel
+    }
+
+    #[inline]
+    pub fn kinds(&self) -> &[ErrorKind] {
+        &self.kinds
+    }
+
+    /// Add an error kind, the duplicated kind is ignored.
+    pub fn add(&mut self, kind: ErrorKind) -> &mut Self {
+        if !self.kinds.contains(&kind) {
+            self.kinds.push(kind);
+        }
+        self
+    }
+
+    #[inline]
+    pub fn counters(&self) -> Option<ErrorCounters> {
+        self.counters
+    }
+
+    #[inline]
+    pub fn set_counters(&mut self, counters: Option<ErrorCounters>) -> &mut Self {
+        self.counters = counters;
+        self
+    }
+
+    /// The reported bus state, otherwise the state is derived from the error counters.
+    pub fn state(&self) -> Option<BusState> {
+        self.kinds.iter()
+            .rev()
+            .find_map(|k| match k {
+                ErrorKind::State(state) => Some(*state),
+                _ => None,
+            })
+            .or(self.counters.as_ref().map(BusState::from_counters))
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.kinds.is_empty() && self.counters.is_none()
+    }
+}
+
+impl<C> From<&ErrorEvent<C>> for CanMessage<C>
+where
+    C: Display + Clone + Default + Send + Sync {
+    fn from(event: &ErrorEvent<C>) -> Self {
+        let mut class = 0;
+        let mut data = [0u8; CAN_ERR_DLC];
+
+        for kind in &event.kinds {
+            match *kind {
+                ErrorKind::State(state) => {
+                    let (tx, rx) = match event.counters {
+                        Some(c) => (c.tx >= c.rx, c.rx >= c.tx),
+                        None => (true, true),
+                    };
+                    match state {
+                        BusState::ErrorActive => {
+                            class |= CAN_ERR_CRTL;
+                            data[1] |= CAN_ERR_CRTL_ACTIVE;
+                        },
+                        BusState::ErrorWarning => {
+                            class |= CAN_ERR_CRTL;
+                            if tx { data[1] |= CAN_ERR_CRTL_TX_WARNING; }
+                            if rx { data[1] |= CAN_ERR_CRTL_RX_WARNING; }
+                        },
+                        BusState::ErrorPassive => {
+                            class |= CAN_ERR_CRTL;
+                            if tx { data[1] |= CAN_ERR_CRTL_TX_PASSIVE; }
+                            if rx { data[1] |= CAN_ERR_CRTL_RX_PASSIVE; }
+                        },
+                        BusState::BusOff => class |= CAN_ERR_BUSOFF,
+                    }
+                },
+                ErrorKind::Protocol { error, transmitting } => {
+                    class |= CAN_ERR_PROT | CAN_ERR_BUSERROR;
+                    if transmitting {
+                        data[2] |= CAN_ERR_PROT_TX;
+                    }
+                    match error {
+                        ProtocolError::Bit => data[2] |= CAN_ERR_PROT_BIT,
+                        ProtocolError::Bit0 => data[2] |= CAN_ERR_PROT_BIT0,
+                        ProtocolError::Bit1 => data[2] |= CAN_ERR_PROT_BIT1,
+                        ProtocolError::Stuff => data[2] |= CAN_ERR_PROT_STUFF,
+                        ProtocolError::Form => data[2] |= CAN_ERR_PROT_FORM,
+                        ProtocolError::Overload => data[2] |= CAN_ERR_PROT_OVERLOAD,
+                        ProtocolError::Ack => {
+                            class |= CAN_ERR_ACK;
+                            data[3] = CAN_ERR_PROT_LOC_ACK;
+                        },
+                        ProtocolError::Crc => data[3] = CAN_ERR_PROT_LOC_CRC_SEQ,
+                        ProtocolError::Other => {},
+                    }
+                },
+                ErrorKind::ArbitrationLost(bit) => {
+                    class |= CAN_ERR_LOSTARB;
+                    data[0] = bit.unwrap_or_default();
+                },
+                ErrorKind::Overrun { transmitting } => {
+                    class |= CAN_ERR_CRTL;
+                    data[1] |= if transmitting { CAN_ERR_CRTL_TX_OVERFLOW } else { CAN_ERR_CRTL_RX_OVERFLOW };
+                },
+                ErrorKind::TransmitTimeout => class |= CAN_ERR_TX_TIMEOUT,
+            }
+        }
+
+        if let Some(counters) = event.counters {
+            class |= CAN_ERR_CNT;
+            data[6] = counters.tx;
+            data[7] = counters.rx;
+        }
+
+        let mut message = CanMessage::new(Id::from_bits(class, false), &data).unwrap();
+        message.set_timestamp(Some(event.timestamp))
+            .set_channel(event.channel.clone())
+            .set_direct(Direct::Receive)
+            .set_error_frame(true);
+        message
+    }
+}
+
+impl<C> TryFrom<&CanMessage<C>> for ErrorEvent<C>
+where
+    C: Display + Clone + Default + Send + Sync {
+    type Error = CanError;
+    fn try_from(message: &CanMessage<C>) -> Result<Self, Self::Error> {
+        if !message.is_error_frame() {
+            return Err(CanError::FrameConvertFailed("not an error frame".into()));
+        }
+
+        let class = message.id().as_raw();
+        let mut data = [0u8; CAN_ERR_DLC];
+        let length = message.data().len().min(CAN_ERR_DLC);
+        data[..length].copy_from_slice(&message.data()[..length]);
+
+        let mut event = ErrorEvent::new(message.channel(), message.timestamp());
+        if class & CAN_ERR_TX_TIMEOUT > 0 {
+            event.add(ErrorKind::TransmitTimeout);
+        }
+        if class & CAN_ERR_LOSTARB > 0 {
+            event.add(ErrorKind::ArbitrationLost(if data[0] > 0 { Some(data[0]) } else { None }));
+        }
+        if class & CAN_ERR_CRTL > 0 {
+            if data[1] & CAN_ERR_CRTL_RX_OVERFLOW > 0 {
+                event.add(ErrorKind::Overrun { transmitting: false });
+            }
+            if data[1] & CAN_ERR_CRTL_TX_OVERFLOW > 0 {
+                event.add(ErrorKind::Overrun { transmitting: true });
+            }
+            if data[1] & (CAN_ERR_CRTL_RX_PASSIVE | CAN_ERR_CRTL_TX_PASSIVE) > 0 {
+                event.add(ErrorKind::State(BusState::ErrorPassive));
+            }
+            else if data[1] & (CAN_ERR_CRTL_RX_WARNING | CAN_ERR_CRTL_TX_WARNING) > 0 {
+                event.add(ErrorKind::State(BusState::ErrorWarning));
+            }
+            else if data[1] & CAN_ERR_CRTL_ACTIVE > 0 {
+                event.add(ErrorKind::State(BusState::ErrorActive));
+            }
+        }
+
+        let prot = class & CAN_ERR_PROT > 0;
+        let transmitting = prot && data[2] & CAN_ERR_PROT_TX > 0;
+        if class & CAN_ERR_ACK > 0 {
+            // the ACK error is only detected by transmitter
+            event.add(ErrorKind::Protocol { error: ProtocolError::Ack, transmitting: !prot || transmitting });
+        }
+        if prot {
+            let types = [
+                (CAN_ERR_PROT_BIT, ProtocolError::Bit),
+                (CAN_ERR_PROT_BIT0, ProtocolError::Bit0),
+                (CAN_ERR_PROT_BIT1, ProtocolError::Bit1),
+                (CAN_ERR_PROT_STUFF, ProtocolError::Stuff),
+                (CAN_ERR_PROT_FORM, ProtocolError::Form),
+                (CAN_ERR_PROT_OVERLOAD, ProtocolError::Overload),
+            ];
+            let mut found = false;
+            types.into_iter()
+                .filter(|(flag, _)| data[2] & flag > 0)
+                .for_each(|(_, error)| {
+                    found = true;
+                    event.add(ErrorKind::Protocol { error, transmitting });
+                });
+            if !found {
+                match data[3] {
+                    CAN_ERR_PROT_LOC_CRC_SEQ | CAN_ERR_PROT_LOC_CRC_DEL => {
+                        event.add(ErrorKind::Protocol { error: ProtocolError::Crc, transmitting });
+                    },
+                    CAN_ERR_PROT_LOC_ACK if class & CAN_ERR_ACK > 0 => {},
+                    _ => {
+                        event.add(ErrorKind::Protocol { error: ProtocolError::Other, transmitting });
+                    },
+                }
+            }
+        }
+        if class & CAN_ERR_BUSOFF > 0 {
+            event.add(ErrorKind::State(BusState::BusOff));
+        }
+        if class & CAN_ERR_CNT > 0 {
+            event.set_counters(Some(ErrorCounters::new(data[6], data[7])));
+        }
+
+        Ok(event)
+    }
+}
+
+/// The SJA1000 error code capture(ECC) of the protocol error in error frame, such as the `ecc` of BLF.
+/// The segment(`bit4-0`) is the same as the location in `data[3]`, `None` if it's not a protocol error.
+pub fn error_code_capture<C>(message: &CanMessage<C>) -> Option<u8>
+where
+    C: Display + Clone + Default + Send + Sync {
+    if !message.is_error_frame() || message.id().as_raw() & CAN_ERR_PROT == 0 {
+        return None;
+    }
+
+    let data = message.data();
+    let (kind, location) = (data.get(2).copied().unwrap_or_default(), data.get(3).copied().unwrap_or_default());
+    let code = if kind & (CAN_ERR_PROT_BIT | CAN_ERR_PROT_BIT0 | CAN_ERR_PROT_BIT1) > 0 {
+        ECC_BIT
+    }
+    else if kind & CAN_ERR_PROT_FORM > 0 {
+        ECC_FORM
+    }
+    else if kind & CAN_ERR_PROT_STUFF > 0 {
+        ECC_STUFF
+    }
+    else {
+        ECC_OTHER
+    };
+    let direct = if kind & CAN_ERR_PROT_TX > 0 { 0 } else { ECC_RX };
+
+    Some(code << 6 | direct | location & ECC_SEGMENT)
+}
+
+/// The error frame of SJA1000 error code capture, the reverse of [`error_code_capture`].
+pub fn from_error_code_capture<C>(ecc: u8) -> CanMessage<C>
+where
+    C: Display + Clone + Default + Send + Sync {
+    let mut data = [0u8; CAN_ERR_DLC];
+    data[2] = match ecc >> 6 {
+        ECC_BIT => CAN_ERR_PROT_BIT,
+        ECC_FORM => CAN_ERR_PROT_FORM,
+        ECC_STUFF => CAN_ERR_PROT_STUFF,
+        _ => 0,
+    };
+    if ecc & ECC_RX == 0 {
+        data[2] |= CAN_ERR_PROT_TX;
+    }
+    data[3] = ecc & ECC_SEGMENT;
+
+    let mut message = CanMessage::new(Id::from_bits(CAN_ERR_PROT | CAN_ERR_BUSERROR, false), &data).unwrap();
+    message.set_direct(Direct::Receive)
+        .set_error_frame(true);
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use isotp_rs::can::{frame::Frame, identifier::Id};
+    use crate::CanMessage;
+    use super::{error_code_capture, from_error_code_capture, BusState, ErrorCounters, ErrorEvent, ErrorKind, ProtocolError};
+
+    #[test]
+    fn test_state() {
+        assert_eq!(BusState::from_counters(&ErrorCounters::new(0, 95)), BusState::ErrorActive);
+        assert_eq!(BusState::from_counters(&ErrorCounters::new(96, 0)), BusState::ErrorWarning);
+        assert_eq!(BusState::from_counters(&ErrorCounters::new(8, 128)), BusState::ErrorPassive);
+
+        let mut event = ErrorEvent::new(0u8, 0);
+        assert!(event.is_empty());
+        assert_eq!(event.state(), None);
+        event.set_counters(Some(ErrorCounters::new(100, 0)));
+        assert_eq!(event.state(), Some(BusState::ErrorWarning));
+        event.add(ErrorKind::State(BusState::BusOff));
+        assert_eq!(event.state(), Some(BusState::BusOff));
+    }
+
+    #[test]
+    fn test_convert() -> anyhow::Result<()> {
+        let mut event = ErrorEvent::new(1u8, 1000);
+        event.add(ErrorKind::State(BusState::ErrorPassive))
+            .add(ErrorKind::Protocol { error: ProtocolError::Stuff, transmitting: true })
+            .add(ErrorKind::Protocol { error: ProtocolError::Ack, transmitting: true })
+            .add(ErrorKind::ArbitrationLost(Some(12)))
+            .add(ErrorKind::Overrun { transmitting: false })
+            .set_counters(Some(ErrorCounters::new(136, 0)));
+
+        let message = CanMessage::from(&event);
+        assert!(message.is_error_frame());
+        assert_eq!(message.id(), Id::from_bits(0x2AE, false));
+        assert_eq!(message.data(), [12, 0x21, 0x84, 0x19, 0, 0, 136, 0]);
+        assert_eq!(message.to_cansend(), "200002AE#0C21841900008800");
+
+        let parsed = ErrorEvent::try_from(&message)?;
+        assert_eq!(parsed.timestamp(), 1000);
+        assert_eq!(*parsed.channel(), 1);
+        assert_eq!(parsed.counters(), event.counters());
+        assert_eq!(parsed.state(), Some(BusState::ErrorPassive));
+        for kind in event.kinds() {
+            assert!(parsed.kinds().contains(kind), "{:?}", kind);
+        }
+        assert_eq!(parsed.kinds().len(), event.kinds().len());
+
+        // bus-off and CRC error without counters
+        let mut event = ErrorEvent::new(0u8, 0);
+        event.add(ErrorKind::Protocol { error: ProtocolError::Crc, transmitting: false })
+            .add(ErrorKind::State(BusState::BusOff));
+        let parsed = ErrorEvent::try_from(&CanMessage::from(&event))?;
+        assert_eq!(parsed, event);
+
+        let frame = CanMessage::<u8>::new(Id::from_bits(0x123, false), &[0x00; 8]).unwrap();
+        assert!(ErrorEvent::try_from(&frame).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_code_capture() -> anyhow::Result<()> {
+        let mut event = ErrorEvent::new(0u8, 0);
+        event.add(ErrorKind::Protocol { error: ProtocolError::Stuff, transmitting: true });
+        let message = CanMessage::from(&event);
+        assert_eq!(error_code_capture(&message), Some(0x80));
+        assert_eq!(from_error_code_capture::<u8>(0x80), message);
+
+        // form error in CRC delimiter while receiving
+        let message = from_error_code_capture::<u8>(0x78);
+        assert_eq!(error_code_capture(&message), Some(0x78));
+        let parsed = ErrorEvent::try_from(&message)?;
+        assert_eq!(parsed.kinds(), [ErrorKind::Protocol { error: ProtocolError::Form, transmitting: false }]);
+
+        let mut event = ErrorEvent::new(0u8, 0);
+        event.add(ErrorKind::State(BusState::BusOff));
+        assert_eq!(error_code_capture(&CanMessage::from(&event)), None);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,493 @@
+//! Open a ready-to-use bus from a descriptor, such as:
+//!
+//! `zlgcan://USBCANFD_200U/0?channel=0&bitrate=500000&dbitrate=2000000`,
+//! `nican://CAN0?bitrate=500000`, `socketcan://can0?fd=true` or `virtual://bus0?receive_own=true`.
+//!
+//! The built-in backends(`virtual`, `socketcan` and `udp_multicast`) are selected by the cargo
+//! features of rs-can and registered automatically.
+//!
+//! The driver crates(such as `zlgcan` and `nican`) depend on rs-can, so rs-can can't register them.
+//! Every binary must call the `register` of driver crates once before opening, such as:
+//!
+//! ```ignore
+//! zlgcan::factory::register();
+//! nican::factory::register();
+//! let bus = rs_can::factory::open("zlgcan://USBCANFD_200U/0?channel=0&bitrate=500000")?;
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+use isotp_rs::can::frame::Frame;
+use isotp_rs::device::Driver;
+use crate::{CanDevice, CanFilter, CanMessage, Capability, ChannelConfig};
+use crate::error::CanError;
+
+/// The bus opened by factory, the channel of all backends is unified to `String`.
+pub type AnyBus = Box<dyn CanDevice<C = String, F = CanMessage<String>, Error = CanError>>;
+/// The constructor of a backend.
+pub type BusCreator = fn(&BusUrl) -> Result<AnyBus, CanError>;
+
+static FACTORIES: OnceLock<Mutex<HashMap<String, BusCreator>>> = OnceLock::new();
+
+/// The parsed bus descriptor: `scheme://host[/path...][?key=value&...]`.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct BusUrl {
+    pub scheme: String,
+    pub host: String,
+    pub path: Vec<String>,
+    pub params: HashMap<String, String>,
+}
+
+impl FromStr for BusUrl {
+    type Err = CanError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || CanError::OtherError(format!("invalid bus descriptor: `{}`", s));
+        let (scheme, rest) = s.split_once("://")
+            .ok_or_else(invalid)?;
+        if scheme.is_empty() {
+            return Err(invalid());
+        }
+
+        let (location, query) = match rest.split_once('?') {
+            Some((l, q)) => (l, q),
+            None => (rest, ""),
+        };
+        let mut location = location.split('/');
+        let host = location.next()
+            .unwrap_or_default()
+            .to_string();
+        let path = location.filter(|v| !v.is_empty())
+            .map(|v| v.to_string())
+            .collect();
+
+        let mut params = HashMap::new();
+        for item in query.split('&').filter(|v| !v.is_empty()) {
+            match item.split_once('=') {
+                Some((k, v)) => params.insert(k.to_string(), v.to_string()),
+                None => params.insert(item.to_string(), Default::default()),
+            };
+        }
+
+        Ok(Self {
+            scheme: scheme.to_lowercase(),
+            host,
+            path,
+            params,
+        })
+    }
+}
+
+impl Display for BusUrl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}://{}", self.scheme, self.host)?;
+        for p in &self.path {
+            write!(f, "/{}", p)?;
+        }
+        let mut params = self.params.iter().collect::<Vec<_>>();
+        params.sort();
+        for (i, (k, v)) in params.into_iter().enumerate() {
+            write!(f, "{}{}={}", if i == 0 { '?' } else { '&' }, k, v)?;
+        }
+        Ok(())
+    }
+}
+
+impl BusUrl {
+    /// Get and parse a parameter, `None` if it is not present.
+    pub fn param<T: FromStr>(&self, key: &str) -> Result<Option<T>, CanError> {
+        match self.params.get(key) {
+            Some(v) => v.parse::<T>()
+                .map(Some)
+                .map_err(|_| CanError::OtherError(format!("invalid parameter `{}={}`", key, v))),
+            None => Ok(None),
+        }
+    }
+
+    /// Get a boolean parameter, `key`, `key=1` and `key=true` are all true.
+    pub fn flag(&self, key: &str) -> Result<Option<bool>, CanError> {
+        match self.params.get(key).map(|v| v.to_lowercase()) {
+            Some(v) => match v.as_str() {
+                "" | "1" | "true" | "yes" | "on" => Ok(Some(true)),
+                "0" | "false" | "no" | "off" => Ok(Some(false)),
+                _ => Err(CanError::OtherError(format!("invalid parameter `{}={}`", key, v))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// The channels of `channel=0,1`, the default is used when not present.
+    pub fn channels(&self, default: &str) -> Vec<String> {
+        match self.params.get("channel") {
+            Some(v) => v.split(',')
+                .filter(|v| !v.is_empty())
+                .map(|v| v.to_string())
+                .collect(),
+            None => vec![default.to_string()],
+        }
+    }
+
+    /// The channel configuration from `bitrate`, `dbitrate`, `resistance`, `listen_only` and
+    /// `filters=7DF:7FF,18DA00F1:1FFFFFFF,18:1FFFFFFF:ext`(hex `id:mask[:std|:ext]`),
+    /// the identifier type is decided by [`is_extended`] when the suffix is not present.
+    pub fn channel_config(&self) -> Result<ChannelConfig, CanError> {
+        let mut cfg = ChannelConfig::new(self.param("bitrate")?.unwrap_or(500_000));
+        cfg.dbitrate = self.param("dbitrate")?;
+        cfg.resistance = self.flag("resistance")?;
+        cfg.listen_only = self.flag("listen_only")?.unwrap_or_default();
+        if let Some(filters) = self.params.get("filters") {
+            for item in filters.split(',').filter(|v| !v.is_empty()) {
+                cfg.filters.push(parse_filter(item)?);
+            }
+        }
+
+        Ok(cfg)
+    }
+
+    /// The channel configuration of `channel`, the parameters prefixed by `<channel>.`
+    /// (such as `1.bitrate=250000&1.listen_only`) replace all the common ones when present.
+    pub fn channel_config_of(&self, channel: &str) -> Result<ChannelConfig, CanError> {
+        let prefix = format!("{}.", channel);
+        let params = self.params.iter()
+            .filter_map(|(k, v)| k.strip_prefix(prefix.as_str()).map(|k| (k.to_string(), v.clone())))
+            .collect::<HashMap<_, _>>();
+        if params.is_empty() {
+            return self.channel_config();
+        }
+
+        Self { params, ..Default::default() }.channel_config()
+    }
+
+    /// Write the channel configuration into parameters, the reverse of [`BusUrl::channel_config`].
+    #[inline]
+    pub fn set_channel_config(&mut self, cfg: &ChannelConfig) {
+        self.write_channel_config("", cfg)
+    }
+
+    /// Write the configuration of `channel`, the reverse of [`BusUrl::channel_config_of`].
+    #[inline]
+    pub fn set_channel_config_of(&mut self, channel: &str, cfg: &ChannelConfig) {
+        self.write_channel_config(format!("{}.", channel).as_str(), cfg)
+    }
+
+    fn write_channel_config(&mut self, prefix: &str, cfg: &ChannelConfig) {
+        self.params.insert(format!("{}bitrate", prefix), cfg.bitrate.to_string());
+        if let Some(dbitrate) = cfg.dbitrate {
+            self.params.insert(format!("{}dbitrate", prefix), dbitrate.to_string());
+        }
+        if let Some(resistance) = cfg.resistance {
+            self.params.insert(format!("{}resistance", prefix), resistance.to_string());
+        }
+        if cfg.listen_only {
+            self.params.insert(format!("{}listen_only", prefix), true.to_string());
+        }
+        if !cfg.filters.is_empty() {
+            self.params.insert(
+                format!("{}filters", prefix),
+                cfg.filters.iter()
+                    .map(|f| match (f.extended, is_extended(f.can_id, f.can_mask)) {
+                        (true, false) => format!("{:X}:{:X}:ext", f.can_id, f.can_mask),
+                        (false, true) => format!("{:X}:{:X}:std", f.can_id, f.can_mask),
+                        _ => format!("{:X}:{:X}", f.can_id, f.can_mask),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(",")
+            );
+        }
+    }
+}
+
+/// The identifier type of filter when it's not given, the filter is extended when the identifier
+/// or the mask is wider than 11 bits.
+#[inline]
+pub fn is_extended(can_id: u32, can_mask: u32) -> bool {
+    can_id > 0x7FF || can_mask > 0x7FF
+}
+
+fn parse_filter(s: &str) -> Result<CanFilter, CanError> {
+    let invalid = || CanError::OtherError(format!("invalid filter: `{}`", s));
+    let mut items = s.split(':');
+    let (Some(can_id), Some(can_mask)) = (items.next(), items.next()) else {
+        return Err(invalid());
+    };
+    let can_id = u32::from_str_radix(can_id.trim_start_matches("0x"), 16)
+        .map_err(|_| invalid())?;
+    let can_mask = u32::from_str_radix(can_mask.trim_start_matches("0x"), 16)
+        .map_err(|_| invalid())?;
+    let extended = match items.next() {
+        None => is_extended(can_id, can_mask),
+        Some("ext" | "x") => true,
+        Some("std") => false,
+        Some(_) => return Err(invalid()),
+    };
+    if items.next().is_some() {
+        return Err(invalid());
+    }
+
+    Ok(CanFilter { can_id, can_mask, extended })
+}
+
+/// Register a backend by scheme, the previous one is replaced.
+pub fn register(scheme: &str, creator: BusCreator) {
+    match factories().lock() {
+        Ok(mut v) => { v.insert(scheme.to_lowercase(), creator); },
+        Err(e) => log::warn!("RUST-CAN - mutex error: {:?} when registering `{}`", e, scheme),
+    }
+}
+
+/// The schemes registered.
+pub fn schemes() -> Vec<String> {
+    match factories().lock() {
+        Ok(v) => v.keys().cloned().collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// Open a bus from descriptor, the device is opened and the channels are initialized.
+pub fn open(descriptor: &str) -> Result<AnyBus, CanError> {
+    let url = BusUrl::from_str(descriptor)?;
+    open_url(&url)
+}
+
+pub fn open_url(url: &BusUrl) -> Result<AnyBus, CanError> {
+    let creator = factories().lock()
+        .map_err(|e| CanError::OtherError(e.to_string()))?
+        .get(&url.scheme)
+        .copied()
+        .ok_or(CanError::DeviceNotSupported)?;
+
+    creator(url)
+}
+
+fn factories() -> &'static Mutex<HashMap<String, BusCreator>> {
+    FACTORIES.get_or_init(|| {
+        #[allow(unused_mut)]
+        let mut result: HashMap<String, BusCreator> = HashMap::new();
+        result.insert("virtual".into(), builtin::virtual_bus);
+        #[cfg(all(target_os = "linux", feature = "socketcan"))]
+        result.insert("socketcan".into(), builtin::socketcan);
+        #[cfg(feature = "udp_multicast")]
+        result.insert("udp_multicast".into(), builtin::udp_multicast);
+
+        Mutex::new(result)
+    })
+}
+
+mod builtin {
+    use crate::CanDevice;
+    use crate::error::CanError;
+    use crate::interfaces::virtual_bus::VirtualCan;
+    use super::{AnyBus, BusUrl};
+
+    pub(super) fn virtual_bus(url: &BusUrl) -> Result<AnyBus, CanError> {
+        let mut device = VirtualCan::new();
+        for channel in url.channels(&url.host) {
+            let cfg = url.channel_config_of(&channel)?;
+            device.open(channel.as_str(), cfg.filters, url.flag("receive_own")?.unwrap_or_default())?;
+        }
+        device.open_device()?;
+
+        Ok(Box::new(device))
+    }
+
+    #[cfg(all(target_os = "linux", feature = "socketcan"))]
+    pub(super) fn socketcan(url: &BusUrl) -> Result<AnyBus, CanError> {
+        use crate::interfaces::socketcan::SocketCan;
+
+        let log_errors = url.flag("log_errors")?.unwrap_or(true);
+        let mut device = SocketCan::new();
+        for channel in url.channels(&url.host) {
+            let cfg = url.channel_config_of(&channel)?;
+            SocketCan::check_config(channel.as_str(), &cfg)?;
+            let canfd = url.flag("fd")?.unwrap_or(cfg.is_can_fd());
+            device.open(channel.as_str(), cfg.filters, canfd, log_errors)?;
+        }
+
+        Ok(Box::new(device))
+    }
+
+    #[cfg(feature = "udp_multicast")]
+    pub(super) fn udp_multicast(url: &BusUrl) -> Result<AnyBus, CanError> {
+        use std::net::SocketAddr;
+        use crate::interfaces::udp_multicast::{UdpMulticast, DEFAULT_GROUP_IPV4};
+
+        let group = match url.host.as_str() {
+            "" | "default" => DEFAULT_GROUP_IPV4,
+            v => v.parse::<SocketAddr>()
+                .map_err(|e| CanError::OtherError(format!("invalid multicast group `{}`: {}", v, e)))?,
+        };
+        let default = url.path.first().cloned().unwrap_or_else(|| "CAN0".into());
+        let mut device = UdpMulticast::new(group);
+        for channel in url.channels(&default) {
+            let cfg = url.channel_config_of(&channel)?;
+            device.open(channel.as_str(), cfg.filters, url.flag("receive_own")?.unwrap_or_default())?;
+        }
+
+        Ok(Box::new(device))
+    }
+}
+
+/// Adapt a backend to [`AnyBus`], the channel is converted by `Display` and `FromStr`.
+#[derive(Debug, Clone)]
+pub struct BusAdapter<D>(pub D);
+
+impl<D, C> BusAdapter<D>
+where
+    D: CanDevice<C = C, F = CanMessage<C>>,
+    D::Error: Into<CanError>,
+    C: FromStr + Display + Clone + Default + Send + Sync + 'static,
+{
+    #[inline]
+    fn channel(channel: &str) -> Result<C, CanError> {
+        C::from_str(channel)
+            .map_err(|_| CanError::ChannelNotOpened(channel.into()))
+    }
+}
+
+impl<D, C> Driver for BusAdapter<D>
+where
+    D: CanDevice<C = C, F = CanMessage<C>>,
+    D::Error: Into<CanError>,
+    C: FromStr + Display + Clone + Default + Send + Sync + 'static,
+{
+    type Error = CanError;
+    type C = String;
+    type F = CanMessage<String>;
+
+    #[inline]
+    fn opened_channels(&self) -> Vec<Self::C> {
+        self.0.opened_channels()
+            .into_iter()
+            .map(|c| c.to_string())
+            .collect()
+    }
+
+    #[inline]
+    fn is_closed(&self) -> bool {
+        self.0.is_closed()
+    }
+
+    fn transmit(&self, msg: Self::F, timeout: Option<u32>) -> Result<(), Self::Error> {
+        let channel = Self::channel(msg.channel().as_str())?;
+        self.0.transmit(msg.with_channel(channel), timeout)
+            .map_err(Into::into)
+    }
+
+    fn receive(&self, channel: Self::C, timeout: Option<u32>) -> Result<Vec<Self::F>, Self::Error> {
+        let results = self.0.receive(Self::channel(channel.as_str())?, timeout)
+            .map_err(Into::into)?;
+        Ok(results.into_iter()
+            .map(|msg| msg.with_channel(channel.clone()))
+            .collect())
+    }
+
+    #[inline]
+    fn shutdown(&mut self) {
+        self.0.shutdown()
+    }
+}
+
+impl<D, C> CanDevice for BusAdapter<D>
+where
+    D: CanDevice<C = C, F = CanMessage<C>>,
+    D::Error: Into<CanError>,
+    C: FromStr + Display + Clone + Default + Send + Sync + 'static,
+{
+    #[inline]
+    fn open_device(&mut self) -> Result<(), Self::Error> {
+        self.0.open_device()
+            .map_err(Into::into)
+    }
+
+    #[inline]
+    fn init_channel(&mut self, channel: Self::C, cfg: &ChannelConfig) -> Result<(), Self::Error> {
+        self.0.init_channel(Self::channel(channel.as_str())?, cfg)
+            .map_err(Into::into)
+    }
+
+    #[inline]
+    fn reset_channel(&mut self, channel: Self::C) -> Result<(), Self::Error> {
+        self.0.reset_channel(Self::channel(channel.as_str())?)
+            .map_err(Into::into)
+    }
+
+    #[inline]
+    fn close_device(&mut self) -> Result<(), Self::Error> {
+        self.0.close_device()
+            .map_err(Into::into)
+    }
+
+    #[inline]
+    fn capability(&self) -> Result<Capability, Self::Error> {
+        self.0.capability()
+            .map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use isotp_rs::can::{frame::Frame, identifier::Id};
+    use crate::{CanFilter, CanMessage};
+    use super::{open, BusUrl};
+
+    #[test]
+    fn test_url() -> anyhow::Result<()> {
+        let url = BusUrl::from_str("zlgcan://USBCANFD_200U/0?channel=0,1&bitrate=500000&dbitrate=2000000")?;
+        assert_eq!(url.scheme, "zlgcan");
+        assert_eq!(url.host, "USBCANFD_200U");
+        assert_eq!(url.path, vec!["0"]);
+        assert_eq!(url.channels("0"), vec!["0", "1"]);
+        let cfg = url.channel_config()?;
+        assert_eq!(cfg.bitrate, 500_000);
+        assert_eq!(cfg.dbitrate, Some(2_000_000));
+        assert!(!cfg.listen_only);
+
+        let url = BusUrl::from_str("nican://CAN0?bitrate=250000&listen_only&filters=7DF:7FF,18DA00F1:1FFFFFFF")?;
+        assert_eq!(url.host, "CAN0");
+        assert!(url.path.is_empty());
+        assert_eq!(url.channels(&url.host), vec!["CAN0"]);
+        let cfg = url.channel_config()?;
+        assert_eq!(cfg.bitrate, 250_000);
+        assert!(cfg.listen_only);
+        assert_eq!(cfg.filters, vec![
+            CanFilter { can_id: 0x7DF, can_mask: 0x7FF, extended: false },
+            CanFilter { can_id: 0x18DA00F1, can_mask: 0x1FFFFFFF, extended: true },
+        ]);
+        assert_eq!(url.to_string(), "nican://CAN0?bitrate=250000&filters=7DF:7FF,18DA00F1:1FFFFFFF&listen_only=");
+
+        let mut url = BusUrl::from_str("virtual://bus0?filters=18:1FFFFFFF,7E0:7F8:x,100:7FF:std")?;
+        let cfg = url.channel_config()?;
+        assert_eq!(cfg.filters, vec![
+            CanFilter { can_id: 0x18, can_mask: 0x1FFFFFFF, extended: true },
+            CanFilter { can_id: 0x7E0, can_mask: 0x7F8, extended: true },
+            CanFilter { can_id: 0x100, can_mask: 0x7FF, extended: false },
+        ]);
+        url.set_channel_config(&cfg);
+        assert_eq!(url.params["filters"], "18:1FFFFFFF,7E0:7F8:ext,100:7FF");
+        assert!(BusUrl::from_str("virtual://bus0?filters=7DF:7FF:fd")?.channel_config().is_err());
+
+        assert!(BusUrl::from_str("CAN0").is_err());
+        assert!(BusUrl::from_str("nican://CAN0?bitrate=fast").unwrap().channel_config().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_open() -> anyhow::Result<()> {
+        let tester = open("virtual://test_factory?receive_own=false")?;
+        let ecu = open("virtual://test_factory?filters=7DF:7FF")?;
+        assert_eq!(tester.opened_channels(), vec!["test_factory"]);
+
+        let mut msg = CanMessage::new(Id::from(0x7DF), &[0x02, 0x10, 0x01]).unwrap();
+        msg.set_channel("test_factory".into());
+        tester.transmit(msg.clone(), None)?;
+        assert_eq!(ecu.receive("test_factory".into(), Some(10))?, vec![msg]);
+
+        assert!(open("unknown://CAN0").is_err());
+
+        Ok(())
+    }
+}
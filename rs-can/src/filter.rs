@@ -0,0 +1,411 @@
+//! The receive filtering, the filters are pushed down to hardware where possible,
+//! and the rest are applied in software.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use isotp_rs::can::frame::Frame;
+use isotp_rs::device::Driver;
+use crate::{CanDevice, CanFilter, Capability, ChannelConfig};
+
+const SFF_MASK: u32 = 0x7FF;
+const EFF_MASK: u32 = 0x1FFFFFFF;
+
+#[inline]
+fn id_mask(extended: bool) -> u32 {
+    if extended { EFF_MASK } else { SFF_MASK }
+}
+
+impl CanFilter {
+    #[inline]
+    pub fn is_matched(&self, id: u32, extended: bool) -> bool {
+        self.extended == extended && (id & self.can_mask) == (self.can_id & self.can_mask)
+    }
+
+    /// The filter covers both, it may accept more identifiers than the two.
+    #[inline]
+    pub fn merge(&self, other: &Self) -> Self {
+        let can_mask = self.can_mask & other.can_mask & !(self.can_id ^ other.can_id);
+        Self { can_id: self.can_id & can_mask, can_mask, extended: self.extended }
+    }
+
+    /// Merge all filters into one, `None` if `filters` is empty.
+    pub fn merge_all<'a>(filters: impl IntoIterator<Item = &'a Self>) -> Option<Self> {
+        filters.into_iter()
+            .fold(None, |r: Option<Self>, f| match r {
+                Some(v) => Some(v.merge(f)),
+                None => Some(*f),
+            })
+    }
+}
+
+/// Check the frame is accepted by any of filters, all frames are accepted when `filters` is empty.
+pub fn is_matched<F: Frame>(filters: &[CanFilter], msg: &F) -> bool {
+    if filters.is_empty() || msg.is_error_frame() {
+        return true;
+    }
+
+    let id = msg.id().as_raw();
+    let extended = msg.is_extended();
+    filters.iter()
+        .any(|f| f.is_matched(id, extended))
+}
+
+/// The rule of [`FrameFilter`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum FilterRule {
+    /// Matched when `id & can_mask == can_id & can_mask`.
+    Mask(CanFilter),
+    /// Matched when the identifier is in `start..=end`.
+    Range { start: u32, end: u32, extended: bool },
+    /// Matched when the inner rule is not matched.
+    Inverted(Box<FilterRule>),
+}
+
+impl From<CanFilter> for FilterRule {
+    #[inline]
+    fn from(value: CanFilter) -> Self {
+        Self::Mask(value)
+    }
+}
+
+impl FilterRule {
+    #[inline]
+    pub fn inverted(self) -> Self {
+        Self::Inverted(Box::new(self))
+    }
+
+    pub fn is_matched(&self, id: u32, extended: bool) -> bool {
+        match self {
+            Self::Mask(f) => f.is_matched(id, extended),
+            Self::Range { start, end, extended: ext } => *ext == extended && (*start..=*end).contains(&id),
+            Self::Inverted(rule) => !rule.is_matched(id, extended),
+        }
+    }
+
+    /// The mask filter covers the rule and whether it is exact, `None` if not convertible.
+    pub fn to_mask(&self) -> Option<(CanFilter, bool)> {
+        match self {
+            Self::Mask(f) => Some((*f, true)),
+            Self::Range { start, end, extended } => {
+                let full = id_mask(*extended);
+                let bits = 32 - (start ^ end).leading_zeros();
+                let can_mask = full & !((1u64 << bits) - 1) as u32;
+                let can_id = start & can_mask;
+                let exact = *start == can_id && *end == can_id | (full & !can_mask);
+                Some((CanFilter { can_id, can_mask, extended: *extended }, exact))
+            },
+            Self::Inverted(_) => None,
+        }
+    }
+}
+
+/// The filter of a channel, a frame is accepted when any rule is matched or there is no rule.
+/// The error frames are always accepted.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct FrameFilter {
+    rules: Vec<FilterRule>,
+}
+
+impl From<&[CanFilter]> for FrameFilter {
+    fn from(value: &[CanFilter]) -> Self {
+        Self::new(value.iter().map(|f| FilterRule::Mask(*f)).collect())
+    }
+}
+
+impl FrameFilter {
+    #[inline]
+    pub fn new(rules: Vec<FilterRule>) -> Self {
+        Self { rules }
+    }
+
+    #[inline]
+    pub fn add(&mut self, rule: impl Into<FilterRule>) -> &mut Self {
+        self.rules.push(rule.into());
+        self
+    }
+
+    #[inline]
+    pub fn rules(&self) -> &[FilterRule] {
+        &self.rules
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn is_matched<F: Frame>(&self, msg: &F) -> bool {
+        if self.rules.is_empty() || msg.is_error_frame() {
+            return true;
+        }
+
+        let id = msg.id().as_raw();
+        let extended = msg.is_extended();
+        self.rules.iter()
+            .any(|r| r.is_matched(id, extended))
+    }
+
+    #[inline]
+    pub fn apply<F: Frame>(&self, frames: Vec<F>) -> Vec<F> {
+        frames.into_iter()
+            .filter(|f| self.is_matched(f))
+            .collect()
+    }
+
+    /// The mask filters pushed down to hardware with at most `max` filters(`None` is unlimited),
+    /// and whether the software filtering is still required.
+    ///
+    /// An empty result means hardware accepts all.
+    pub fn hardware_filters(&self, max: Option<usize>) -> (Vec<CanFilter>, bool) {
+        let mut exact = true;
+        let mut results = Vec::with_capacity(self.rules.len());
+        for rule in &self.rules {
+            match rule.to_mask() {
+                Some((f, e)) => {
+                    exact &= e;
+                    results.push(f);
+                },
+                None => return (vec![], true),
+            }
+        }
+
+        if let Some(max) = max {
+            while results.len() > max {
+                // merge the pair with the most mask bits remained.
+                let mut best: Option<(usize, usize, CanFilter)> = None;
+                for i in 0..results.len() {
+                    for j in i + 1..results.len() {
+                        if results[i].extended != results[j].extended {
+                            continue;
+                        }
+                        let merged = results[i].merge(&results[j]);
+                        if best.is_none_or(|(_, _, b)| merged.can_mask.count_ones() > b.can_mask.count_ones()) {
+                            best = Some((i, j, merged));
+                        }
+                    }
+                }
+
+                match best {
+                    Some((i, j, merged)) => {
+                        results.remove(j);
+                        results[i] = merged;
+                        exact = false;
+                    },
+                    None => return (vec![], true),
+                }
+            }
+        }
+
+        (results, !exact)
+    }
+}
+
+/// Filter the frames received from any backend by [`FrameFilter`].
+#[derive(Debug, Clone)]
+pub struct FilteredDevice<D: CanDevice>
+where
+    D::C: Clone + Hash + Eq + Send,
+{
+    inner: D,
+    /// The filter and whether it is applied in software.
+    filters: HashMap<D::C, (FrameFilter, bool)>,
+}
+
+impl<D: CanDevice> FilteredDevice<D>
+where
+    D::C: Clone + Hash + Eq + Send,
+    D::F: Frame,
+{
+    pub fn new(inner: D) -> Self {
+        Self { inner, filters: Default::default() }
+    }
+
+    #[inline]
+    pub fn inner(&self) -> &D {
+        &self.inner
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> D {
+        self.inner
+    }
+
+    /// Initialize channel, the filter is pushed down to hardware when supported,
+    /// the `filters` of `cfg` are ignored.
+    pub fn init_channel_with_filter(
+        &mut self,
+        channel: D::C,
+        cfg: &ChannelConfig,
+        filter: FrameFilter,
+    ) -> Result<(), D::Error> {
+        let mut cfg = cfg.clone();
+        let capability = self.inner.capability()?;
+        let software = if capability.hardware_filter {
+            let (filters, software) = filter.hardware_filters(capability.filter_count);
+            cfg.filters = filters;
+            software
+        }
+        else {
+            cfg.filters.clear();
+            !filter.is_empty()
+        };
+
+        self.inner.init_channel(channel.clone(), &cfg)?;
+        self.filters.insert(channel, (filter, software));
+
+        Ok(())
+    }
+
+    /// Replace the filter of channel in software, the hardware filters are not changed.
+    #[inline]
+    pub fn set_filter(&mut self, channel: D::C, filter: FrameFilter) {
+        self.filters.insert(channel, (filter, true));
+    }
+
+    #[inline]
+    pub fn filter(&self, channel: &D::C) -> Option<&FrameFilter> {
+        self.filters.get(channel)
+            .map(|(f, _)| f)
+    }
+}
+
+impl<D: CanDevice> Driver for FilteredDevice<D>
+where
+    D::C: Clone + Hash + Eq + Send,
+    D::F: Frame,
+{
+    type Error = D::Error;
+    type C = D::C;
+    type F = D::F;
+
+    #[inline]
+    fn opened_channels(&self) -> Vec<Self::C> {
+        self.inner.opened_channels()
+    }
+
+    #[inline]
+    fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    #[inline]
+    fn transmit(&self, msg: Self::F, timeout: Option<u32>) -> Result<(), Self::Error> {
+        self.inner.transmit(msg, timeout)
+    }
+
+    fn receive(&self, channel: Self::C, timeout: Option<u32>) -> Result<Vec<Self::F>, Self::Error> {
+        let results = self.inner.receive(channel.clone(), timeout)?;
+        match self.filters.get(&channel) {
+            Some((filter, true)) => Ok(filter.apply(results)),
+            _ => Ok(results),
+        }
+    }
+
+    #[inline]
+    fn shutdown(&mut self) {
+        self.filters.clear();
+        self.inner.shutdown()
+    }
+}
+
+impl<D: CanDevice> CanDevice for FilteredDevice<D>
+where
+    D::C: Clone + Hash + Eq + Send,
+    D::F: Frame,
+{
+    #[inline]
+    fn open_device(&mut self) -> Result<(), Self::Error> {
+        self.inner.open_device()
+    }
+
+    #[inline]
+    fn init_channel(&mut self, channel: Self::C, cfg: &ChannelConfig) -> Result<(), Self::Error> {
+        self.init_channel_with_filter(channel, cfg, FrameFilter::from(cfg.filters.as_slice()))
+    }
+
+    #[inline]
+    fn reset_channel(&mut self, channel: Self::C) -> Result<(), Self::Error> {
+        self.inner.reset_channel(channel)
+    }
+
+    #[inline]
+    fn close_device(&mut self) -> Result<(), Self::Error> {
+        self.filters.clear();
+        self.inner.close_device()
+    }
+
+    #[inline]
+    fn capability(&self) -> Result<Capability, Self::Error> {
+        self.inner.capability()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use isotp_rs::can::{frame::Frame, identifier::Id};
+    use isotp_rs::device::Driver;
+    use crate::{CanFilter, CanMessage, ChannelConfig};
+    use crate::interfaces::virtual_bus::VirtualCan;
+    use super::{FilterRule, FilteredDevice, FrameFilter};
+
+    #[test]
+    fn test_filter() {
+        let mut filter = FrameFilter::default();
+        filter.add(CanFilter { can_id: 0x7DF, can_mask: 0x7FF, extended: false })
+            .add(FilterRule::Range { start: 0x700, end: 0x70F, extended: false })
+            .add(FilterRule::Range { start: 0x18DA00F1, end: 0x18DA00FF, extended: true }.inverted());
+
+        assert!(filter.rules()[0].is_matched(0x7DF, false));
+        assert!(!filter.rules()[0].is_matched(0x7DF, true));
+        assert!(filter.rules()[1].is_matched(0x70F, false));
+        assert!(!filter.rules()[1].is_matched(0x710, false));
+        assert!(filter.rules()[2].is_matched(0x18DA00F0, true));
+        assert!(!filter.rules()[2].is_matched(0x18DA00F1, true));
+
+        // the inverted rule can't be pushed down
+        assert_eq!(filter.hardware_filters(None), (vec![], true));
+
+        let mut filter = FrameFilter::default();
+        filter.add(FilterRule::Range { start: 0x700, end: 0x70F, extended: false })
+            .add(CanFilter { can_id: 0x7DF, can_mask: 0x7FF, extended: false });
+        assert_eq!(filter.hardware_filters(None), (vec![
+            CanFilter { can_id: 0x700, can_mask: 0x7F0, extended: false },
+            CanFilter { can_id: 0x7DF, can_mask: 0x7FF, extended: false },
+        ], false));
+        assert_eq!(filter.hardware_filters(Some(1)), (vec![
+            CanFilter { can_id: 0x700, can_mask: 0x720, extended: false },
+        ], true));
+
+        let filter = FrameFilter::new(vec![FilterRule::Range { start: 0x701, end: 0x710, extended: false }]);
+        assert_eq!(filter.hardware_filters(None), (vec![
+            CanFilter { can_id: 0x700, can_mask: 0x7E0, extended: false },
+        ], true));
+    }
+
+    #[test]
+    fn test_filtered_device() -> anyhow::Result<()> {
+        let channel = "test_filtered_device";
+        let mut tester = VirtualCan::new();
+        tester.open(channel, vec![], false)?;
+        let mut ecu = FilteredDevice::new(VirtualCan::new());
+        ecu.init_channel_with_filter(
+            channel.into(),
+            &ChannelConfig::new(500_000),
+            FrameFilter::new(vec![FilterRule::Range { start: 0x7E0, end: 0x7E7, extended: false }.inverted()]),
+        )?;
+
+        for id in [0x7DF, 0x7E0, 0x7E8] {
+            let mut msg = CanMessage::new(Id::from(id), &[0x02, 0x10, 0x01]).unwrap();
+            msg.set_channel(channel.into());
+            tester.transmit(msg, None)?;
+        }
+
+        let ids = ecu.receive(channel.into(), Some(10))?
+            .into_iter()
+            .map(|msg| msg.id().as_raw())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![0x7DF, 0x7E8]);
+
+        Ok(())
+    }
+}
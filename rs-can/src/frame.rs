@@ -0,0 +1,491 @@
+use std::fmt::{Display, Formatter};
+use isotp_rs::can::{CAN_FRAME_MAX_SIZE, CANFD_FRAME_MAX_SIZE, EFF_MASK, frame::{Frame, Direct}, identifier::Id};
+use crate::error::CanError;
+use crate::utils::{data_resize, monotonic_timestamp};
+
+/// The error frame flag of SocketCAN identifier.
+pub(crate) const CAN_ERR_FLAG: u32 = 0x2000_0000;
+/// The flags of SocketCAN CAN-FD frame.
+pub(crate) const CANFD_BRS: u8 = 0x01;
+pub(crate) const CANFD_ESI: u8 = 0x02;
+
+/// The time base of frame timestamp, see [`crate::utils`].
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum TimeBase {
+    /// The host monotonic clock, which is anchored to the wall-clock once per process.
+    #[default]
+    Monotonic,
+    /// The system real time clock, such as the software timestamps of kernel and the log files.
+    WallClock,
+    /// The clock of interface hardware, such as the PHC of NIC, it's not comparable with the others.
+    Hardware,
+}
+
+/// The CAN message shared by all backends.
+///
+/// The channel type is decided by backend, such as `u8` for ZLGCAN and `String` for NI-CAN.
+///
+/// The timestamp is nanoseconds since UNIX epoch on the [`TimeBase`] of message,
+/// it's the current monotonic timestamp when set as `None`.
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct CanMessage<C> {
+    timestamp: u64,
+    time_base: TimeBase,
+    arbitration_id: u32,
+    is_extended_id: bool,
+    is_remote_frame: bool,
+    is_error_frame: bool,
+    channel: C,
+    length: usize,
+    data: Vec<u8>,
+    is_fd: bool,
+    direct: Direct,
+    bitrate_switch: bool,
+    error_state_indicator: bool,
+    tx_mode: u8,
+}
+
+unsafe impl<C: Send> Send for CanMessage<C> {}
+unsafe impl<C: Sync> Sync for CanMessage<C> {}
+
+impl<C> Frame for CanMessage<C>
+where
+    C: Display + Clone + Default + Send + Sync {
+    type Channel = C;
+    #[inline]
+    fn new(id: impl Into<Id>, data: &[u8]) -> Option<Self> {
+        let length = data.len();
+
+        match is_can_fd(length) {
+            Some(is_fd) => {
+                let id: Id = id.into();
+                Some(Self {
+                    timestamp: 0,
+                    time_base: Default::default(),
+                    arbitration_id: id.as_raw(),
+                    is_extended_id: id.is_extended(),
+                    is_remote_frame: false,
+                    is_error_frame: false,
+                    channel: Default::default(),
+                    length,
+                    data: data.to_vec(),
+                    is_fd,
+                    direct: Default::default(),
+                    bitrate_switch: false,
+                    error_state_indicator: false,
+                    tx_mode: 0,
+                })
+            },
+            None => None,
+        }
+    }
+
+    #[inline]
+    fn new_remote(id: impl Into<Id>, len: usize) -> Option<Self> {
+        match is_can_fd(len) {
+            Some(is_fd) => {
+                let id = id.into();
+                let mut data = Vec::new();
+                data_resize(&mut data, len);
+                Some(Self {
+                    timestamp: 0,
+                    time_base: Default::default(),
+                    arbitration_id: id.as_raw(),
+                    is_extended_id: id.is_extended(),
+                    is_remote_frame: true,
+                    is_error_frame: false,
+                    channel: Default::default(),
+                    length: len,
+                    data,
+                    is_fd,
+                    direct: Default::default(),
+                    bitrate_switch: false,
+                    error_state_indicator: false,
+                    tx_mode: 0,
+                })
+            },
+            None => None,
+        }
+    }
+
+    #[inline]
+    fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    #[inline]
+    fn set_timestamp(&mut self, value: Option<u64>) -> &mut Self where Self: Sized {
+        self.timestamp = match value {
+            Some(v) => v,
+            None => {
+                self.time_base = TimeBase::Monotonic;
+                monotonic_timestamp()
+            },
+        };
+        self
+    }
+
+    #[inline]
+    fn id(&self) -> Id {
+        Id::from_bits(self.arbitration_id, self.is_extended_id)
+    }
+
+    #[inline]
+    fn is_can_fd(&self) -> bool {
+        self.is_fd
+    }
+
+    #[inline]
+    fn set_can_fd(&mut self, value: bool) -> &mut Self where Self: Sized {
+        if !value && self.length > CAN_FRAME_MAX_SIZE {
+            log::warn!("resize a fd-frame to: {}", CAN_FRAME_MAX_SIZE);
+            self.length = CAN_FRAME_MAX_SIZE;
+            self.data.truncate(CAN_FRAME_MAX_SIZE);
+        }
+        self.is_fd = value;
+        self
+    }
+
+    #[inline]
+    fn is_remote(&self) -> bool {
+        self.is_remote_frame
+    }
+
+    #[inline]
+    fn is_extended(&self) -> bool {
+        self.is_extended_id
+    }
+
+    #[inline]
+    fn direct(&self) -> Direct {
+        self.direct
+    }
+
+    #[inline]
+    fn set_direct(&mut self, direct: Direct) -> &mut Self where Self: Sized {
+        self.direct = direct;
+        self
+    }
+
+    #[inline]
+    fn is_bitrate_switch(&self) -> bool {
+        self.bitrate_switch
+    }
+
+    #[inline]
+    fn set_bitrate_switch(&mut self, value: bool) -> &mut Self where Self: Sized {
+        self.bitrate_switch = value;
+        self
+    }
+
+    #[inline]
+    fn is_error_frame(&self) -> bool {
+        self.is_error_frame
+    }
+
+    #[inline]
+    fn set_error_frame(&mut self, value: bool) -> &mut Self where Self: Sized {
+        self.is_error_frame = value;
+        self
+    }
+
+    #[inline]
+    fn is_esi(&self) -> bool {
+        self.error_state_indicator
+    }
+
+    #[inline]
+    fn set_esi(&mut self, value: bool) -> &mut Self where Self: Sized {
+        self.error_state_indicator = value;
+        self
+    }
+
+    #[inline]
+    fn channel(&self) -> Self::Channel {
+        self.channel.clone()
+    }
+
+    #[inline]
+    fn set_channel(&mut self, value: Self::Channel) -> &mut Self where Self: Sized {
+        self.channel = value;
+        self
+    }
+
+    #[inline]
+    fn data(&self) -> &[u8] {
+        self.data.as_slice()
+    }
+
+    #[inline]
+    fn dlc(&self) -> Option<usize> {
+        let len = self.length;
+        match len {
+            ..=CAN_FRAME_MAX_SIZE => Some(len),
+            9..=CANFD_FRAME_MAX_SIZE => {
+                if !self.is_fd {
+                    return None;
+                }
+                match len {
+                    9..=12 =>  Some(12),
+                    13..=16 => Some(16),
+                    17..=20 => Some(20),
+                    21..=24 => Some(24),
+                    25..=32 => Some(32),
+                    33..=48 => Some(48),
+                    49..=64 => Some(64),
+                    _ => None,
+                }
+            },
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn length(&self) -> usize {
+        self.length
+    }
+}
+
+impl<C> PartialEq for CanMessage<C> {
+    fn eq(&self, other: &Self) -> bool {
+        if self.length != other.length {
+            return false;
+        }
+
+        if self.is_remote_frame {
+            other.is_remote_frame && (self.arbitration_id == other.arbitration_id)
+        }
+        else {
+            (self.arbitration_id == other.arbitration_id) &&
+                (self.is_extended_id == other.is_extended_id) &&
+                (self.is_error_frame == other.is_error_frame) &&
+                (self.error_state_indicator == other.error_state_indicator) &&
+                (self.data == other.data)
+        }
+    }
+}
+
+impl<C> CanMessage<C> {
+    #[inline(always)]
+    pub const fn time_base(&self) -> TimeBase { self.time_base }
+    #[inline(always)]
+    pub fn set_time_base(&mut self, time_base: TimeBase) -> &mut Self {
+        self.time_base = time_base;
+        self
+    }
+    /// The backend specific transmit mode, such as the `TxMode` of ZLGCAN.
+    #[inline(always)]
+    pub const fn tx_mode(&self) -> u8 { self.tx_mode }
+    #[inline(always)]
+    pub fn set_tx_mode(&mut self, tx_mode: u8) -> &mut Self {
+        self.tx_mode = if tx_mode > 3 { Default::default() } else { tx_mode };
+        self
+    }
+    /// Convert the message to another channel type, used to route frames between backends.
+    pub fn with_channel<T>(self, channel: T) -> CanMessage<T> {
+        CanMessage {
+            timestamp: self.timestamp,
+            time_base: self.time_base,
+            arbitration_id: self.arbitration_id,
+            is_extended_id: self.is_extended_id,
+            is_remote_frame: self.is_remote_frame,
+            is_error_frame: self.is_error_frame,
+            channel,
+            length: self.length,
+            data: self.data,
+            is_fd: self.is_fd,
+            direct: self.direct,
+            bitrate_switch: self.bitrate_switch,
+            error_state_indicator: self.error_state_indicator,
+            tx_mode: self.tx_mode,
+        }
+    }
+}
+
+impl<C> CanMessage<C>
+where
+    C: Display + Clone + Default + Send + Sync {
+    /// Parse the frame string of `cansend`, such as `123#DEADBEEF`, `12345678#R2` or `123##1AABB`.
+    ///
+    /// The identifier is extended when it has 8 digits, and the error frame has the `0x20000000` flag.
+    pub fn from_cansend(value: &str) -> Result<Self, CanError> {
+        let error = || CanError::FrameConvertFailed(format!("invalid frame string: `{}`", value));
+        let (id, rest) = value.trim().split_once('#').ok_or_else(error)?;
+        let raw = u32::from_str_radix(id, 16).map_err(|_| error())?;
+        let (is_error, extended) = match id.len() {
+            3 => (false, false),
+            8 => (raw & CAN_ERR_FLAG > 0, raw & CAN_ERR_FLAG == 0),
+            _ => return Err(error()),
+        };
+        let id = Id::from_bits(raw & EFF_MASK, extended);
+
+        let mut msg = if let Some(rest) = rest.strip_prefix('#') {
+            let flags = rest.get(..1)
+                .and_then(|v| u8::from_str_radix(v, 16).ok())
+                .ok_or_else(error)?;
+            let data = parse_hex(&rest[1..]).ok_or_else(error)?;
+            let mut msg = Self::new(id, &data).ok_or_else(error)?;
+            msg.set_can_fd(true)
+                .set_bitrate_switch(flags & CANFD_BRS > 0)
+                .set_esi(flags & CANFD_ESI > 0);
+            msg
+        }
+        else if let Some(len) = rest.strip_prefix(['R', 'r']) {
+            // the `_{dlc}` of length 8 is ignored
+            let len = match len.split('_').next() {
+                Some("") | None => 0,
+                Some(v) => v.parse::<usize>().map_err(|_| error())?,
+            };
+            if len > CAN_FRAME_MAX_SIZE {
+                return Err(error());
+            }
+            Self::new_remote(id, len).ok_or_else(error)?
+        }
+        else {
+            let data = rest.split('_').next()
+                .and_then(parse_hex)
+                .filter(|v| v.len() <= CAN_FRAME_MAX_SIZE)
+                .ok_or_else(error)?;
+            Self::new(id, &data).ok_or_else(error)?
+        };
+        msg.set_error_frame(is_error);
+
+        Ok(msg)
+    }
+
+    /// Format as the frame string of `cansend`.
+    pub fn to_cansend(&self) -> String {
+        let mut result = if self.is_error_frame {
+            format!("{:08X}", (self.arbitration_id & EFF_MASK) | CAN_ERR_FLAG)
+        }
+        else if self.is_extended_id {
+            format!("{:08X}", self.arbitration_id)
+        }
+        else {
+            format!("{:03X}", self.arbitration_id)
+        };
+
+        if self.is_fd {
+            let mut flags = 0;
+            if self.bitrate_switch {
+                flags |= CANFD_BRS;
+            }
+            if self.error_state_indicator {
+                flags |= CANFD_ESI;
+            }
+            result.push_str(&format!("##{:X}", flags));
+        }
+        else {
+            result.push('#');
+            if self.is_remote_frame {
+                result.push('R');
+                if self.length > 0 {
+                    result.push_str(&self.length.to_string());
+                }
+                return result;
+            }
+        }
+
+        self.data.iter()
+            .take(self.length)
+            .for_each(|v| result.push_str(&format!("{:02X}", v)));
+        result
+    }
+}
+
+impl<C> Display for CanMessage<C>
+where
+    C: Display + Clone + Default + Send + Sync + 'static {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        <dyn Frame<Channel=C> as Display>::fmt(self, f)
+    }
+}
+
+/// Parse the hex string, the `.` separators are ignored.
+fn parse_hex(value: &str) -> Option<Vec<u8>> {
+    let value = value.replace('.', "");
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[inline]
+fn is_can_fd(len: usize) -> Option<bool> {
+    match len {
+        ..=CAN_FRAME_MAX_SIZE => Some(false),
+        9..=CANFD_FRAME_MAX_SIZE => Some(true),
+        _ => {
+            log::warn!("CanMessage - invalid data length: {}", len);
+            None
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use isotp_rs::can::{frame::Frame, identifier::Id};
+    use super::CanMessage;
+
+    #[test]
+    fn test_flags() {
+        let mut msg = CanMessage::<u8>::new(Id::from(0x7DF), &[0x02, 0x10, 0x01]).unwrap();
+        assert!(!msg.is_can_fd());
+        msg.set_bitrate_switch(true);
+        assert!(msg.is_bitrate_switch());
+        assert!(!msg.is_error_frame());
+
+        let msg = CanMessage::<u8>::new(Id::from(0x18DA00F1), &[0x00; 12]).unwrap();
+        assert!(msg.is_can_fd());
+        assert!(msg.is_extended());
+        assert_eq!(msg.dlc(), Some(12));
+
+        assert!(CanMessage::<u8>::new(Id::from(0x7DF), &[0x00; 65]).is_none());
+    }
+
+    #[test]
+    fn test_cansend() -> anyhow::Result<()> {
+        let msg = CanMessage::<u8>::from_cansend("123#DE.AD.BE.EF")?;
+        assert_eq!(msg, CanMessage::new(Id::from(0x123), &[0xDE, 0xAD, 0xBE, 0xEF]).unwrap());
+        assert_eq!(msg.to_cansend(), "123#DEADBEEF");
+
+        let msg = CanMessage::<u8>::from_cansend("18DA00F1#R3")?;
+        assert!(msg.is_remote());
+        assert!(msg.is_extended());
+        assert_eq!(msg.length(), 3);
+        assert_eq!(msg.to_cansend(), "18DA00F1#R3");
+
+        let msg = CanMessage::<u8>::from_cansend("7DF##3021003")?;
+        assert!(msg.is_can_fd());
+        assert!(msg.is_bitrate_switch());
+        assert!(msg.is_esi());
+        assert_eq!(msg.data(), &[0x02, 0x10, 0x03]);
+        assert_eq!(msg.to_cansend(), "7DF##3021003");
+
+        let msg = CanMessage::<u8>::from_cansend("20000080#0000000000000000")?;
+        assert!(msg.is_error_frame());
+        assert_eq!(msg.id().as_raw(), 0x80);
+        assert_eq!(msg.to_cansend(), "20000080#0000000000000000");
+
+        assert_eq!(CanMessage::<u8>::from_cansend("123#")?.length(), 0);
+        for value in ["123", "1234#00", "123#0", "123#R9", "123#001122334455667788", "123##"] {
+            assert!(CanMessage::<u8>::from_cansend(value).is_err(), "{}", value);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_with_channel() {
+        let mut msg = CanMessage::<u8>::new(Id::from(0x7DF), &[0x02, 0x10, 0x01]).unwrap();
+        msg.set_channel(1);
+        let other = msg.clone().with_channel(String::from("CAN0"));
+        assert_eq!(other.channel(), "CAN0");
+        assert_eq!(other.data(), msg.data());
+        assert_eq!(other.id(), msg.id());
+    }
+}
@@ -0,0 +1,9 @@
+//! The backends implemented in pure Rust, enabled by features.
+
+#[cfg(all(target_os = "linux", feature = "socketcan"))]
+pub mod socketcan;
+
+#[cfg(feature = "udp_multicast")]
+pub mod udp_multicast;
+
+pub mod virtual_bus;
@@ -0,0 +1,626 @@
+//! The Linux SocketCAN backend, the bitrate of interface should be configured by `ip link`.
+
+use std::collections::HashMap;
+use std::ffi::{c_int, c_void, CString};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::Arc;
+use isotp_rs::can::{CAN_FRAME_MAX_SIZE, CANFD_FRAME_MAX_SIZE, frame::{Direct, Frame}, identifier::Id};
+use isotp_rs::device::Driver;
+use crate::{CanDevice, CanFilter, CanMessage, Capability, ChannelConfig, TimeBase};
+use crate::error::CanError;
+use crate::utils::system_timestamp;
+
+const CAN_EFF_FLAG: u32 = 0x8000_0000;
+const CAN_RTR_FLAG: u32 = 0x4000_0000;
+const CAN_ERR_FLAG: u32 = 0x2000_0000;
+const CAN_EFF_MASK: u32 = 0x1FFF_FFFF;
+const CAN_ERR_MASK: u32 = 0x1FFF_FFFF;
+
+const CANFD_BRS: u8 = 0x01;
+const CANFD_ESI: u8 = 0x02;
+const CANFD_FDF: u8 = 0x04;
+
+const CAN_RAW: c_int = 1;
+const SOL_CAN_RAW: c_int = 100 + CAN_RAW;
+const CAN_RAW_FILTER: c_int = 1;
+const CAN_RAW_ERR_FILTER: c_int = 2;
+const CAN_RAW_FD_FRAMES: c_int = 5;
+
+const SO_TIMESTAMPING: c_int = 37;
+const SOF_TIMESTAMPING_RX_HARDWARE: u32 = 1 << 2;
+const SOF_TIMESTAMPING_RX_SOFTWARE: u32 = 1 << 3;
+const SOF_TIMESTAMPING_SOFTWARE: u32 = 1 << 4;
+const SOF_TIMESTAMPING_RAW_HARDWARE: u32 = 1 << 6;
+
+/// The timestamp of received frame and its time base.
+type Timestamp = (u64, TimeBase);
+
+const CAN_MTU: usize = 16;
+const CANFD_MTU: usize = 72;
+
+/// The `struct canfd_frame`, the `struct can_frame` is the first 16 bytes of it.
+#[repr(C, align(8))]
+#[derive(Debug, Copy, Clone)]
+struct RawFrame {
+    can_id: u32,
+    len: u8,
+    flags: u8,
+    res0: u8,
+    res1: u8,
+    data: [u8; CANFD_FRAME_MAX_SIZE],
+}
+
+impl Default for RawFrame {
+    fn default() -> Self {
+        Self {
+            can_id: Default::default(),
+            len: Default::default(),
+            flags: Default::default(),
+            res0: Default::default(),
+            res1: Default::default(),
+            data: [0; CANFD_FRAME_MAX_SIZE],
+        }
+    }
+}
+
+/// The `struct can_filter`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct RawFilter {
+    can_id: u32,
+    can_mask: u32,
+}
+
+impl From<&CanFilter> for RawFilter {
+    fn from(value: &CanFilter) -> Self {
+        let can_id = if value.extended { value.can_id | CAN_EFF_FLAG } else { value.can_id };
+        Self {
+            can_id,
+            // the frame type is always matched.
+            can_mask: value.can_mask | CAN_EFF_FLAG,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SocketContext {
+    fd: OwnedFd,
+    filters: Vec<CanFilter>,
+    canfd: bool,
+    log_errors: bool,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SocketCan {
+    channels: HashMap<String, Arc<SocketContext>>,
+}
+
+impl SocketCan {
+    pub fn new() -> Self {
+        Self {
+            channels: Default::default(),
+        }
+    }
+
+    /// Open a raw socket bound to interface, such as `can0` or `vcan0`.
+    pub fn open(&mut self,
+                channel: &str,
+                filters: Vec<CanFilter>,
+                canfd: bool,
+                log_errors: bool,
+    ) -> Result<(), CanError> {
+        let fd = unsafe { libc::socket(libc::PF_CAN, libc::SOCK_RAW | libc::SOCK_CLOEXEC, CAN_RAW) };
+        if fd < 0 {
+            log::warn!("{} error {} when open", Self::channel_info(channel), io::Error::last_os_error());
+            return Err(CanError::DeviceOpenFailed);
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let raw_fd = fd.as_raw_fd();
+
+        let init = || -> io::Result<()> {
+            if canfd {
+                set_option(raw_fd, SOL_CAN_RAW, CAN_RAW_FD_FRAMES, &(1 as c_int))?;
+            }
+
+            if !filters.is_empty() {
+                let raw_filters = filters.iter()
+                    .map(RawFilter::from)
+                    .collect::<Vec<_>>();
+                set_option_slice(raw_fd, SOL_CAN_RAW, CAN_RAW_FILTER, raw_filters.as_slice())?;
+            }
+
+            if log_errors {
+                set_option(raw_fd, SOL_CAN_RAW, CAN_RAW_ERR_FILTER, &CAN_ERR_MASK)?;
+            }
+
+            let flags = SOF_TIMESTAMPING_RX_HARDWARE | SOF_TIMESTAMPING_RAW_HARDWARE
+                | SOF_TIMESTAMPING_RX_SOFTWARE | SOF_TIMESTAMPING_SOFTWARE;
+            set_option(raw_fd, libc::SOL_SOCKET, SO_TIMESTAMPING, &flags)?;
+
+            let ifname = CString::new(channel)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            let ifindex = unsafe { libc::if_nametoindex(ifname.as_ptr()) };
+            if ifindex == 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut addr: libc::sockaddr_can = unsafe { std::mem::zeroed() };
+            addr.can_family = libc::AF_CAN as libc::sa_family_t;
+            addr.can_ifindex = ifindex as c_int;
+            let ret = unsafe {
+                libc::bind(
+                    raw_fd,
+                    &addr as *const libc::sockaddr_can as *const libc::sockaddr,
+                    std::mem::size_of::<libc::sockaddr_can>() as libc::socklen_t,
+                )
+            };
+            if ret < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            Ok(())
+        };
+
+        if let Err(e) = init() {
+            log::warn!("{} error {} when initialize", Self::channel_info(channel), e);
+            return Err(CanError::ChannelInitializeError(channel.into()));
+        }
+
+        self.channels.insert(channel.into(), Arc::new(SocketContext {
+            fd,
+            filters,
+            canfd,
+            log_errors,
+        }));
+
+        Ok(())
+    }
+
+    pub fn close(&mut self, channel: String) -> Result<(), CanError> {
+        match self.channels.remove(&channel) {
+            Some(_) => Ok(()),
+            None => Err(CanError::ChannelNotOpened(channel)),
+        }
+    }
+
+    pub fn transmit_can(&self, msg: CanMessage<String>, timeout: Option<u32>) -> Result<(), CanError> {
+        let channel = msg.channel();
+        match self.channels.get(&channel) {
+            Some(ctx) => {
+                if msg.is_can_fd() && !ctx.canfd {
+                    return Err(CanError::FrameConvertFailed(
+                        format!("{} is not opened with CAN-FD", Self::channel_info(channel.as_str()))
+                    ));
+                }
+
+                let raw_fd = ctx.fd.as_raw_fd();
+                let timeout = timeout.map(|v| v as c_int).unwrap_or(-1);
+                match wait_for(raw_fd, libc::POLLOUT, timeout) {
+                    Ok(true) => {},
+                    Ok(false) => return Err(CanError::TimeoutError(Self::channel_info(channel.as_str()))),
+                    Err(e) => {
+                        log::warn!("{} error {} when transmit", Self::channel_info(channel.as_str()), e);
+                        return Err(CanError::OperationError(Self::channel_info(channel.as_str())));
+                    }
+                }
+
+                let (frame, size) = Self::to_raw_frame(&msg);
+                let ret = unsafe {
+                    libc::write(raw_fd, &frame as *const RawFrame as *const c_void, size)
+                };
+                if ret != size as isize {
+                    log::warn!(
+                        "{} error {} when transmit",
+                        Self::channel_info(channel.as_str()),
+                        io::Error::last_os_error()
+                    );
+                    return Err(CanError::OperationError(Self::channel_info(channel.as_str())));
+                }
+
+                Ok(())
+            },
+            None => Err(CanError::ChannelNotOpened(channel)),
+        }
+    }
+
+    /// Receive all frames in socket buffer, `None` timeout means no waiting.
+    pub fn receive_can(&self, channel: String, timeout: Option<u32>) -> Result<Vec<CanMessage<String>>, CanError> {
+        match self.channels.get(&channel) {
+            Some(ctx) => {
+                let raw_fd = ctx.fd.as_raw_fd();
+                let timeout = timeout.map(|v| v as c_int).unwrap_or_default();
+                match wait_for(raw_fd, libc::POLLIN, timeout) {
+                    Ok(true) => {},
+                    Ok(false) => return Ok(vec![]),
+                    Err(e) => {
+                        log::warn!("{} error {} when receive", Self::channel_info(channel.as_str()), e);
+                        return Err(CanError::OperationError(Self::channel_info(channel.as_str())));
+                    }
+                }
+
+                let mut results = Vec::new();
+                loop {
+                    match Self::read_frame(raw_fd) {
+                        Ok(Some((frame, size, (timestamp, time_base), direct))) => {
+                            match Self::from_raw_frame(&frame, size) {
+                                Some(mut msg) => {
+                                    msg.set_timestamp(Some(timestamp))
+                                        .set_time_base(time_base)
+                                        .set_direct(direct)
+                                        .set_channel(channel.clone());
+                                    results.push(msg);
+                                },
+                                None => log::warn!(
+                                    "{} invalid frame with size: {}",
+                                    Self::channel_info(channel.as_str()),
+                                    size
+                                ),
+                            }
+                        },
+                        Ok(None) => break,
+                        Err(e) => {
+                            log::warn!("{} error {} when receive", Self::channel_info(channel.as_str()), e);
+                            if results.is_empty() {
+                                return Err(CanError::OperationError(Self::channel_info(channel.as_str())));
+                            }
+                            break;
+                        }
+                    }
+                }
+
+                Ok(results)
+            },
+            None => Err(CanError::ChannelNotOpened(channel)),
+        }
+    }
+
+    #[inline]
+    pub fn channel_info(channel: &str) -> String {
+        format!("SocketCAN: {}", channel)
+    }
+
+    /// The listen only mode and resistance can't be set by socket, they are rejected.
+    pub(crate) fn check_config(channel: &str, cfg: &ChannelConfig) -> Result<(), CanError> {
+        if cfg.listen_only || cfg.resistance.is_some() {
+            return Err(CanError::OperationError(format!(
+                "{} listen only and resistance should be configured by `ip link`",
+                Self::channel_info(channel)
+            )));
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn filters(&self, channel: String) -> Result<Vec<CanFilter>, CanError> {
+        self.channel_util(channel, |ctx| Ok(ctx.filters.clone()))
+    }
+
+    #[inline]
+    pub fn is_can_fd(&self, channel: String) -> Result<bool, CanError> {
+        self.channel_util(channel, |ctx| Ok(ctx.canfd))
+    }
+
+    #[inline]
+    pub fn is_log_errors(&self, channel: String) -> Result<bool, CanError> {
+        self.channel_util(channel, |ctx| Ok(ctx.log_errors))
+    }
+
+    #[inline]
+    fn channel_util<R>(&self,
+                       channel: String,
+                       cb: fn(ctx: &SocketContext) -> Result<R, CanError>
+    ) -> Result<R, CanError> {
+        match self.channels.get(&channel) {
+            Some(ctx) => cb(ctx),
+            None => Err(CanError::ChannelNotOpened(channel)),
+        }
+    }
+
+    fn to_raw_frame(msg: &CanMessage<String>) -> (RawFrame, usize) {
+        let mut can_id = msg.id().as_raw();
+        if msg.is_extended() {
+            can_id |= CAN_EFF_FLAG;
+        }
+        if msg.is_remote() {
+            can_id |= CAN_RTR_FLAG;
+        }
+        if msg.is_error_frame() {
+            can_id |= CAN_ERR_FLAG;
+        }
+
+        let data = msg.data();
+        let mut frame = RawFrame {
+            can_id,
+            len: msg.length() as u8,
+            ..Default::default()
+        };
+        frame.data[..data.len()].copy_from_slice(data);
+
+        if msg.is_can_fd() {
+            frame.len = msg.dlc().unwrap_or(msg.length()) as u8;
+            frame.flags = CANFD_FDF;
+            if msg.is_bitrate_switch() {
+                frame.flags |= CANFD_BRS;
+            }
+            if msg.is_esi() {
+                frame.flags |= CANFD_ESI;
+            }
+            (frame, CANFD_MTU)
+        }
+        else {
+            (frame, CAN_MTU)
+        }
+    }
+
+    fn from_raw_frame(frame: &RawFrame, size: usize) -> Option<CanMessage<String>> {
+        let can_id = frame.can_id;
+        let is_fd = match size {
+            CAN_MTU => false,
+            CANFD_MTU => true,
+            _ => return None,
+        };
+        let max = if is_fd { CANFD_FRAME_MAX_SIZE } else { CAN_FRAME_MAX_SIZE };
+        let len = (frame.len as usize).min(max);
+
+        if can_id & CAN_ERR_FLAG > 0 {
+            let mut msg = CanMessage::new(Id::from_bits(can_id & CAN_ERR_MASK, false), &frame.data[..len])?;
+            msg.set_error_frame(true);
+            return Some(msg);
+        }
+
+        let id = Id::from_bits(can_id & CAN_EFF_MASK, can_id & CAN_EFF_FLAG > 0);
+        if can_id & CAN_RTR_FLAG > 0 {
+            return CanMessage::new_remote(id, len);
+        }
+
+        let mut msg = CanMessage::new(id, &frame.data[..len])?;
+        if is_fd {
+            msg.set_can_fd(true)
+                .set_bitrate_switch(frame.flags & CANFD_BRS > 0)
+                .set_esi(frame.flags & CANFD_ESI > 0);
+        }
+        Some(msg)
+    }
+
+    /// Read a frame without blocking, the hardware timestamp is preferred.
+    fn read_frame(fd: RawFd) -> io::Result<Option<(RawFrame, usize, Timestamp, Direct)>> {
+        let mut frame = RawFrame::default();
+        let mut iov = libc::iovec {
+            iov_base: &mut frame as *mut RawFrame as *mut c_void,
+            iov_len: std::mem::size_of::<RawFrame>(),
+        };
+        let mut control = [0u64; 16];
+        let mut header: libc::msghdr = unsafe { std::mem::zeroed() };
+        header.msg_iov = &mut iov;
+        header.msg_iovlen = 1;
+        header.msg_control = control.as_mut_ptr() as *mut c_void;
+        header.msg_controllen = std::mem::size_of_val(&control) as _;
+
+        let size = unsafe { libc::recvmsg(fd, &mut header, libc::MSG_DONTWAIT) };
+        if size < 0 {
+            let e = io::Error::last_os_error();
+            return match e.kind() {
+                io::ErrorKind::WouldBlock => Ok(None),
+                _ => Err(e),
+            };
+        }
+
+        let mut timestamp = None;
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(&header);
+            while !cmsg.is_null() {
+                if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == SO_TIMESTAMPING {
+                    // [software, deprecated, raw hardware]
+                    let ts = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const [libc::timespec; 3]);
+                    let (ts, time_base) = if ts[2].tv_sec != 0 || ts[2].tv_nsec != 0 {
+                        (ts[2], TimeBase::Hardware)
+                    }
+                    else {
+                        (ts[0], TimeBase::WallClock)
+                    };
+                    timestamp = Some((ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64, time_base));
+                }
+                cmsg = libc::CMSG_NXTHDR(&header, cmsg);
+            }
+        }
+
+        let direct = if header.msg_flags & libc::MSG_DONTROUTE > 0 { Direct::Transmit } else { Direct::Receive };
+
+        let timestamp = timestamp.unwrap_or_else(|| (system_timestamp(), TimeBase::WallClock));
+        Ok(Some((frame, size as usize, timestamp, direct)))
+    }
+}
+
+impl Driver for SocketCan {
+    type Error = CanError;
+    type C = String;
+    type F = CanMessage<String>;
+
+    #[inline]
+    fn opened_channels(&self) -> Vec<Self::C> {
+        self.channels.keys()
+            .cloned()
+            .collect()
+    }
+
+    #[inline]
+    fn is_closed(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    #[inline]
+    fn transmit(&self, msg: Self::F, timeout: Option<u32>) -> Result<(), Self::Error> {
+        self.transmit_can(msg, timeout)
+    }
+
+    #[inline]
+    fn receive(&self, channel: Self::C, timeout: Option<u32>) -> Result<Vec<Self::F>, Self::Error> {
+        self.receive_can(channel, timeout)
+    }
+
+    #[inline]
+    fn shutdown(&mut self) {
+        self.channels.clear();
+    }
+}
+
+impl CanDevice for SocketCan {
+    #[inline]
+    fn open_device(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn init_channel(&mut self, channel: Self::C, cfg: &ChannelConfig) -> Result<(), Self::Error> {
+        Self::check_config(channel.as_str(), cfg)?;
+        log::info!(
+            "{} bitrate: {} should be configured by `ip link`",
+            Self::channel_info(channel.as_str()),
+            cfg.bitrate
+        );
+
+        let log_errors = self.is_log_errors(channel.clone()).unwrap_or(true);
+        self.channels.remove(&channel);
+        self.open(channel.as_str(), cfg.filters.clone(), cfg.is_can_fd(), log_errors)
+    }
+
+    #[inline]
+    fn reset_channel(&mut self, channel: Self::C) -> Result<(), Self::Error> {
+        let ctx = self.channels.get(&channel)
+            .ok_or(CanError::ChannelNotOpened(channel.clone()))?;
+        let (filters, canfd, log_errors) = (ctx.filters.clone(), ctx.canfd, ctx.log_errors);
+        self.channels.remove(&channel);
+        self.open(channel.as_str(), filters, canfd, log_errors)
+    }
+
+    #[inline]
+    fn close_device(&mut self) -> Result<(), Self::Error> {
+        self.shutdown();
+        Ok(())
+    }
+
+    fn capability(&self) -> Result<Capability, Self::Error> {
+        Ok(Capability {
+            // the interfaces are discovered by the system, not by the device
+            channels: None,
+            canfd: true,
+            resistance: false,
+            hardware_filter: true,
+            filter_count: None,
+            auto_send: false,
+            listen_only: false,
+        })
+    }
+}
+
+#[inline]
+fn set_option<T>(fd: RawFd, level: c_int, name: c_int, value: &T) -> io::Result<()> {
+    set_option_slice(fd, level, name, std::slice::from_ref(value))
+}
+
+fn set_option_slice<T>(fd: RawFd, level: c_int, name: c_int, value: &[T]) -> io::Result<()> {
+    let ret = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            value.as_ptr() as *const c_void,
+            std::mem::size_of_val(value) as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Wait for the socket events, return `false` when timeout.
+fn wait_for(fd: RawFd, events: libc::c_short, timeout: c_int) -> io::Result<bool> {
+    let mut pfd = libc::pollfd { fd, events, revents: 0 };
+    loop {
+        let ret = unsafe { libc::poll(&mut pfd, 1, timeout) };
+        if ret < 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(e);
+        }
+
+        return Ok(ret > 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use isotp_rs::can::{frame::Frame, identifier::Id};
+    use isotp_rs::device::Driver;
+    use crate::{CanDevice, CanMessage, ChannelConfig};
+    use super::{SocketCan, CAN_MTU, CANFD_MTU};
+
+    #[test]
+    fn test_raw_frame() {
+        let mut msg = CanMessage::<String>::new(Id::from(0x18DA00F1), &[0x55; 22]).unwrap();
+        msg.set_bitrate_switch(true);
+        let (frame, size) = SocketCan::to_raw_frame(&msg);
+        assert_eq!(size, CANFD_MTU);
+        assert_eq!(frame.len, 24);
+
+        let other = SocketCan::from_raw_frame(&frame, size).unwrap();
+        assert!(other.is_extended());
+        assert!(other.is_can_fd());
+        assert!(other.is_bitrate_switch());
+        assert_eq!(other.id(), msg.id());
+
+        let msg = CanMessage::<String>::new_remote(Id::from(0x7DF), 8).unwrap();
+        let (frame, size) = SocketCan::to_raw_frame(&msg);
+        assert_eq!(size, CAN_MTU);
+        let other = SocketCan::from_raw_frame(&frame, size).unwrap();
+        assert!(other.is_remote());
+    }
+
+    #[test]
+    fn test_init_channel() {
+        let mut device = SocketCan::new();
+        let mut cfg = ChannelConfig::new(500_000);
+        cfg.set_listen_only(true);
+        assert!(device.init_channel("vcan0".into(), &cfg).is_err());
+
+        let mut cfg = ChannelConfig::new(500_000);
+        cfg.set_resistance(true);
+        assert!(device.init_channel("vcan0".into(), &cfg).is_err());
+        assert!(device.opened_channels().is_empty());
+    }
+
+    #[test]
+    fn test_vcan() -> anyhow::Result<()> {
+        let channel = "vcan0";
+        // vcan required: `ip link add dev vcan0 type vcan && ip link set up vcan0`
+        if !std::path::Path::new("/sys/class/net").join(channel).exists() {
+            return Ok(());
+        }
+        let mut tx = SocketCan::new();
+        tx.open(channel, vec![], true, true)?;
+        let mut rx = SocketCan::new();
+        rx.open(channel, vec![], true, true)?;
+
+        let mut msg = CanMessage::new(Id::from(0x7DF), &[0x02, 0x10, 0x01]).unwrap();
+        msg.set_channel(channel.into());
+        tx.transmit(msg.clone(), Some(10))?;
+
+        let mut fd_msg = CanMessage::new(Id::from(0x7E0), &[0xAA; 64]).unwrap();
+        fd_msg.set_channel(channel.into());
+        tx.transmit(fd_msg.clone(), Some(10))?;
+
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let frames = rx.receive(channel.into(), Some(10))?;
+        assert_eq!(frames, vec![msg, fd_msg]);
+
+        tx.shutdown();
+        rx.shutdown();
+
+        Ok(())
+    }
+}
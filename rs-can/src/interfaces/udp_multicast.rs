@@ -0,0 +1,426 @@
+//! The virtual bus between processes by UDP multicast, like `udp_multicast` of python-can.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+use socket2::{Domain, Protocol, Socket, Type};
+use isotp_rs::can::{frame::{Direct, Frame}, identifier::Id};
+use isotp_rs::device::Driver;
+use crate::{CanDevice, CanFilter, CanMessage, Capability, ChannelConfig, TimeBase};
+use crate::error::CanError;
+use crate::filter;
+
+/// The default IPv4 multicast group.
+pub const DEFAULT_GROUP_IPV4: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(239, 74, 163, 2)), 43113);
+/// The default IPv6 multicast group.
+pub const DEFAULT_GROUP_IPV6: SocketAddr = SocketAddr::new(
+    IpAddr::V6(Ipv6Addr::new(0xff15, 0x7079, 0x7468, 0x6f6e, 0x6465, 0x6d6f, 0x6d63, 0x6173)), 43113
+);
+
+const VERSION: u8 = 1;
+const HEADER_SIZE: usize = 22;
+const MAX_PACKET_SIZE: usize = 512;
+
+const FLAG_EXTENDED: u8 = 0x01;
+const FLAG_REMOTE: u8 = 0x02;
+const FLAG_ERROR: u8 = 0x04;
+const FLAG_FD: u8 = 0x08;
+const FLAG_BRS: u8 = 0x10;
+const FLAG_ESI: u8 = 0x20;
+/// The bits 6~7 of flags are the time base.
+const TIME_BASE_SHIFT: u8 = 6;
+
+static SENDER_INDEX: AtomicU32 = AtomicU32::new(0);
+
+/// Encode a message to packet.
+///
+/// `| version(1) | sender(8) | flags(1) | id(4) | timestamp(8) | channel length(1) | channel | data length(1) | data |`
+/// and all numbers are big endian, the timestamp is nanoseconds since UNIX epoch on the time base of sender.
+pub fn encode(sender: u64, msg: &CanMessage<String>) -> Vec<u8> {
+    let mut flags = 0;
+    if msg.is_extended() { flags |= FLAG_EXTENDED; }
+    if msg.is_remote() { flags |= FLAG_REMOTE; }
+    if msg.is_error_frame() { flags |= FLAG_ERROR; }
+    if msg.is_can_fd() { flags |= FLAG_FD; }
+    if msg.is_bitrate_switch() { flags |= FLAG_BRS; }
+    if msg.is_esi() { flags |= FLAG_ESI; }
+    flags |= (msg.time_base() as u8) << TIME_BASE_SHIFT;
+
+    let channel = msg.channel();
+    let channel = &channel.as_bytes()[..channel.len().min(u8::MAX as usize)];
+    let data = msg.data();
+
+    let mut result = Vec::with_capacity(HEADER_SIZE + 2 + channel.len() + data.len());
+    result.push(VERSION);
+    result.extend(sender.to_be_bytes());
+    result.push(flags);
+    result.extend(msg.id().as_raw().to_be_bytes());
+    result.extend(msg.timestamp().to_be_bytes());
+    result.push(channel.len() as u8);
+    result.extend(channel);
+    result.push(if msg.is_remote() { msg.length() } else { data.len() } as u8);
+    if !msg.is_remote() {
+        result.extend(data);
+    }
+
+    result
+}
+
+/// Decode a packet to sender and message.
+pub fn decode(data: &[u8]) -> Option<(u64, CanMessage<String>)> {
+    if data.len() < HEADER_SIZE + 2 || data[0] != VERSION {
+        return None;
+    }
+
+    let sender = u64::from_be_bytes(data[1..9].try_into().ok()?);
+    let flags = data[9];
+    let id = u32::from_be_bytes(data[10..14].try_into().ok()?);
+    let timestamp = u64::from_be_bytes(data[14..22].try_into().ok()?);
+    let mut offset = HEADER_SIZE;
+    let channel_len = data[offset] as usize;
+    offset += 1;
+    let channel = String::from_utf8(data.get(offset..offset + channel_len)?.to_vec()).ok()?;
+    offset += channel_len;
+    let length = *data.get(offset)? as usize;
+    offset += 1;
+
+    let id = Id::from_bits(id, flags & FLAG_EXTENDED > 0);
+    let mut msg = if flags & FLAG_REMOTE > 0 {
+        CanMessage::new_remote(id, length)?
+    }
+    else {
+        CanMessage::new(id, data.get(offset..offset + length)?)?
+    };
+    let time_base = match flags >> TIME_BASE_SHIFT {
+        0 => TimeBase::Monotonic,
+        1 => TimeBase::WallClock,
+        2 => TimeBase::Hardware,
+        _ => return None,
+    };
+    msg.set_timestamp(Some(timestamp))
+        .set_time_base(time_base)
+        .set_channel(channel)
+        .set_error_frame(flags & FLAG_ERROR > 0)
+        .set_can_fd(flags & FLAG_FD > 0)
+        .set_bitrate_switch(flags & FLAG_BRS > 0)
+        .set_esi(flags & FLAG_ESI > 0);
+
+    Some((sender, msg))
+}
+
+#[derive(Debug)]
+struct UdpContext {
+    socket: UdpSocket,
+    sender: u64,
+    filters: Vec<CanFilter>,
+    receive_own: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct UdpMulticast {
+    group: SocketAddr,
+    channels: HashMap<String, Arc<UdpContext>>,
+}
+
+impl Default for UdpMulticast {
+    fn default() -> Self {
+        Self::new(DEFAULT_GROUP_IPV4)
+    }
+}
+
+impl UdpMulticast {
+    pub fn new(group: SocketAddr) -> Self {
+        Self {
+            group,
+            channels: Default::default(),
+        }
+    }
+
+    #[inline]
+    pub fn group(&self) -> SocketAddr {
+        self.group
+    }
+
+    /// Join the multicast group, the channel name is carried by frames and shared by all processes.
+    pub fn open(&mut self,
+                channel: &str,
+                filters: Vec<CanFilter>,
+                receive_own: bool,
+    ) -> Result<(), CanError> {
+        let socket = Self::new_socket(self.group)
+            .map_err(|e| {
+                log::warn!("{} error {} when open", Self::channel_info(channel), e);
+                CanError::ChannelInitializeError(channel.into())
+            })?;
+        let sender = ((std::process::id() as u64) << 32) | SENDER_INDEX.fetch_add(1, Ordering::Relaxed) as u64;
+
+        self.channels.insert(channel.into(), Arc::new(UdpContext {
+            socket,
+            sender,
+            filters,
+            receive_own,
+        }));
+
+        Ok(())
+    }
+
+    pub fn close(&mut self, channel: String) -> Result<(), CanError> {
+        match self.channels.remove(&channel) {
+            Some(_) => Ok(()),
+            None => Err(CanError::ChannelNotOpened(channel)),
+        }
+    }
+
+    pub fn transmit_can(&self, mut msg: CanMessage<String>) -> Result<(), CanError> {
+        let channel = msg.channel();
+        match self.channels.get(&channel) {
+            Some(ctx) => {
+                msg.set_timestamp(None);
+                let packet = encode(ctx.sender, &msg);
+                ctx.socket.send_to(packet.as_slice(), self.group)
+                    .map_err(|e| {
+                        log::warn!("{} error {} when transmit", Self::channel_info(channel.as_str()), e);
+                        CanError::OperationError(Self::channel_info(channel.as_str()))
+                    })?;
+
+                Ok(())
+            },
+            None => Err(CanError::ChannelNotOpened(channel)),
+        }
+    }
+
+    /// Receive all pending frames, `None` timeout means no waiting.
+    pub fn receive_can(&self, channel: String, timeout: Option<u32>) -> Result<Vec<CanMessage<String>>, CanError> {
+        match self.channels.get(&channel) {
+            Some(ctx) => {
+                let mut results = Vec::new();
+                let mut buffer = [0u8; MAX_PACKET_SIZE];
+                let deadline = timeout.filter(|v| *v > 0)
+                    .map(|v| Instant::now() + Duration::from_millis(v as u64));
+                loop {
+                    // keep waiting until a frame is accepted, the dropped packets don't end the waiting
+                    let wait = deadline.filter(|_| results.is_empty())
+                        .and_then(|v| v.checked_duration_since(Instant::now()))
+                        .filter(|v| !v.is_zero());
+                    let ret = match wait {
+                        Some(v) => ctx.socket.set_nonblocking(false)
+                            .and_then(|_| ctx.socket.set_read_timeout(Some(v)))
+                            .and_then(|_| ctx.socket.recv(&mut buffer)),
+                        None => ctx.socket.set_nonblocking(true)
+                            .and_then(|_| ctx.socket.recv(&mut buffer)),
+                    };
+
+                    match ret {
+                        Ok(size) => {
+                            let (sender, mut msg) = match decode(&buffer[..size]) {
+                                Some(v) => v,
+                                None => {
+                                    log::warn!("{} invalid packet", Self::channel_info(channel.as_str()));
+                                    continue;
+                                }
+                            };
+                            if msg.channel() != channel || !filter::is_matched(&ctx.filters, &msg) {
+                                continue;
+                            }
+                            if sender == ctx.sender {
+                                if !ctx.receive_own {
+                                    continue;
+                                }
+                                msg.set_direct(Direct::Transmit);
+                            }
+                            else {
+                                msg.set_direct(Direct::Receive);
+                            }
+
+                            results.push(msg);
+                        },
+                        Err(e) => match e.kind() {
+                            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => break,
+                            _ => {
+                                log::warn!("{} error {} when receive", Self::channel_info(channel.as_str()), e);
+                                return Err(CanError::OperationError(Self::channel_info(channel.as_str())));
+                            }
+                        }
+                    }
+                }
+
+                Ok(results)
+            },
+            None => Err(CanError::ChannelNotOpened(channel)),
+        }
+    }
+
+    #[inline]
+    pub fn channel_info(channel: &str) -> String {
+        format!("UDP-Multicast: {}", channel)
+    }
+
+    fn new_socket(group: SocketAddr) -> io::Result<UdpSocket> {
+        let socket = Socket::new(Domain::for_address(group), Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_reuse_address(true)?;
+        #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+        socket.set_reuse_port(true)?;
+
+        match group.ip() {
+            IpAddr::V4(addr) => {
+                socket.join_multicast_v4(&addr, &Ipv4Addr::UNSPECIFIED)?;
+                socket.set_multicast_loop_v4(true)?;
+                socket.set_multicast_ttl_v4(1)?;
+                socket.bind(&SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), group.port()).into())?;
+            },
+            IpAddr::V6(addr) => {
+                socket.join_multicast_v6(&addr, 0)?;
+                socket.set_multicast_loop_v6(true)?;
+                socket.set_multicast_hops_v6(1)?;
+                socket.bind(&SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), group.port()).into())?;
+            },
+        }
+
+        Ok(socket.into())
+    }
+}
+
+impl Driver for UdpMulticast {
+    type Error = CanError;
+    type C = String;
+    type F = CanMessage<String>;
+
+    #[inline]
+    fn opened_channels(&self) -> Vec<Self::C> {
+        self.channels.keys()
+            .cloned()
+            .collect()
+    }
+
+    #[inline]
+    fn is_closed(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    #[inline]
+    fn transmit(&self, msg: Self::F, _: Option<u32>) -> Result<(), Self::Error> {
+        self.transmit_can(msg)
+    }
+
+    #[inline]
+    fn receive(&self, channel: Self::C, timeout: Option<u32>) -> Result<Vec<Self::F>, Self::Error> {
+        self.receive_can(channel, timeout)
+    }
+
+    #[inline]
+    fn shutdown(&mut self) {
+        self.channels.clear();
+    }
+}
+
+impl CanDevice for UdpMulticast {
+    #[inline]
+    fn open_device(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn init_channel(&mut self, channel: Self::C, cfg: &ChannelConfig) -> Result<(), Self::Error> {
+        let receive_own = self.channels.get(&channel)
+            .map(|ctx| ctx.receive_own)
+            .unwrap_or_default();
+        self.channels.remove(&channel);
+        self.open(channel.as_str(), cfg.filters.clone(), receive_own)
+    }
+
+    #[inline]
+    fn reset_channel(&mut self, channel: Self::C) -> Result<(), Self::Error> {
+        self.receive_can(channel, None)
+            .map(|_| ())
+    }
+
+    #[inline]
+    fn close_device(&mut self) -> Result<(), Self::Error> {
+        self.shutdown();
+        Ok(())
+    }
+
+    fn capability(&self) -> Result<Capability, Self::Error> {
+        Ok(Capability {
+            channels: None,
+            canfd: true,
+            resistance: false,
+            hardware_filter: false,
+            filter_count: None,
+            auto_send: false,
+            listen_only: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use isotp_rs::can::{frame::Frame, identifier::Id};
+    use isotp_rs::device::Driver;
+    use crate::{CanMessage, TimeBase};
+    use super::{decode, encode, UdpMulticast};
+
+    #[test]
+    fn test_codec() {
+        let mut msg = CanMessage::new(Id::from(0x18DA00F1), &[0x55; 20]).unwrap();
+        msg.set_channel("CAN0".into())
+            .set_bitrate_switch(true)
+            .set_timestamp(Some(1_700_000_000_123_456_789))
+            .set_time_base(TimeBase::WallClock);
+        let packet = encode(0x01, &msg);
+        let (sender, other) = decode(packet.as_slice()).unwrap();
+        assert_eq!(sender, 0x01);
+        assert_eq!(other, msg);
+        assert_eq!(other.channel(), "CAN0");
+        assert_eq!(other.timestamp(), 1_700_000_000_123_456_789);
+        assert_eq!(other.time_base(), TimeBase::WallClock);
+        assert!(other.is_can_fd());
+        assert!(other.is_bitrate_switch());
+
+        let mut msg = CanMessage::new_remote(Id::from(0x7DF), 8).unwrap();
+        msg.set_channel("CAN0".into());
+        let (_, other) = decode(encode(0x01, &msg).as_slice()).unwrap();
+        assert!(other.is_remote());
+        assert_eq!(other.length(), 8);
+
+        assert!(decode(&packet[..packet.len() - 1]).is_none());
+    }
+
+    #[test]
+    fn test_bus() -> anyhow::Result<()> {
+        let group = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(239, 74, 163, 3)), 43114);
+        let channel = "CAN0";
+        let mut tester = UdpMulticast::new(group);
+        tester.open(channel, vec![], false)?;
+        let mut ecu = UdpMulticast::new(group);
+        ecu.open(channel, vec![], false)?;
+
+        let mut msg = CanMessage::new(Id::from(0x7DF), &[0x02, 0x10, 0x01]).unwrap();
+        msg.set_channel(channel.into());
+        tester.transmit(msg.clone(), None)?;
+
+        assert_eq!(ecu.receive(channel.into(), Some(100))?, vec![msg.clone()]);
+        assert!(tester.receive(channel.into(), Some(10))?.is_empty());
+
+        // the packet of other channel doesn't end the waiting
+        let other = "CAN1";
+        tester.open(other, vec![], false)?;
+        let mut foreign = msg.clone();
+        foreign.set_channel(other.into());
+        tester.transmit(foreign, None)?;
+        let sender = tester.clone();
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            sender.transmit(msg, None)
+        });
+        let frames = ecu.receive(channel.into(), Some(1000))?;
+        handle.join().unwrap()?;
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].channel(), channel);
+
+        Ok(())
+    }
+}
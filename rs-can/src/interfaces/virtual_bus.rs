@@ -0,0 +1,295 @@
+//! The in-process virtual bus, any driver opened the same channel name shares the bus.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+use isotp_rs::can::frame::{Direct, Frame};
+use isotp_rs::device::Driver;
+use crate::{CanDevice, CanFilter, CanMessage, Capability, ChannelConfig};
+use crate::error::CanError;
+use crate::filter;
+
+/// The endpoints joined the bus, the frames are filtered before sending to endpoint.
+type Bus = Mutex<HashMap<u64, (Vec<CanFilter>, Sender<CanMessage<String>>)>>;
+
+static BUSES: OnceLock<Mutex<HashMap<String, Arc<Bus>>>> = OnceLock::new();
+static ENDPOINT_ID: AtomicU64 = AtomicU64::new(0);
+
+#[inline]
+fn bus(channel: &str) -> Result<Arc<Bus>, CanError> {
+    let mut buses = BUSES.get_or_init(Default::default)
+        .lock()
+        .map_err(|e| CanError::OtherError(e.to_string()))?;
+    Ok(Arc::clone(buses.entry(channel.into()).or_default()))
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    id: u64,
+    bus: Arc<Bus>,
+    receiver: Mutex<Receiver<CanMessage<String>>>,
+    filters: Vec<CanFilter>,
+    receive_own: bool,
+}
+
+impl Drop for Endpoint {
+    fn drop(&mut self) {
+        if let Ok(mut v) = self.bus.lock() {
+            v.remove(&self.id);
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct VirtualCan {
+    channels: HashMap<String, Arc<Endpoint>>,
+}
+
+impl VirtualCan {
+    pub fn new() -> Self {
+        Self {
+            channels: Default::default(),
+        }
+    }
+
+    /// Join a named channel, the frames transmitted by self are received when `receive_own` is true.
+    pub fn open(&mut self,
+                channel: &str,
+                filters: Vec<CanFilter>,
+                receive_own: bool,
+    ) -> Result<(), CanError> {
+        let bus = bus(channel)?;
+        let id = ENDPOINT_ID.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = mpsc::channel();
+        bus.lock()
+            .map_err(|e| CanError::OtherError(e.to_string()))?
+            .insert(id, (filters.clone(), tx));
+
+        self.channels.insert(channel.into(), Arc::new(Endpoint {
+            id,
+            bus,
+            receiver: Mutex::new(rx),
+            filters,
+            receive_own,
+        }));
+
+        Ok(())
+    }
+
+    pub fn close(&mut self, channel: String) -> Result<(), CanError> {
+        match self.channels.remove(&channel) {
+            Some(_) => Ok(()),
+            None => Err(CanError::ChannelNotOpened(channel)),
+        }
+    }
+
+    pub fn transmit_can(&self, mut msg: CanMessage<String>) -> Result<(), CanError> {
+        let channel = msg.channel();
+        match self.channels.get(&channel) {
+            Some(ctx) => {
+                msg.set_timestamp(None);
+                let endpoints = ctx.bus.lock()
+                    .map_err(|e| CanError::OtherError(e.to_string()))?;
+                for (id, (filters, sender)) in endpoints.iter() {
+                    if !filter::is_matched(filters, &msg) {
+                        continue;
+                    }
+
+                    let mut msg = msg.clone();
+                    if *id == ctx.id {
+                        if !ctx.receive_own {
+                            continue;
+                        }
+                        msg.set_direct(Direct::Transmit);
+                    }
+                    else {
+                        msg.set_direct(Direct::Receive);
+                    }
+
+                    // the receiver may be dropped at the same time.
+                    let _ = sender.send(msg);
+                }
+
+                Ok(())
+            },
+            None => Err(CanError::ChannelNotOpened(channel)),
+        }
+    }
+
+    /// Receive all pending frames, `None` timeout means no waiting.
+    pub fn receive_can(&self, channel: String, timeout: Option<u32>) -> Result<Vec<CanMessage<String>>, CanError> {
+        match self.channels.get(&channel) {
+            Some(ctx) => {
+                let receiver = ctx.receiver.lock()
+                    .map_err(|e| CanError::OtherError(e.to_string()))?;
+
+                let mut results = Vec::new();
+                let first = match timeout {
+                    Some(v) => receiver.recv_timeout(Duration::from_millis(v as u64)),
+                    None => receiver.try_recv()
+                        .map_err(|_| RecvTimeoutError::Timeout),
+                };
+                if let Ok(msg) = first {
+                    results.push(msg);
+                    results.extend(receiver.try_iter());
+                }
+
+                Ok(results)
+            },
+            None => Err(CanError::ChannelNotOpened(channel)),
+        }
+    }
+
+    #[inline]
+    pub fn filters(&self, channel: String) -> Result<Vec<CanFilter>, CanError> {
+        match self.channels.get(&channel) {
+            Some(ctx) => Ok(ctx.filters.clone()),
+            None => Err(CanError::ChannelNotOpened(channel)),
+        }
+    }
+}
+
+impl Driver for VirtualCan {
+    type Error = CanError;
+    type C = String;
+    type F = CanMessage<String>;
+
+    #[inline]
+    fn opened_channels(&self) -> Vec<Self::C> {
+        self.channels.keys()
+            .cloned()
+            .collect()
+    }
+
+    #[inline]
+    fn is_closed(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    #[inline]
+    fn transmit(&self, msg: Self::F, _: Option<u32>) -> Result<(), Self::Error> {
+        self.transmit_can(msg)
+    }
+
+    #[inline]
+    fn receive(&self, channel: Self::C, timeout: Option<u32>) -> Result<Vec<Self::F>, Self::Error> {
+        self.receive_can(channel, timeout)
+    }
+
+    #[inline]
+    fn shutdown(&mut self) {
+        self.channels.clear();
+    }
+}
+
+impl CanDevice for VirtualCan {
+    #[inline]
+    fn open_device(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn init_channel(&mut self, channel: Self::C, cfg: &ChannelConfig) -> Result<(), Self::Error> {
+        let receive_own = self.channels.get(&channel)
+            .map(|ctx| ctx.receive_own)
+            .unwrap_or_default();
+        self.channels.remove(&channel);
+        self.open(channel.as_str(), cfg.filters.clone(), receive_own)
+    }
+
+    #[inline]
+    fn reset_channel(&mut self, channel: Self::C) -> Result<(), Self::Error> {
+        match self.channels.get(&channel) {
+            Some(ctx) => {
+                if let Ok(receiver) = ctx.receiver.lock() {
+                    receiver.try_iter().for_each(drop);
+                }
+                Ok(())
+            },
+            None => Err(CanError::ChannelNotOpened(channel)),
+        }
+    }
+
+    #[inline]
+    fn close_device(&mut self) -> Result<(), Self::Error> {
+        self.shutdown();
+        Ok(())
+    }
+
+    fn capability(&self) -> Result<Capability, Self::Error> {
+        Ok(Capability {
+            channels: None,
+            canfd: true,
+            resistance: false,
+            hardware_filter: false,
+            filter_count: None,
+            auto_send: false,
+            listen_only: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use isotp_rs::can::{frame::{Direct, Frame}, identifier::Id};
+    use isotp_rs::can::driver::SyncCan;
+    use isotp_rs::device::Driver;
+    use crate::{CanFilter, CanMessage};
+    use super::VirtualCan;
+
+    #[test]
+    fn test_bus() -> anyhow::Result<()> {
+        let channel = "test_bus";
+        let mut tester = VirtualCan::new();
+        tester.open(channel, vec![], true)?;
+        let mut ecu = VirtualCan::new();
+        ecu.open(channel, vec![CanFilter { can_id: 0x7DF, can_mask: 0x7FF, extended: false }], false)?;
+
+        let mut msg = CanMessage::new(Id::from(0x7DF), &[0x02, 0x10, 0x01]).unwrap();
+        msg.set_channel(channel.into());
+        tester.transmit(msg.clone(), None)?;
+        let mut fd_msg = CanMessage::new(Id::from(0x7E0), &[0xAA; 64]).unwrap();
+        fd_msg.set_channel(channel.into());
+        tester.transmit(fd_msg.clone(), None)?;
+
+        let own = tester.receive(channel.into(), Some(10))?;
+        assert_eq!(own, vec![msg.clone(), fd_msg]);
+        assert_eq!(own[0].direct(), Direct::Transmit);
+
+        let recv = ecu.receive(channel.into(), Some(10))?;
+        assert_eq!(recv, vec![msg.clone()]);
+        assert_eq!(recv[0].direct(), Direct::Receive);
+
+        ecu.transmit(msg.clone(), None)?;
+        assert!(ecu.receive(channel.into(), None)?.is_empty());
+        assert_eq!(tester.receive(channel.into(), Some(10))?, vec![msg]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_can() -> anyhow::Result<()> {
+        let channel = "test_sync_can";
+        let mut driver = VirtualCan::new();
+        driver.open(channel, vec![], false)?;
+        let mut other = VirtualCan::new();
+        other.open(channel, vec![], false)?;
+
+        let mut uni = SyncCan::new(driver.clone());
+        uni.sync_start(100);
+
+        let mut msg = CanMessage::new(Id::from(0x7DF), &[0x02, 0x10, 0x01]).unwrap();
+        msg.set_channel(channel.into());
+        uni.sender().send(msg.clone())?;
+
+        std::thread::sleep(Duration::from_millis(10));
+        assert_eq!(other.receive(channel.into(), Some(100))?, vec![msg]);
+
+        uni.stop();
+        driver.shutdown();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,533 @@
+//! The Vector ASCII log(`.asc`) used by CANalyzer and CANoe.
+//!
+//! The channel of file starts at 1, and the `date` of header is treated as UTC.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+use isotp_rs::can::frame::{Direct, Frame};
+use isotp_rs::can::identifier::Id;
+use crate::{CanMessage, TimeBase};
+use crate::error::CanError;
+use crate::utils::{dlc_to_len, len_to_dlc, system_timestamp};
+use super::{io_error, parse_error, DateTime, LogChannel};
+
+const FLAG_REMOTE: u32 = 1 << 4;
+const FLAG_EDL: u32 = 1 << 12;
+const FLAG_BRS: u32 = 1 << 13;
+const FLAG_ESI: u32 = 1 << 14;
+
+/// Read the frames of ASC file, the other events are skipped.
+pub struct AscReader<R, C> {
+    reader: R,
+    line: usize,
+    hex: bool,
+    relative: bool,
+    /// The start time(ns since UNIX epoch) of measurement.
+    start: u64,
+    /// The elapsed seconds when timestamps are relative.
+    elapsed: f64,
+    buffer: String,
+    _channel: PhantomData<C>,
+}
+
+impl<C: LogChannel> AscReader<BufReader<File>, C> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let file = File::open(path).map_err(io_error)?;
+        Ok(Self::new(BufReader::new(file)))
+    }
+}
+
+impl<R: BufRead, C: LogChannel> AscReader<R, C> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: Default::default(),
+            hex: true,
+            relative: false,
+            start: Default::default(),
+            elapsed: Default::default(),
+            buffer: Default::default(),
+            _channel: Default::default(),
+        }
+    }
+
+    /// The start time(ns since UNIX epoch) of measurement, 0 if the `date` is not parsed yet.
+    #[inline]
+    pub fn start_time(&self) -> u64 {
+        self.start
+    }
+
+    fn parse_line(&mut self) -> Result<Option<CanMessage<C>>, CanError> {
+        let content = std::mem::take(&mut self.buffer);
+        let result = self.parse(&content);
+        self.buffer = content;
+        result
+    }
+
+    fn parse(&mut self, content: &str) -> Result<Option<CanMessage<C>>, CanError> {
+        let mut tokens = content.split_whitespace();
+        let Some(first) = tokens.next() else { return Ok(None); };
+        let Ok(time) = first.parse::<f64>() else {
+            self.parse_header(first, tokens);
+            return Ok(None);
+        };
+        let time = if self.relative {
+            self.elapsed += time;
+            self.elapsed
+        }
+        else {
+            time
+        };
+
+        let tokens = tokens.collect::<Vec<_>>();
+        let msg = match tokens.as_slice() {
+            ["CANFD", rest @ ..] => self.parse_canfd(rest),
+            [channel, "ErrorFrame", ..] |
+            [channel, _, "Rx" | "Tx", ..] if channel.parse::<u8>().is_ok() => self.parse_can(&tokens),
+            _ => return Ok(None),
+        };
+
+        match msg {
+            Some(mut msg) => {
+                // the resolution of ASC is microsecond
+                msg.set_timestamp(Some(self.start + (time * 1e6).round() as u64 * 1000))
+                    .set_time_base(TimeBase::WallClock);
+                Ok(Some(msg))
+            },
+            None => Err(parse_error(self.line, content)),
+        }
+    }
+
+    fn parse_header<'a>(&mut self, first: &str, mut tokens: impl Iterator<Item = &'a str>) {
+        match first.to_lowercase().as_str() {
+            "date" => {
+                if let Some(start) = parse_date(tokens) {
+                    self.start = start;
+                }
+            },
+            "begin" if tokens.next().is_some_and(|v| v.eq_ignore_ascii_case("triggerblock")) => {
+                if let Some(start) = parse_date(tokens) {
+                    self.start = start;
+                }
+            },
+            "base" => {
+                self.hex = tokens.next() != Some("dec");
+                if tokens.next() == Some("timestamps") {
+                    self.relative = tokens.next() == Some("relative");
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// `<channel> <id>[x] <Rx|Tx> d <dlc> <data>...` or `<channel> <id>[x] <Rx|Tx> r [dlc]`
+    fn parse_can(&self, tokens: &[&str]) -> Option<CanMessage<C>> {
+        let channel = parse_channel(tokens[0])?;
+        if tokens[1] == "ErrorFrame" {
+            return error_frame(channel, false);
+        }
+
+        let id = self.parse_id(tokens[1])?;
+        let direct = parse_direct(tokens[2])?;
+        let mut msg = match *tokens.get(3)? {
+            "r" | "R" => {
+                let dlc = match tokens.get(4) {
+                    Some(v) => self.parse_number(v)? as u8,
+                    None => 0,
+                };
+                CanMessage::new_remote(id, dlc_to_len(dlc, false))?
+            },
+            "d" | "D" => {
+                let len = dlc_to_len(self.parse_number(tokens.get(4)?)? as u8, false);
+                let data = self.parse_data(tokens.get(5..5 + len)?)?;
+                CanMessage::new(id, &data)?
+            },
+            _ => return None,
+        };
+        msg.set_channel(channel)
+            .set_direct(direct);
+
+        Some(msg)
+    }
+
+    /// `<channel> <Rx|Tx> <id>[x] [name] <brs> <esi> <dlc> <length> <data>... <duration> <bits> <flags> ...`
+    fn parse_canfd(&self, tokens: &[&str]) -> Option<CanMessage<C>> {
+        let channel = parse_channel(tokens.first()?)?;
+        let direct = parse_direct(tokens.get(1)?)?;
+        if *tokens.get(2)? == "ErrorFrame" {
+            let mut msg = error_frame(channel, true)?;
+            msg.set_direct(direct);
+            return Some(msg);
+        }
+
+        let id = self.parse_id(tokens.get(2)?)?;
+        // skip the symbolic name
+        let pos = if tokens.get(3)?.bytes().all(|c| c.is_ascii_digit()) { 3 } else { 4 };
+        let brs = *tokens.get(pos)? == "1";
+        let esi = *tokens.get(pos + 1)? == "1";
+        let dlc = u8::from_str_radix(tokens.get(pos + 2)?, 16).ok()?;
+        let len = tokens.get(pos + 3)?.parse::<usize>().ok()?;
+        let data = self.parse_data(tokens.get(pos + 4..pos + 4 + len)?)?;
+        let flags = tokens.get(pos + 4 + len + 2)
+            .and_then(|v| u32::from_str_radix(v, 16).ok());
+
+        let mut msg = if flags.is_some_and(|v| v & FLAG_REMOTE > 0) {
+            CanMessage::new_remote(id, dlc_to_len(dlc, false))?
+        }
+        else {
+            let mut msg = CanMessage::new(id, &data)?;
+            msg.set_can_fd(flags.is_none_or(|v| v & FLAG_EDL > 0))
+                .set_bitrate_switch(brs)
+                .set_esi(esi);
+            msg
+        };
+        msg.set_channel(channel)
+            .set_direct(direct);
+
+        Some(msg)
+    }
+
+    #[inline]
+    fn parse_number(&self, value: &str) -> Option<u32> {
+        u32::from_str_radix(value, if self.hex { 16 } else { 10 }).ok()
+    }
+
+    #[inline]
+    fn parse_id(&self, value: &str) -> Option<Id> {
+        match value.strip_suffix(['x', 'X']) {
+            Some(v) => Some(Id::from_bits(self.parse_number(v)?, true)),
+            None => Some(Id::from_bits(self.parse_number(value)?, false)),
+        }
+    }
+
+    #[inline]
+    fn parse_data(&self, values: &[&str]) -> Option<Vec<u8>> {
+        values.iter()
+            .map(|v| self.parse_number(v).and_then(|v| u8::try_from(v).ok()))
+            .collect()
+    }
+}
+
+impl<R: BufRead, C: LogChannel> Iterator for AscReader<R, C> {
+    type Item = Result<CanMessage<C>, CanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buffer.clear();
+            match self.reader.read_line(&mut self.buffer) {
+                Ok(0) => return None,
+                Ok(_) => self.line += 1,
+                Err(e) => return Some(Err(io_error(e))),
+            }
+
+            match self.parse_line() {
+                Ok(Some(msg)) => return Some(Ok(msg)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Write frames as ASC file, the timestamps are relative to the first frame.
+pub struct AscWriter<W: Write> {
+    writer: W,
+    relative: bool,
+    /// The timestamp of first frame.
+    start: Option<u64>,
+    last: u64,
+    finished: bool,
+}
+
+impl AscWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P, relative: bool) -> Result<Self, CanError> {
+        let file = File::create(path).map_err(io_error)?;
+        Ok(Self::new(BufWriter::new(file), relative))
+    }
+}
+
+impl<W: Write> AscWriter<W> {
+    /// The timestamp of each event is the delta of previous one when `relative` is true.
+    pub fn new(writer: W, relative: bool) -> Self {
+        Self { writer, relative, start: None, last: 0, finished: false }
+    }
+
+    pub fn write<C: LogChannel>(&mut self, msg: &CanMessage<C>) -> Result<(), CanError> {
+        let timestamp = msg.timestamp();
+        if self.start.is_none() {
+            self.write_header(timestamp)?;
+        }
+        let start = self.start.unwrap_or_default();
+        let time = if self.relative {
+            timestamp.saturating_sub(self.last.max(start))
+        }
+        else {
+            timestamp.saturating_sub(start)
+        };
+        self.last = timestamp;
+
+        let channel = msg.channel().index() as u32 + 1;
+        let direct = match msg.direct() {
+            Direct::Transmit => "Tx",
+            Direct::Receive => "Rx",
+        };
+        let mut id = format!("{:X}", msg.id().as_raw());
+        if msg.is_extended() {
+            id.push('x');
+        }
+        let data = msg.data().iter()
+            .map(|v| format!("{:02X}", v))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let record = if msg.is_error_frame() {
+            if msg.is_can_fd() {
+                format!("CANFD {:>3} {:<4} ErrorFrame", channel, direct)
+            }
+            else {
+                format!("{}  ErrorFrame", channel)
+            }
+        }
+        else if msg.is_can_fd() {
+            let mut flags = FLAG_EDL;
+            if msg.is_bitrate_switch() {
+                flags |= FLAG_BRS;
+            }
+            if msg.is_esi() {
+                flags |= FLAG_ESI;
+            }
+            format!("CANFD {:>3} {:<4} {:>8}  {:>32} {} {} {:x} {:>2} {} {:>8} {:>4} {:>8X} {:>8} {:>8} {:>8} {:>8} {:>8}",
+                    channel, direct, id, "",
+                    msg.is_bitrate_switch() as u8, msg.is_esi() as u8,
+                    len_to_dlc(msg.length()), msg.length(), data,
+                    0, 0, flags, 0, 0, 0, 0, 0)
+        }
+        else if msg.is_remote() {
+            format!("{}  {:<15} {:<4} r {:x}", channel, id, direct, msg.length())
+        }
+        else {
+            format!("{}  {:<15} {:<4} d {:x} {}", channel, id, direct, msg.length(), data)
+        };
+
+        writeln!(self.writer, "{:>11.6} {}", time as f64 / 1e9, record)
+            .map_err(io_error)
+    }
+
+    /// Write the end of trigger block and flush, it is called when dropped.
+    pub fn finish(&mut self) -> Result<(), CanError> {
+        if self.finished {
+            return Ok(());
+        }
+        if self.start.is_none() {
+            self.write_header(system_timestamp())?;
+        }
+        self.finished = true;
+        writeln!(self.writer, "End TriggerBlock")
+            .and_then(|_| self.writer.flush())
+            .map_err(io_error)
+    }
+
+    fn write_header(&mut self, start: u64) -> Result<(), CanError> {
+        // the resolution of date is millisecond
+        let start = start - start % 1_000_000;
+        self.start = Some(start);
+        self.last = start;
+        let date = format_date(start);
+        write!(self.writer,
+               "date {}\nbase hex  timestamps {}\ninternal events logged\n// version 9.0.0\nBegin Triggerblock {}\n{:>11.6} Start of measurement\n",
+               date, if self.relative { "relative" } else { "absolute" }, date, 0.)
+            .map_err(io_error)
+    }
+}
+
+impl<W: Write> Drop for AscWriter<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            log::warn!("{}", e);
+        }
+    }
+}
+
+#[inline]
+fn parse_channel<C: LogChannel>(value: &str) -> Option<C> {
+    let channel = value.parse::<u8>().ok()?;
+    Some(C::from_index(channel.saturating_sub(1)))
+}
+
+#[inline]
+fn parse_direct(value: &str) -> Option<Direct> {
+    match value {
+        "Rx" => Some(Direct::Receive),
+        "Tx" => Some(Direct::Transmit),
+        _ => None,
+    }
+}
+
+#[inline]
+fn error_frame<C: LogChannel>(channel: C, is_fd: bool) -> Option<CanMessage<C>> {
+    let mut msg = CanMessage::new(Id::from_bits(0, false), &[])?;
+    msg.set_error_frame(true)
+        .set_can_fd(is_fd)
+        .set_channel(channel)
+        .set_direct(Direct::Receive);
+    Some(msg)
+}
+
+/// `Wed Apr 12 03:18:51.612 pm 2023`, the `am|pm` is optional.
+fn parse_date<'a>(tokens: impl Iterator<Item = &'a str>) -> Option<u64> {
+    let tokens = tokens.collect::<Vec<_>>();
+    let (month, day, time, rest) = match tokens.as_slice() {
+        [_, month, day, time, rest @ ..] => (*month, *day, *time, rest),
+        _ => return None,
+    };
+    let (meridiem, year) = match rest {
+        [meridiem, year, ..] if !meridiem.starts_with(|c: char| c.is_ascii_digit()) => (Some(*meridiem), *year),
+        [year, ..] => (None, *year),
+        _ => return None,
+    };
+
+    let month = DateTime::MONTHS.iter()
+        .position(|v| month.get(..3).is_some_and(|m| m.eq_ignore_ascii_case(v)))? as u32 + 1;
+    let mut parts = time.splitn(3, ':');
+    let mut hour = parts.next()?.parse::<u32>().ok()?;
+    let minute = parts.next()?.parse::<u32>().ok()?;
+    let second = parts.next()?;
+    let (second, millis) = match second.split_once('.') {
+        Some((s, ms)) => (s.parse().ok()?, format!("{:0<3}", ms).get(..3)?.parse().ok()?),
+        None => (second.parse().ok()?, 0),
+    };
+    match meridiem.map(|v| v.to_lowercase()).as_deref() {
+        Some("pm") if hour < 12 => hour += 12,
+        Some("am") if hour == 12 => hour = 0,
+        _ => {},
+    }
+
+    Some(DateTime {
+        year: year.parse().ok()?,
+        month,
+        day: day.parse().ok()?,
+        hour,
+        minute,
+        second,
+        millis,
+        ..Default::default()
+    }.to_nanos())
+}
+
+fn format_date(nanos: u64) -> String {
+    let dt = DateTime::from_nanos(nanos);
+    let hour = match dt.hour % 12 {
+        0 => 12,
+        v => v,
+    };
+    format!("{} {} {:02} {:02}:{:02}:{:02}.{:03} {} {}",
+            DateTime::WEEKDAYS[dt.weekday as usize],
+            DateTime::MONTHS[dt.month as usize - 1],
+            dt.day, hour, dt.minute, dt.second, dt.millis,
+            if dt.hour < 12 { "am" } else { "pm" },
+            dt.year)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use isotp_rs::can::{frame::{Direct, Frame}, identifier::Id};
+    use crate::{CanMessage, TimeBase};
+    use super::{AscReader, AscWriter};
+
+    const ASC: &str = r#"date Wed Apr 12 03:18:51.612 pm 2023
+base dec  timestamps relative
+internal events logged
+Begin Triggerblock Wed Apr 12 03:18:51.612 pm 2023
+   0.000000 Start of measurement
+   0.010000 1  Statistic: D 0 R 0 XD 0 XR 0 E 0 O 0 B 0.00%
+   0.005000 1  291             Rx   d 3 2 16 1  Length = 0 BitCount = 0 ID = 291
+   0.005000 2  419430401x      Tx   r 8
+   0.005000 1  ErrorFrame
+   0.005000 CANFD   1 Tx        2015  EngineData  1 0 9 12 0 1 2 3 4 5 6 7 8 9 10 11        0    0     3000        0        0        0        0        0
+End TriggerBlock
+"#;
+
+    #[test]
+    fn test_read() -> anyhow::Result<()> {
+        let mut reader = AscReader::<_, u8>::new(Cursor::new(ASC));
+        let frames = reader.by_ref().collect::<Result<Vec<_>, _>>()?;
+        let start = reader.start_time();
+        assert_eq!(start, 1_681_312_731_612_000_000);
+        assert_eq!(frames.len(), 4);
+
+        assert_eq!(frames[0], CanMessage::new(Id::from(0x123), &[0x02, 0x10, 0x01]).unwrap());
+        assert_eq!(frames[0].timestamp(), start + 15_000_000);
+        assert_eq!(frames[0].time_base(), TimeBase::WallClock);
+        assert_eq!(frames[0].direct(), Direct::Receive);
+        assert_eq!(frames[1].id(), Id::from_bits(0x19000001, true));
+        assert!(frames[1].is_remote());
+        assert_eq!(frames[1].channel(), 1);
+        assert!(frames[2].is_error_frame());
+        assert!(frames[3].is_can_fd());
+        assert!(frames[3].is_bitrate_switch());
+        assert_eq!(frames[3].id(), Id::from(0x7DF));
+        assert_eq!(frames[3].data(), (0..12).collect::<Vec<_>>());
+        assert_eq!(frames[3].timestamp(), start + 30_000_000);
+
+        let mut reader = AscReader::<_, u8>::new(Cursor::new("   0.1 1  12G Rx d 1 00\n"));
+        assert!(reader.next().is_some_and(|v| v.is_err()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write() -> anyhow::Result<()> {
+        let mut frames = Vec::new();
+        let mut msg = CanMessage::new(Id::from(0x7DF), &[0x02, 0x3E, 0x00]).unwrap();
+        msg.set_timestamp(Some(1_681_312_731_612_345_000))
+            .set_channel(String::from("CAN1"));
+        frames.push(msg);
+        let mut msg = CanMessage::new(Id::from_bits(0x18DA00F1, true), &[0xAA; 20]).unwrap();
+        msg.set_timestamp(Some(1_681_312_731_700_123_000))
+            .set_channel(String::from("CAN0"))
+            .set_direct(Direct::Receive)
+            .set_bitrate_switch(true);
+        frames.push(msg);
+        let mut msg = CanMessage::new_remote(Id::from(0x100), 4).unwrap();
+        msg.set_timestamp(Some(1_681_312_731_712_000_000))
+            .set_channel(String::from("CAN0"));
+        frames.push(msg);
+        let mut msg = CanMessage::new(Id::from(0), &[]).unwrap();
+        msg.set_error_frame(true)
+            .set_can_fd(true)
+            .set_timestamp(Some(1_681_312_731_713_000_000))
+            .set_channel(String::from("CAN1"))
+            .set_direct(Direct::Receive);
+        frames.push(msg);
+
+        for relative in [false, true] {
+            let mut buffer = Vec::new();
+            {
+                let mut writer = AscWriter::new(&mut buffer, relative);
+                for frame in &frames {
+                    writer.write(frame)?;
+                }
+            }
+            let content = String::from_utf8(buffer)?;
+            assert!(content.starts_with("date Wed Apr 12 03:18:51.612 pm 2023\n"));
+            assert!(content.ends_with("End TriggerBlock\n"));
+
+            let reader = AscReader::<_, String>::new(Cursor::new(content));
+            let result = reader.collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(result, frames);
+            for (r, f) in result.iter().zip(&frames) {
+                assert_eq!(r.timestamp(), f.timestamp());
+                assert_eq!(r.channel(), f.channel());
+                assert_eq!(r.direct(), f.direct());
+                assert_eq!(r.is_can_fd(), f.is_can_fd());
+                assert_eq!(r.is_error_frame(), f.is_error_frame());
+                assert_eq!(r.is_bitrate_switch(), f.is_bitrate_switch());
+            }
+        }
+
+        Ok(())
+    }
+}
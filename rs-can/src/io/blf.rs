@@ -0,0 +1,722 @@
+//! The Vector Binary Logging Format(`.blf`), the objects are stored in zlib compressed containers.
+//!
+//! The reader decompresses one container at a time, so a large file is iterated without loading it fully.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::path::Path;
+use flate2::Compression;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use isotp_rs::can::frame::{Direct, Frame};
+use isotp_rs::can::identifier::Id;
+use isotp_rs::can::{CANFD_FRAME_MAX_SIZE, CAN_FRAME_MAX_SIZE, EFF_MASK};
+use crate::{CanMessage, TimeBase};
+use crate::error::CanError;
+use crate::error_frame::{error_code_capture, from_error_code_capture};
+use crate::utils::{dlc_to_len, len_to_dlc};
+use super::{io_error, DateTime, LinMessage, LogChannel, LogObject};
+
+const FILE_SIGNATURE: &[u8; 4] = b"LOGG";
+const OBJ_SIGNATURE: &[u8; 4] = b"LOBJ";
+const FILE_HEADER_SIZE: usize = 144;
+const OBJ_HEADER_BASE_SIZE: usize = 16;
+const OBJ_HEADER_V1_SIZE: usize = 16;
+const LOG_CONTAINER_HEADER_SIZE: usize = 16;
+/// The uncompressed size of container.
+const MAX_CONTAINER_SIZE: usize = 128 * 1024;
+const APPLICATION_ID: u8 = 5;
+const BIN_LOG_VERSION: [u8; 4] = [2, 6, 8, 1];
+
+const CAN_MESSAGE: u32 = 1;
+const LOG_CONTAINER: u32 = 10;
+const LIN_MESSAGE: u32 = 11;
+const LIN_MESSAGE2: u32 = 57;
+const CAN_ERROR_EXT: u32 = 73;
+const CAN_MESSAGE2: u32 = 86;
+const CAN_FD_MESSAGE: u32 = 100;
+const CAN_FD_MESSAGE_64: u32 = 101;
+
+const NO_COMPRESSION: u16 = 0;
+const ZLIB_DEFLATE: u16 = 2;
+
+const TIME_TEN_MICS: u32 = 0x01;
+const TIME_ONE_NANS: u32 = 0x02;
+
+const CAN_MSG_EXT: u32 = 0x8000_0000;
+const CAN_MSG_DIR_TX: u8 = 0x01;
+const CAN_MSG_REMOTE: u8 = 0x80;
+const FD_EDL: u8 = 0x01;
+const FD_BRS: u8 = 0x02;
+const FD_ESI: u8 = 0x04;
+const FD64_REMOTE: u32 = 0x0010;
+const FD64_EDL: u32 = 0x1000;
+const FD64_BRS: u32 = 0x2000;
+const FD64_ESI: u32 = 0x4000;
+/// The `ecc` of `CAN_ERROR_EXT` is valid.
+const ERROR_EXT_ECC: u32 = 0x01;
+
+/// Read the objects of BLF file, the [`Iterator`] yields the CAN frames only.
+pub struct BlfReader<R, C> {
+    reader: R,
+    /// The start time(ns since UNIX epoch) of measurement.
+    start: u64,
+    object_count: u32,
+    /// The uncompressed objects, an object may be continued in the next container.
+    buffer: Vec<u8>,
+    pos: usize,
+    _channel: PhantomData<C>,
+}
+
+impl<C: LogChannel> BlfReader<BufReader<File>, C> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let file = File::open(path).map_err(io_error)?;
+        Self::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read, C: LogChannel> BlfReader<R, C> {
+    /// Read the file header.
+    pub fn new(mut reader: R) -> Result<Self, CanError> {
+        let mut header = [0u8; 72];
+        reader.read_exact(&mut header).map_err(io_error)?;
+        if &header[..4] != FILE_SIGNATURE {
+            return Err(format_error("invalid file signature"));
+        }
+        let header_size = read_u32(&header, 4) as usize;
+        if header_size > header.len() {
+            let mut rest = vec![0u8; header_size - header.len()];
+            reader.read_exact(&mut rest).map_err(io_error)?;
+        }
+
+        Ok(Self {
+            reader,
+            start: read_systemtime(&header[40..56]),
+            object_count: read_u32(&header, 32),
+            buffer: Default::default(),
+            pos: Default::default(),
+            _channel: Default::default(),
+        })
+    }
+
+    /// The start time(ns since UNIX epoch) of measurement.
+    #[inline]
+    pub fn start_time(&self) -> u64 {
+        self.start
+    }
+
+    /// The object count in file header.
+    #[inline]
+    pub fn object_count(&self) -> u32 {
+        self.object_count
+    }
+
+    /// Read the next supported object, the others are skipped.
+    pub fn next_object(&mut self) -> Option<Result<LogObject<C>, CanError>> {
+        loop {
+            match self.pop_object() {
+                Ok(Some(range)) => match parse_object(&self.buffer[range], self.start) {
+                    Ok(Some(object)) => return Some(Ok(object)),
+                    Ok(None) => continue,
+                    Err(e) => return Some(Err(e)),
+                },
+                Ok(None) => {},
+                Err(e) => {
+                    // drop the broken container
+                    self.buffer.clear();
+                    self.pos = 0;
+                    return Some(Err(e));
+                },
+            }
+
+            match self.read_container() {
+                Ok(true) => continue,
+                Ok(false) => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+
+    /// The range of next complete object in buffer.
+    fn pop_object(&mut self) -> Result<Option<Range<usize>>, CanError> {
+        // skip the padding of previous object
+        let mut padding = 0;
+        while padding < 3 && self.buffer.get(self.pos) == Some(&0) {
+            self.pos += 1;
+            padding += 1;
+        }
+
+        let remain = &self.buffer[self.pos..];
+        if remain.len() < OBJ_HEADER_BASE_SIZE {
+            return Ok(None);
+        }
+        if &remain[..4] != OBJ_SIGNATURE {
+            return Err(format_error("invalid object signature"));
+        }
+        let obj_size = read_u32(remain, 8) as usize;
+        if obj_size < OBJ_HEADER_BASE_SIZE {
+            return Err(format_error("invalid object size"));
+        }
+        if remain.len() < obj_size {
+            return Ok(None);
+        }
+
+        let range = self.pos..self.pos + obj_size;
+        self.pos += obj_size;
+        Ok(Some(range))
+    }
+
+    /// Read the next object of file into buffer, `false` if the end of file is reached.
+    fn read_container(&mut self) -> Result<bool, CanError> {
+        self.buffer.drain(..self.pos);
+        self.pos = 0;
+
+        let mut header = [0u8; OBJ_HEADER_BASE_SIZE];
+        match self.reader.read_exact(&mut header) {
+            Ok(_) => {},
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(false),
+            Err(e) => return Err(io_error(e)),
+        }
+        if &header[..4] != OBJ_SIGNATURE {
+            return Err(format_error("invalid object signature"));
+        }
+        let obj_size = read_u32(&header, 8) as usize;
+        let obj_type = read_u32(&header, 12);
+        let mut body = vec![0u8; obj_size.saturating_sub(OBJ_HEADER_BASE_SIZE)];
+        match self.reader.read_exact(&mut body) {
+            Ok(_) => {},
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                log::warn!("RUST-CAN - the BLF file is truncated");
+                return Ok(false);
+            },
+            Err(e) => return Err(io_error(e)),
+        }
+        let mut padding = vec![0u8; obj_size % 4];
+        let _ = self.reader.read_exact(&mut padding);
+
+        if obj_type != LOG_CONTAINER {
+            self.buffer.extend_from_slice(&header);
+            self.buffer.extend_from_slice(&body);
+            return Ok(true);
+        }
+
+        let data = body.get(LOG_CONTAINER_HEADER_SIZE..)
+            .ok_or_else(|| format_error("invalid container size"))?;
+        match read_u16(&body, 0) {
+            NO_COMPRESSION => self.buffer.extend_from_slice(data),
+            ZLIB_DEFLATE => {
+                ZlibDecoder::new(data).read_to_end(&mut self.buffer)
+                    .map_err(io_error)?;
+            },
+            v => return Err(format_error(&format!("unknown compression method: {}", v))),
+        }
+
+        Ok(true)
+    }
+}
+
+impl<R: Read, C: LogChannel> Iterator for BlfReader<R, C> {
+    type Item = Result<CanMessage<C>, CanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.next_object()? {
+                Ok(LogObject::Can(msg)) => return Some(Ok(msg)),
+                Ok(LogObject::Lin(_)) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Write the objects as BLF file, the file header is updated when finished.
+pub struct BlfWriter<W: Write + Seek> {
+    writer: W,
+    level: u32,
+    /// The uncompressed objects which are not written.
+    buffer: Vec<u8>,
+    /// The timestamp of first object.
+    start: Option<u64>,
+    stop: u64,
+    object_count: u32,
+    file_size: u64,
+    uncompressed_size: u64,
+    finished: bool,
+}
+
+impl BlfWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let file = File::create(path).map_err(io_error)?;
+        Self::new(BufWriter::new(file))
+    }
+}
+
+impl<W: Write + Seek> BlfWriter<W> {
+    /// The file header is reserved, and the default compression level is 6.
+    pub fn new(mut writer: W) -> Result<Self, CanError> {
+        writer.write_all(&[0u8; FILE_HEADER_SIZE]).map_err(io_error)?;
+        Ok(Self {
+            writer,
+            level: 6,
+            buffer: Vec::with_capacity(MAX_CONTAINER_SIZE),
+            start: None,
+            stop: 0,
+            object_count: 0,
+            file_size: FILE_HEADER_SIZE as u64,
+            uncompressed_size: FILE_HEADER_SIZE as u64,
+            finished: false,
+        })
+    }
+
+    /// The zlib compression level(0-9), the containers are not compressed when 0.
+    #[inline]
+    pub fn set_compression_level(&mut self, level: u32) {
+        self.level = level.min(9);
+    }
+
+    /// The error frame is written as `CAN_ERROR_EXT` with the protocol error in `ecc`,
+    /// and the CAN-FD frame as `CAN_FD_MESSAGE`.
+    pub fn write<C: LogChannel>(&mut self, msg: &CanMessage<C>) -> Result<(), CanError> {
+        let channel = msg.channel().index() as u16 + 1;
+        let mut id = msg.id().as_raw();
+        if msg.is_extended() {
+            id |= CAN_MSG_EXT;
+        }
+        let mut flags = match msg.direct() {
+            Direct::Transmit => CAN_MSG_DIR_TX,
+            Direct::Receive => 0,
+        };
+        let dlc = len_to_dlc(msg.length());
+
+        let mut body = Vec::new();
+        body.extend_from_slice(&channel.to_le_bytes());
+        let obj_type = if msg.is_error_frame() {
+            // the identifier of error frame is the error class, not the one on bus.
+            let ecc = error_code_capture(msg);
+            let ext_flags = if ecc.is_some() { ERROR_EXT_ECC } else { 0 };
+            body.extend_from_slice(&(msg.length() as u16).to_le_bytes());  // length
+            body.extend_from_slice(&ext_flags.to_le_bytes());
+            body.extend_from_slice(&[ecc.unwrap_or_default(), 0, dlc, 0]);  // ecc, position, dlc, reserved
+            body.extend_from_slice(&[0; 4]);                                // frame length
+            body.extend_from_slice(&[0; 4]);                                // id
+            body.extend_from_slice(&[0; 4]);                                // extended flags, reserved
+            body.extend_from_slice(&padded::<CAN_FRAME_MAX_SIZE>(msg.data()));
+            CAN_ERROR_EXT
+        }
+        else if msg.is_can_fd() {
+            let mut fd_flags = FD_EDL;
+            if msg.is_bitrate_switch() {
+                fd_flags |= FD_BRS;
+            }
+            if msg.is_esi() {
+                fd_flags |= FD_ESI;
+            }
+            body.extend_from_slice(&[flags, dlc]);
+            body.extend_from_slice(&id.to_le_bytes());
+            body.extend_from_slice(&[0; 5]);                                // frame length, bit count
+            body.extend_from_slice(&[fd_flags, msg.length() as u8]);
+            body.extend_from_slice(&[0; 5]);
+            body.extend_from_slice(&padded::<CANFD_FRAME_MAX_SIZE>(msg.data()));
+            CAN_FD_MESSAGE
+        }
+        else {
+            if msg.is_remote() {
+                flags |= CAN_MSG_REMOTE;
+            }
+            body.extend_from_slice(&[flags, dlc]);
+            body.extend_from_slice(&id.to_le_bytes());
+            body.extend_from_slice(&padded::<CAN_FRAME_MAX_SIZE>(msg.data()));
+            CAN_MESSAGE
+        };
+
+        self.write_object(obj_type, msg.timestamp(), &body)
+    }
+
+    pub fn write_lin(&mut self, msg: &LinMessage) -> Result<(), CanError> {
+        let len = msg.data.len().min(8);
+        let mut body = Vec::new();
+        body.extend_from_slice(&(msg.channel as u16 + 1).to_le_bytes());
+        body.extend_from_slice(&[msg.id, len as u8]);
+        body.extend_from_slice(&padded::<8>(&msg.data[..len]));
+        body.extend_from_slice(&[0; 4]);                                    // fsm id, fsm state, header time, full time
+        body.extend_from_slice(&msg.checksum.to_le_bytes());
+        body.extend_from_slice(&[(msg.direct == Direct::Transmit) as u8, 0]);
+
+        self.write_object(LIN_MESSAGE, msg.timestamp, &body)
+    }
+
+    /// Write the remaining objects and the file header, it is called when dropped.
+    pub fn finish(&mut self) -> Result<(), CanError> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        while !self.buffer.is_empty() {
+            self.write_container()?;
+        }
+
+        let start = self.start.unwrap_or_default();
+        let mut header = Vec::with_capacity(FILE_HEADER_SIZE);
+        header.extend_from_slice(FILE_SIGNATURE);
+        header.extend_from_slice(&(FILE_HEADER_SIZE as u32).to_le_bytes());
+        header.extend_from_slice(&[APPLICATION_ID, 0, 0, 0]);
+        header.extend_from_slice(&BIN_LOG_VERSION);
+        header.extend_from_slice(&self.file_size.to_le_bytes());
+        header.extend_from_slice(&self.uncompressed_size.to_le_bytes());
+        header.extend_from_slice(&self.object_count.to_le_bytes());
+        header.extend_from_slice(&self.object_count.to_le_bytes());         // objects read
+        header.extend_from_slice(&systemtime(start));
+        header.extend_from_slice(&systemtime(self.stop.max(start)));
+        header.resize(FILE_HEADER_SIZE, 0);
+
+        self.writer.seek(SeekFrom::Start(0))
+            .and_then(|_| self.writer.write_all(&header))
+            .and_then(|_| self.writer.seek(SeekFrom::End(0)))
+            .and_then(|_| self.writer.flush())
+            .map_err(io_error)
+    }
+
+    fn write_object(&mut self, obj_type: u32, timestamp: u64, body: &[u8]) -> Result<(), CanError> {
+        if self.finished {
+            return Err(CanError::OperationError("the BLF writer is finished".into()));
+        }
+        // the resolution of `SYSTEMTIME` in header is millisecond
+        let start = *self.start.get_or_insert(timestamp - timestamp % 1_000_000);
+        self.stop = self.stop.max(timestamp);
+        let nanos = timestamp.saturating_sub(start);
+
+        let header_size = OBJ_HEADER_BASE_SIZE + OBJ_HEADER_V1_SIZE;
+        let obj_size = header_size + body.len();
+        self.buffer.extend_from_slice(OBJ_SIGNATURE);
+        self.buffer.extend_from_slice(&(header_size as u16).to_le_bytes());
+        self.buffer.extend_from_slice(&1u16.to_le_bytes());                // header version
+        self.buffer.extend_from_slice(&(obj_size as u32).to_le_bytes());
+        self.buffer.extend_from_slice(&obj_type.to_le_bytes());
+        self.buffer.extend_from_slice(&TIME_ONE_NANS.to_le_bytes());
+        self.buffer.extend_from_slice(&[0; 4]);                             // client index, object version
+        self.buffer.extend_from_slice(&nanos.to_le_bytes());
+        self.buffer.extend_from_slice(body);
+        self.buffer.resize(self.buffer.len() + obj_size % 4, 0);
+        self.object_count += 1;
+
+        if self.buffer.len() >= MAX_CONTAINER_SIZE {
+            self.write_container()?;
+        }
+
+        Ok(())
+    }
+
+    /// Write a container with the head of buffer, the rest object is continued in next container.
+    fn write_container(&mut self) -> Result<(), CanError> {
+        let size = self.buffer.len().min(MAX_CONTAINER_SIZE);
+        let data = &self.buffer[..size];
+        let (method, data) = if self.level > 0 {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(self.level));
+            encoder.write_all(data).map_err(io_error)?;
+            (ZLIB_DEFLATE, encoder.finish().map_err(io_error)?)
+        }
+        else {
+            (NO_COMPRESSION, data.to_vec())
+        };
+
+        let obj_size = OBJ_HEADER_BASE_SIZE + LOG_CONTAINER_HEADER_SIZE + data.len();
+        let mut container = Vec::with_capacity(obj_size + 3);
+        container.extend_from_slice(OBJ_SIGNATURE);
+        container.extend_from_slice(&(OBJ_HEADER_BASE_SIZE as u16).to_le_bytes());
+        container.extend_from_slice(&1u16.to_le_bytes());
+        container.extend_from_slice(&(obj_size as u32).to_le_bytes());
+        container.extend_from_slice(&LOG_CONTAINER.to_le_bytes());
+        container.extend_from_slice(&method.to_le_bytes());
+        container.extend_from_slice(&[0; 6]);
+        container.extend_from_slice(&(size as u32).to_le_bytes());
+        container.extend_from_slice(&[0; 4]);
+        container.extend_from_slice(&data);
+        container.resize(obj_size + obj_size % 4, 0);
+
+        self.writer.write_all(&container).map_err(io_error)?;
+        self.file_size += container.len() as u64;
+        self.uncompressed_size += (OBJ_HEADER_BASE_SIZE + LOG_CONTAINER_HEADER_SIZE + size) as u64;
+        self.buffer.drain(..size);
+
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek> Drop for BlfWriter<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            log::warn!("{}", e);
+        }
+    }
+}
+
+/// Parse an object, `None` if the type is not supported.
+fn parse_object<C: LogChannel>(data: &[u8], start: u64) -> Result<Option<LogObject<C>>, CanError> {
+    let header_size = read_u16(data, 4) as usize;
+    let header_version = read_u16(data, 6);
+    let obj_type = read_u32(data, 12);
+    if !matches!(header_version, 1 | 2) || data.len() < header_size || header_size < OBJ_HEADER_BASE_SIZE + OBJ_HEADER_V1_SIZE {
+        log::debug!("RUST-CAN - BLF object: {} with header version: {} is skipped", obj_type, header_version);
+        return Ok(None);
+    }
+    // the flags and timestamp of header V1 and V2 are at the same offset.
+    let flags = read_u32(data, 16);
+    let timestamp = read_u64(data, 24);
+    let nanos = if flags & TIME_TEN_MICS > 0 { timestamp * 10_000 } else { timestamp };
+    let timestamp = start + nanos;
+    let body = &data[header_size..];
+
+    let check = |size: usize| match body.len() < size {
+        true => Err(format_error(&format!("object: {} is too short", obj_type))),
+        false => Ok(()),
+    };
+    let direct = |tx: bool| if tx { Direct::Transmit } else { Direct::Receive };
+
+    let mut msg = match obj_type {
+        CAN_MESSAGE | CAN_MESSAGE2 => {
+            check(16)?;
+            let flags = body[2];
+            let len = dlc_to_len(body[3], false);
+            let id = parse_id(read_u32(body, 4));
+            let mut msg = match flags & CAN_MSG_REMOTE > 0 {
+                true => CanMessage::new_remote(id, len),
+                false => CanMessage::new(id, &body[8..8 + len]),
+            }.ok_or_else(|| format_error("invalid CAN message"))?;
+            msg.set_direct(direct(flags & CAN_MSG_DIR_TX > 0))
+                .set_channel(parse_channel(read_u16(body, 0)));
+            msg
+        },
+        CAN_FD_MESSAGE => {
+            check(84)?;
+            let flags = body[2];
+            let fd_flags = body[13];
+            let is_fd = fd_flags & FD_EDL > 0;
+            let len = dlc_to_len(body[3], is_fd).min(body[14] as usize);
+            let id = parse_id(read_u32(body, 4));
+            let mut msg = match flags & CAN_MSG_REMOTE > 0 {
+                true => CanMessage::new_remote(id, dlc_to_len(body[3], false)),
+                false => CanMessage::new(id, &body[20..20 + len]),
+            }.ok_or_else(|| format_error("invalid CAN-FD message"))?;
+            msg.set_can_fd(is_fd)
+                .set_bitrate_switch(fd_flags & FD_BRS > 0)
+                .set_esi(fd_flags & FD_ESI > 0)
+                .set_direct(direct(flags & CAN_MSG_DIR_TX > 0))
+                .set_channel(parse_channel(read_u16(body, 0)));
+            msg
+        },
+        CAN_FD_MESSAGE_64 => {
+            check(40)?;
+            let flags = read_u32(body, 12);
+            let is_fd = flags & FD64_EDL > 0;
+            let len = dlc_to_len(body[1], is_fd).min(body[2] as usize);
+            check(40 + len)?;
+            let id = parse_id(read_u32(body, 4));
+            let mut msg = match flags & FD64_REMOTE > 0 {
+                true => CanMessage::new_remote(id, dlc_to_len(body[1], false)),
+                false => CanMessage::new(id, &body[40..40 + len]),
+            }.ok_or_else(|| format_error("invalid CAN-FD message"))?;
+            msg.set_can_fd(is_fd)
+                .set_bitrate_switch(flags & FD64_BRS > 0)
+                .set_esi(flags & FD64_ESI > 0)
+                .set_direct(direct(body[34] == 1))
+                .set_channel(parse_channel(body[0] as u16));
+            msg
+        },
+        CAN_ERROR_EXT => {
+            check(32)?;
+            let mut msg = if read_u32(body, 4) & ERROR_EXT_ECC > 0 {
+                from_error_code_capture(body[8])
+            }
+            else {
+                let len = dlc_to_len(body[10], false);
+                CanMessage::new(parse_id(read_u32(body, 16)), &body[24..24 + len])
+                    .ok_or_else(|| format_error("invalid CAN error frame"))?
+            };
+            msg.set_error_frame(true)
+                .set_direct(Direct::Receive)
+                .set_channel(parse_channel(read_u16(body, 0)));
+            msg
+        },
+        LIN_MESSAGE => {
+            check(20)?;
+            let len = (body[3] as usize).min(8);
+            return Ok(Some(LogObject::Lin(LinMessage {
+                timestamp,
+                channel: (read_u16(body, 0) as u8).saturating_sub(1),
+                id: body[2],
+                data: body[4..4 + len].to_vec(),
+                checksum: read_u16(body, 16),
+                direct: direct(body[18] > 0),
+            })));
+        },
+        LIN_MESSAGE2 => {
+            check(123)?;
+            let len = (body[38] as usize).min(8);
+            return Ok(Some(LogObject::Lin(LinMessage {
+                timestamp,
+                channel: (read_u16(body, 12) as u8).saturating_sub(1),
+                id: body[37],
+                data: body[112..112 + len].to_vec(),
+                checksum: read_u16(body, 120),
+                direct: direct(body[122] > 0),
+            })));
+        },
+        _ => return Ok(None),
+    };
+
+    msg.set_timestamp(Some(timestamp))
+        .set_time_base(TimeBase::WallClock);
+    Ok(Some(LogObject::Can(msg)))
+}
+
+#[inline]
+fn parse_id(id: u32) -> Id {
+    Id::from_bits(id & EFF_MASK, id & CAN_MSG_EXT > 0)
+}
+
+#[inline]
+fn parse_channel<C: LogChannel>(channel: u16) -> C {
+    C::from_index((channel as u8).saturating_sub(1))
+}
+
+#[inline]
+fn format_error(reason: &str) -> CanError {
+    CanError::FrameConvertFailed(format!("invalid BLF file: {}", reason))
+}
+
+#[inline]
+fn padded<const N: usize>(data: &[u8]) -> [u8; N] {
+    let mut result = [0u8; N];
+    let len = data.len().min(N);
+    result[..len].copy_from_slice(&data[..len]);
+    result
+}
+
+#[inline]
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+#[inline]
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(padded(&data[offset..offset + 4]))
+}
+
+#[inline]
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(padded(&data[offset..offset + 8]))
+}
+
+/// The `SYSTEMTIME` of Windows.
+fn systemtime(nanos: u64) -> [u8; 16] {
+    let dt = DateTime::from_nanos(nanos);
+    let fields = [dt.year as u16, dt.month as u16, dt.weekday as u16, dt.day as u16,
+        dt.hour as u16, dt.minute as u16, dt.second as u16, dt.millis as u16];
+    let mut result = [0u8; 16];
+    for (i, v) in fields.iter().enumerate() {
+        result[i * 2..i * 2 + 2].copy_from_slice(&v.to_le_bytes());
+    }
+    result
+}
+
+fn read_systemtime(data: &[u8]) -> u64 {
+    let field = |i: usize| read_u16(data, i * 2) as u32;
+    if field(0) == 0 {
+        return 0;
+    }
+    DateTime {
+        year: field(0) as i64,
+        month: field(1),
+        day: field(3),
+        hour: field(4),
+        minute: field(5),
+        second: field(6),
+        millis: field(7),
+        ..Default::default()
+    }.to_nanos()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use isotp_rs::can::{frame::{Direct, Frame}, identifier::Id};
+    use crate::CanMessage;
+    use crate::error_frame::{ErrorEvent, ErrorKind, ProtocolError};
+    use crate::io::{LinMessage, LogObject};
+    use super::{BlfReader, BlfWriter};
+
+    #[test]
+    fn test_blf() -> anyhow::Result<()> {
+        let start = 1_681_312_731_612_000_000;
+        let mut frames = Vec::new();
+        for i in 0..10_000u64 {
+            let mut msg = match i % 4 {
+                0 => CanMessage::new(Id::from(0x7DF), &[0x02, 0x3E, i as u8]),
+                1 => CanMessage::new(Id::from_bits(0x18DA00F1, true), &[i as u8; 20]),
+                2 => CanMessage::new_remote(Id::from(0x100), 4),
+                _ => {
+                    let mut event = ErrorEvent::new(0, 0);
+                    event.add(ErrorKind::Protocol { error: ProtocolError::Form, transmitting: false });
+                    Some(CanMessage::from(&event))
+                },
+            }.unwrap();
+            msg.set_timestamp(Some(start + i * 1_000_123))
+                .set_channel((i % 2) as u8)
+                .set_direct(if i % 3 == 0 { Direct::Transmit } else { Direct::Receive });
+            match i % 4 {
+                1 => { msg.set_bitrate_switch(true); },
+                3 => { msg.set_direct(Direct::Receive); },
+                _ => {},
+            }
+            frames.push(msg);
+        }
+        let lin = LinMessage {
+            timestamp: start + 10_001_230_000,
+            channel: 0,
+            id: 0x3C,
+            data: vec![0x01, 0x02],
+            checksum: 0xAA,
+            direct: Direct::Receive,
+        };
+
+        for level in [0, 6] {
+            let mut cursor = Cursor::new(Vec::new());
+            {
+                let mut writer = BlfWriter::new(&mut cursor)?;
+                writer.set_compression_level(level);
+                for frame in &frames {
+                    writer.write(frame)?;
+                }
+                writer.write_lin(&lin)?;
+            }
+
+            cursor.set_position(0);
+            let mut reader = BlfReader::<_, u8>::new(cursor)?;
+            assert_eq!(reader.start_time(), start);
+            assert_eq!(reader.object_count(), frames.len() as u32 + 1);
+            let mut count = 0;
+            while let Some(object) = reader.next_object() {
+                match object? {
+                    LogObject::Can(msg) => {
+                        let frame = &frames[count];
+                        assert_eq!(&msg, frame);
+                        assert_eq!(msg.timestamp(), frame.timestamp());
+                        assert_eq!(msg.channel(), frame.channel());
+                        assert_eq!(msg.direct(), frame.direct());
+                        assert_eq!(msg.is_can_fd(), frame.is_can_fd());
+                        assert_eq!(msg.is_bitrate_switch(), frame.is_bitrate_switch());
+                        count += 1;
+                    },
+                    LogObject::Lin(msg) => assert_eq!(msg, lin),
+                }
+            }
+            assert_eq!(count, frames.len());
+        }
+
+        assert!(BlfReader::<_, u8>::new(Cursor::new([0u8; 144])).is_err());
+
+        Ok(())
+    }
+}
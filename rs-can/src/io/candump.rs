@@ -0,0 +1,171 @@
+//! The log of `candump -L` in can-utils, such as `(1700000000.123456) can0 123#DEADBEEF R`.
+//!
+//! The trailing direction(`R` or `T`) is optional, and the frame is in the syntax of `cansend`.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+use isotp_rs::can::frame::{Direct, Frame};
+use crate::{CanMessage, TimeBase};
+use crate::error::CanError;
+use super::{io_error, parse_error, LogChannel};
+
+/// Read the frames of candump log.
+pub struct CandumpReader<R, C> {
+    reader: R,
+    line: usize,
+    buffer: String,
+    _channel: PhantomData<C>,
+}
+
+impl<C: LogChannel> CandumpReader<BufReader<File>, C> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let file = File::open(path).map_err(io_error)?;
+        Ok(Self::new(BufReader::new(file)))
+    }
+}
+
+impl<R: BufRead, C: LogChannel> CandumpReader<R, C> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: Default::default(),
+            buffer: Default::default(),
+            _channel: Default::default(),
+        }
+    }
+
+    fn parse(&self) -> Option<CanMessage<C>> {
+        let mut tokens = self.buffer.split_whitespace();
+        let timestamp = parse_timestamp(tokens.next()?.strip_prefix('(')?.strip_suffix(')')?)?;
+        let channel = C::from_name(tokens.next()?);
+        let mut msg = CanMessage::<C>::from_cansend(tokens.next()?).ok()?;
+        let direct = match tokens.next() {
+            Some("T") => Direct::Transmit,
+            Some("R") | None => Direct::Receive,
+            Some(_) => return None,
+        };
+        msg.set_timestamp(Some(timestamp))
+            .set_time_base(TimeBase::WallClock)
+            .set_channel(channel)
+            .set_direct(direct);
+
+        Some(msg)
+    }
+}
+
+impl<R: BufRead, C: LogChannel> Iterator for CandumpReader<R, C> {
+    type Item = Result<CanMessage<C>, CanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buffer.clear();
+            match self.reader.read_line(&mut self.buffer) {
+                Ok(0) => return None,
+                Ok(_) => self.line += 1,
+                Err(e) => return Some(Err(io_error(e))),
+            }
+            if self.buffer.trim().is_empty() {
+                continue;
+            }
+
+            return Some(self.parse().ok_or_else(|| parse_error(self.line, &self.buffer)));
+        }
+    }
+}
+
+/// Write frames as candump log.
+pub struct CandumpWriter<W: Write> {
+    writer: W,
+}
+
+impl CandumpWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let file = File::create(path).map_err(io_error)?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl<W: Write> CandumpWriter<W> {
+    #[inline]
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    pub fn write<C: LogChannel>(&mut self, msg: &CanMessage<C>) -> Result<(), CanError> {
+        let timestamp = msg.timestamp();
+        writeln!(self.writer, "({}.{:06}) {} {} {}",
+                 timestamp / 1_000_000_000, timestamp % 1_000_000_000 / 1000,
+                 msg.channel().name(),
+                 msg.to_cansend(),
+                 match msg.direct() {
+                     Direct::Transmit => "T",
+                     Direct::Receive => "R",
+                 })
+            .map_err(io_error)
+    }
+
+    #[inline]
+    pub fn flush(&mut self) -> Result<(), CanError> {
+        self.writer.flush().map_err(io_error)
+    }
+}
+
+/// `<seconds>.<microseconds>` to nanoseconds.
+fn parse_timestamp(value: &str) -> Option<u64> {
+    let (secs, frac) = value.split_once('.').unwrap_or((value, ""));
+    let secs = secs.parse::<u64>().ok()?;
+    if !frac.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let nanos = format!("{:0<9}", &frac[..frac.len().min(9)]).parse::<u64>().ok()?;
+
+    Some(secs * 1_000_000_000 + nanos)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use isotp_rs::can::{frame::{Direct, Frame}, identifier::Id};
+    use crate::CanMessage;
+    use super::{CandumpReader, CandumpWriter};
+
+    #[test]
+    fn test_candump() -> anyhow::Result<()> {
+        let log = "(1700000000.123456) can0 123#DEADBEEF\n\
+                   (1700000000.200000) vcan1 18DA00F1##1AABB T\n\
+                   \n\
+                   (1700000000.300000) can0 7DF#R\n";
+        let frames = CandumpReader::<_, String>::new(Cursor::new(log))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0], CanMessage::new(Id::from(0x123), &[0xDE, 0xAD, 0xBE, 0xEF]).unwrap());
+        assert_eq!(frames[0].timestamp(), 1_700_000_000_123_456_000);
+        assert_eq!(frames[0].channel(), "can0");
+        assert_eq!(frames[0].direct(), Direct::Receive);
+        assert_eq!(frames[1].channel(), "vcan1");
+        assert_eq!(frames[1].direct(), Direct::Transmit);
+        assert!(frames[1].is_can_fd());
+        assert!(frames[2].is_remote());
+
+        let mut buffer = Vec::new();
+        let mut writer = CandumpWriter::new(&mut buffer);
+        for frame in &frames {
+            writer.write(frame)?;
+        }
+        writer.flush()?;
+        let content = String::from_utf8(buffer)?;
+        assert_eq!(content.lines().next(), Some("(1700000000.123456) can0 123#DEADBEEF R"));
+        let result = CandumpReader::<_, u8>::new(Cursor::new(content))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(result.len(), frames.len());
+        assert_eq!(result[1].channel(), 1);
+        assert_eq!(result[1].timestamp(), 1_700_000_000_200_000_000);
+
+        let mut reader = CandumpReader::<_, u8>::new(Cursor::new("1700000000.1 can0 123#00\n"));
+        assert!(reader.next().is_some_and(|v| v.is_err()));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1156 @@
+//! The ASAM MDF 4.1(`.mf4`) with the channel groups of bus logging,
+//! such as `CAN_DataFrame`, `CAN_ErrorFrame`, `CAN_RemoteFrame` and `LIN_Frame`.
+//!
+//! The writer stores all groups as unsorted records of one data group, the file is finalized when finished.
+//! The reader supports the sorted and unsorted data groups, the data lists and the compressed data blocks,
+//! and the frames of all data groups are merged by timestamp.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use flate2::read::ZlibDecoder;
+use isotp_rs::can::frame::{Direct, Frame};
+use isotp_rs::can::identifier::Id;
+use isotp_rs::can::EFF_MASK;
+use crate::{CanMessage, TimeBase};
+use crate::error::CanError;
+use crate::utils::{dlc_to_len, len_to_dlc, system_timestamp};
+use super::{io_error, LinMessage, LogChannel, LogObject};
+
+const FILE_ID: &[u8; 8] = b"MDF     ";
+const UNFINISHED_FILE_ID: &[u8; 8] = b"UnFinMF ";
+const VERSION: &[u8; 8] = b"4.10    ";
+const PROGRAM: &[u8; 8] = b"rs-can  ";
+const VERSION_NUMBER: u16 = 410;
+const ID_BLOCK_SIZE: usize = 64;
+const HD_OFFSET: u64 = 64;
+const BLOCK_HEADER_SIZE: usize = 24;
+/// The minimum data sizes of `##CG` and `##CN` blocks used by reader.
+const CG_DATA_SIZE: usize = 32;
+const CN_DATA_SIZE: usize = 12;
+/// The size of data read from `##DT` block at a time.
+const CHUNK_SIZE: u64 = 64 * 1024;
+
+/// The cycle counters of channel groups and the length of last `##DT` block are not updated.
+const UNFIN_CYCLE_COUNT: u16 = 0x01;
+const UNFIN_DT_LENGTH: u16 = 0x04;
+
+const CG_FL_VLSD: u16 = 0x01;
+const CG_FL_BUS_EVENT: u16 = 0x02;
+const CG_FL_PLAIN_BUS_EVENT: u16 = 0x04;
+const CN_FL_BUS_EVENT: u32 = 0x400;
+
+const CN_TYPE_FIXED: u8 = 0;
+const CN_TYPE_VLSD: u8 = 1;
+const CN_TYPE_MASTER: u8 = 2;
+const CN_SYNC_TIME: u8 = 1;
+
+const UINT_LE: u8 = 0;
+const UINT_BE: u8 = 1;
+const INT_LE: u8 = 2;
+const INT_BE: u8 = 3;
+const FLOAT_LE: u8 = 4;
+const FLOAT_BE: u8 = 5;
+const BYTE_ARRAY: u8 = 10;
+
+const SI_TYPE_BUS: u8 = 2;
+const BUS_TYPE_CAN: u8 = 2;
+const BUS_TYPE_LIN: u8 = 3;
+
+const CC_TYPE_LINEAR: u8 = 1;
+/// The offset of `cc_val` in the data of `##CC` block.
+const CC_VAL_OFFSET: usize = 24;
+const ZIP_TYPE_TRANSPOSE: u8 = 1;
+
+/// The channel of bus logging group, the offset is relative to the record without record ID.
+struct FieldDef {
+    name: &'static str,
+    data_type: u8,
+    byte_offset: u32,
+    bit_offset: u8,
+    bit_count: u32,
+}
+
+const fn field(name: &'static str, byte_offset: u32, bit_offset: u8, bit_count: u32) -> FieldDef {
+    FieldDef { name, data_type: UINT_LE, byte_offset, bit_offset, bit_count }
+}
+
+const fn bytes(name: &'static str, byte_offset: u32, len: u32) -> FieldDef {
+    FieldDef { name, data_type: BYTE_ARRAY, byte_offset, bit_offset: 0, bit_count: len * 8 }
+}
+
+/// The channel group of bus logging, the master channel(f64 seconds) is the first 8 bytes of record.
+struct GroupDef {
+    kind: Kind,
+    bus_type: u8,
+    size: usize,
+    fields: &'static [FieldDef],
+}
+
+const CAN_FIELDS: [FieldDef; 10] = [
+    field("BusChannel", 8, 0, 8),
+    field("ID", 9, 0, 29),
+    field("IDE", 13, 0, 1),
+    field("Dir", 13, 1, 1),
+    field("EDL", 13, 2, 1),
+    field("BRS", 13, 3, 1),
+    field("ESI", 13, 4, 1),
+    field("DLC", 14, 0, 4),
+    field("DataLength", 15, 0, 8),
+    bytes("DataBytes", 16, 64),
+];
+
+const REMOTE_FIELDS: [FieldDef; 6] = [
+    field("BusChannel", 8, 0, 8),
+    field("ID", 9, 0, 29),
+    field("IDE", 13, 0, 1),
+    field("Dir", 13, 1, 1),
+    field("DLC", 14, 0, 4),
+    field("DataLength", 15, 0, 8),
+];
+
+const LIN_FIELDS: [FieldDef; 7] = [
+    field("BusChannel", 8, 0, 8),
+    field("ID", 9, 0, 6),
+    field("Dir", 10, 0, 1),
+    field("DataLength", 11, 0, 8),
+    field("ReceivedDataByteCount", 12, 0, 8),
+    field("Checksum", 13, 0, 8),
+    bytes("DataBytes", 14, 8),
+];
+
+/// The record ID of group is the index + 1.
+const GROUPS: [GroupDef; 4] = [
+    GroupDef { kind: Kind::CanData, bus_type: BUS_TYPE_CAN, size: 80, fields: &CAN_FIELDS },
+    GroupDef { kind: Kind::CanError, bus_type: BUS_TYPE_CAN, size: 80, fields: &CAN_FIELDS },
+    GroupDef { kind: Kind::CanRemote, bus_type: BUS_TYPE_CAN, size: 16, fields: &REMOTE_FIELDS },
+    GroupDef { kind: Kind::Lin, bus_type: BUS_TYPE_LIN, size: 22, fields: &LIN_FIELDS },
+];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Kind {
+    CanData,
+    CanError,
+    CanRemote,
+    Lin,
+}
+
+impl Kind {
+    const ALL: [Kind; 4] = [Kind::CanData, Kind::CanError, Kind::CanRemote, Kind::Lin];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::CanData => "CAN_DataFrame",
+            Self::CanError => "CAN_ErrorFrame",
+            Self::CanRemote => "CAN_RemoteFrame",
+            Self::Lin => "LIN_Frame",
+        }
+    }
+}
+
+/// Read the objects of MDF file, the [`Iterator`] yields the CAN frames only.
+pub struct MdfReader<R, C> {
+    reader: R,
+    /// The start time(ns since UNIX epoch) of measurement.
+    start: u64,
+    /// The length of file, the unfinished `##DT` block is extended to the end of file.
+    unfinished: Option<u64>,
+    groups: Vec<DataGroup<C>>,
+}
+
+impl<C: LogChannel> MdfReader<BufReader<File>, C> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let file = File::open(path).map_err(io_error)?;
+        Self::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read + Seek, C: LogChannel> MdfReader<R, C> {
+    /// Read the `##ID`, `##HD` blocks and the channel groups.
+    pub fn new(mut reader: R) -> Result<Self, CanError> {
+        let mut id = [0u8; ID_BLOCK_SIZE];
+        reader.seek(SeekFrom::Start(0))
+            .and_then(|_| reader.read_exact(&mut id))
+            .map_err(io_error)?;
+        let finished = match &id[..8] {
+            v if v == FILE_ID => true,
+            v if v == UNFINISHED_FILE_ID => false,
+            _ => return Err(format_error("invalid file identification")),
+        };
+        let version = read_u16(&id, 28);
+        if version < 400 {
+            return Err(format_error(format!("version: {} is not supported", version)));
+        }
+        let unfinished = match !finished && read_u16(&id, 60) & UNFIN_DT_LENGTH > 0 {
+            true => Some(reader.seek(SeekFrom::End(0)).map_err(io_error)?),
+            false => None,
+        };
+
+        let hd = read_block(&mut reader, HD_OFFSET, b"##HD")?;
+        let start = read_u64(&hd.data, 0);
+        let mut groups = Vec::new();
+        let mut next = hd.link(0);
+        while next > 0 {
+            let dg = read_block(&mut reader, next, b"##DG")?;
+            groups.push(DataGroup::new(&mut reader, &dg)?);
+            next = dg.link(0);
+        }
+
+        Ok(Self { reader, start, unfinished, groups })
+    }
+
+    /// The start time(ns since UNIX epoch) of measurement.
+    #[inline]
+    pub fn start_time(&self) -> u64 {
+        self.start
+    }
+
+    /// Read the next object with the minimum timestamp of all data groups.
+    pub fn next_object(&mut self) -> Option<Result<LogObject<C>, CanError>> {
+        for i in 0..self.groups.len() {
+            let group = &mut self.groups[i];
+            if group.pending.is_none() && !group.is_finished() {
+                match group.next_object(&mut self.reader, self.start, self.unfinished) {
+                    Ok(Some(v)) => group.pending = Some(v),
+                    Ok(None) => group.finish(),
+                    Err(e) => {
+                        // drop the broken data group
+                        group.finish();
+                        return Some(Err(e));
+                    },
+                }
+            }
+        }
+
+        self.groups.iter_mut()
+            .filter(|g| g.pending.is_some())
+            .min_by_key(|g| match &g.pending {
+                Some(LogObject::Can(msg)) => msg.timestamp(),
+                Some(LogObject::Lin(msg)) => msg.timestamp,
+                None => u64::MAX,
+            })
+            .and_then(|g| g.pending.take())
+            .map(Ok)
+    }
+}
+
+impl<R: Read + Seek, C: LogChannel> Iterator for MdfReader<R, C> {
+    type Item = Result<CanMessage<C>, CanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.next_object()? {
+                Ok(LogObject::Can(msg)) => return Some(Ok(msg)),
+                Ok(LogObject::Lin(_)) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// The records of a `##DG` block.
+struct DataGroup<C> {
+    rec_id_size: u8,
+    groups: Vec<ChannelGroup>,
+    stream: DataStream,
+    pending: Option<LogObject<C>>,
+}
+
+impl<C: LogChannel> DataGroup<C> {
+    fn new<R: Read + Seek>(reader: &mut R, dg: &Block) -> Result<Self, CanError> {
+        let rec_id_size = dg.data.first().copied().unwrap_or_default();
+        if !matches!(rec_id_size, 0 | 1 | 2 | 4 | 8) {
+            return Err(format_error(format!("record ID size: {} is not supported", rec_id_size)));
+        }
+        let mut groups = Vec::new();
+        let mut next = dg.link(1);
+        while next > 0 {
+            let cg = read_block(reader, next, b"##CG")?;
+            groups.push(ChannelGroup::new(reader, next, &cg)?);
+            next = cg.link(0);
+        }
+        if rec_id_size == 0 && groups.len() > 1 {
+            return Err(format_error("sorted data group with multiple channel groups"));
+        }
+        let blocks = data_blocks(reader, dg.link(2))?;
+
+        Ok(Self { rec_id_size, groups, stream: DataStream::new(blocks), pending: None })
+    }
+
+    #[inline]
+    fn is_finished(&self) -> bool {
+        self.stream.is_finished()
+    }
+
+    #[inline]
+    fn finish(&mut self) {
+        self.stream = DataStream::new(Default::default());
+    }
+
+    fn next_object<R: Read + Seek>(&mut self, reader: &mut R, start: u64, unfinished: Option<u64>)
+        -> Result<Option<LogObject<C>>, CanError> {
+        loop {
+            let size = self.rec_id_size as usize;
+            let index = match size {
+                0 => 0,
+                _ => {
+                    if !self.stream.fill(reader, size, unfinished)? {
+                        return Ok(None);
+                    }
+                    let id = read_u64(self.stream.take(size), 0);
+                    self.groups.iter()
+                        .position(|g| g.record_id == id)
+                        .ok_or_else(|| format_error(format!("unknown record ID: {}", id)))?
+                },
+            };
+
+            let group = &self.groups[index];
+            if group.is_vlsd {
+                if !self.stream.fill(reader, 4, unfinished)? {
+                    return Ok(None);
+                }
+                let len = read_u32(self.stream.take(4), 0) as usize;
+                if !self.stream.fill(reader, len, unfinished)? {
+                    return Ok(None);
+                }
+                let data = self.stream.take(len).to_vec();
+                let group = &mut self.groups[index];
+                group.vlsd_last = (group.vlsd_pos, data);
+                group.vlsd_pos += 4 + len as u64;
+                continue;
+            }
+
+            let len = group.size;
+            if !self.stream.fill(reader, len, unfinished)? {
+                return Ok(None);
+            }
+            let record = self.stream.take(len).to_vec();
+            if let Some(layout) = &self.groups[index].layout {
+                return decode(layout, &record, &self.groups, start).map(Some);
+            }
+        }
+    }
+}
+
+/// A `##CG` block, the layout is `None` if the group is not a bus logging group.
+struct ChannelGroup {
+    offset: u64,
+    record_id: u64,
+    size: usize,
+    is_vlsd: bool,
+    layout: Option<Layout>,
+    /// The offset and data of last VLSD record.
+    vlsd_pos: u64,
+    vlsd_last: (u64, Vec<u8>),
+}
+
+impl ChannelGroup {
+    fn new<R: Read + Seek>(reader: &mut R, offset: u64, cg: &Block) -> Result<Self, CanError> {
+        if cg.data.len() < CG_DATA_SIZE {
+            return Err(format_error(format!("invalid channel group block at: {:#X}", offset)));
+        }
+        let flags = read_u16(&cg.data, 16);
+        let mut channels = Vec::new();
+        read_channels(reader, cg.link(1), None, &mut channels)?;
+
+        let mut layout = None;
+        for kind in Kind::ALL {
+            let prefix = format!("{}.", kind.name());
+            let fields = channels.iter()
+                .filter_map(|(name, channel)| name.strip_prefix(&prefix)
+                    .map(|v| (v.to_owned(), channel.clone())))
+                .collect::<HashMap<_, _>>();
+            if !fields.is_empty() {
+                let time = channels.iter()
+                    .find(|(_, c)| c.cn_type == CN_TYPE_MASTER && c.sync_type == CN_SYNC_TIME)
+                    .map(|(_, c)| c.clone());
+                layout = Some(Layout { kind, time, fields });
+                break;
+            }
+        }
+
+        Ok(Self {
+            offset,
+            record_id: read_u64(&cg.data, 0),
+            size: read_u32(&cg.data, 24) as usize + read_u32(&cg.data, 28) as usize,
+            is_vlsd: flags & CG_FL_VLSD > 0,
+            layout,
+            vlsd_pos: 0,
+            vlsd_last: Default::default(),
+        })
+    }
+}
+
+struct Layout {
+    kind: Kind,
+    time: Option<Channel>,
+    /// The members of bus logging channel, such as `ID` of `CAN_DataFrame.ID`.
+    fields: HashMap<String, Channel>,
+}
+
+#[derive(Clone)]
+enum VlsdSource {
+    /// The data of `##SD` blocks.
+    Data(Vec<u8>),
+    /// The offset of VLSD channel group.
+    Group(u64),
+}
+
+#[derive(Clone)]
+struct Channel {
+    cn_type: u8,
+    sync_type: u8,
+    data_type: u8,
+    bit_offset: u8,
+    byte_offset: usize,
+    bit_count: u32,
+    /// The linear conversion `p1 + p2 * raw`.
+    conversion: Option<(f64, f64)>,
+    vlsd: Option<VlsdSource>,
+}
+
+impl Channel {
+    fn uint(&self, record: &[u8]) -> u64 {
+        let count = ((self.bit_offset as u32 + self.bit_count).div_ceil(8) as usize).min(8);
+        let mut buffer = [0u8; 8];
+        let end = (self.byte_offset + count).min(record.len());
+        let Some(data) = record.get(self.byte_offset..end) else { return 0; };
+        let value = match self.data_type {
+            UINT_BE | INT_BE | FLOAT_BE => {
+                buffer[8 - data.len()..].copy_from_slice(data);
+                u64::from_be_bytes(buffer)
+            },
+            _ => {
+                buffer[..data.len()].copy_from_slice(data);
+                u64::from_le_bytes(buffer)
+            },
+        };
+        let value = value.checked_shr(self.bit_offset as u32).unwrap_or_default();
+        match self.bit_count {
+            64.. => value,
+            v => value & ((1u64 << v) - 1),
+        }
+    }
+
+    fn float(&self, record: &[u8]) -> f64 {
+        let raw = self.uint(record);
+        let value = match (self.data_type, self.bit_count) {
+            (FLOAT_LE | FLOAT_BE, 64) => f64::from_bits(raw),
+            (FLOAT_LE | FLOAT_BE, 32) => f32::from_bits(raw as u32) as f64,
+            (INT_LE | INT_BE, v) if v > 0 && v < 64 => ((raw << (64 - v)) as i64 >> (64 - v)) as f64,
+            (INT_LE | INT_BE, _) => raw as i64 as f64,
+            _ => raw as f64,
+        };
+        match self.conversion {
+            Some((p1, p2)) => p1 + p2 * value,
+            None => value,
+        }
+    }
+
+    fn bytes(&self, record: &[u8], groups: &[ChannelGroup]) -> Option<Vec<u8>> {
+        if self.cn_type != CN_TYPE_VLSD {
+            let len = self.bit_count as usize / 8;
+            return record.get(self.byte_offset..self.byte_offset + len)
+                .map(|v| v.to_vec());
+        }
+
+        let offset = read_u64(record.get(self.byte_offset..)?, 0);
+        match self.vlsd.as_ref()? {
+            VlsdSource::Data(data) => {
+                let offset = offset as usize;
+                let len = read_u32(data.get(offset..offset + 4)?, 0) as usize;
+                data.get(offset + 4..offset + 4 + len).map(|v| v.to_vec())
+            },
+            VlsdSource::Group(cg) => {
+                let group = groups.iter().find(|g| g.offset == *cg)?;
+                (group.vlsd_last.0 == offset).then(|| group.vlsd_last.1.clone())
+            },
+        }
+    }
+}
+
+/// Read the `##CN` blocks recursively, the name of member is prefixed with the name of structure.
+fn read_channels<R: Read + Seek>(reader: &mut R,
+                                 mut next: u64,
+                                 parent: Option<&str>,
+                                 channels: &mut Vec<(String, Channel)>) -> Result<(), CanError> {
+    while next > 0 {
+        let cn = read_block(reader, next, b"##CN")?;
+        let name = read_text(reader, cn.link(2))?;
+        let name = match parent {
+            Some(p) if !name.contains('.') => format!("{}.{}", p, name),
+            _ => name,
+        };
+        let conversion = match cn.link(4) {
+            0 => None,
+            v => {
+                let cc = read_block(reader, v, b"##CC")?;
+                match cc.data.first() {
+                    Some(&CC_TYPE_LINEAR) if cc.data.len() >= CC_VAL_OFFSET + 16 =>
+                        Some((read_f64(&cc.data, CC_VAL_OFFSET), read_f64(&cc.data, CC_VAL_OFFSET + 8))),
+                    _ => None,
+                }
+            },
+        };
+        if cn.data.len() < CN_DATA_SIZE {
+            return Err(format_error(format!("invalid channel block at: {:#X}", next)));
+        }
+        let cn_type = cn.data[0];
+        let (data_type, bit_offset, bit_count) = (cn.data[2], cn.data[3], read_u32(&cn.data, 8));
+        if bit_offset > 7 || (data_type <= FLOAT_BE && bit_offset as u32 + bit_count > 64) {
+            return Err(format_error(format!("invalid bit offset or count of channel at: {:#X}", next)));
+        }
+        let vlsd = match (cn_type, cn.link(5)) {
+            (CN_TYPE_VLSD, v) if v > 0 => Some(match read_header(reader, v)?.0 {
+                id if &id == b"##CG" => VlsdSource::Group(v),
+                _ => VlsdSource::Data(read_data(reader, v)?),
+            }),
+            _ => None,
+        };
+        channels.push((name.clone(), Channel {
+            cn_type,
+            sync_type: cn.data[1],
+            data_type,
+            bit_offset,
+            byte_offset: read_u32(&cn.data, 4) as usize,
+            bit_count,
+            conversion,
+            vlsd,
+        }));
+
+        let composition = cn.link(1);
+        if composition > 0 && &read_header(reader, composition)?.0 == b"##CN" {
+            read_channels(reader, composition, Some(&name), channels)?;
+        }
+        next = cn.link(0);
+    }
+
+    Ok(())
+}
+
+/// Convert a record of bus logging group.
+fn decode<C: LogChannel>(layout: &Layout, record: &[u8], groups: &[ChannelGroup], start: u64)
+    -> Result<LogObject<C>, CanError> {
+    let value = |name: &str| layout.fields.get(name).map(|c| c.uint(record));
+    let data = || layout.fields.get("DataBytes")
+        .and_then(|c| c.bytes(record, groups))
+        .unwrap_or_default();
+    let seconds = layout.time.as_ref().map(|c| c.float(record)).unwrap_or_default();
+    let timestamp = (start as i64 + (seconds * 1e9).round() as i64).max(0) as u64;
+    let channel = value("BusChannel").unwrap_or_default().saturating_sub(1) as u8;
+    let direct = match value("Dir") {
+        Some(1) => Direct::Transmit,
+        _ => Direct::Receive,
+    };
+
+    if layout.kind == Kind::Lin {
+        let mut data = data();
+        let len = value("DataLength").map(|v| v as usize).unwrap_or(data.len());
+        data.truncate(len.min(8));
+        return Ok(LogObject::Lin(LinMessage {
+            timestamp,
+            channel,
+            id: value("ID").unwrap_or_default() as u8,
+            data,
+            checksum: value("Checksum").unwrap_or_default() as u16,
+            direct,
+        }));
+    }
+
+    let raw_id = value("ID").unwrap_or_default();
+    let extended = value("IDE").map(|v| v > 0).unwrap_or(raw_id & 0x8000_0000 > 0);
+    let id = Id::from_bits(raw_id as u32 & EFF_MASK, extended);
+    let is_fd = value("EDL").is_some_and(|v| v > 0);
+    let dlc = value("DLC").unwrap_or_default() as u8;
+    let len = value("DataLength").map(|v| v as usize).unwrap_or_else(|| dlc_to_len(dlc, is_fd));
+
+    let msg = match layout.kind {
+        Kind::CanRemote => CanMessage::new_remote(id, len),
+        _ => {
+            let mut data = data();
+            data.truncate(len);
+            CanMessage::new(id, &data)
+        },
+    };
+    let mut msg = msg.ok_or_else(|| format_error(format!("invalid record of `{}`", layout.kind.name())))?;
+    msg.set_timestamp(Some(timestamp))
+        .set_time_base(TimeBase::WallClock)
+        .set_channel(C::from_index(channel))
+        .set_direct(direct)
+        .set_can_fd(is_fd)
+        .set_bitrate_switch(value("BRS").is_some_and(|v| v > 0))
+        .set_esi(value("ESI").is_some_and(|v| v > 0))
+        .set_error_frame(layout.kind == Kind::CanError);
+
+    Ok(LogObject::Can(msg))
+}
+
+/// The data of `##DT`, `##SD` and `##DZ` blocks, the `##DT` block is read by chunks.
+struct DataStream {
+    blocks: VecDeque<u64>,
+    /// The position and remaining size of current `##DT` block.
+    raw: Option<(u64, u64)>,
+    buffer: Vec<u8>,
+    pos: usize,
+}
+
+impl DataStream {
+    fn new(blocks: VecDeque<u64>) -> Self {
+        Self { blocks, raw: None, buffer: Default::default(), pos: 0 }
+    }
+
+    #[inline]
+    fn is_finished(&self) -> bool {
+        self.blocks.is_empty() && self.raw.is_none() && self.pos >= self.buffer.len()
+    }
+
+    /// Ensure `size` bytes are buffered, `false` if the data is ended.
+    fn fill<R: Read + Seek>(&mut self, reader: &mut R, size: usize, unfinished: Option<u64>) -> Result<bool, CanError> {
+        while self.buffer.len() - self.pos < size {
+            self.buffer.drain(..self.pos);
+            self.pos = 0;
+
+            if let Some((offset, remaining)) = self.raw {
+                let len = remaining.min(CHUNK_SIZE);
+                let mut chunk = vec![0u8; len as usize];
+                reader.seek(SeekFrom::Start(offset))
+                    .and_then(|_| reader.read_exact(&mut chunk))
+                    .map_err(io_error)?;
+                self.buffer.extend(chunk);
+                self.raw = match remaining - len {
+                    0 => None,
+                    v => Some((offset + len, v)),
+                };
+                continue;
+            }
+
+            let Some(offset) = self.blocks.pop_front() else { return Ok(false); };
+            let (id, length, _) = read_header(reader, offset)?;
+            match &id {
+                b"##DT" | b"##SD" => {
+                    let mut length = length.saturating_sub(BLOCK_HEADER_SIZE as u64);
+                    if let (0, Some(end), b"##DT") = (length, unfinished, &id) {
+                        length = end.saturating_sub(offset + BLOCK_HEADER_SIZE as u64);
+                    }
+                    if length > 0 {
+                        self.raw = Some((offset + BLOCK_HEADER_SIZE as u64, length));
+                    }
+                },
+                b"##DZ" => {
+                    let block = read_block(reader, offset, b"##DZ")?;
+                    self.buffer.extend(inflate(&block.data)?);
+                },
+                _ => return Err(format_error(format!("unexpected data block at: {:#X}", offset))),
+            }
+        }
+
+        Ok(true)
+    }
+
+    #[inline]
+    fn take(&mut self, size: usize) -> &[u8] {
+        let result = &self.buffer[self.pos..self.pos + size];
+        self.pos += size;
+        result
+    }
+}
+
+/// Write the frames as MDF file, the cycle counters and data length are updated when finished.
+pub struct MdfWriter<W: Write + Seek> {
+    writer: W,
+    /// The timestamp(ns since UNIX epoch) of first object.
+    start: Option<u64>,
+    counts: [u64; GROUPS.len()],
+    cg_offsets: [u64; GROUPS.len()],
+    fh_offset: u64,
+    dt_offset: u64,
+    /// The size of records.
+    size: u64,
+    finished: bool,
+}
+
+impl MdfWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let file = File::create(path).map_err(io_error)?;
+        Self::new(BufWriter::new(file))
+    }
+}
+
+impl<W: Write + Seek> MdfWriter<W> {
+    /// Write the unfinished file with the blocks of bus logging groups.
+    pub fn new(mut writer: W) -> Result<Self, CanError> {
+        let mut meta = MetaBuilder::default();
+        let hd = meta.push(b"##HD", &[0; 6], &[0; 32]);
+        let comment = format!("<FHcomment><TX>bus logging</TX><tool_id>rs-can</tool_id>\
+            <tool_vendor>rs-can</tool_vendor><tool_version>{}</tool_version></FHcomment>", env!("CARGO_PKG_VERSION"));
+        let comment = meta.push_text(b"##MD", &comment);
+        let fh_offset = meta.push(b"##FH", &[0, comment], &[0; 16]);
+        meta.set_link(hd, 1, fh_offset);
+
+        let mut sources = HashMap::new();
+        for (name, bus_type) in [("CAN", BUS_TYPE_CAN), ("LIN", BUS_TYPE_LIN)] {
+            let tx = meta.push_text(b"##TX", name);
+            let si = meta.push(b"##SI", &[tx, 0, 0], &[SI_TYPE_BUS, bus_type, 0, 0, 0, 0, 0, 0]);
+            sources.insert(bus_type, (tx, si));
+        }
+
+        let mut cg_offsets = [0; GROUPS.len()];
+        for (i, group) in GROUPS.iter().enumerate() {
+            let (acq_name, acq_source) = sources[&group.bus_type];
+            let name = group.kind.name();
+
+            let mut next = 0;
+            for field in group.fields.iter().rev() {
+                let tx = meta.push_text(b"##TX", &format!("{}.{}", name, field.name));
+                next = meta.push(b"##CN", &[next, 0, tx, 0, 0, 0, 0, 0],
+                                 &channel_data(CN_TYPE_FIXED, 0, field.data_type, field.bit_offset, field.byte_offset, field.bit_count, 0));
+            }
+            let tx = meta.push_text(b"##TX", name);
+            let bits = (group.size as u32 - 8) * 8;
+            let frame = meta.push(b"##CN", &[0, next, tx, acq_source, 0, 0, 0, 0],
+                                  &channel_data(CN_TYPE_FIXED, 0, BYTE_ARRAY, 0, 8, bits, CN_FL_BUS_EVENT));
+            let tx = meta.push_text(b"##TX", "Timestamp");
+            let time = meta.push(b"##CN", &[frame, 0, tx, acq_source, 0, 0, 0, 0],
+                                 &channel_data(CN_TYPE_MASTER, CN_SYNC_TIME, FLOAT_LE, 0, 0, 64, 0));
+
+            let mut data = Vec::with_capacity(32);
+            data.extend(((i + 1) as u64).to_le_bytes());
+            data.extend(0u64.to_le_bytes());
+            data.extend((CG_FL_BUS_EVENT | CG_FL_PLAIN_BUS_EVENT).to_le_bytes());
+            data.extend((b'.' as u16).to_le_bytes());
+            data.extend([0; 4]);
+            data.extend((group.size as u32).to_le_bytes());
+            data.extend(0u32.to_le_bytes());
+            cg_offsets[i] = meta.push(b"##CG", &[0, time, acq_name, acq_source, 0, 0], &data);
+        }
+        for i in 1..GROUPS.len() {
+            meta.set_link(cg_offsets[i - 1], 0, cg_offsets[i]);
+        }
+
+        let dg = meta.push(b"##DG", &[0, cg_offsets[0], 0, 0], &[1, 0, 0, 0, 0, 0, 0, 0]);
+        meta.set_link(hd, 0, dg);
+        let dt_offset = meta.push(b"##DT", &[], &[]);
+        meta.set_link(dg, 2, dt_offset);
+
+        writer.write_all(&meta.data).map_err(io_error)?;
+
+        Ok(Self {
+            writer,
+            start: None,
+            counts: Default::default(),
+            cg_offsets,
+            fh_offset,
+            dt_offset,
+            size: 0,
+            finished: false,
+        })
+    }
+
+    pub fn write<C: LogChannel>(&mut self, msg: &CanMessage<C>) -> Result<(), CanError> {
+        let (index, kind) = match (msg.is_error_frame(), msg.is_remote()) {
+            (true, _) => (1, Kind::CanError),
+            (_, true) => (2, Kind::CanRemote),
+            _ => (0, Kind::CanData),
+        };
+        let mut record = self.record(index, msg.timestamp());
+        let mut flags = msg.is_extended() as u8;
+        flags |= ((msg.direct() == Direct::Transmit) as u8) << 1;
+        if kind != Kind::CanRemote {
+            flags |= (msg.is_can_fd() as u8) << 2
+                | (msg.is_bitrate_switch() as u8) << 3
+                | (msg.is_esi() as u8) << 4;
+        }
+        record[9] = channel_number(msg.channel().index());
+        record[10..14].copy_from_slice(&msg.id().as_raw().to_le_bytes());
+        record[14] = flags;
+        record[15] = len_to_dlc(msg.length());
+        record[16] = msg.length() as u8;
+        if kind != Kind::CanRemote {
+            let data = msg.data();
+            let len = data.len().min(64);
+            record[17..17 + len].copy_from_slice(&data[..len]);
+        }
+
+        self.write_record(index, &record)
+    }
+
+    pub fn write_lin(&mut self, msg: &LinMessage) -> Result<(), CanError> {
+        let index = 3;
+        let mut record = self.record(index, msg.timestamp);
+        let len = msg.data.len().min(8);
+        record[9] = channel_number(msg.channel);
+        record[10] = msg.id & 0x3F;
+        record[11] = (msg.direct == Direct::Transmit) as u8;
+        record[12] = len as u8;
+        record[13] = len as u8;
+        record[14] = msg.checksum as u8;
+        record[15..15 + len].copy_from_slice(&msg.data[..len]);
+
+        self.write_record(index, &record)
+    }
+
+    /// Update the header, cycle counters, data length and finalize the file, it is called when dropped.
+    pub fn finish(&mut self) -> Result<(), CanError> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+
+        let start = *self.start.get_or_insert_with(system_timestamp);
+        let mut patches = vec![
+            (0, FILE_ID.to_vec()),
+            (60, vec![0; 4]),
+            (HD_OFFSET + (BLOCK_HEADER_SIZE + 6 * 8) as u64, start.to_le_bytes().to_vec()),
+            (self.fh_offset + (BLOCK_HEADER_SIZE + 2 * 8) as u64, start.to_le_bytes().to_vec()),
+            (self.dt_offset + 8, (self.size + BLOCK_HEADER_SIZE as u64).to_le_bytes().to_vec()),
+        ];
+        for (offset, count) in self.cg_offsets.iter().zip(self.counts) {
+            patches.push((offset + (BLOCK_HEADER_SIZE + 6 * 8 + 8) as u64, count.to_le_bytes().to_vec()));
+        }
+
+        for (offset, data) in patches {
+            self.writer.seek(SeekFrom::Start(offset))
+                .and_then(|_| self.writer.write_all(&data))
+                .map_err(io_error)?;
+        }
+        self.writer.seek(SeekFrom::End(0))
+            .and_then(|_| self.writer.flush())
+            .map_err(io_error)
+    }
+
+    /// The record with ID and the master channel.
+    fn record(&mut self, index: usize, timestamp: u64) -> Vec<u8> {
+        let start = *self.start.get_or_insert(timestamp);
+        let seconds = (timestamp as i64 - start as i64) as f64 / 1e9;
+        let mut record = vec![0u8; GROUPS[index].size + 1];
+        record[0] = (index + 1) as u8;
+        record[1..9].copy_from_slice(&seconds.to_le_bytes());
+        record
+    }
+
+    fn write_record(&mut self, index: usize, record: &[u8]) -> Result<(), CanError> {
+        if self.finished {
+            return Err(CanError::OperationError("the MDF file is finished".into()));
+        }
+        self.writer.write_all(record).map_err(io_error)?;
+        self.counts[index] += 1;
+        self.size += record.len() as u64;
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek> Drop for MdfWriter<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            log::warn!("{}", e);
+        }
+    }
+}
+
+/// The blocks before data, the links can be updated after the blocks are pushed.
+struct MetaBuilder {
+    data: Vec<u8>,
+}
+
+impl Default for MetaBuilder {
+    fn default() -> Self {
+        let mut data = Vec::with_capacity(16 * 1024);
+        data.extend(UNFINISHED_FILE_ID);
+        data.extend(VERSION);
+        data.extend(PROGRAM);
+        data.extend([0; 4]);
+        data.extend(VERSION_NUMBER.to_le_bytes());
+        data.extend([0; 30]);
+        data.extend((UNFIN_CYCLE_COUNT | UNFIN_DT_LENGTH).to_le_bytes());
+        data.extend(0u16.to_le_bytes());
+        Self { data }
+    }
+}
+
+impl MetaBuilder {
+    /// Push a block and return the offset, the data is padded to 8 bytes.
+    fn push(&mut self, id: &[u8; 4], links: &[u64], data: &[u8]) -> u64 {
+        let offset = self.data.len() as u64;
+        let padding = (8 - data.len() % 8) % 8;
+        let length = BLOCK_HEADER_SIZE + links.len() * 8 + data.len() + padding;
+        self.data.extend(id);
+        self.data.extend([0; 4]);
+        self.data.extend((length as u64).to_le_bytes());
+        self.data.extend((links.len() as u64).to_le_bytes());
+        links.iter().for_each(|v| self.data.extend(v.to_le_bytes()));
+        self.data.extend(data);
+        self.data.extend(std::iter::repeat_n(0, padding));
+        offset
+    }
+
+    /// Push a `##TX` or `##MD` block with zero terminated text.
+    fn push_text(&mut self, id: &[u8; 4], text: &str) -> u64 {
+        let mut data = text.as_bytes().to_vec();
+        data.push(0);
+        self.push(id, &[], &data)
+    }
+
+    fn set_link(&mut self, block: u64, index: usize, value: u64) {
+        let offset = block as usize + BLOCK_HEADER_SIZE + index * 8;
+        self.data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn channel_data(cn_type: u8, sync_type: u8, data_type: u8, bit_offset: u8, byte_offset: u32, bit_count: u32, flags: u32) -> Vec<u8> {
+    let mut data = Vec::with_capacity(72);
+    data.extend([cn_type, sync_type, data_type, bit_offset]);
+    data.extend(byte_offset.to_le_bytes());
+    data.extend(bit_count.to_le_bytes());
+    data.extend(flags.to_le_bytes());
+    // invalidation bit position, precision, reserved and attachment count
+    data.extend([0; 8]);
+    // the value range and limits
+    data.extend([0; 48]);
+    data
+}
+
+/// The bus channel number is 1-based.
+#[inline]
+fn channel_number(index: u8) -> u8 {
+    index.saturating_add(1)
+}
+
+struct Block {
+    links: Vec<u64>,
+    data: Vec<u8>,
+}
+
+impl Block {
+    #[inline]
+    fn link(&self, index: usize) -> u64 {
+        self.links.get(index).copied().unwrap_or_default()
+    }
+}
+
+/// Read the id, length and link count of block.
+fn read_header<R: Read + Seek>(reader: &mut R, offset: u64) -> Result<([u8; 4], u64, u64), CanError> {
+    let mut header = [0u8; BLOCK_HEADER_SIZE];
+    reader.seek(SeekFrom::Start(offset))
+        .and_then(|_| reader.read_exact(&mut header))
+        .map_err(io_error)?;
+    let id = [header[0], header[1], header[2], header[3]];
+    Ok((id, read_u64(&header, 8), read_u64(&header, 16)))
+}
+
+fn read_block<R: Read + Seek>(reader: &mut R, offset: u64, expected: &[u8; 4]) -> Result<Block, CanError> {
+    let (id, length, count) = read_header(reader, offset)?;
+    if &id != expected {
+        return Err(format_error(format!("expected {} block at: {:#X}", String::from_utf8_lossy(expected), offset)));
+    }
+    let size = length.checked_sub(BLOCK_HEADER_SIZE as u64)
+        .filter(|v| count.checked_mul(8).is_some_and(|c| *v >= c))
+        .ok_or_else(|| format_error(format!("invalid block length at: {:#X}", offset)))?;
+    let mut data = vec![0u8; size as usize];
+    reader.read_exact(&mut data).map_err(io_error)?;
+    let links = data[..count as usize * 8].chunks_exact(8)
+        .map(|v| read_u64(v, 0))
+        .collect();
+    data.drain(..count as usize * 8);
+
+    Ok(Block { links, data })
+}
+
+/// Read the text of `##TX` or `##MD` block, empty if the link is NIL.
+fn read_text<R: Read + Seek>(reader: &mut R, offset: u64) -> Result<String, CanError> {
+    if offset == 0 {
+        return Ok(Default::default());
+    }
+    let id = read_header(reader, offset)?.0;
+    let block = read_block(reader, offset, &id)?;
+    let end = block.data.iter().position(|v| *v == 0).unwrap_or(block.data.len());
+    Ok(String::from_utf8_lossy(&block.data[..end]).into_owned())
+}
+
+/// The `##DT`, `##SD` or `##DZ` blocks of data link, the `##DL` and `##HL` blocks are expanded.
+fn data_blocks<R: Read + Seek>(reader: &mut R, mut next: u64) -> Result<VecDeque<u64>, CanError> {
+    let mut result = VecDeque::new();
+    while next > 0 {
+        let (id, ..) = read_header(reader, next)?;
+        match &id {
+            b"##DT" | b"##SD" | b"##DZ" => {
+                result.push_back(next);
+                break;
+            },
+            b"##HL" => next = read_block(reader, next, &id)?.link(0),
+            b"##DL" => {
+                let block = read_block(reader, next, &id)?;
+                result.extend(block.links.iter().skip(1).filter(|v| **v > 0));
+                next = block.link(0);
+            },
+            _ => return Err(format_error(format!("unexpected data block at: {:#X}", next))),
+        }
+    }
+    Ok(result)
+}
+
+/// Read all data of data link, it is used by the `##SD` blocks.
+fn read_data<R: Read + Seek>(reader: &mut R, offset: u64) -> Result<Vec<u8>, CanError> {
+    let mut stream = DataStream::new(data_blocks(reader, offset)?);
+    while stream.fill(reader, stream.buffer.len() + 1, None)? {}
+    Ok(stream.buffer)
+}
+
+/// Decompress the data of `##DZ` block.
+fn inflate(data: &[u8]) -> Result<Vec<u8>, CanError> {
+    if data.len() < 24 {
+        return Err(format_error("invalid DZ block"));
+    }
+    let zip_type = data[2];
+    let columns = read_u32(data, 4) as usize;
+    let length = read_u64(data, 8) as usize;
+    let mut result = Vec::with_capacity(length);
+    ZlibDecoder::new(&data[24..])
+        .read_to_end(&mut result)
+        .map_err(io_error)?;
+
+    if zip_type == ZIP_TYPE_TRANSPOSE && columns > 0 {
+        let rows = result.len() / columns;
+        let mut origin = result.clone();
+        for r in 0..rows {
+            for c in 0..columns {
+                origin[r * columns + c] = result[c * rows + r];
+            }
+        }
+        result = origin;
+    }
+    Ok(result)
+}
+
+#[inline]
+fn format_error<T: AsRef<str>>(reason: T) -> CanError {
+    CanError::FrameConvertFailed(format!("invalid MDF file: {}", reason.as_ref()))
+}
+
+#[inline]
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+#[inline]
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    let mut buffer = [0u8; 4];
+    let end = (offset + 4).min(data.len());
+    buffer[..end.saturating_sub(offset)].copy_from_slice(&data[offset.min(end)..end]);
+    u32::from_le_bytes(buffer)
+}
+
+#[inline]
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut buffer = [0u8; 8];
+    let end = (offset + 8).min(data.len());
+    buffer[..end.saturating_sub(offset)].copy_from_slice(&data[offset.min(end)..end]);
+    u64::from_le_bytes(buffer)
+}
+
+#[inline]
+fn read_f64(data: &[u8], offset: usize) -> f64 {
+    f64::from_bits(read_u64(data, offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Seek, SeekFrom, Write};
+    use isotp_rs::can::{frame::{Direct, Frame}, identifier::Id};
+    use crate::CanMessage;
+    use crate::io::{LinMessage, LogObject};
+    use super::{MdfReader, MdfWriter};
+
+    #[test]
+    fn test_mdf() -> anyhow::Result<()> {
+        let start = 1_681_312_731_612_345_678;
+        let mut frames = Vec::new();
+        for i in 0..1_000u64 {
+            let mut msg = match i % 4 {
+                0 => CanMessage::new(Id::from(0x7DF), &[0x02, 0x3E, i as u8]),
+                1 => CanMessage::new(Id::from_bits(0x18DA00F1, true), &[i as u8; 20]),
+                2 => CanMessage::new_remote(Id::from(0x100), 4),
+                _ => CanMessage::new(Id::from(0x7E8), &[0x03, 0x7F, 0x3E, 0x11]),
+            }.unwrap();
+            msg.set_timestamp(Some(start + i * 1_000_123))
+                .set_channel((i % 2) as u8)
+                .set_direct(if i % 3 == 0 { Direct::Transmit } else { Direct::Receive });
+            match i % 4 {
+                1 => { msg.set_bitrate_switch(true); },
+                3 => { msg.set_error_frame(true).set_direct(Direct::Receive); },
+                _ => {},
+            }
+            frames.push(msg);
+        }
+        let lin = LinMessage {
+            timestamp: start + 500_061_500,
+            channel: 1,
+            id: 0x3C,
+            data: vec![0x01, 0x02],
+            checksum: 0xAA,
+            direct: Direct::Transmit,
+        };
+
+        let mut cursor = Cursor::new(Vec::new());
+        {
+            let mut writer = MdfWriter::new(&mut cursor)?;
+            for (i, frame) in frames.iter().enumerate() {
+                writer.write(frame)?;
+                if i == 500 {
+                    writer.write_lin(&lin)?;
+                }
+            }
+        }
+        let data = cursor.into_inner();
+        assert_eq!(&data[..8], b"MDF     ");
+
+        let mut reader = MdfReader::<_, u8>::new(Cursor::new(data.clone()))?;
+        assert_eq!(reader.start_time(), start);
+        let mut count = 0;
+        while let Some(object) = reader.next_object() {
+            match object? {
+                LogObject::Can(msg) => {
+                    let frame = &frames[count];
+                    assert_eq!(&msg, frame);
+                    assert_eq!(msg.timestamp(), frame.timestamp());
+                    assert_eq!(msg.channel(), frame.channel());
+                    assert_eq!(msg.direct(), frame.direct());
+                    assert_eq!(msg.is_can_fd(), frame.is_can_fd());
+                    assert_eq!(msg.is_bitrate_switch(), frame.is_bitrate_switch());
+                    assert_eq!(msg.is_error_frame(), frame.is_error_frame());
+                    count += 1;
+                },
+                LogObject::Lin(msg) => assert_eq!(msg, lin),
+            }
+        }
+        assert_eq!(count, frames.len());
+
+        // the unfinished file is read to the end
+        let mut cursor = Cursor::new(Vec::new());
+        let mut writer = MdfWriter::new(&mut cursor)?;
+        for frame in &frames[..10] {
+            writer.write(frame)?;
+        }
+        writer.writer.flush()?;
+        let unfinished = writer.writer.get_ref().clone();
+        writer.finish()?;
+        drop(writer);
+        let reader = MdfReader::<_, u8>::new(Cursor::new(unfinished))?;
+        assert_eq!(reader.count(), 10);
+        cursor.seek(SeekFrom::Start(0))?;
+        let reader = MdfReader::<_, u8>::new(cursor.clone())?;
+        assert_eq!(reader.count(), 10);
+
+        // a channel with an invalid bit offset is rejected
+        let mut data = cursor.into_inner();
+        let pos = data.windows(4).position(|v| v == b"##CN").unwrap();
+        let links = u64::from_le_bytes(data[pos + 16..pos + 24].try_into()?) as usize;
+        data[pos + 24 + links * 8 + 3] = 64;
+        assert!(MdfReader::<_, u8>::new(Cursor::new(data)).is_err());
+
+        assert!(MdfReader::<_, u8>::new(Cursor::new([0u8; 64])).is_err());
+
+        Ok(())
+    }
+}
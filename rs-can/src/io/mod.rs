@@ -0,0 +1,231 @@
+//! The readers and writers of bus log files, all records are converted from and to [`CanMessage`].
+//! The frames read are on the wall-clock time base.
+//!
+//! [`CanMessage`]: crate::CanMessage
+
+pub mod asc;
+#[cfg(feature = "blf")]
+pub mod blf;
+pub mod candump;
+#[cfg(feature = "mdf")]
+pub mod mdf;
+pub mod pcap;
+pub mod trc;
+
+use std::fmt::Display;
+use std::io::Error;
+use std::path::Path;
+use isotp_rs::can::frame::Direct;
+use crate::CanMessage;
+use crate::error::CanError;
+
+/// The LIN frame of log files, the channel is 0-based.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct LinMessage {
+    /// The nanoseconds since UNIX epoch.
+    pub timestamp: u64,
+    pub channel: u8,
+    pub id: u8,
+    pub data: Vec<u8>,
+    pub checksum: u16,
+    pub direct: Direct,
+}
+
+/// The objects of log files which support other buses than CAN.
+#[derive(Debug, Clone)]
+pub enum LogObject<C> {
+    Can(CanMessage<C>),
+    Lin(LinMessage),
+}
+
+/// The channel of log files, the index of channel is 0-based, such as `1` of ASC is index `0`.
+pub trait LogChannel: Display + Clone + Default + Send + Sync + 'static {
+    fn index(&self) -> u8;
+    fn from_index(index: u8) -> Self;
+    /// The interface name, such as `can0` of candump.
+    #[inline]
+    fn name(&self) -> String {
+        format!("can{}", self.index())
+    }
+    #[inline]
+    fn from_name(name: &str) -> Self {
+        Self::from_index(trailing_number(name))
+    }
+}
+
+impl LogChannel for u8 {
+    #[inline]
+    fn index(&self) -> u8 {
+        *self
+    }
+
+    #[inline]
+    fn from_index(index: u8) -> Self {
+        index
+    }
+}
+
+/// The trailing number of name is used as index, such as `CAN1` of NI-CAN, `0` if there is no number.
+impl LogChannel for String {
+    #[inline]
+    fn index(&self) -> u8 {
+        trailing_number(self)
+    }
+
+    #[inline]
+    fn from_index(index: u8) -> Self {
+        format!("CAN{}", index)
+    }
+
+    #[inline]
+    fn name(&self) -> String {
+        self.clone()
+    }
+
+    #[inline]
+    fn from_name(name: &str) -> Self {
+        name.to_owned()
+    }
+}
+
+/// The reader of any log file.
+pub type LogReader<C> = Box<dyn Iterator<Item = Result<CanMessage<C>, CanError>> + Send>;
+
+/// Open the reader of log file by the extension,
+/// such as `asc`, `blf`, `log`(candump), `trc`, `mf4`, `pcap` and `pcapng`.
+pub fn open_log<C: LogChannel, P: AsRef<Path>>(path: P) -> Result<LogReader<C>, CanError> {
+    let path = path.as_ref();
+    let ext = path.extension()
+        .and_then(|v| v.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    match ext.as_str() {
+        "asc" => Ok(Box::new(asc::AscReader::<_, C>::open(path)?)),
+        #[cfg(feature = "blf")]
+        "blf" => Ok(Box::new(blf::BlfReader::<_, C>::open(path)?)),
+        "log" => Ok(Box::new(candump::CandumpReader::<_, C>::open(path)?)),
+        "trc" => Ok(Box::new(trc::TrcReader::<_, C>::open(path)?)),
+        #[cfg(feature = "mdf")]
+        "mf4" | "mdf" => Ok(Box::new(mdf::MdfReader::<_, C>::open(path)?)),
+        "pcap" | "pcapng" => Ok(Box::new(pcap::PcapReader::<_, C>::open(path)?)),
+        _ => Err(CanError::OtherError(format!("unsupported log file: `{}`", path.display()))),
+    }
+}
+
+#[inline]
+fn trailing_number(name: &str) -> u8 {
+    let pos = name.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    name[pos..].parse().unwrap_or_default()
+}
+
+#[inline]
+pub(crate) fn io_error(e: Error) -> CanError {
+    CanError::OtherError(format!("log file I/O error: {}", e))
+}
+
+#[inline]
+pub(crate) fn parse_error(line: usize, content: &str) -> CanError {
+    CanError::FrameConvertFailed(format!("invalid record at line {}: `{}`", line, content.trim()))
+}
+
+/// The broken-down UTC time.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub(crate) struct DateTime {
+    pub(crate) year: i64,
+    /// 1..=12
+    pub(crate) month: u32,
+    /// 1..=31
+    pub(crate) day: u32,
+    /// 0 is Sunday.
+    pub(crate) weekday: u32,
+    pub(crate) hour: u32,
+    pub(crate) minute: u32,
+    pub(crate) second: u32,
+    pub(crate) millis: u32,
+}
+
+impl DateTime {
+    pub(crate) const MONTHS: [&'static str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+    pub(crate) const WEEKDAYS: [&'static str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+
+    /// Convert from the milliseconds since UNIX epoch.
+    pub(crate) fn from_millis(millis: u64) -> Self {
+        let days = (millis / 86_400_000) as i64;
+        let rem = (millis % 86_400_000) as u32;
+        // the civil calendar algorithm of Howard Hinnant.
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + (month <= 2) as i64;
+
+        Self {
+            year,
+            month,
+            day,
+            weekday: ((days + 4).rem_euclid(7)) as u32,
+            hour: rem / 3_600_000,
+            minute: rem / 60_000 % 60,
+            second: rem / 1000 % 60,
+            millis: rem % 1000,
+        }
+    }
+
+    /// Convert from the nanoseconds since UNIX epoch.
+    #[inline]
+    pub(crate) fn from_nanos(nanos: u64) -> Self {
+        Self::from_millis(nanos / 1_000_000)
+    }
+
+    /// Convert to the nanoseconds since UNIX epoch, the `weekday` is ignored.
+    #[inline]
+    pub(crate) fn to_nanos(self) -> u64 {
+        self.to_millis() * 1_000_000
+    }
+
+    /// Convert to the milliseconds since UNIX epoch, the `weekday` is ignored.
+    pub(crate) fn to_millis(self) -> u64 {
+        let year = self.year - (self.month <= 2) as i64;
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let month = self.month as i64;
+        let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        let days = era * 146_097 + doe - 719_468;
+        let millis = days * 86_400_000
+            + (self.hour as i64 * 3600 + self.minute as i64 * 60 + self.second as i64) * 1000
+            + self.millis as i64;
+
+        millis.max(0) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DateTime, LogChannel};
+
+    #[test]
+    fn test_datetime() {
+        // Wed Apr 12 15:18:51.612 2023
+        let millis = 1_681_312_731_612;
+        let dt = DateTime::from_millis(millis);
+        assert_eq!(dt, DateTime { year: 2023, month: 4, day: 12, weekday: 3, hour: 15, minute: 18, second: 51, millis: 612 });
+        assert_eq!(dt.to_millis(), millis);
+        assert_eq!(DateTime::from_millis(0).weekday, 4);
+        assert_eq!(DateTime { year: 2024, month: 2, day: 29, ..Default::default() }.to_millis(), 1_709_164_800_000);
+    }
+
+    #[test]
+    fn test_channel() {
+        assert_eq!(String::from("CAN1").index(), 1);
+        assert_eq!(String::from("vcan").index(), 0);
+        assert_eq!(String::from_index(2), "CAN2");
+        assert_eq!(3u8.index(), 3);
+        assert_eq!(u8::from_name("vcan1"), 1);
+        assert_eq!(1u8.name(), "can1");
+    }
+}
@@ -0,0 +1,647 @@
+//! The pcap and pcapng captures with `LINKTYPE_CAN_SOCKETCAN`, which are opened by Wireshark.
+//!
+//! The pcapng writer adds an interface block for each channel with nanosecond timestamps,
+//! and the writer never seeks, so it is able to stream into a pipe.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+use isotp_rs::can::frame::{Direct, Frame};
+use isotp_rs::can::identifier::Id;
+use isotp_rs::can::{CANFD_FRAME_MAX_SIZE, CAN_FRAME_MAX_SIZE, EFF_MASK, SFF_MASK};
+use crate::{CanMessage, TimeBase};
+use crate::error::CanError;
+use crate::frame::{CANFD_BRS, CANFD_ESI, CAN_ERR_FLAG};
+use super::{io_error, LogChannel};
+
+pub const LINKTYPE_CAN_SOCKETCAN: u16 = 227;
+
+const PCAP_MAGIC_MICROS: u32 = 0xA1B2_C3D4;
+const PCAP_MAGIC_NANOS: u32 = 0xA1B2_3C4D;
+const PCAP_HEADER_SIZE: usize = 24;
+const PCAP_RECORD_HEADER_SIZE: usize = 16;
+
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 1;
+const SIMPLE_PACKET_BLOCK: u32 = 3;
+const INTERFACE_STATISTICS_BLOCK: u32 = 5;
+const ENHANCED_PACKET_BLOCK: u32 = 6;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+const OPT_END: u16 = 0;
+const SHB_USERAPPL: u16 = 4;
+const IF_NAME: u16 = 2;
+const IF_TSRESOL: u16 = 9;
+const IF_TSOFFSET: u16 = 14;
+const EPB_FLAGS: u16 = 2;
+const EPB_INBOUND: u32 = 0x01;
+const EPB_OUTBOUND: u32 = 0x02;
+
+const CAN_EFF_FLAG: u32 = 0x8000_0000;
+const CAN_RTR_FLAG: u32 = 0x4000_0000;
+const CANFD_FDF: u8 = 0x04;
+const CANXL_XLF: u8 = 0x80;
+/// The size of SocketCAN header.
+const CAN_HEADER_SIZE: usize = 8;
+const SNAPLEN: u32 = 0xFFFF;
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum PcapFormat {
+    /// The classic pcap with nanosecond timestamps, the channel and direction are lost.
+    Pcap,
+    #[default]
+    Pcapng,
+}
+
+/// Read the CAN frames of pcap or pcapng capture, the format is detected by magic.
+///
+/// The packets of other link types are skipped in pcapng, and an error is returned for pcap.
+pub struct PcapReader<R, C> {
+    reader: R,
+    format: PcapFormat,
+    big_endian: bool,
+    /// The pcap timestamps are nanoseconds rather than microseconds.
+    nanos: bool,
+    interfaces: Vec<Interface>,
+    _channel: PhantomData<C>,
+}
+
+/// The interface description of pcapng.
+struct Interface {
+    link_type: u16,
+    name: Option<String>,
+    tsresol: u8,
+    /// The seconds added to the timestamps.
+    tsoffset: i64,
+}
+
+impl<C: LogChannel> PcapReader<BufReader<File>, C> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let file = File::open(path).map_err(io_error)?;
+        Self::new(BufReader::new(file))
+    }
+}
+
+impl<R: Read, C: LogChannel> PcapReader<R, C> {
+    /// Read the pcap header or the first section header of pcapng.
+    pub fn new(mut reader: R) -> Result<Self, CanError> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic).map_err(io_error)?;
+        let mut result = Self {
+            reader,
+            format: PcapFormat::Pcapng,
+            big_endian: false,
+            nanos: false,
+            interfaces: Default::default(),
+            _channel: Default::default(),
+        };
+
+        if u32::from_le_bytes(magic) == SECTION_HEADER_BLOCK {
+            result.read_section()?;
+            return Ok(result);
+        }
+
+        let (big_endian, nanos) = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+            (PCAP_MAGIC_MICROS, _) => (false, false),
+            (PCAP_MAGIC_NANOS, _) => (false, true),
+            (_, PCAP_MAGIC_MICROS) => (true, false),
+            (_, PCAP_MAGIC_NANOS) => (true, true),
+            _ => return Err(format_error("invalid magic number")),
+        };
+        let mut header = [0u8; PCAP_HEADER_SIZE - 4];
+        result.reader.read_exact(&mut header).map_err(io_error)?;
+        result.format = PcapFormat::Pcap;
+        result.big_endian = big_endian;
+        result.nanos = nanos;
+        let link_type = result.u32(&header, 16) as u16;
+        if link_type != LINKTYPE_CAN_SOCKETCAN {
+            return Err(format_error(format!("link type: {} is not supported", link_type)));
+        }
+
+        Ok(result)
+    }
+
+    #[inline]
+    pub fn format(&self) -> PcapFormat {
+        self.format
+    }
+
+    /// Read the record of pcap, `None` if the capture is ended.
+    fn next_pcap(&mut self) -> Result<Option<CanMessage<C>>, CanError> {
+        let mut header = [0u8; PCAP_RECORD_HEADER_SIZE];
+        if !read_or_eof(&mut self.reader, &mut header)? {
+            return Ok(None);
+        }
+        let secs = self.u32(&header, 0) as u64;
+        let frac = self.u32(&header, 4) as u64;
+        let mut data = vec![0u8; self.u32(&header, 8) as usize];
+        self.reader.read_exact(&mut data).map_err(io_error)?;
+
+        let nanos = secs * 1_000_000_000 + if self.nanos { frac } else { frac * 1000 };
+        let mut msg = decode(&data)?;
+        msg.set_timestamp(Some(nanos))
+            .set_time_base(TimeBase::WallClock)
+            .set_direct(Direct::Receive);
+
+        Ok(Some(msg))
+    }
+
+    /// Read the blocks of pcapng until a CAN packet is found, `None` if the capture is ended.
+    fn next_pcapng(&mut self) -> Result<Option<CanMessage<C>>, CanError> {
+        loop {
+            let mut header = [0u8; 8];
+            if !read_or_eof(&mut self.reader, &mut header)? {
+                return Ok(None);
+            }
+            if u32::from_le_bytes([header[0], header[1], header[2], header[3]]) == SECTION_HEADER_BLOCK {
+                self.read_section()?;
+                continue;
+            }
+
+            let kind = self.u32(&header, 0);
+            let length = self.u32(&header, 4) as usize;
+            if length < 12 || !length.is_multiple_of(4) {
+                return Err(format_error(format!("invalid block length: {}", length)));
+            }
+            let mut body = vec![0u8; length - 8];
+            self.reader.read_exact(&mut body).map_err(io_error)?;
+            body.truncate(length - 12);
+
+            match kind {
+                INTERFACE_DESCRIPTION_BLOCK => {
+                    if body.len() < 8 {
+                        return Err(format_error("invalid interface description block"));
+                    }
+                    let mut interface = Interface {
+                        link_type: self.u16(&body, 0),
+                        name: None,
+                        tsresol: 6,
+                        tsoffset: 0,
+                    };
+                    for (code, value) in self.options(&body[8..]) {
+                        match code {
+                            IF_NAME => interface.name = Some(String::from_utf8_lossy(value).trim_end_matches('\0').to_owned()),
+                            IF_TSRESOL if !value.is_empty() => interface.tsresol = value[0],
+                            IF_TSOFFSET if value.len() == 8 => interface.tsoffset = self.u64(value, 0) as i64,
+                            _ => {},
+                        }
+                    }
+                    self.interfaces.push(interface);
+                },
+                ENHANCED_PACKET_BLOCK => {
+                    if body.len() < 20 {
+                        return Err(format_error("invalid enhanced packet block"));
+                    }
+                    let index = self.u32(&body, 0) as usize;
+                    let ticks = ((self.u32(&body, 4) as u64) << 32) | self.u32(&body, 8) as u64;
+                    let len = self.u32(&body, 12) as usize;
+                    let Some(data) = body.get(20..20 + len) else {
+                        return Err(format_error("invalid enhanced packet block"));
+                    };
+                    let interface = self.interfaces.get(index)
+                        .ok_or_else(|| format_error(format!("unknown interface: {}", index)))?;
+                    if interface.link_type != LINKTYPE_CAN_SOCKETCAN || is_can_xl(data) {
+                        continue;
+                    }
+
+                    let offset = 20 + len.div_ceil(4) * 4;
+                    let flags = body.get(offset..)
+                        .map(|v| self.options(v))
+                        .unwrap_or_default()
+                        .into_iter()
+                        .find(|(code, value)| *code == EPB_FLAGS && value.len() == 4)
+                        .map(|(_, value)| self.u32(value, 0))
+                        .unwrap_or_default();
+                    let direct = match flags & 0x03 {
+                        EPB_OUTBOUND => Direct::Transmit,
+                        _ => Direct::Receive,
+                    };
+                    let channel = match &interface.name {
+                        Some(name) => C::from_name(name),
+                        None => C::from_index(index as u8),
+                    };
+                    let nanos = to_nanos(ticks, interface.tsresol) as i128 + interface.tsoffset as i128 * 1_000_000_000;
+
+                    let mut msg = decode(data)?;
+                    msg.set_timestamp(Some(nanos.max(0) as u64))
+                        .set_time_base(TimeBase::WallClock)
+                        .set_channel(channel)
+                        .set_direct(direct);
+                    return Ok(Some(msg));
+                },
+                SIMPLE_PACKET_BLOCK => {
+                    let interface = self.interfaces.first()
+                        .ok_or_else(|| format_error("unknown interface: 0"))?;
+                    let Some(data) = body.get(4..) else { continue; };
+                    let len = (self.u32(&body, 0) as usize).min(data.len());
+                    if interface.link_type != LINKTYPE_CAN_SOCKETCAN || is_can_xl(&data[..len]) {
+                        continue;
+                    }
+                    let channel = match &interface.name {
+                        Some(name) => C::from_name(name),
+                        None => C::from_index(0),
+                    };
+                    let mut msg = decode(&data[..len])?;
+                    msg.set_channel(channel)
+                        .set_direct(Direct::Receive);
+                    return Ok(Some(msg));
+                },
+                // the statistics, name resolution and others
+                _ => {},
+            }
+        }
+    }
+
+    /// Read the rest of section header block, the interfaces are reset.
+    fn read_section(&mut self) -> Result<(), CanError> {
+        let mut header = [0u8; 8];
+        self.reader.read_exact(&mut header).map_err(io_error)?;
+        self.big_endian = match u32::from_le_bytes([header[4], header[5], header[6], header[7]]) {
+            BYTE_ORDER_MAGIC => false,
+            v if v.swap_bytes() == BYTE_ORDER_MAGIC => true,
+            _ => return Err(format_error("invalid byte-order magic")),
+        };
+        let length = self.u32(&header, 0) as usize;
+        if length < 28 || !length.is_multiple_of(4) {
+            return Err(format_error(format!("invalid section header length: {}", length)));
+        }
+        let mut body = vec![0u8; length - 12];
+        self.reader.read_exact(&mut body).map_err(io_error)?;
+        self.interfaces.clear();
+
+        Ok(())
+    }
+
+    /// The options of block, the values are without padding.
+    fn options<'a>(&self, mut data: &'a [u8]) -> Vec<(u16, &'a [u8])> {
+        let mut result = Vec::new();
+        while data.len() >= 4 {
+            let code = self.u16(data, 0);
+            let len = self.u16(data, 2) as usize;
+            if code == OPT_END {
+                break;
+            }
+            let Some(value) = data.get(4..4 + len) else { break; };
+            result.push((code, value));
+            data = data.get(4 + len.div_ceil(4) * 4..).unwrap_or_default();
+        }
+        result
+    }
+
+    #[inline]
+    fn u16(&self, data: &[u8], offset: usize) -> u16 {
+        let bytes = [data[offset], data[offset + 1]];
+        if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) }
+    }
+
+    #[inline]
+    fn u32(&self, data: &[u8], offset: usize) -> u32 {
+        let bytes = [data[offset], data[offset + 1], data[offset + 2], data[offset + 3]];
+        if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) }
+    }
+
+    #[inline]
+    fn u64(&self, data: &[u8], offset: usize) -> u64 {
+        let (first, second) = (self.u32(data, offset) as u64, self.u32(data, offset + 4) as u64);
+        if self.big_endian { (first << 32) | second } else { (second << 32) | first }
+    }
+}
+
+impl<R: Read, C: LogChannel> Iterator for PcapReader<R, C> {
+    type Item = Result<CanMessage<C>, CanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.format {
+            PcapFormat::Pcap => self.next_pcap(),
+            PcapFormat::Pcapng => self.next_pcapng(),
+        }.transpose()
+    }
+}
+
+/// Write the frames as pcap or pcapng capture.
+pub struct PcapWriter<W: Write> {
+    writer: W,
+    format: PcapFormat,
+    /// The interface ID of channel name.
+    interfaces: HashMap<String, u32>,
+}
+
+impl PcapWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P, format: PcapFormat) -> Result<Self, CanError> {
+        let file = File::create(path).map_err(io_error)?;
+        Self::new(BufWriter::new(file), format)
+    }
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// Write the pcap header or the section header of pcapng.
+    pub fn new(mut writer: W, format: PcapFormat) -> Result<Self, CanError> {
+        let header = match format {
+            PcapFormat::Pcap => {
+                let mut header = Vec::with_capacity(PCAP_HEADER_SIZE);
+                header.extend(PCAP_MAGIC_NANOS.to_le_bytes());
+                header.extend(2u16.to_le_bytes());
+                header.extend(4u16.to_le_bytes());
+                // the time zone and accuracy of timestamps
+                header.extend([0; 8]);
+                header.extend(SNAPLEN.to_le_bytes());
+                header.extend((LINKTYPE_CAN_SOCKETCAN as u32).to_le_bytes());
+                header
+            },
+            PcapFormat::Pcapng => {
+                let mut body = Vec::new();
+                body.extend(BYTE_ORDER_MAGIC.to_le_bytes());
+                body.extend(1u16.to_le_bytes());
+                body.extend(0u16.to_le_bytes());
+                // the section length is not specified
+                body.extend(u64::MAX.to_le_bytes());
+                push_option(&mut body, SHB_USERAPPL, format!("rs-can {}", env!("CARGO_PKG_VERSION")).as_bytes());
+                push_option(&mut body, OPT_END, &[]);
+                block(SECTION_HEADER_BLOCK, &body)
+            },
+        };
+        writer.write_all(&header).map_err(io_error)?;
+
+        Ok(Self { writer, format, interfaces: Default::default() })
+    }
+
+    /// Write a frame, the interface block of pcapng is written when a new channel is found.
+    pub fn write<C: LogChannel>(&mut self, msg: &CanMessage<C>) -> Result<(), CanError> {
+        let packet = encode(msg);
+        let nanos = msg.timestamp();
+        match self.format {
+            PcapFormat::Pcap => {
+                let mut data = Vec::with_capacity(PCAP_RECORD_HEADER_SIZE + packet.len());
+                data.extend(((nanos / 1_000_000_000) as u32).to_le_bytes());
+                data.extend(((nanos % 1_000_000_000) as u32).to_le_bytes());
+                data.extend((packet.len() as u32).to_le_bytes());
+                data.extend((packet.len() as u32).to_le_bytes());
+                data.extend(packet);
+                self.writer.write_all(&data).map_err(io_error)
+            },
+            PcapFormat::Pcapng => {
+                let index = self.interface(&msg.channel().name())?;
+                let mut body = Vec::with_capacity(packet.len() + 32);
+                body.extend(index.to_le_bytes());
+                body.extend(((nanos >> 32) as u32).to_le_bytes());
+                body.extend((nanos as u32).to_le_bytes());
+                body.extend((packet.len() as u32).to_le_bytes());
+                body.extend((packet.len() as u32).to_le_bytes());
+                body.extend(&packet);
+                body.resize(body.len().div_ceil(4) * 4, 0);
+                let flags = match msg.direct() {
+                    Direct::Transmit => EPB_OUTBOUND,
+                    Direct::Receive => EPB_INBOUND,
+                };
+                push_option(&mut body, EPB_FLAGS, &flags.to_le_bytes());
+                push_option(&mut body, OPT_END, &[]);
+                self.writer.write_all(&block(ENHANCED_PACKET_BLOCK, &body))
+                    .map_err(io_error)
+            },
+        }
+    }
+
+    #[inline]
+    pub fn flush(&mut self) -> Result<(), CanError> {
+        self.writer.flush().map_err(io_error)
+    }
+
+    /// Write the interface block of channel(pcapng only) before any frame of it.
+    pub fn add_interface(&mut self, name: &str) -> Result<(), CanError> {
+        if self.format == PcapFormat::Pcapng {
+            self.interface(name)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the statistics block of each interface(pcapng only) without any counter,
+    /// it also probes the closed pipe when no frame is written.
+    pub fn write_statistics(&mut self, timestamp: u64) -> Result<(), CanError> {
+        if self.format != PcapFormat::Pcapng {
+            return Ok(());
+        }
+
+        for index in 0..self.interfaces.len() as u32 {
+            let mut body = Vec::with_capacity(12);
+            body.extend(index.to_le_bytes());
+            body.extend(((timestamp >> 32) as u32).to_le_bytes());
+            body.extend((timestamp as u32).to_le_bytes());
+            self.writer.write_all(&block(INTERFACE_STATISTICS_BLOCK, &body))
+                .map_err(io_error)?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the interface ID of channel, the interface block is written if it is not found.
+    fn interface(&mut self, name: &str) -> Result<u32, CanError> {
+        if let Some(index) = self.interfaces.get(name) {
+            return Ok(*index);
+        }
+
+        let index = self.interfaces.len() as u32;
+        let mut body = Vec::new();
+        body.extend(LINKTYPE_CAN_SOCKETCAN.to_le_bytes());
+        body.extend(0u16.to_le_bytes());
+        body.extend(SNAPLEN.to_le_bytes());
+        push_option(&mut body, IF_NAME, name.as_bytes());
+        push_option(&mut body, IF_TSRESOL, &[9]);
+        push_option(&mut body, OPT_END, &[]);
+        self.writer.write_all(&block(INTERFACE_DESCRIPTION_BLOCK, &body))
+            .map_err(io_error)?;
+        self.interfaces.insert(name.to_owned(), index);
+
+        Ok(index)
+    }
+}
+
+/// Convert the frame to SocketCAN `can_frame` or `canfd_frame`, the identifier is big-endian.
+pub fn encode<C: LogChannel>(msg: &CanMessage<C>) -> Vec<u8> {
+    let mut can_id = match msg.is_extended() {
+        true => msg.id().as_raw() | CAN_EFF_FLAG,
+        false => msg.id().as_raw(),
+    };
+    if msg.is_remote() {
+        can_id |= CAN_RTR_FLAG;
+    }
+    if msg.is_error_frame() {
+        can_id = (msg.id().as_raw() & EFF_MASK) | CAN_ERR_FLAG;
+    }
+
+    let size = if msg.is_can_fd() { CANFD_FRAME_MAX_SIZE } else { CAN_FRAME_MAX_SIZE };
+    let mut result = vec![0u8; CAN_HEADER_SIZE + size];
+    result[..4].copy_from_slice(&can_id.to_be_bytes());
+    result[4] = msg.length() as u8;
+    if msg.is_can_fd() {
+        result[5] = CANFD_FDF
+            | if msg.is_bitrate_switch() { CANFD_BRS } else { 0 }
+            | if msg.is_esi() { CANFD_ESI } else { 0 };
+    }
+    if !msg.is_remote() {
+        let data = msg.data();
+        let len = data.len().min(size);
+        result[CAN_HEADER_SIZE..CAN_HEADER_SIZE + len].copy_from_slice(&data[..len]);
+    }
+
+    result
+}
+
+/// Convert the SocketCAN `can_frame` or `canfd_frame` to frame.
+pub fn decode<C: LogChannel>(data: &[u8]) -> Result<CanMessage<C>, CanError> {
+    if data.len() < CAN_HEADER_SIZE {
+        return Err(format_error("the packet is too short"));
+    }
+    let raw = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+    let len = data[4] as usize;
+    let flags = data[5];
+    let is_fd = flags & CANFD_FDF > 0 || data.len() == CAN_HEADER_SIZE + CANFD_FRAME_MAX_SIZE;
+    let is_error = raw & CAN_ERR_FLAG > 0;
+    let extended = raw & CAN_EFF_FLAG > 0;
+    let id = match (is_error, extended) {
+        (true, _) => Id::from_bits(raw & EFF_MASK, false),
+        (false, true) => Id::from_bits(raw & EFF_MASK, true),
+        (false, false) => Id::from_bits(raw & SFF_MASK, false),
+    };
+
+    let msg = if raw & CAN_RTR_FLAG > 0 && !is_fd && !is_error {
+        CanMessage::new_remote(id, len)
+    }
+    else {
+        data.get(CAN_HEADER_SIZE..CAN_HEADER_SIZE + len)
+            .and_then(|v| CanMessage::new(id, v))
+    };
+    let mut msg = msg.ok_or_else(|| format_error(format!("invalid SocketCAN frame of length: {}", len)))?;
+    msg.set_can_fd(is_fd)
+        .set_bitrate_switch(is_fd && flags & CANFD_BRS > 0)
+        .set_esi(is_fd && flags & CANFD_ESI > 0)
+        .set_error_frame(is_error);
+
+    Ok(msg)
+}
+
+/// The CAN XL frames are not supported.
+#[inline]
+fn is_can_xl(data: &[u8]) -> bool {
+    data.get(4).is_some_and(|v| v & CANXL_XLF > 0)
+}
+
+/// Convert the ticks of `if_tsresol` to nanoseconds.
+fn to_nanos(ticks: u64, tsresol: u8) -> u64 {
+    let value = tsresol & 0x7F;
+    if tsresol & 0x80 > 0 {
+        return ((ticks as u128 * 1_000_000_000) >> value.min(127)) as u64;
+    }
+    match value {
+        ..=9 => ticks.saturating_mul(10u64.pow(9 - value as u32)),
+        _ => ticks / 10u64.checked_pow(value as u32 - 9).unwrap_or(u64::MAX),
+    }
+}
+
+/// Build a block of pcapng little-endian, the body is padded to 4 bytes.
+fn block(kind: u32, body: &[u8]) -> Vec<u8> {
+    let padded = body.len().div_ceil(4) * 4;
+    let length = (padded + 12) as u32;
+    let mut result = Vec::with_capacity(length as usize);
+    result.extend(kind.to_le_bytes());
+    result.extend(length.to_le_bytes());
+    result.extend(body);
+    result.resize(padded + 8, 0);
+    result.extend(length.to_le_bytes());
+    result
+}
+
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend(code.to_le_bytes());
+    body.extend((value.len() as u16).to_le_bytes());
+    body.extend(value);
+    body.resize(body.len().div_ceil(4) * 4, 0);
+}
+
+/// Fill the buffer, `false` if the reader is ended before any byte is read.
+fn read_or_eof<R: Read>(reader: &mut R, buffer: &mut [u8]) -> Result<bool, CanError> {
+    let mut count = 0;
+    while count < buffer.len() {
+        match reader.read(&mut buffer[count..]) {
+            Ok(0) if count == 0 => return Ok(false),
+            Ok(0) => return Err(format_error("unexpected end of file")),
+            Ok(n) => count += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(io_error(e)),
+        }
+    }
+    Ok(true)
+}
+
+#[inline]
+fn format_error<T: AsRef<str>>(reason: T) -> CanError {
+    CanError::FrameConvertFailed(format!("invalid capture: {}", reason.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use isotp_rs::can::{frame::{Direct, Frame}, identifier::Id};
+    use crate::CanMessage;
+    use super::{to_nanos, PcapFormat, PcapReader, PcapWriter};
+
+    #[test]
+    fn test_pcap() -> anyhow::Result<()> {
+        let start = 1_681_312_731_612_345_678;
+        let mut frames = Vec::new();
+        for i in 0..100u64 {
+            let mut msg = match i % 4 {
+                0 => CanMessage::new(Id::from(0x7DF), &[0x02, 0x3E, i as u8]),
+                1 => CanMessage::new(Id::from_bits(0x18DA00F1, true), &[i as u8; 20]),
+                2 => CanMessage::new_remote(Id::from(0x100), 4),
+                _ => CanMessage::new(Id::from(0x7E8), &[0x03, 0x7F, 0x3E, 0x11]),
+            }.unwrap();
+            msg.set_timestamp(Some(start + i * 1_000_123))
+                .set_channel(format!("can{}", i % 2))
+                .set_direct(if i % 3 == 0 { Direct::Transmit } else { Direct::Receive });
+            match i % 4 {
+                1 => { msg.set_bitrate_switch(true); },
+                3 => { msg.set_error_frame(true).set_direct(Direct::Receive); },
+                _ => {},
+            }
+            frames.push(msg);
+        }
+
+        for format in [PcapFormat::Pcap, PcapFormat::Pcapng] {
+            let mut buffer = Vec::new();
+            let mut writer = PcapWriter::new(&mut buffer, format)?;
+            writer.add_interface("can0")?;
+            for (i, frame) in frames.iter().enumerate() {
+                writer.write(frame)?;
+                if i == 50 {
+                    writer.write_statistics(frame.timestamp())?;
+                }
+            }
+            writer.flush()?;
+
+            let mut reader = PcapReader::<_, String>::new(Cursor::new(buffer))?;
+            assert_eq!(reader.format(), format);
+            let result = reader.by_ref().collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(result, frames);
+            for (r, f) in result.iter().zip(&frames) {
+                assert_eq!(r.timestamp(), f.timestamp());
+                assert_eq!(r.is_can_fd(), f.is_can_fd());
+                assert_eq!(r.is_bitrate_switch(), f.is_bitrate_switch());
+                assert_eq!(r.is_error_frame(), f.is_error_frame());
+                if format == PcapFormat::Pcapng {
+                    assert_eq!(r.channel(), f.channel());
+                    assert_eq!(r.direct(), f.direct());
+                }
+            }
+            assert_eq!(reader.interfaces.len(), if format == PcapFormat::Pcapng { 2 } else { 0 });
+        }
+
+        assert!(PcapReader::<_, u8>::new(Cursor::new([0u8; 24])).is_err());
+        assert_eq!(to_nanos(1_500, 6), 1_500_000);
+        assert_eq!(to_nanos(1 << 20, 0x80 | 20), 1_000_000_000);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,564 @@
+//! The PEAK PCAN-View trace(`.trc`), the file versions 1.0 to 2.1 are read, and 1.1 to 2.1 are written.
+//!
+//! The CAN-FD frames are only supported since version 2.0, and the bus of file starts at 1.
+
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+use isotp_rs::can::frame::{Direct, Frame};
+use isotp_rs::can::identifier::Id;
+use crate::{CanMessage, TimeBase};
+use crate::error::CanError;
+use crate::utils::{dlc_to_len, len_to_dlc, system_timestamp};
+use super::{io_error, parse_error, DateTime, LogChannel};
+
+/// The days between 1899-12-30(`$STARTTIME` epoch) and 1970-01-01.
+const STARTTIME_OFFSET: f64 = 25_569.;
+const MILLIS_PER_DAY: f64 = 86_400_000.;
+const NANOS_PER_MILLI: u64 = 1_000_000;
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+pub enum TrcVersion {
+    V1_0,
+    V1_1,
+    V1_2,
+    V1_3,
+    V2_0,
+    #[default]
+    V2_1,
+}
+
+impl TrcVersion {
+    fn from_str(value: &str) -> Option<Self> {
+        match value.trim() {
+            "1.0" => Some(Self::V1_0),
+            "1.1" => Some(Self::V1_1),
+            "1.2" => Some(Self::V1_2),
+            "1.3" => Some(Self::V1_3),
+            "2.0" => Some(Self::V2_0),
+            "2.1" => Some(Self::V2_1),
+            _ => None,
+        }
+    }
+
+    /// The default `$COLUMNS` of version 2.x.
+    fn columns(&self) -> &'static str {
+        match self {
+            Self::V2_1 => "N,O,T,B,I,d,R,L,D",
+            _ => "N,O,T,I,d,l,D",
+        }
+    }
+}
+
+impl Display for TrcVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::V1_0 => "1.0",
+            Self::V1_1 => "1.1",
+            Self::V1_2 => "1.2",
+            Self::V1_3 => "1.3",
+            Self::V2_0 => "2.0",
+            Self::V2_1 => "2.1",
+        })
+    }
+}
+
+/// Read the frames of TRC file, the status and event records are skipped.
+pub struct TrcReader<R, C> {
+    reader: R,
+    line: usize,
+    version: TrcVersion,
+    columns: Vec<char>,
+    /// The start time(ns since UNIX epoch) of measurement.
+    start: u64,
+    buffer: String,
+    _channel: PhantomData<C>,
+}
+
+impl<C: LogChannel> TrcReader<BufReader<File>, C> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, CanError> {
+        let file = File::open(path).map_err(io_error)?;
+        Ok(Self::new(BufReader::new(file)))
+    }
+}
+
+impl<R: BufRead, C: LogChannel> TrcReader<R, C> {
+    /// The version is 1.0 unless `$FILEVERSION` is found.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: Default::default(),
+            version: TrcVersion::V1_0,
+            columns: Default::default(),
+            start: Default::default(),
+            buffer: Default::default(),
+            _channel: Default::default(),
+        }
+    }
+
+    #[inline]
+    pub fn version(&self) -> TrcVersion {
+        self.version
+    }
+
+    /// The start time(ns since UNIX epoch) of measurement, 0 if the `$STARTTIME` is not parsed yet.
+    #[inline]
+    pub fn start_time(&self) -> u64 {
+        self.start
+    }
+
+    fn parse_header(&mut self, content: &str) {
+        let Some((key, value)) = content.trim_start_matches(';').trim().split_once('=') else { return; };
+        match key {
+            "$FILEVERSION" => {
+                if let Some(version) = TrcVersion::from_str(value) {
+                    self.version = version;
+                }
+            },
+            "$STARTTIME" => {
+                if let Ok(days) = value.trim().parse::<f64>() {
+                    self.start = ((days - STARTTIME_OFFSET) * MILLIS_PER_DAY).round().max(0.) as u64 * NANOS_PER_MILLI;
+                }
+            },
+            "$COLUMNS" => {
+                self.columns = value.split(',')
+                    .filter_map(|v| v.trim().chars().next())
+                    .collect();
+            },
+            _ => {},
+        }
+    }
+
+    /// `Ok(None)` if the record is not a frame.
+    fn parse_record(&self, content: &str) -> Result<Option<CanMessage<C>>, ()> {
+        let tokens = content.split_whitespace().collect::<Vec<_>>();
+        let record = if self.version >= TrcVersion::V2_0 {
+            self.parse_v2(&tokens)
+        }
+        else {
+            self.parse_v1(&tokens)
+        }?;
+
+        Ok(record.map(|(offset, mut msg)| {
+            // the offset is milliseconds, and the resolution is microsecond at most
+            msg.set_timestamp(Some(self.start + (offset * 1000.).round().max(0.) as u64 * 1000))
+                .set_time_base(TimeBase::WallClock);
+            msg
+        }))
+    }
+
+    /// `N) O [B] [T] I [-] L D...`
+    fn parse_v1(&self, tokens: &[&str]) -> Result<Option<(f64, CanMessage<C>)>, ()> {
+        let mut tokens = tokens.iter().copied();
+        tokens.next().filter(|v| v.ends_with(')')).ok_or(())?;
+        let offset = tokens.next().and_then(|v| v.parse::<f64>().ok()).ok_or(())?;
+        let channel = match self.version {
+            TrcVersion::V1_2 | TrcVersion::V1_3 => parse_bus(tokens.next())?,
+            _ => Default::default(),
+        };
+        let (direct, is_error) = match self.version {
+            TrcVersion::V1_0 => (Direct::Receive, false),
+            _ => match tokens.next().ok_or(())? {
+                "Rx" => (Direct::Receive, false),
+                "Tx" => (Direct::Transmit, false),
+                "Error" => (Direct::Receive, true),
+                // the warning and other records
+                _ => return Ok(None),
+            },
+        };
+        let id = parse_id(tokens.next())?;
+        if self.version == TrcVersion::V1_3 {
+            tokens.next();
+        }
+        let len = tokens.next().and_then(|v| v.parse::<u8>().ok()).ok_or(())?;
+        let len = dlc_to_len(len, false);
+        let data = tokens.collect::<Vec<_>>();
+
+        let mut msg = if data.first() == Some(&"RTR") {
+            CanMessage::new_remote(id, len)
+        }
+        else {
+            CanMessage::new(id, &parse_data(data.get(..len))?)
+        }.ok_or(())?;
+        msg.set_channel(channel)
+            .set_direct(direct)
+            .set_error_frame(is_error);
+
+        Ok(Some((offset, msg)))
+    }
+
+    /// The columns are decided by `$COLUMNS`.
+    fn parse_v2(&self, tokens: &[&str]) -> Result<Option<(f64, CanMessage<C>)>, ()> {
+        let columns = match self.columns.is_empty() {
+            true => self.version.columns().split(',').filter_map(|v| v.chars().next()).collect(),
+            false => self.columns.clone(),
+        };
+        let column = |name: char| columns.iter()
+            .position(|v| *v == name)
+            .and_then(|i| tokens.get(i).copied());
+
+        let (is_fd, brs, esi) = match column('T').ok_or(())? {
+            "DT" | "RR" | "ER" => (false, false, false),
+            "FD" => (true, false, false),
+            "FB" => (true, true, false),
+            "FE" => (true, false, true),
+            "BI" => (true, true, true),
+            // the status, error counter and event records
+            _ => return Ok(None),
+        };
+        let kind = column('T').ok_or(())?;
+        let offset = column('O').and_then(|v| v.parse::<f64>().ok()).ok_or(())?;
+        let channel = match column('B') {
+            Some(v) => parse_bus(Some(v))?,
+            None => Default::default(),
+        };
+        let direct = match column('d') {
+            Some("Tx") => Direct::Transmit,
+            _ => Direct::Receive,
+        };
+        let id = match column('I') {
+            Some("-") if kind == "ER" => Id::from_bits(0, false),
+            v => parse_id(v)?,
+        };
+        let len = match (column('L'), column('l')) {
+            (Some(v), _) => dlc_to_len(v.parse::<u8>().map_err(|_| ())?, is_fd),
+            (None, Some(v)) => v.parse::<usize>().map_err(|_| ())?,
+            _ => return Err(()),
+        };
+        let data = columns.iter()
+            .position(|v| *v == 'D')
+            .and_then(|i| tokens.get(i..))
+            .unwrap_or_default();
+
+        let mut msg = if kind == "RR" {
+            CanMessage::new_remote(id, len)
+        }
+        else {
+            CanMessage::new(id, &parse_data(data.get(..len))?)
+        }.ok_or(())?;
+        msg.set_can_fd(is_fd)
+            .set_bitrate_switch(brs)
+            .set_esi(esi)
+            .set_error_frame(kind == "ER")
+            .set_channel(channel)
+            .set_direct(direct);
+
+        Ok(Some((offset, msg)))
+    }
+}
+
+impl<R: BufRead, C: LogChannel> Iterator for TrcReader<R, C> {
+    type Item = Result<CanMessage<C>, CanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.buffer.clear();
+            match self.reader.read_line(&mut self.buffer) {
+                Ok(0) => return None,
+                Ok(_) => self.line += 1,
+                Err(e) => return Some(Err(io_error(e))),
+            }
+
+            let content = std::mem::take(&mut self.buffer);
+            let result = match content.trim() {
+                "" => None,
+                v if v.starts_with(';') => {
+                    self.parse_header(v);
+                    None
+                },
+                v => match self.parse_record(v) {
+                    Ok(Some(msg)) => Some(Ok(msg)),
+                    Ok(None) => None,
+                    Err(_) => Some(Err(parse_error(self.line, v))),
+                },
+            };
+            self.buffer = content;
+
+            if result.is_some() {
+                return result;
+            }
+        }
+    }
+}
+
+/// Write frames as TRC file, the time offsets are relative to the first frame.
+pub struct TrcWriter<W: Write> {
+    writer: W,
+    version: TrcVersion,
+    start: Option<u64>,
+    count: usize,
+    finished: bool,
+}
+
+impl TrcWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P, version: TrcVersion) -> Result<Self, CanError> {
+        let file = File::create(path).map_err(io_error)?;
+        Self::new(BufWriter::new(file), version)
+    }
+}
+
+impl<W: Write> TrcWriter<W> {
+    /// The version 1.0 is not supported.
+    pub fn new(writer: W, version: TrcVersion) -> Result<Self, CanError> {
+        if version == TrcVersion::V1_0 {
+            return Err(CanError::OperationError(format!("TRC version: {} is not supported", version)));
+        }
+        Ok(Self { writer, version, start: None, count: 0, finished: false })
+    }
+
+    pub fn write<C: LogChannel>(&mut self, msg: &CanMessage<C>) -> Result<(), CanError> {
+        if msg.is_can_fd() && self.version < TrcVersion::V2_0 {
+            return Err(CanError::FrameConvertFailed(format!("CAN-FD frame is not supported by TRC version: {}", self.version)));
+        }
+        let timestamp = msg.timestamp();
+        if self.start.is_none() {
+            self.write_header(timestamp)?;
+        }
+        let start = self.start.unwrap_or_default();
+        self.count += 1;
+
+        let offset = timestamp.saturating_sub(start) as f64 / NANOS_PER_MILLI as f64;
+        let bus = msg.channel().index() as u32 + 1;
+        let direct = match msg.direct() {
+            Direct::Transmit => "Tx",
+            Direct::Receive => "Rx",
+        };
+        let id = match msg.is_extended() {
+            true => format!("{:08X}", msg.id().as_raw()),
+            false => format!("{:04X}", msg.id().as_raw()),
+        };
+        let data = if msg.is_remote() && self.version < TrcVersion::V2_0 {
+            String::from("RTR")
+        }
+        else if msg.is_remote() {
+            String::new()
+        }
+        else {
+            msg.data().iter()
+                .map(|v| format!("{:02X}", v))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        let record = match self.version {
+            TrcVersion::V1_0 | TrcVersion::V1_1 | TrcVersion::V1_2 | TrcVersion::V1_3 => {
+                let kind = if msg.is_error_frame() { "Error" } else { direct };
+                let bus = match self.version {
+                    TrcVersion::V1_2 | TrcVersion::V1_3 => format!(" {}", bus),
+                    _ => String::new(),
+                };
+                let reserved = if self.version == TrcVersion::V1_3 { " -" } else { "" };
+                format!("{:>6}) {:>11.1}{} {:<5} {:>8}{}  {}  {}",
+                        self.count, offset, bus, kind, id, reserved, msg.length(), data)
+            },
+            TrcVersion::V2_0 | TrcVersion::V2_1 => {
+                let kind = match (msg.is_error_frame(), msg.is_remote(), msg.is_can_fd(), msg.is_bitrate_switch(), msg.is_esi()) {
+                    (true, ..) => "ER",
+                    (_, true, ..) => "RR",
+                    (_, _, false, ..) => "DT",
+                    (_, _, true, false, false) => "FD",
+                    (_, _, true, true, false) => "FB",
+                    (_, _, true, false, true) => "FE",
+                    (_, _, true, true, true) => "BI",
+                };
+                match self.version {
+                    TrcVersion::V2_1 => format!("{:>7} {:>13.3} {:<2} {:>2} {:>8} {:<2} - {:<4} {}",
+                                                self.count, offset, kind, bus, id, direct, len_to_dlc(msg.length()), data),
+                    _ => format!("{:>7} {:>13.3} {:<2} {:>8} {:<2} {:<4} {}",
+                                 self.count, offset, kind, id, direct, msg.length(), data),
+                }
+            },
+        };
+
+        writeln!(self.writer, "{}", record.trim_end())
+            .map_err(io_error)
+    }
+
+    /// Write the header if no frame is written and flush, it is called when dropped.
+    pub fn finish(&mut self) -> Result<(), CanError> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        if self.start.is_none() {
+            self.write_header(system_timestamp())?;
+        }
+        self.writer.flush().map_err(io_error)
+    }
+
+    fn write_header(&mut self, start: u64) -> Result<(), CanError> {
+        // the resolution of start time is millisecond
+        let millis = start / NANOS_PER_MILLI;
+        self.start = Some(millis * NANOS_PER_MILLI);
+        let dt = DateTime::from_millis(millis);
+        let mut header = vec![
+            format!(";$FILEVERSION={}", self.version),
+            format!(";$STARTTIME={:.10}", millis as f64 / MILLIS_PER_DAY + STARTTIME_OFFSET),
+        ];
+        if self.version >= TrcVersion::V2_0 {
+            header.push(format!(";$COLUMNS={}", self.version.columns()));
+        }
+        header.push(";".into());
+        header.push(format!(";   Start time: {:02}.{:02}.{} {:02}:{:02}:{:02}.{:03}.0",
+                            dt.day, dt.month, dt.year, dt.hour, dt.minute, dt.second, dt.millis));
+        header.push(";   Generated by rs-can".into());
+        header.push(";-------------------------------------------------------------------------------".into());
+
+        writeln!(self.writer, "{}", header.join("\n"))
+            .map_err(io_error)
+    }
+}
+
+impl<W: Write> Drop for TrcWriter<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            log::warn!("{}", e);
+        }
+    }
+}
+
+#[inline]
+fn parse_bus<C: LogChannel>(value: Option<&str>) -> Result<C, ()> {
+    let bus = value.and_then(|v| v.parse::<u8>().ok()).ok_or(())?;
+    Ok(C::from_index(bus.saturating_sub(1)))
+}
+
+/// The identifier is extended when it has 8 digits.
+#[inline]
+fn parse_id(value: Option<&str>) -> Result<Id, ()> {
+    let value = value.ok_or(())?;
+    let id = u32::from_str_radix(value, 16).map_err(|_| ())?;
+    Ok(Id::from_bits(id, value.len() > 4))
+}
+
+#[inline]
+fn parse_data(values: Option<&[&str]>) -> Result<Vec<u8>, ()> {
+    values.ok_or(())?
+        .iter()
+        .map(|v| u8::from_str_radix(v, 16).map_err(|_| ()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use isotp_rs::can::{frame::{Direct, Frame}, identifier::Id};
+    use crate::CanMessage;
+    use super::{TrcReader, TrcVersion, TrcWriter};
+
+    const TRC_V1_1: &str = r#";$FILEVERSION=1.1
+;$STARTTIME=42209.4075405092
+;
+;   Start time: 24.07.2015 09:46:51.5
+;---+--   ----+----  --+--  ----+---  +  -+ -- -- -- -- -- -- --
+     1)      1841.0  Rx         0001  8  00 00 00 00 00 00 00 00
+     2)      1842.4  Tx     18DA00F1  3  02 10 03
+     3)      1843.0  Warng  FFFFFFFF  4  00 00 00 08  BUSHEAVY
+     4)      1844.0  Rx         0100  2  RTR
+"#;
+
+    const TRC_V2_1: &str = r#";$FILEVERSION=2.1
+;$STARTTIME=43008.920986006946
+;$COLUMNS=N,O,T,B,I,d,R,L,D
+;
+      1        17.123 DT  1     0300 Rx -  8    00 00 00 00 04 00 00 00
+      2        18.000 FB  2 18DA00F1 Tx -  9    00 01 02 03 04 05 06 07 08 09 0A 0B
+      3        19.000 ST  1     Rx    00 00 00 08
+      4        20.000 RR  1     0123 Rx -  2
+"#;
+
+    #[test]
+    fn test_read() -> anyhow::Result<()> {
+        let mut reader = TrcReader::<_, u8>::new(Cursor::new(TRC_V1_1));
+        let frames = reader.by_ref().collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(reader.version(), TrcVersion::V1_1);
+        let start = reader.start_time();
+        assert_eq!(start, 1_437_731_211_500_000_000);
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0], CanMessage::new(Id::from(0x001), &[0x00; 8]).unwrap());
+        assert_eq!(frames[0].timestamp(), start + 1_841_000_000);
+        assert_eq!(frames[1].id(), Id::from_bits(0x18DA00F1, true));
+        assert_eq!(frames[1].direct(), Direct::Transmit);
+        assert!(frames[2].is_remote());
+
+        let mut reader = TrcReader::<_, u8>::new(Cursor::new(TRC_V2_1));
+        let frames = reader.by_ref().collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(reader.version(), TrcVersion::V2_1);
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].timestamp(), reader.start_time() + 17_123_000);
+        assert!(frames[1].is_can_fd());
+        assert!(frames[1].is_bitrate_switch());
+        assert_eq!(frames[1].channel(), 1);
+        assert_eq!(frames[1].length(), 12);
+        assert!(frames[2].is_remote());
+        assert_eq!(frames[2].length(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write() -> anyhow::Result<()> {
+        let start = 1_681_312_731_612_000_000;
+        let mut frames = Vec::new();
+        let mut msg = CanMessage::new(Id::from(0x7DF), &[0x02, 0x3E, 0x00]).unwrap();
+        msg.set_timestamp(Some(start))
+            .set_channel(1);
+        frames.push(msg);
+        let mut msg = CanMessage::new_remote(Id::from_bits(0x18DA00F1, true), 3).unwrap();
+        msg.set_timestamp(Some(start + 10_100_000))
+            .set_direct(Direct::Receive);
+        frames.push(msg);
+        let mut msg = CanMessage::new(Id::from(0x100), &[0x01]).unwrap();
+        msg.set_timestamp(Some(start + 20_000_000))
+            .set_error_frame(true)
+            .set_direct(Direct::Receive);
+        frames.push(msg);
+        let mut fd = CanMessage::new(Id::from(0x7E8), &[0xAA; 16]).unwrap();
+        fd.set_timestamp(Some(start + 30_123_000))
+            .set_esi(true)
+            .set_direct(Direct::Receive);
+
+        for version in [TrcVersion::V1_1, TrcVersion::V1_2, TrcVersion::V1_3, TrcVersion::V2_0, TrcVersion::V2_1] {
+            let mut buffer = Vec::new();
+            {
+                let mut writer = TrcWriter::new(&mut buffer, version)?;
+                for frame in &frames {
+                    writer.write(frame)?;
+                }
+                if version >= TrcVersion::V2_0 {
+                    writer.write(&fd)?;
+                }
+                else {
+                    assert!(writer.write(&fd).is_err());
+                }
+            }
+
+            let mut reader = TrcReader::<_, u8>::new(Cursor::new(buffer));
+            let result = reader.by_ref().collect::<Result<Vec<_>, _>>()?;
+            assert_eq!(reader.version(), version);
+            assert_eq!(reader.start_time(), start);
+            let expected = if version >= TrcVersion::V2_0 {
+                frames.iter().chain([&fd]).cloned().collect::<Vec<_>>()
+            }
+            else {
+                frames.clone()
+            };
+            assert_eq!(result, expected, "{}", version);
+            for (r, f) in result.iter().zip(&expected) {
+                assert_eq!(r.timestamp(), f.timestamp());
+                assert_eq!(r.direct(), f.direct());
+                assert_eq!(r.is_can_fd(), f.is_can_fd());
+                assert_eq!(r.is_esi(), f.is_esi());
+                if matches!(version, TrcVersion::V1_2 | TrcVersion::V1_3 | TrcVersion::V2_1) {
+                    assert_eq!(r.channel(), f.channel());
+                }
+            }
+        }
+
+        assert!(TrcWriter::new(Vec::new(), TrcVersion::V1_0).is_err());
+
+        Ok(())
+    }
+}
@@ -2,6 +2,24 @@
 
 mod device;
 pub use device::*;
+mod frame;
+pub use frame::*;
 
+#[cfg(feature = "async")]
+pub mod asyncio;
+pub mod bus_load;
+pub mod clock_sync;
+#[cfg(feature = "config")]
+pub mod config;
+pub mod dbc;
 pub mod error;
+pub mod error_frame;
+pub mod factory;
+pub mod filter;
+pub mod interfaces;
+pub mod io;
+pub mod notifier;
+pub mod periodic;
+pub mod replay;
+pub mod timing;
 pub mod utils;
@@ -0,0 +1,381 @@
+//! The notifier owns a background receiving loop of a bus and fans each frame out to the listeners,
+//! just like the `Notifier` of python-can. The listeners are the [`Listener`] of `isotp-rs`,
+//! so the ISO-TP clients can be registered directly.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use isotp_rs::can::frame::Frame;
+use isotp_rs::device::{Driver, Listener};
+use crate::filter::FrameFilter;
+
+pub type ListenerType<C, F> = Box<dyn Listener<C, u32, F>>;
+type Listeners<C, F> = Arc<Mutex<HashMap<String, ListenerType<C, F>>>>;
+type Callback<C, F> = Box<dyn FnMut(C, &[F]) + Send>;
+
+pub struct Notifier<D: Driver> {
+    device: D,
+    listeners: Listeners<D::C, D::F>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl<D> Notifier<D>
+where
+    D: Driver + Clone + 'static,
+    D::C: Clone + Display + 'static,
+    D::F: Frame<Channel = D::C> + 'static,
+    D::Error: Display,
+{
+    pub fn new(device: D) -> Self {
+        Self {
+            device,
+            listeners: Default::default(),
+            stop: Default::default(),
+            handle: None,
+        }
+    }
+
+    #[inline]
+    pub fn device(&self) -> &D {
+        &self.device
+    }
+
+    /// Start the receiving loop, `timeout`(ms) is passed to [`Driver::receive`] of every opened channel.
+    pub fn start(&mut self, timeout: u32) {
+        if self.handle.is_some() {
+            return;
+        }
+
+        self.stop.store(false, Ordering::Relaxed);
+        let device = self.device.clone();
+        let listeners = Arc::clone(&self.listeners);
+        let stop = Arc::clone(&self.stop);
+        self.handle = Some(std::thread::spawn(move || {
+            while !stop.load(Ordering::Relaxed) {
+                let mut received = false;
+                for channel in device.opened_channels() {
+                    match device.receive(channel.clone(), Some(timeout)) {
+                        Ok(frames) => if !frames.is_empty() {
+                            received = true;
+                            match listeners.lock() {
+                                Ok(mut v) => v.values_mut()
+                                    .for_each(|l| l.on_frame_received(channel.clone(), &frames)),
+                                Err(e) => log::warn!("RUST-CAN - mutex error: {:?} when notifying", e),
+                            }
+                        },
+                        Err(e) => log::trace!("RUST-CAN - {} when receiving from channel: {}", e, channel),
+                    }
+                }
+
+                if !received {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            }
+        }));
+    }
+
+    /// Transmit a frame and notify the listeners.
+    pub fn transmit(&self, msg: D::F, timeout: Option<u32>) -> Result<(), D::Error> {
+        let channel = msg.channel();
+        let id = msg.id().into_bits();
+        if let Ok(mut v) = self.listeners.lock() {
+            v.values_mut()
+                .for_each(|l| l.on_frame_transmitting(channel.clone(), &msg));
+        }
+        self.device.transmit(msg, timeout)?;
+        if let Ok(mut v) = self.listeners.lock() {
+            v.values_mut()
+                .for_each(|l| l.on_frame_transmitted(channel.clone(), id));
+        }
+
+        Ok(())
+    }
+
+    /// Register a listener, the previous one with the same name is replaced.
+    pub fn register_listener(&self, name: String, listener: ListenerType<D::C, D::F>) -> bool {
+        match self.listeners.lock() {
+            Ok(mut v) => {
+                v.insert(name, listener);
+                true
+            },
+            Err(e) => {
+                log::warn!("RUST-CAN - mutex error: {:?} when inserting listener", e);
+                false
+            },
+        }
+    }
+
+    pub fn unregister_listener(&self, name: String) -> bool {
+        match self.listeners.lock() {
+            Ok(mut v) => v.remove(&name).is_some(),
+            Err(e) => {
+                log::warn!("RUST-CAN - mutex error: {:?} when removing listener", e);
+                false
+            },
+        }
+    }
+
+    pub fn unregister_all(&self) -> bool {
+        match self.listeners.lock() {
+            Ok(mut v) => {
+                v.clear();
+                true
+            },
+            Err(e) => {
+                log::warn!("RUST-CAN - mutex error: {:?} when removing all listeners", e);
+                false
+            },
+        }
+    }
+
+    pub fn listener_names(&self) -> Vec<String> {
+        match self.listeners.lock() {
+            Ok(v) => v.keys().cloned().collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    /// Call back with the listener, used to get the state of listener by [`Listener::as_any`].
+    pub fn listener_callback(&self, name: &str, callback: impl FnOnce(&ListenerType<D::C, D::F>)) {
+        if let Ok(v) = self.listeners.lock() {
+            if let Some(l) = v.get(name) {
+                callback(l);
+            }
+        }
+    }
+}
+
+impl<D: Driver> Notifier<D> {
+    /// Stop the receiving loop, the listeners are kept.
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                log::warn!("RUST-CAN - the receiving thread of notifier is panicked");
+            }
+        }
+    }
+}
+
+impl<D: Driver> Drop for Notifier<D> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Call the closure with the received frames.
+pub struct CallbackListener<C, F> {
+    callback: Callback<C, F>,
+}
+
+impl<C, F> CallbackListener<C, F> {
+    pub fn new(callback: impl FnMut(C, &[F]) + Send + 'static) -> Self {
+        Self { callback: Box::new(callback) }
+    }
+}
+
+impl<C: 'static, F: 'static> Listener<C, u32, F> for CallbackListener<C, F> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn on_frame_transmitting(&mut self, _: C, _: &F) {}
+
+    fn on_frame_transmitted(&mut self, _: C, _: u32) {}
+
+    fn on_frame_received(&mut self, channel: C, frames: &[F]) {
+        (self.callback)(channel, frames)
+    }
+}
+
+/// Send the received frames to a bounded channel, the frames are dropped when it is full.
+pub struct BufferedListener<F> {
+    sender: SyncSender<F>,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl<F> BufferedListener<F> {
+    pub fn new(capacity: usize) -> (Self, Receiver<F>) {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        (Self { sender, dropped: Default::default() }, receiver)
+    }
+
+    /// The count of frames dropped because of full.
+    #[inline]
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<C: 'static, F: Clone + Send + 'static> Listener<C, u32, F> for BufferedListener<F> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn on_frame_transmitting(&mut self, _: C, _: &F) {}
+
+    fn on_frame_transmitted(&mut self, _: C, _: u32) {}
+
+    fn on_frame_received(&mut self, _: C, frames: &[F]) {
+        for frame in frames {
+            match self.sender.try_send(frame.clone()) {
+                Ok(_) | Err(TrySendError::Disconnected(_)) => {},
+                Err(TrySendError::Full(_)) => {
+                    if self.dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+                        log::warn!("RUST-CAN - the buffer of listener is full, frames are dropped");
+                    }
+                },
+            }
+        }
+    }
+}
+
+/// Write the received frames line by line with the `Display` of [`Frame`] to writer, such as `std::io::stdout()`.
+/// The output is for reading only, use [`crate::io::asc::AscWriter`] for a valid ASC file.
+pub struct LogListener<W> {
+    writer: W,
+}
+
+impl<W: Write> LogListener<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<C, F, W> Listener<C, u32, F> for LogListener<W>
+where
+    C: Display + 'static,
+    F: Frame<Channel = C> + 'static,
+    W: Write + Send + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn on_frame_transmitting(&mut self, _: C, _: &F) {}
+
+    fn on_frame_transmitted(&mut self, _: C, _: u32) {}
+
+    fn on_frame_received(&mut self, _: C, frames: &[F]) {
+        for frame in frames {
+            let frame: &dyn Frame<Channel = C> = frame;
+            if let Err(e) = writeln!(self.writer, "{}", frame) {
+                log::warn!("RUST-CAN - {} when writing log", e);
+            }
+        }
+    }
+}
+
+/// Forward the frames matched the filter(and channel if present) to the inner listener.
+pub struct FilteredListener<C, F> {
+    filter: FrameFilter,
+    channel: Option<C>,
+    inner: ListenerType<C, F>,
+}
+
+impl<C, F> FilteredListener<C, F> {
+    pub fn new(filter: FrameFilter, channel: Option<C>, inner: ListenerType<C, F>) -> Self {
+        Self { filter, channel, inner }
+    }
+
+    #[inline]
+    pub fn inner(&self) -> &ListenerType<C, F> {
+        &self.inner
+    }
+}
+
+impl<C, F> Listener<C, u32, F> for FilteredListener<C, F>
+where
+    C: PartialEq + Send + 'static,
+    F: Frame + Clone + 'static,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn on_frame_transmitting(&mut self, channel: C, frame: &F) {
+        if self.channel.as_ref().is_none_or(|c| *c == channel) {
+            self.inner.on_frame_transmitting(channel, frame);
+        }
+    }
+
+    fn on_frame_transmitted(&mut self, channel: C, id: u32) {
+        if self.channel.as_ref().is_none_or(|c| *c == channel) {
+            self.inner.on_frame_transmitted(channel, id);
+        }
+    }
+
+    fn on_frame_received(&mut self, channel: C, frames: &[F]) {
+        if self.channel.as_ref().is_some_and(|c| *c != channel) {
+            return;
+        }
+
+        let frames = frames.iter()
+            .filter(|f| self.filter.is_matched(*f))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !frames.is_empty() {
+            self.inner.on_frame_received(channel, &frames);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use isotp_rs::can::{frame::Frame, identifier::Id};
+    use crate::{CanFilter, CanMessage};
+    use crate::filter::FrameFilter;
+    use crate::interfaces::virtual_bus::VirtualCan;
+    use super::{BufferedListener, CallbackListener, FilteredListener, LogListener, Notifier};
+
+    #[test]
+    fn test_notifier() -> anyhow::Result<()> {
+        let channel = "test_notifier";
+        let mut tester = VirtualCan::new();
+        tester.open(channel, vec![], false)?;
+        let mut ecu = VirtualCan::new();
+        ecu.open(channel, vec![], false)?;
+
+        let mut notifier = Notifier::new(ecu);
+        let counter = Arc::new(Mutex::new(0));
+        let c = Arc::clone(&counter);
+        notifier.register_listener("callback".into(), Box::new(CallbackListener::new(move |_, frames: &[CanMessage<String>]| {
+            *c.lock().unwrap() += frames.len();
+        })));
+        let (buffered, all) = BufferedListener::new(16);
+        notifier.register_listener("buffered".into(), Box::new(buffered));
+        let (buffered, diag) = BufferedListener::new(16);
+        let filter = FrameFilter::from([CanFilter { can_id: 0x7DF, can_mask: 0x7FF, extended: false }].as_slice());
+        notifier.register_listener("filtered".into(), Box::new(FilteredListener::new(filter, None, Box::new(buffered))));
+        notifier.register_listener("logger".into(), Box::new(LogListener::new(std::io::sink())));
+        assert_eq!(notifier.listener_names().len(), 4);
+        notifier.start(10);
+
+        for id in [0x7DF, 0x7E0] {
+            let mut msg = CanMessage::new(Id::from(id), &[0x02, 0x10, 0x01]).unwrap();
+            msg.set_channel(channel.into());
+            tester.transmit_can(msg)?;
+        }
+
+        assert_eq!(all.recv_timeout(Duration::from_secs(1))?.id(), Id::from(0x7DF));
+        assert_eq!(all.recv_timeout(Duration::from_secs(1))?.id(), Id::from(0x7E0));
+        assert_eq!(diag.recv_timeout(Duration::from_secs(1))?.id(), Id::from(0x7DF));
+        notifier.stop();
+        assert!(diag.try_recv().is_err());
+        assert_eq!(*counter.lock().unwrap(), 2);
+
+        assert!(notifier.unregister_listener("logger".into()));
+        assert!(notifier.unregister_all());
+
+        Ok(())
+    }
+}
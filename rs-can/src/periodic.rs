@@ -0,0 +1,318 @@
+//! The periodic(cyclic) transmission, [`send_periodic`] transmits the frame at fixed deadlines
+//! by a scheduler thread, and [`send_periodic_auto`] offloads it to hardware by [`AutoSend`].
+
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use isotp_rs::can::frame::Frame;
+use isotp_rs::device::Driver;
+use crate::error::CanError;
+
+/// The hardware cyclic transmission of backend.
+pub trait AutoSend: Driver {
+    /// Send `msg` every `period` in the `index` slot of channel, the previous one is replaced.
+    fn start_auto_send(&self, index: u32, msg: &Self::F, period: Duration) -> Result<(), Self::Error>;
+    /// Stop the `index` slot of channel.
+    fn stop_auto_send(&self, channel: Self::C, index: u32) -> Result<(), Self::Error>;
+}
+
+type Transmit<F> = Box<dyn Fn(F) -> Result<(), CanError> + Send>;
+/// Start(with frame and period) or stop(`None`) the hardware transmission.
+type Apply<F> = Arc<dyn Fn(Option<(&F, Duration)>) -> Result<(), CanError> + Send + Sync>;
+
+#[derive(Debug)]
+struct State<F> {
+    msg: F,
+    period: Duration,
+    paused: bool,
+    stopped: bool,
+}
+
+#[derive(Debug)]
+struct Shared<F> {
+    state: Mutex<State<F>>,
+    cond: Condvar,
+}
+
+impl<F> Shared<F> {
+    #[inline]
+    fn lock(&self) -> Result<MutexGuard<'_, State<F>>, CanError> {
+        self.state.lock()
+            .map_err(|e| CanError::OtherError(e.to_string()))
+    }
+}
+
+/// The handle of periodic task, the task is stopped when dropped.
+pub struct PeriodicTask<F: Clone + Send + 'static> {
+    shared: Arc<Shared<F>>,
+    hardware: Option<Apply<F>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl<F: Clone + Send + 'static> PeriodicTask<F> {
+    fn start(msg: F, period: Duration, duration: Option<Duration>, transmit: Transmit<F>, hardware: Option<Apply<F>>) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State { msg, period, paused: false, stopped: false }),
+            cond: Condvar::new(),
+        });
+        let end = duration.map(|v| Instant::now() + v);
+        let is_hardware = hardware.is_some();
+        let apply = hardware.clone();
+        let s = Arc::clone(&shared);
+        let handle = std::thread::spawn(move || {
+            let mut next = Instant::now();
+            let Ok(mut state) = s.state.lock() else { return; };
+            loop {
+                let now = Instant::now();
+                if state.stopped || end.is_some_and(|v| now >= v) {
+                    break;
+                }
+
+                // the hardware task waits for the end only.
+                if is_hardware || state.paused {
+                    state = match end {
+                        Some(v) => s.cond.wait_timeout(state, v - now)
+                            .map(|(g, _)| g)
+                            .unwrap_or_else(|e| e.into_inner().0),
+                        None => s.cond.wait(state)
+                            .unwrap_or_else(|e| e.into_inner()),
+                    };
+                    next = Instant::now();
+                    continue;
+                }
+
+                if now < next {
+                    let timeout = end.map_or(next - now, |v| (v - now).min(next - now));
+                    state = s.cond.wait_timeout(state, timeout)
+                        .map(|(g, _)| g)
+                        .unwrap_or_else(|e| e.into_inner().0);
+                    continue;
+                }
+
+                let msg = state.msg.clone();
+                let period = state.period;
+                drop(state);
+                if let Err(e) = transmit(msg) {
+                    log::warn!("RUST-CAN - {} when transmitting periodically", e);
+                }
+                // keep the deadlines without drift, and skip the missed ones.
+                next += period;
+                let now = Instant::now();
+                if next < now {
+                    next = now;
+                }
+
+                state = match s.state.lock() {
+                    Ok(v) => v,
+                    Err(_) => return,
+                };
+            }
+
+            state.stopped = true;
+            drop(state);
+            if let Some(apply) = apply {
+                if let Err(e) = apply(None) {
+                    log::warn!("RUST-CAN - {} when stopping hardware periodic task", e);
+                }
+            }
+        });
+
+        Self { shared, hardware, handle: Some(handle) }
+    }
+
+    /// Whether the transmission is offloaded to hardware.
+    #[inline]
+    pub fn is_hardware(&self) -> bool {
+        self.hardware.is_some()
+    }
+
+    /// Whether the task is stopped or finished.
+    #[inline]
+    pub fn is_stopped(&self) -> bool {
+        self.shared.lock()
+            .map(|v| v.stopped)
+            .unwrap_or(true)
+    }
+
+    /// Replace the frame(with new payload) sent since next period.
+    pub fn modify(&self, msg: F) -> Result<(), CanError> {
+        self.update(|state| state.msg = msg)
+    }
+
+    pub fn set_period(&self, period: Duration) -> Result<(), CanError> {
+        self.update(|state| state.period = period)
+    }
+
+    pub fn pause(&self) -> Result<(), CanError> {
+        self.update(|state| state.paused = true)
+    }
+
+    pub fn resume(&self) -> Result<(), CanError> {
+        self.update(|state| state.paused = false)
+    }
+
+    /// Stop the task and wait for the scheduler thread finished.
+    pub fn stop(&mut self) {
+        if let Ok(mut state) = self.shared.lock() {
+            state.stopped = true;
+        }
+        self.shared.cond.notify_all();
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                log::warn!("RUST-CAN - the periodic task is panicked");
+            }
+        }
+    }
+
+    fn update(&self, cb: impl FnOnce(&mut State<F>)) -> Result<(), CanError> {
+        let mut state = self.shared.lock()?;
+        if state.stopped {
+            return Err(CanError::OperationError("the periodic task is stopped".into()));
+        }
+        cb(&mut state);
+        if let Some(apply) = &self.hardware {
+            if state.paused {
+                apply(None)?;
+            }
+            else {
+                apply(Some((&state.msg, state.period)))?;
+            }
+        }
+        drop(state);
+        self.shared.cond.notify_all();
+
+        Ok(())
+    }
+}
+
+impl<F: Clone + Send + 'static> Drop for PeriodicTask<F> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Transmit `msg` every `period` by the scheduler thread until `duration` elapsed or stopped.
+pub fn send_periodic<D>(device: &D, msg: D::F, period: Duration, duration: Option<Duration>) -> PeriodicTask<D::F>
+where
+    D: Driver + Clone + 'static,
+    D::F: Clone + Send + 'static,
+    D::Error: Into<CanError>,
+{
+    let device = device.clone();
+    PeriodicTask::start(
+        msg,
+        period,
+        duration,
+        Box::new(move |msg| device.transmit(msg, None).map_err(Into::into)),
+        None,
+    )
+}
+
+/// Transmit `msg` every `period` by the `index` slot of hardware,
+/// the scheduler thread is used when the hardware cyclic transmission is failed.
+pub fn send_periodic_auto<D>(device: &D, index: u32, msg: D::F, period: Duration, duration: Option<Duration>) -> PeriodicTask<D::F>
+where
+    D: AutoSend + Clone + Sync + 'static,
+    D::C: Clone + Send + Sync,
+    D::F: Frame<Channel = D::C> + Clone + Send + 'static,
+    D::Error: Into<CanError>,
+{
+    if let Err(e) = device.start_auto_send(index, &msg, period) {
+        log::info!("RUST-CAN - {} when starting hardware periodic task, the software is used", e.into());
+        return send_periodic(device, msg, period, duration);
+    }
+
+    let dev = device.clone();
+    let channel = msg.channel();
+    let apply: Apply<D::F> = Arc::new(move |v| match v {
+        Some((msg, period)) => dev.start_auto_send(index, msg, period).map_err(Into::into),
+        None => dev.stop_auto_send(channel.clone(), index).map_err(Into::into),
+    });
+
+    PeriodicTask::start(msg, period, duration, Box::new(|_| Ok(())), Some(apply))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+    use isotp_rs::can::{frame::Frame, identifier::Id};
+    use crate::CanMessage;
+    use crate::interfaces::virtual_bus::VirtualCan;
+    use super::{send_periodic, PeriodicTask};
+
+    /// Poll `cond` until it is true, the deadline is generous for the loaded machine.
+    fn wait_until(mut cond: impl FnMut() -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            if cond() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        cond()
+    }
+
+    #[test]
+    fn test_send_periodic() -> anyhow::Result<()> {
+        let channel = "test_send_periodic";
+        let mut tester = VirtualCan::new();
+        tester.open(channel, vec![], false)?;
+        let mut ecu = VirtualCan::new();
+        ecu.open(channel, vec![], false)?;
+
+        let mut msg = CanMessage::new(Id::from(0x7DF), &[0x02, 0x3E, 0x80]).unwrap();
+        msg.set_channel(channel.into());
+        let start = Instant::now();
+        let mut task = send_periodic(&tester, msg.clone(), Duration::from_millis(10), None);
+        assert!(!task.is_hardware());
+
+        let mut count = 0;
+        assert!(wait_until(|| {
+            count += ecu.receive_can(channel.into(), None).unwrap().len();
+            count >= 3
+        }));
+        // the missed deadlines are skipped, so never more than one frame each period.
+        let limit = start.elapsed().as_millis() / 10 + 1;
+        assert!(count as u128 <= limit, "count: {}, limit: {}", count, limit);
+
+        let mut modified = CanMessage::new(Id::from(0x7DF), &[0x02, 0x3E, 0x00]).unwrap();
+        modified.set_channel(channel.into());
+        task.modify(modified.clone())?;
+        task.set_period(Duration::from_millis(5))?;
+        assert!(wait_until(|| ecu.receive_can(channel.into(), None).unwrap().contains(&modified)));
+
+        task.stop();
+        assert!(task.is_stopped());
+        assert!(task.resume().is_err());
+
+        let task = send_periodic(&tester, msg, Duration::from_millis(5), Some(Duration::from_millis(20)));
+        assert!(wait_until(|| task.is_stopped()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pause() -> anyhow::Result<()> {
+        let count = Arc::new(AtomicUsize::new(0));
+        let c = Arc::clone(&count);
+        let task = PeriodicTask::start(0u8, Duration::from_millis(1), None, Box::new(move |_| {
+            c.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }), None);
+        assert!(wait_until(|| count.load(Ordering::SeqCst) >= 2));
+
+        task.pause()?;
+        // one transmission may be in flight when paused.
+        let paused = count.load(Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(count.load(Ordering::SeqCst) <= paused + 1);
+
+        task.resume()?;
+        assert!(wait_until(|| count.load(Ordering::SeqCst) > paused + 1));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,512 @@
+//! Replay the frames of log file onto one or more buses,
+//! the inter-frame timing of log is kept(or scaled) by a scheduler thread.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use isotp_rs::can::frame::Frame;
+use isotp_rs::device::Driver;
+use crate::CanMessage;
+use crate::error::CanError;
+use crate::filter::FrameFilter;
+use crate::io::{open_log, LogChannel, LogReader};
+
+type Open<L> = Box<dyn FnMut() -> Result<LogReader<L>, CanError> + Send>;
+type Transmit<C> = Box<dyn Fn(CanMessage<C>) -> Result<(), CanError> + Send>;
+
+/// The statistics of replay, the drift is the lateness of transmission against the schedule.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct ReplayReport {
+    pub sent: usize,
+    /// The frames filtered out, unmapped or error frames.
+    pub skipped: usize,
+    /// The read and transmit errors.
+    pub errors: usize,
+    /// The completed passes of log.
+    pub loops: usize,
+    /// The offset of last sent frame since the first frame of log.
+    pub position: Duration,
+    pub max_drift: Duration,
+    pub total_drift: Duration,
+}
+
+impl ReplayReport {
+    #[inline]
+    pub fn mean_drift(&self) -> Duration {
+        match self.sent {
+            0 => Duration::ZERO,
+            n => self.total_drift / n as u32,
+        }
+    }
+}
+
+/// The builder of replay, the frames are sent to bus `0` with the same channel index
+/// when there is no channel mapped, otherwise the unmapped channels are skipped.
+pub struct Replay<L: LogChannel + Hash + Eq, C: LogChannel> {
+    open: Open<L>,
+    speed: f64,
+    loops: Option<usize>,
+    filter: FrameFilter,
+    channels: HashMap<L, (usize, C)>,
+    targets: Vec<Transmit<C>>,
+}
+
+impl<L: LogChannel + Hash + Eq, C: LogChannel> Replay<L, C> {
+    /// Replay the log file, which is opened by [`open_log`] for each pass.
+    pub fn from_file<P: Into<PathBuf>>(path: P) -> Self {
+        let path = path.into();
+        Self::new(Box::new(move || open_log(&path)))
+    }
+
+    /// Replay the frames in memory.
+    pub fn from_frames(frames: Vec<CanMessage<L>>) -> Self {
+        let frames = Arc::new(frames);
+        Self::new(Box::new(move || {
+            let frames = Arc::clone(&frames);
+            Ok(Box::new((0..frames.len()).map(move |i| Ok(frames[i].clone()))))
+        }))
+    }
+
+    fn new(open: Open<L>) -> Self {
+        Self {
+            open,
+            speed: 1.,
+            loops: Some(1),
+            filter: Default::default(),
+            channels: Default::default(),
+            targets: Default::default(),
+        }
+    }
+
+    /// The time scale, `2.0` is twice as fast as recorded, `0` or infinity is as fast as possible.
+    #[inline]
+    pub fn set_speed(&mut self, speed: f64) -> &mut Self {
+        self.speed = speed;
+        self
+    }
+
+    /// The passes of log, `None` is looped until stopped.
+    #[inline]
+    pub fn set_loops(&mut self, loops: Option<usize>) -> &mut Self {
+        self.loops = loops;
+        self
+    }
+
+    /// Only the frames matched are sent, such as [`FilterRule::inverted`] to skip identifiers.
+    ///
+    /// [`FilterRule::inverted`]: crate::filter::FilterRule::inverted
+    #[inline]
+    pub fn set_filter(&mut self, filter: FrameFilter) -> &mut Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Send the frames of log `channel` to `target` channel of the `bus` which is returned by [`Self::add_bus`].
+    #[inline]
+    pub fn map_channel(&mut self, channel: L, bus: usize, target: C) -> &mut Self {
+        self.channels.insert(channel, (bus, target));
+        self
+    }
+
+    /// Add the target bus, the index of bus is returned.
+    pub fn add_bus<D>(&mut self, device: &D) -> usize
+    where
+        D: Driver<C = C, F = CanMessage<C>> + Clone + Send + 'static,
+        D::Error: Into<CanError>,
+    {
+        let device = device.clone();
+        self.targets.push(Box::new(move |msg| device.transmit(msg, None).map_err(Into::into)));
+        self.targets.len() - 1
+    }
+
+    /// Start the scheduler thread, the log is opened before starting.
+    pub fn start(mut self) -> Result<ReplayTask, CanError> {
+        if self.targets.is_empty() {
+            return Err(CanError::OperationError("no bus to replay".into()));
+        }
+        let reader = (self.open)()?;
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                speed: self.speed,
+                paused: false,
+                stopped: false,
+                seek: None,
+                report: Default::default(),
+            }),
+            cond: Condvar::new(),
+        });
+        let s = Arc::clone(&shared);
+        let handle = std::thread::spawn(move || {
+            self.run(reader, &s);
+            if let Ok(mut state) = s.state.lock() {
+                state.stopped = true;
+            }
+            s.cond.notify_all();
+        });
+
+        Ok(ReplayTask { shared, handle: Some(handle) })
+    }
+
+    fn route(&self, msg: CanMessage<L>) -> Option<(usize, CanMessage<C>)> {
+        if msg.is_error_frame() || !self.filter.is_matched(&msg) {
+            return None;
+        }
+
+        let channel = msg.channel();
+        let (bus, target) = if self.channels.is_empty() {
+            (0, C::from_index(channel.index()))
+        }
+        else {
+            self.channels.get(&channel)?.clone()
+        };
+        (bus < self.targets.len())
+            .then(|| (bus, msg.with_channel(target)))
+    }
+
+    fn run(&mut self, mut reader: LogReader<L>, shared: &Shared) {
+        let mut speed = self.speed;
+        // the instant of log offset, the deadline of frame is scaled from it.
+        let mut anchor = (Instant::now(), Duration::ZERO);
+        let mut position = Duration::ZERO;
+        let mut seek: Option<Duration> = None;
+        let mut paused = false;
+        let mut loops = 0;
+
+        'pass: loop {
+            let mut first = None;
+            let mut routed = false;
+            while let Some(item) = reader.next() {
+                if shared.is_stopped() {
+                    break 'pass;
+                }
+                let msg = match item {
+                    Ok(v) => v,
+                    Err(e) => {
+                        log::warn!("RUST-CAN - {} when reading log", e);
+                        shared.update(|r| r.errors += 1);
+                        continue;
+                    },
+                };
+                let timestamp = msg.timestamp();
+                let offset = Duration::from_nanos(timestamp.saturating_sub(*first.get_or_insert(timestamp)));
+                if let Some(target) = seek {
+                    if offset < target {
+                        continue;
+                    }
+                    seek = None;
+                    anchor = (Instant::now(), target);
+                }
+
+                let Some((bus, msg)) = self.route(msg) else {
+                    shared.update(|r| r.skipped += 1);
+                    continue;
+                };
+                routed = true;
+
+                let deadline = {
+                    let Ok(mut state) = shared.state.lock() else { return; };
+                    loop {
+                        if state.stopped {
+                            break 'pass;
+                        }
+                        if let Some(target) = state.seek.take() {
+                            seek = Some(target);
+                            position = target;
+                            drop(state);
+                            match (self.open)() {
+                                Ok(v) => reader = v,
+                                Err(e) => {
+                                    log::warn!("RUST-CAN - {} when seeking log", e);
+                                    shared.update(|r| r.errors += 1);
+                                    break 'pass;
+                                },
+                            }
+                            continue 'pass;
+                        }
+                        if state.paused {
+                            paused = true;
+                            state = shared.cond.wait(state)
+                                .unwrap_or_else(|e| e.into_inner());
+                            continue;
+                        }
+
+                        let now = Instant::now();
+                        // rebase the schedule from the last sent frame.
+                        if paused || state.speed != speed {
+                            paused = false;
+                            speed = state.speed;
+                            anchor = (now, position);
+                        }
+                        let deadline = if speed > 0. && speed.is_finite() {
+                            anchor.0 + offset.saturating_sub(anchor.1).div_f64(speed)
+                        }
+                        else {
+                            now
+                        };
+                        if now >= deadline {
+                            break deadline;
+                        }
+                        state = shared.cond.wait_timeout(state, deadline - now)
+                            .map(|(g, _)| g)
+                            .unwrap_or_else(|e| e.into_inner().0);
+                    }
+                };
+
+                let result = self.targets[bus](msg);
+                let drift = Instant::now().saturating_duration_since(deadline);
+                position = offset;
+                shared.update(|r| {
+                    match result {
+                        Ok(()) => {
+                            r.sent += 1;
+                            r.max_drift = r.max_drift.max(drift);
+                            r.total_drift += drift;
+                        },
+                        Err(e) => {
+                            log::warn!("RUST-CAN - {} when replaying to bus: {}", e, bus);
+                            r.errors += 1;
+                        },
+                    }
+                    r.position = offset;
+                });
+            }
+
+            loops += 1;
+            shared.update(|r| r.loops = loops);
+            if self.loops.is_some_and(|v| loops >= v) || shared.is_stopped() {
+                break;
+            }
+            // the next passes are the same as this one
+            if !routed {
+                log::debug!("RUST-CAN - no frame is replayed in the pass, stop looping");
+                break;
+            }
+            match (self.open)() {
+                Ok(v) => reader = v,
+                Err(e) => {
+                    log::warn!("RUST-CAN - {} when reopening log", e);
+                    shared.update(|r| r.errors += 1);
+                    break;
+                },
+            }
+            position = Duration::ZERO;
+            anchor = (Instant::now(), Duration::ZERO);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    speed: f64,
+    paused: bool,
+    stopped: bool,
+    seek: Option<Duration>,
+    report: ReplayReport,
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+impl Shared {
+    #[inline]
+    fn lock(&self) -> Result<MutexGuard<'_, State>, CanError> {
+        self.state.lock()
+            .map_err(|e| CanError::OtherError(e.to_string()))
+    }
+
+    #[inline]
+    fn is_stopped(&self) -> bool {
+        self.state.lock()
+            .map(|v| v.stopped)
+            .unwrap_or(true)
+    }
+
+    #[inline]
+    fn update(&self, cb: impl FnOnce(&mut ReplayReport)) {
+        if let Ok(mut state) = self.state.lock() {
+            cb(&mut state.report);
+        }
+    }
+}
+
+/// The handle of replay, the replay is stopped when dropped.
+pub struct ReplayTask {
+    shared: Arc<Shared>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ReplayTask {
+    /// Whether the replay is stopped or all passes are finished.
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.shared.lock()
+            .map(|v| v.stopped)
+            .unwrap_or(true)
+    }
+
+    #[inline]
+    pub fn report(&self) -> ReplayReport {
+        self.shared.lock()
+            .map(|v| v.report)
+            .unwrap_or_default()
+    }
+
+    pub fn pause(&self) -> Result<(), CanError> {
+        self.update(|state| state.paused = true)
+    }
+
+    pub fn resume(&self) -> Result<(), CanError> {
+        self.update(|state| state.paused = false)
+    }
+
+    /// Jump to the `offset` since the first frame of log in current pass.
+    pub fn seek(&self, offset: Duration) -> Result<(), CanError> {
+        self.update(|state| state.seek = Some(offset))
+    }
+
+    /// See [`Replay::set_speed`].
+    pub fn set_speed(&self, speed: f64) -> Result<(), CanError> {
+        self.update(|state| state.speed = speed)
+    }
+
+    /// Block until all passes are finished.
+    pub fn wait(&mut self) -> ReplayReport {
+        self.join();
+        self.report()
+    }
+
+    /// Stop the replay and wait for the scheduler thread finished.
+    pub fn stop(&mut self) -> ReplayReport {
+        if let Ok(mut state) = self.shared.lock() {
+            state.stopped = true;
+        }
+        self.shared.cond.notify_all();
+        self.wait()
+    }
+
+    fn join(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                log::warn!("RUST-CAN - the replay task is panicked");
+            }
+        }
+    }
+
+    fn update(&self, cb: impl FnOnce(&mut State)) -> Result<(), CanError> {
+        let mut state = self.shared.lock()?;
+        if state.stopped {
+            return Err(CanError::OperationError("the replay task is stopped".into()));
+        }
+        cb(&mut state);
+        drop(state);
+        self.shared.cond.notify_all();
+
+        Ok(())
+    }
+}
+
+impl Drop for ReplayTask {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use isotp_rs::can::{frame::Frame, identifier::Id};
+    use crate::CanMessage;
+    use crate::filter::{FilterRule, FrameFilter};
+    use crate::interfaces::virtual_bus::VirtualCan;
+    use super::Replay;
+
+    fn frames() -> Vec<CanMessage<u8>> {
+        (0..10u8).map(|i| {
+            let mut msg = CanMessage::new(Id::from(0x100 + (i % 2) as u32), &[i]).unwrap();
+            msg.set_timestamp(Some(1_000_000_000 + i as u64 * 10_000_000));
+            msg.set_channel(i % 2);
+            msg
+        })
+            .collect()
+    }
+
+    #[test]
+    fn test_replay() -> anyhow::Result<()> {
+        let channel = "test_replay";
+        let mut tester = VirtualCan::new();
+        tester.open(channel, vec![], false)?;
+        let mut ecu = VirtualCan::new();
+        ecu.open(channel, vec![], false)?;
+
+        // 90ms at double speed
+        let mut replay = Replay::from_frames(frames());
+        let bus = replay.add_bus(&tester);
+        replay.set_speed(2.)
+            .map_channel(0, bus, channel.into())
+            .map_channel(1, bus, channel.into());
+        let start = Instant::now();
+        let report = replay.start()?.wait();
+        let elapsed = start.elapsed();
+        // the frames are never sent early, the delay of a loaded machine is in the drift report
+        assert!(elapsed >= Duration::from_millis(45), "elapsed: {:?}", elapsed);
+        assert_eq!(report.sent, 10);
+        assert_eq!(report.loops, 1);
+        assert_eq!(report.position, Duration::from_millis(90));
+        assert!(report.max_drift >= report.mean_drift());
+        let received = ecu.receive_can(channel.into(), None)?;
+        assert_eq!(received.iter().map(|m| m.data()[0]).collect::<Vec<_>>(), (0..10).collect::<Vec<_>>());
+
+        // the channel 1 is unmapped and 0x100 is skipped
+        let mut replay = Replay::from_frames(frames());
+        let bus = replay.add_bus(&tester);
+        replay.set_speed(0.)
+            .set_loops(Some(2))
+            .set_filter(FrameFilter::new(vec![FilterRule::Range { start: 0x100, end: 0x100, extended: false }.inverted()]))
+            .map_channel(1, bus, channel.into());
+        let report = replay.start()?.wait();
+        assert_eq!((report.sent, report.skipped, report.loops), (10, 10, 2));
+        assert!(ecu.receive_can(channel.into(), None)?.iter().all(|m| m.data()[0] % 2 == 1));
+
+        // pause, seek and stop
+        let mut replay = Replay::from_frames(frames());
+        replay.add_bus(&tester);
+        replay.map_channel(0, 0, channel.into())
+            .map_channel(1, 0, channel.into())
+            .set_loops(None);
+        let mut task = replay.start()?;
+        task.pause()?;
+        std::thread::sleep(Duration::from_millis(20));
+        let sent = task.report().sent;
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(task.report().sent, sent);
+        task.seek(Duration::from_millis(80))?;
+        task.resume()?;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while task.report().loops < 1 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let report = task.stop();
+        assert!(report.loops >= 1);
+        assert!(task.is_finished());
+        assert!(task.resume().is_err());
+
+        // nothing is routable in a looped replay
+        let mut replay = Replay::from_frames(frames());
+        replay.add_bus(&tester);
+        replay.set_loops(None)
+            .set_filter(FrameFilter::new(vec![FilterRule::Range { start: 0x100, end: 0x101, extended: false }.inverted()]));
+        let mut task = replay.start()?;
+        let report = task.stop();
+        assert_eq!(report.sent, 0);
+        assert!(task.is_finished());
+
+        Ok(())
+    }
+}
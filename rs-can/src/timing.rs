@@ -0,0 +1,194 @@
+//! The bit timing calculator, the algorithm is similar to `can_calc_bittiming` of Linux.
+//!
+//! All segments are in time quanta, and the sample point is in per-mille.
+
+use crate::error::CanError;
+
+/// The maximum bitrate error in per-mille.
+pub const MAX_BITRATE_ERROR: u32 = 5;
+
+/// The bit timing constraints of a CAN controller.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct BitTimingConst {
+    /// The propagation segment + phase segment 1.
+    pub tseg1_min: u32,
+    pub tseg1_max: u32,
+    /// The phase segment 2.
+    pub tseg2_min: u32,
+    pub tseg2_max: u32,
+    pub sjw_max: u32,
+    pub brp_min: u32,
+    pub brp_max: u32,
+    pub brp_inc: u32,
+}
+
+impl BitTimingConst {
+    /// SJA1000, the clock used to calculate is half of oscillator.
+    pub const SJA1000: Self = Self {
+        tseg1_min: 1, tseg1_max: 16,
+        tseg2_min: 1, tseg2_max: 8,
+        sjw_max: 4,
+        brp_min: 1, brp_max: 64, brp_inc: 1,
+    };
+    /// Bosch M_CAN nominal bit timing.
+    pub const MCAN_NOMINAL: Self = Self {
+        tseg1_min: 2, tseg1_max: 256,
+        tseg2_min: 2, tseg2_max: 128,
+        sjw_max: 128,
+        brp_min: 1, brp_max: 512, brp_inc: 1,
+    };
+    /// Bosch M_CAN data bit timing.
+    pub const MCAN_DATA: Self = Self {
+        tseg1_min: 1, tseg1_max: 32,
+        tseg2_min: 1, tseg2_max: 16,
+        sjw_max: 16,
+        brp_min: 1, brp_max: 32, brp_inc: 1,
+    };
+}
+
+/// The calculated bit timing.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct BitTiming {
+    /// The real bitrate.
+    pub bitrate: u32,
+    /// The real sample point in per-mille.
+    pub sample_point: u32,
+    pub brp: u32,
+    pub tseg1: u32,
+    pub tseg2: u32,
+    pub sjw: u32,
+}
+
+impl BitTiming {
+    /// The recommended sample point of CiA.
+    #[inline]
+    pub fn default_sample_point(bitrate: u32) -> u32 {
+        if bitrate > 800_000 {
+            750
+        }
+        else if bitrate > 500_000 {
+            800
+        }
+        else {
+            875
+        }
+    }
+
+    /// Calculate the bit timing with the minimum bitrate error, then the nearest sample point,
+    /// the default sample point is used when `sample_point` is `None`.
+    pub fn calculate(
+        clock: u32,
+        bitrate: u32,
+        sample_point: Option<u32>,
+        cst: &BitTimingConst,
+    ) -> Result<Self, CanError> {
+        if clock == 0 || bitrate == 0 || bitrate > clock {
+            return Err(CanError::OtherError(format!("invalid bitrate: {} with clock: {}", bitrate, clock)));
+        }
+        let sp_target = sample_point.unwrap_or(Self::default_sample_point(bitrate));
+        if !(1..1000).contains(&sp_target) {
+            return Err(CanError::OtherError(format!("invalid sample point: {}", sp_target)));
+        }
+
+        let tq_min = 1 + cst.tseg1_min + cst.tseg2_min;
+        let tq_max = 1 + cst.tseg1_max + cst.tseg2_max;
+        // (bitrate error, sample point error, timing)
+        let mut best: Option<(u32, u32, Self)> = None;
+        for brp in (cst.brp_min..=cst.brp_max).step_by(cst.brp_inc.max(1) as usize) {
+            let div = brp as u64 * bitrate as u64;
+            let tq = ((clock as u64 + div / 2) / div) as u32;
+            if tq < tq_min || tq > tq_max {
+                continue;
+            }
+
+            let real = (clock as u64 / (brp as u64 * tq as u64)) as u32;
+            let error = bitrate.abs_diff(real);
+            let mut tseg2 = tq - (tq * sp_target + 500) / 1000;
+            tseg2 = tseg2.clamp(cst.tseg2_min, cst.tseg2_max);
+            let mut tseg1 = tq - 1 - tseg2;
+            if tseg1 > cst.tseg1_max {
+                tseg1 = cst.tseg1_max;
+                tseg2 = tq - 1 - tseg1;
+            }
+            else if tseg1 < cst.tseg1_min {
+                tseg1 = cst.tseg1_min;
+                tseg2 = tq - 1 - tseg1;
+            }
+
+            let sp = 1000 * (1 + tseg1) / tq;
+            let sp_error = sp_target.abs_diff(sp);
+            let better = match &best {
+                Some((e, s, _)) => error < *e || (error == *e && sp_error < *s),
+                None => true,
+            };
+            if better {
+                best = Some((error, sp_error, Self {
+                    bitrate: real,
+                    sample_point: sp,
+                    brp,
+                    tseg1,
+                    tseg2,
+                    sjw: tseg2.min(cst.sjw_max),
+                }));
+                if error == 0 && sp_error == 0 {
+                    break;
+                }
+            }
+        }
+
+        match best {
+            Some((error, _, timing)) if error as u64 * 1000 <= bitrate as u64 * MAX_BITRATE_ERROR as u64 => Ok(timing),
+            _ => Err(CanError::OtherError(format!("bitrate: {} is not reachable with clock: {}", bitrate, clock))),
+        }
+    }
+
+    /// Calculate the nominal and data bit timing of CAN-FD.
+    pub fn calculate_fd(
+        clock: u32,
+        bitrate: u32,
+        dbitrate: u32,
+        nominal: &BitTimingConst,
+        data: &BitTimingConst,
+    ) -> Result<(Self, Self), CanError> {
+        Ok((
+            Self::calculate(clock, bitrate, None, nominal)?,
+            Self::calculate(clock, dbitrate, None, data)?,
+        ))
+    }
+
+    /// The time quanta of a bit.
+    #[inline]
+    pub fn tq_per_bit(&self) -> u32 {
+        1 + self.tseg1 + self.tseg2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BitTiming, BitTimingConst};
+
+    #[test]
+    fn test_calculate() -> anyhow::Result<()> {
+        // 500k of SJA1000 with 16MHz oscillator: BTR0 = 0x00, BTR1 = 0x1C
+        let timing = BitTiming::calculate(8_000_000, 500_000, None, &BitTimingConst::SJA1000)?;
+        assert_eq!(timing, BitTiming { bitrate: 500_000, sample_point: 875, brp: 1, tseg1: 13, tseg2: 2, sjw: 2 });
+        assert_eq!(timing.tq_per_bit(), 16);
+
+        let (nominal, data) = BitTiming::calculate_fd(
+            80_000_000, 500_000, 2_000_000, &BitTimingConst::MCAN_NOMINAL, &BitTimingConst::MCAN_DATA
+        )?;
+        assert_eq!(nominal.bitrate, 500_000);
+        assert_eq!(nominal.sample_point, 875);
+        assert_eq!(data.bitrate, 2_000_000);
+        assert_eq!(data.sample_point, 750);
+
+        let timing = BitTiming::calculate(60_000_000, 250_000, Some(800), &BitTimingConst::MCAN_NOMINAL)?;
+        assert_eq!(timing.bitrate, 250_000);
+        assert_eq!(timing.sample_point, 800);
+
+        assert!(BitTiming::calculate(8_000_000, 3_000_000, None, &BitTimingConst::SJA1000).is_err());
+        assert!(BitTiming::calculate(8_000_000, 500_000, Some(1000), &BitTimingConst::SJA1000).is_err());
+
+        Ok(())
+    }
+}
@@ -1,20 +1,95 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+//! The timestamps of frames are nanoseconds since UNIX epoch.
+//!
+//! There are two time bases:
+//! * wall-clock, from [`system_timestamp`], it jumps when the system time is adjusted(NTP and so on).
+//! * monotonic, from [`monotonic_timestamp`] and [`instant_timestamp`], the host monotonic clock is
+//!   anchored to the wall-clock once per process, so it never goes backwards and the differences
+//!   between timestamps are exact, but it may drift away from the wall-clock in a long session.
+//!
+//! The time base of each frame is carried by [`crate::TimeBase`], and it's decided by the backend:
+//! * ZLGCAN, the virtual bus and the transmitted frames are on the monotonic time base.
+//! * NI-CAN and the log files are on the wall-clock time base.
+//! * SocketCAN is on the raw hardware clock of interface when the hardware timestamp is reported,
+//!   otherwise on the wall-clock time base, so the frames of one socket may be on either.
+//! * UDP multicast keeps the time base of sender.
+
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use isotp_rs::can::DEFAULT_PADDING;
 
-/// Get system timestamp(ms)
+static MONOTONIC_ORIGIN: OnceLock<(Instant, Duration)> = OnceLock::new();
+
 #[inline]
-pub fn system_timestamp() -> u64 {
+fn monotonic_origin() -> &'static (Instant, Duration) {
+    MONOTONIC_ORIGIN.get_or_init(|| (Instant::now(), wall_clock()))
+}
+
+#[inline]
+fn wall_clock() -> Duration {
     match SystemTime::now().duration_since(UNIX_EPOCH) {
-        Ok(v) => v.as_millis() as u64,
+        Ok(v) => v,
         Err(e) => {
             log::warn!("RUST-CAN - SystemTimeError: {0} when conversion failed!", e);
-            0
+            Default::default()
         }
     }
 }
 
+/// Get system timestamp(ns) on the wall-clock time base.
+#[inline]
+pub fn system_timestamp() -> u64 {
+    wall_clock().as_nanos() as u64
+}
+
+/// Get current timestamp(ns) on the monotonic time base.
+#[inline]
+pub fn monotonic_timestamp() -> u64 {
+    instant_timestamp(Instant::now())
+}
+
+/// Convert an instant of host monotonic clock to timestamp(ns) on the monotonic time base.
+pub fn instant_timestamp(instant: Instant) -> u64 {
+    let (origin, epoch) = monotonic_origin();
+    let time = match instant.checked_duration_since(*origin) {
+        Some(elapsed) => *epoch + elapsed,
+        None => epoch.saturating_sub(*origin - instant),
+    };
+    time.as_nanos() as u64
+}
+
 /// resize data with default padding.
 #[inline]
 pub fn data_resize(data: &mut Vec<u8>, size: usize) {
     data.resize(size, DEFAULT_PADDING);
 }
+
+/// Convert the data length to DLC code, the length is rounded up to the next valid CAN-FD length.
+#[inline]
+pub fn len_to_dlc(len: usize) -> u8 {
+    match len {
+        ..=8 => len as u8,
+        9..=12 => 9,
+        13..=16 => 10,
+        17..=20 => 11,
+        21..=24 => 12,
+        25..=32 => 13,
+        33..=48 => 14,
+        _ => 15,
+    }
+}
+
+/// Convert the DLC code to data length, the DLC above 8 is 8 for classic CAN.
+#[inline]
+pub fn dlc_to_len(dlc: u8, is_fd: bool) -> usize {
+    match dlc {
+        ..=8 => dlc as usize,
+        _ if !is_fd => 8,
+        9 => 12,
+        10 => 16,
+        11 => 20,
+        12 => 24,
+        13 => 32,
+        14 => 48,
+        _ => 64,
+    }
+}
@@ -52,16 +52,16 @@ impl USBCANEApi<'_> {
         &self,
         dev_hdl: &mut Handler,
         channels: u8,
-        cfg: &Vec<CanChlCfg>,
+        cfg: &Vec<(u8, CanChlCfg)>,
     ) -> Result<(), ZCanError> {
         let p = self.self_get_property(dev_hdl.device_context())?;
         let set_value_func = p.SetValue;
         let mut error = None;
-        for (idx, cfg) in cfg.iter().enumerate() {
-            let idx = idx as u8;
+        for (idx, cfg) in cfg.iter() {
+            let idx = *idx;
             if idx >= channels {
-                log::warn!("ZLGCAN - the length of CAN channel configuration is out of channels!");
-                break;
+                log::warn!("ZLGCAN - the CAN channel: {} is out of channels!", idx);
+                continue;
             }
 
             if let Some(chl_hdl) = dev_hdl.find_can(idx) {
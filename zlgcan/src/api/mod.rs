@@ -6,7 +6,7 @@ pub(crate) mod linux;
 pub(crate) mod windows;
 
 use std::ffi::{c_char, c_void};
-use crate::can::{CanChlCfg, ZCanChlError, ZCanChlStatus, ZCanFrameType};
+use crate::can::{CanChlCfg, ZCanAutoTransmitObj, ZCanChlError, ZCanChlStatus, ZCanFrameType};
 use crate::cloud::{ZCloudGpsFrame, ZCloudServerInfo, ZCloudUserData};
 use crate::device::{CmdPath, IProperty, ZChannelContext, ZDeviceContext, ZDeviceInfo};
 use crate::error::ZCanError;
@@ -67,6 +67,15 @@ pub trait ZCanApi {
     fn transmit_canfd(&self, context: &ZChannelContext, frames: Vec<Self::FdFrame>) -> Result<u32, ZCanError> {
         Err(ZCanError::MethodNotSupported)
     }
+    fn set_auto_send(&self, context: &ZChannelContext, obj: &ZCanAutoTransmitObj<Self::Frame>) -> Result<(), ZCanError> {
+        Err(ZCanError::MethodNotSupported)
+    }
+    fn set_auto_send_canfd(&self, context: &ZChannelContext, obj: &ZCanAutoTransmitObj<Self::FdFrame>) -> Result<(), ZCanError> {
+        Err(ZCanError::MethodNotSupported)
+    }
+    fn apply_auto_send(&self, context: &ZChannelContext) -> Result<(), ZCanError> {
+        Err(ZCanError::MethodNotSupported)
+    }
 }
 
 #[allow(unused_variables, dead_code)]
@@ -1,7 +1,7 @@
 use std::ffi::{c_char, c_int, c_uchar, c_uint, c_ushort, c_void, CString};
 use std::pin::Pin;
 use dlopen2::symbor::{Symbol, SymBorApi};
-use crate::can::{CanChlCfg, ZCanChlError, ZCanChlErrorV2, ZCanChlStatus, ZCanChlType, ZCanFdFrameV2, ZCanFrameV3, ZCanFrameType, ZCanChlCfgV1};
+use crate::can::{CanChlCfg, ZCanAutoTransmitObj, ZCanChlError, ZCanChlErrorV2, ZCanChlStatus, ZCanChlType, ZCanFdFrameV2, ZCanFrameV3, ZCanFrameType, ZCanChlCfgV1};
 use crate::cloud::{ZCloudGpsFrame, ZCloudServerInfo, ZCloudUserData};
 use crate::device::{CmdPath, IProperty, ZCanDeviceType, ZChannelContext, ZDeviceContext, ZDeviceInfo};
 use crate::lin::{ZLinChlCfg, ZLinFrame, ZLinPublish, ZLinPublishEx, ZLinSubscribe};
@@ -9,7 +9,7 @@ use crate::utils::c_str_to_string;
 
 use crate::api::{ZCanApi, ZCloudApi, ZDeviceApi, ZLinApi};
 use crate::error::ZCanError;
-use crate::constant::{STATUS_OFFLINE, STATUS_ONLINE, INTERNAL_RESISTANCE, PROTOCOL, CANFD_ABIT_BAUD_RATE, CANFD_DBIT_BAUD_RATE, BAUD_RATE, CLOCK};
+use crate::constant::{STATUS_OFFLINE, STATUS_ONLINE, INTERNAL_RESISTANCE, PROTOCOL, CANFD_ABIT_BAUD_RATE, CANFD_DBIT_BAUD_RATE, BAUD_RATE, CLOCK, AUTO_SEND, AUTO_SEND_CANFD, APPLY_AUTO_SEND};
 
 #[allow(non_snake_case)]
 #[derive(Debug, Clone, SymBorApi)]
@@ -455,6 +455,22 @@ impl ZCanApi for Api<'_> {
         }
         Ok(count)
     }
+
+    fn set_auto_send(&self, context: &ZChannelContext, obj: &ZCanAutoTransmitObj<Self::Frame>) -> Result<(), ZCanError> {
+        let path = format!("{}/{}", context.channel(), AUTO_SEND);
+        self.set_value(context, &CmdPath::new_path(path.as_str()), obj as *const _ as *const c_void)
+    }
+
+    fn set_auto_send_canfd(&self, context: &ZChannelContext, obj: &ZCanAutoTransmitObj<Self::FdFrame>) -> Result<(), ZCanError> {
+        let path = format!("{}/{}", context.channel(), AUTO_SEND_CANFD);
+        self.set_value(context, &CmdPath::new_path(path.as_str()), obj as *const _ as *const c_void)
+    }
+
+    fn apply_auto_send(&self, context: &ZChannelContext) -> Result<(), ZCanError> {
+        let path = format!("{}/{}", context.channel(), APPLY_AUTO_SEND);
+        let value = CString::new("0").map_err(|e| ZCanError::CStringConvertFailed(e.to_string()))?;
+        self.set_value(context, &CmdPath::new_path(path.as_str()), value.as_ptr() as *const c_void)
+    }
 }
 
 impl ZLinApi for Api<'_> {
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::ffi::{c_uchar, c_uint, c_ushort};
+use rs_can::timing::BitTiming;
 use crate::can::frame::ZCanHeaderV1;
 use crate::error::ZCanError;
 use super::constant::{BRP, CANERR_FRAME_LENGTH, SJW, SMP, TSEG1, TSEG2, ZCanChlMode, ZCanChlType, ZCanFilterType};
@@ -113,6 +114,13 @@ impl TryFrom<&HashMap<String, u32>> for ZCanFdChlCfgSet {
     }
 }
 
+/// The registers are the segments minus 1, and `smp` is the sample point in percent for reference only.
+impl From<&BitTiming> for ZCanFdChlCfgSet {
+    fn from(value: &BitTiming) -> Self {
+        Self::new(value.tseg1 - 1, value.tseg2 - 1, value.sjw - 1, value.sample_point / 10, value.brp - 1)
+    }
+}
+
 impl ZCanFdChlCfgSet {
     #[inline(always)]
     pub fn new(tseg1: u32, tseg2: u32, sjw: u32, smp: u32, brp: u32) -> Self {
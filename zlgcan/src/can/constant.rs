@@ -12,6 +12,10 @@ pub const TSEG2: &str = "tseg2";     // Time Segment 2
 pub const SJW: &str = "sjw";         // Synchronization Jump Width
 pub const SMP: &str = "smp";         // Sampling specifies
 pub const BRP: &str = "brp";         // BaudRate Pre-scale
+/// The clock(Hz) of SJA1000 based devices to calculate bit timing, which is half of oscillator.
+pub(crate) const SJA1000_CLOCK: u32 = 8_000_000;
+/// The clock(Hz) of CANFD devices to calculate bit timing when `clock` is not configured.
+pub(crate) const CANFD_CLOCK_DEFAULT: u32 = 60_000_000;
 
 // pub const CAN_EFF_FLAG: u32 = 0x80000000; /* EFF/SFF is set in the MSB */
 // pub const CAN_RTR_FLAG: u32 = 0x40000000; /* remote transmission request */
@@ -24,6 +28,41 @@ pub const CANFD_ESI: u8 = 0x02; /* error state indicator of the transmitting nod
 
 // pub const CAN_FRAME_LENGTH: usize = 8;
 pub const CANERR_FRAME_LENGTH: usize = 8;
+
+/// The error code of `ZCanChlErrorV2`.
+pub const ZCAN_ERROR_CAN_OVERFLOW: u32 = 0x0001;        /* CAN controller FIFO overflow */
+pub const ZCAN_ERROR_CAN_ERRALARM: u32 = 0x0002;        /* CAN controller error warning */
+pub const ZCAN_ERROR_CAN_PASSIVE: u32 = 0x0004;         /* CAN controller error passive */
+pub const ZCAN_ERROR_CAN_LOSE: u32 = 0x0008;            /* CAN controller arbitration lost */
+pub const ZCAN_ERROR_CAN_BUSERR: u32 = 0x0010;          /* CAN controller bus error */
+pub const ZCAN_ERROR_CAN_BUSOFF: u32 = 0x0020;          /* CAN controller bus off */
+pub const ZCAN_ERROR_CAN_BUFFER_OVERFLOW: u32 = 0x0040; /* CAN controller buffer overflow */
+
+/// The error type in `data[0]` of `ZCanChlErrorV1`, the layout of data is same as `ZCANErrorData`:
+/// error type, error subtype, node state, RX error counter, TX error counter and error data.
+pub const ZCAN_ERR_TYPE_BUS_ERR: u8 = 1;
+pub const ZCAN_ERR_TYPE_CONTROLLER_ERR: u8 = 2;
+pub const ZCAN_ERR_TYPE_DEVICE_ERR: u8 = 3;
+/// The node state in `data[2]` of `ZCanChlErrorV1`.
+pub const ZCAN_NODE_STATE_ACTIVE: u8 = 1;
+pub const ZCAN_NODE_STATE_WARNING: u8 = 2;
+pub const ZCAN_NODE_STATE_PASSIVE: u8 = 3;
+pub const ZCAN_NODE_STATE_BUSOFF: u8 = 4;
+/// The bus error subtype in `data[1]` of `ZCanChlErrorV1`.
+pub const ZCAN_BUS_ERR_BIT_ERR: u8 = 1;
+pub const ZCAN_BUS_ERR_ACK_ERR: u8 = 2;
+pub const ZCAN_BUS_ERR_CRC_ERR: u8 = 3;
+pub const ZCAN_BUS_ERR_FORM_ERR: u8 = 4;
+pub const ZCAN_BUS_ERR_STUFF_ERR: u8 = 5;
+pub const ZCAN_BUS_ERR_OVERLOAD_ERR: u8 = 6;
+pub const ZCAN_BUS_ERR_ARBITRATION_LOST: u8 = 7;
+/// The controller and device error subtypes in `data[1]` of `ZCanChlErrorV1`.
+pub const ZCAN_CONTROLLER_RX_FIFO_OVERFLOW: u8 = 1;
+pub const ZCAN_CONTROLLER_DRIVER_RX_BUFFER_OVERFLOW: u8 = 2;
+pub const ZCAN_CONTROLLER_DRIVER_TX_BUFFER_OVERFLOW: u8 = 3;
+pub const ZCAN_DEVICE_APP_RX_BUFFER_OVERFLOW: u8 = 1;
+pub const ZCAN_DEVICE_APP_TX_BUFFER_OVERFLOW: u8 = 2;
+pub const ZCAN_DEVICE_APP_AUTO_SEND_FAILED: u8 = 3;
 // pub const CANFD_FRAME_LENGTH: usize = 64;
 pub(crate) const TIME_FLAG_VALID: u8 = 1;
 
@@ -24,6 +24,25 @@ pub struct USBCanEUWhiteList {
     pub stop: u32,
 }
 
+/// The cyclic transmission object(`ZCAN_AUTO_TRANSMIT_OBJ` or `ZCANFD_AUTO_TRANSMIT_OBJ`),
+/// `T` is the transmitting frame of device.
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct ZCanAutoTransmitObj<T> {
+    pub(crate) enable: c_ushort,
+    pub(crate) index: c_ushort,
+    pub(crate) interval: c_uint,    // ms
+    pub(crate) obj: T,
+}
+
+impl<T> ZCanAutoTransmitObj<T> {
+    /// The `obj` is disabled when `interval` is 0.
+    #[inline]
+    pub fn new(index: u16, interval: u32, obj: T) -> Self {
+        Self { enable: (interval > 0) as c_ushort, index, interval, obj }
+    }
+}
+
 pub trait NewZCanFrame {
     type Error;
     fn new<T>(
@@ -13,12 +13,15 @@ use std::collections::HashMap;
 use std::fs::read_to_string;
 use std::sync::{Arc, Weak};
 use serde::Deserialize;
+use rs_can::{CanFilter, ChannelConfig};
+use rs_can::timing::{BitTiming, BitTimingConst};
 use crate::device::ZCanDeviceType;
 use crate::error::ZCanError;
 
 /// The deserialize object mapped to configuration file context.
 #[derive(Debug, Deserialize)]
 pub struct BitrateCfg {
+    #[serde(default)]
     pub(crate) bitrate: HashMap<String, HashMap<String, u32>>,
     pub(crate) clock: Option<u32>,
     pub(crate) data_bitrate: Option<HashMap<String, HashMap<String, u32>>>
@@ -161,21 +164,36 @@ impl CanChlCfg {
     }
 }
 
-fn to_chl_cfg(mode: u8, bitrate: u32, cfg_ctx: &BitrateCfg, ext: &CanChlCfgExt) -> Result<ZCanChlCfg, ZCanError> {
-    match cfg_ctx.bitrate.get(&bitrate.to_string()) {
+/// Calculate the bit timing when it is not configured in file.
+#[inline]
+fn calc_timing(clock: u32, bitrate: u32, cst: &BitTimingConst) -> Result<BitTiming, ZCanError> {
+    let timing = BitTiming::calculate(clock, bitrate, None, cst)
+        .map_err(|e| ZCanError::ConfigurationError(e.to_string()))?;
+    log::debug!("ZLGCAN - bitrate: {} is not configured, calculated: {:?}", bitrate, timing);
+    Ok(timing)
+}
+
+fn to_chl_cfg(mode: u8, bitrate: u32, cfg_ctx: Option<&BitrateCfg>, ext: &CanChlCfgExt) -> Result<ZCanChlCfg, ZCanError> {
+    let (timing0, timing1) = match cfg_ctx.and_then(|c| c.bitrate.get(&bitrate.to_string())) {
         Some(v) => {
             let timing0 = v.get(TIMING0)
                 .ok_or(ZCanError::ConfigurationError(format!("`{}` is not configured in file!", TIMING0)))?;
             let timing1 = v.get(TIMING1)
                 .ok_or(ZCanError::ConfigurationError(format!("`{}` is not configured in file!", TIMING1)))?;
-            ZCanChlCfg::new(
-                mode, *timing0, *timing1, ext.filter, ext.acc_code, ext.acc_mask
+            (*timing0, *timing1)
+        },
+        None => {
+            let timing = calc_timing(SJA1000_CLOCK, bitrate, &BitTimingConst::SJA1000)?;
+            // BTR0: SJW[7:6] BRP[5:0], BTR1: SAM[7] TSEG2[6:4] TSEG1[3:0]
+            (
+                (timing.sjw - 1) << 6 | (timing.brp - 1),
+                (timing.tseg2 - 1) << 4 | (timing.tseg1 - 1),
             )
         },
-        None => Err(ZCanError::ConfigurationError(
-            format!("the bitrate: `{}` is not configured", bitrate)
-        )),
-    }
+    };
+    ZCanChlCfg::new(
+        mode, timing0, timing1, ext.filter, ext.acc_code, ext.acc_mask
+    )
 }
 
 impl TryFrom<&CanChlCfg> for ZCanChlCfgV1 {
@@ -185,8 +203,7 @@ impl TryFrom<&CanChlCfg> for ZCanChlCfgV1 {
         let dev_type = value.dev_type;
         let binding = value.cfg_ctx.upgrade()
             .ok_or(ZCanError::ConfigurationError("Failed to upgrade configuration context".to_string()))?;
-        let cfg = binding.get(&dev_type.to_string())
-            .ok_or(ZCanError::ConfigurationError(format!("device: {:?} is not configured in file!", dev_type)))?;
+        let cfg = binding.get(&dev_type.to_string());
         let dev_type = value.device_type()?;
         match dev_type {
             ZCanDeviceType::ZCAN_USBCANFD_800U => {
@@ -236,12 +253,11 @@ impl TryFrom<&CanChlCfg> for ZCanChlCfgV2 {
         let dev_type = value.dev_type;
         let binding = value.cfg_ctx.upgrade()
             .ok_or(ZCanError::ConfigurationError("Failed to upgrade configuration context".to_string()))?;
-        let cfg = binding.get(&dev_type.to_string())
-            .ok_or(ZCanError::ConfigurationError(format!("device: {:?} is not configured in file!", dev_type)))?;
+        let cfg = binding.get(&dev_type.to_string());
         if value.device_type()?
             .canfd_support() {
-            let clock = cfg.clock
-                .ok_or(ZCanError::ConfigurationError("`clock` is not configured in file!".to_string()))?;
+            let clock = cfg.and_then(|c| c.clock)
+                .unwrap_or(CANFD_CLOCK_DEFAULT);
             let ext = &value.extra;
             let (aset, dset) = get_fd_set(value, cfg, ext.dbitrate)?;
             Ok(Self::from(
@@ -276,8 +292,14 @@ impl CanChlCfgFactory {
             },
             Err(_) => BITRATE_CFG_FILENAME.into(),
         };
-        let data = read_to_string(libpath.clone())
-            .map_err(|e| ZCanError::ConfigurationError(format!("Unable to read `{}`: {:?}", libpath, e)))?;
+        let data = match read_to_string(libpath.clone()) {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                log::info!("ZLGCAN - `{}` is not found, the bit timing will be calculated", libpath);
+                return Ok(Self(Default::default()));
+            },
+            Err(e) => return Err(ZCanError::ConfigurationError(format!("Unable to read `{}`: {:?}", libpath, e))),
+        };
         let result = serde_yaml::from_str(&data)
             .map_err(|e| ZCanError::ConfigurationError(format!("Error parsing YAML: {:?}", e)))?;
         Ok(Self(Arc::new(result)))
@@ -291,59 +313,125 @@ impl CanChlCfgFactory {
         bitrate: u32,
         extra: CanChlCfgExt
     ) -> Result<CanChlCfg, ZCanError> {
-        if self.0.contains_key(&dev_type.to_string()) {
-            Ok(CanChlCfg::new(dev_type, can_type, mode, bitrate, extra, Arc::downgrade(&self.0)))
+        // the bit timing is calculated when device is not configured in file.
+        Ok(CanChlCfg::new(dev_type, can_type, mode, bitrate, extra, Arc::downgrade(&self.0)))
+    }
+
+    /// Create the channel configuration from the common [`ChannelConfig`].
+    pub fn from_channel_config(
+        &self,
+        dev_type: u32,
+        cfg: &ChannelConfig
+    ) -> Result<CanChlCfg, ZCanError> {
+        let can_type = if ZCanDeviceType::try_from(dev_type)?.canfd_support() {
+            ZCanChlType::CANFD_ISO
         }
         else {
-            Err(ZCanError::ConfigurationError(
-                format!("device: {:?} is not configured in file!", dev_type)
-            ))
-        }
+            ZCanChlType::CAN
+        };
+        let mode = if cfg.listen_only { ZCanChlMode::ListenOnly } else { ZCanChlMode::Normal };
+        let extra = match acceptance_filter(&cfg.filters) {
+            Some((code, mask)) => CanChlCfgExt::new(
+                Some(ZCanFilterType::Single as u8), cfg.dbitrate, cfg.resistance, Some(code), Some(mask), None
+            ),
+            None => CanChlCfgExt::new(None, cfg.dbitrate, cfg.resistance, None, None, None),
+        };
+
+        self.new_can_chl_cfg(dev_type, can_type as u8, mode as u8, cfg.bitrate, extra)
+    }
+}
+
+/// The acceptance code and mask of single filter mode, `None` when all frames are accepted.
+///
+/// There is only one comparator, so the filters are merged and the frames are matched exactly
+/// in software when receiving. The filters of both identifier types can't be merged.
+pub(crate) fn acceptance_filter(filters: &[CanFilter]) -> Option<(u32, u32)> {
+    let first = filters.first()?;
+    if filters.iter().any(|f| f.extended != first.extended) {
+        return None;
     }
+    let merged = CanFilter::merge_all(filters)?;
+    // the identifier is left aligned, and the bit 1 of mask is "don't care"
+    let (width, shift) = if merged.extended { (0x1FFFFFFF, 3) } else { (0x7FF, 21) };
+    let can_mask = merged.can_mask & width;
+    Some(((merged.can_id & can_mask) << shift, !(can_mask << shift)))
 }
 
 fn get_fd_set(
     value: &CanChlCfg,
-    cfg: &BitrateCfg,
+    cfg: Option<&BitrateCfg>,
     dbitrate: Option<u32>
 ) -> Result<(ZCanFdChlCfgSet, ZCanFdChlCfgSet), ZCanError> {
     let bitrate = value.bitrate;
-    let bitrate_ctx = &cfg.bitrate;
-    let dbitrate_ctx = &cfg.data_bitrate;
-    let aset = bitrate_ctx
-        .get(&bitrate.to_string())
-        .ok_or(ZCanError::ConfigurationError(format!("bitrate `{}` is not configured in file!", bitrate)))?;
-    let dset=
-        match dbitrate {
-            Some(v) => {    // dbitrate is not None
-                match dbitrate_ctx {
-                    Some(ctx) => {  // dbitrate context is not None
-                        match ctx.get(&v.to_string()) {
-                            Some(value) => Ok(value),
-                            None => Err(ZCanError::ConfigurationError(format!("data bitrate `{}` is not configured in file!", v))),
-                        }
-                    },
-                    None => {   // dbitrate context is None
-                        match bitrate_ctx.get(&v.to_string()) {
-                            Some(value) => Ok(value),
-                            None => Err(ZCanError::ConfigurationError(format!("data bitrate `{}` is not configured in file!", v))),
-                        }
-                    }
-                }
-            },
-            None => {   // dbitrate is None
-                match dbitrate_ctx {
-                    Some(ctx) => {
-                        match ctx.get(&bitrate.to_string()) {
-                            Some(value) => Ok(value),
-                            None => Ok(aset),
-                        }
-                    },
-                    None => Ok(aset),
-                }
-            }
-        }?;
+    let clock = cfg.and_then(|c| c.clock)
+        .unwrap_or(CANFD_CLOCK_DEFAULT);
+    let aset = cfg.and_then(|c| c.bitrate.get(&bitrate.to_string()));
+    let dset = match (cfg, dbitrate) {
+        // the data bitrate is searched in `bitrate` when `data_bitrate` is not configured
+        (Some(c), Some(v)) => c.data_bitrate.as_ref()
+            .unwrap_or(&c.bitrate)
+            .get(&v.to_string()),
+        (Some(c), None) => c.data_bitrate.as_ref()
+            .and_then(|ctx| ctx.get(&bitrate.to_string()))
+            .or(aset),
+        (None, _) => None,
+    };
 
-    Ok((ZCanFdChlCfgSet::try_from(aset)?, ZCanFdChlCfgSet::try_from(dset)?))
+    let aset = match aset {
+        Some(v) => ZCanFdChlCfgSet::try_from(v)?,
+        None => ZCanFdChlCfgSet::from(&calc_timing(clock, bitrate, &BitTimingConst::MCAN_NOMINAL)?),
+    };
+    let dset = match dset {
+        Some(v) => ZCanFdChlCfgSet::try_from(v)?,
+        None => ZCanFdChlCfgSet::from(&calc_timing(clock, dbitrate.unwrap_or(bitrate), &BitTimingConst::MCAN_DATA)?),
+    };
+
+    Ok((aset, dset))
 }
 
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use crate::device::ZCanDeviceType;
+    use rs_can::CanFilter;
+    use super::{acceptance_filter, get_fd_set, to_chl_cfg, CanChlCfgExt, CanChlCfgFactory, ZCanChlMode, ZCanChlType};
+
+    #[test]
+    fn test_calculated_timing() -> anyhow::Result<()> {
+        let factory = CanChlCfgFactory(Arc::new(Default::default()));
+        let ext = CanChlCfgExt::new(None, Some(2_000_000), None, None, None, None);
+        let cfg = factory.new_can_chl_cfg(
+            ZCanDeviceType::ZCAN_USBCANFD_800U as u32,
+            ZCanChlType::CANFD_ISO as u8,
+            ZCanChlMode::Normal as u8,
+            500_000,
+            ext
+        )?;
+
+        // 60MHz: 500k = 1 + 104 + 15, 2M = 1 + 22 + 7
+        let (aset, dset) = get_fd_set(&cfg, None, ext.dbitrate())?;
+        assert_eq!(aset.get_timing(), 14 << 15 | 14 << 8 | 103);
+        assert_eq!(dset.get_timing(), 6 << 15 | 6 << 8 | 21);
+
+        assert!(to_chl_cfg(ZCanChlMode::Normal as u8, 500_000, None, &ext).is_ok());
+        assert!(to_chl_cfg(ZCanChlMode::Normal as u8, 3_000_000, None, &ext).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_acceptance_filter() {
+        assert_eq!(acceptance_filter(&[]), None);
+        assert_eq!(acceptance_filter(&[
+            CanFilter { can_id: 0x7E0, can_mask: 0x7FF, extended: false },
+            CanFilter { can_id: 0x7E8, can_mask: 0x7FF, extended: false },
+        ]), Some((0x7E0 << 21, !(0x7F7 << 21))));
+        assert_eq!(acceptance_filter(&[
+            CanFilter { can_id: 0x18DA00F1, can_mask: 0x1FFFFFFF, extended: true },
+        ]), Some((0x18DA00F1 << 3, 0x07)));
+        assert_eq!(acceptance_filter(&[
+            CanFilter { can_id: 0x7DF, can_mask: 0x7FF, extended: false },
+            CanFilter { can_id: 0x18DA00F1, can_mask: 0x1FFFFFFF, extended: true },
+        ]), None);
+    }
+}
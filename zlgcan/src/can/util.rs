@@ -1,13 +1,12 @@
+use std::time::Instant;
 use isotp_rs::can::{IdentifierFlags, SFF_MASK, EFF_MASK, frame::{Frame, Direct}, identifier::Id};
-use rs_can::utils::data_resize;
-use crate::can::constant::{CANFD_BRS, CANFD_ESI, ZCanFrameType};
+use rs_can::{clock_sync::ClockSync, TimeBase, error_frame::{BusState, ErrorCounters, ErrorEvent, ErrorKind, ProtocolError}, utils::data_resize};
+use crate::can::constant::*;
 use crate::can::frame::NewZCanFrame;
 use crate::{TryFrom, TryFromIterator};
 use crate::error::ZCanError;
-use crate::utils::{fix_device_time, fix_system_time};
 use super::{
-    channel::{ZCanChlErrorV1, ZCanChlErrorV2},
-    constant::ZCanHdrInfoField,
+    channel::{ZCanChlErrorV1, ZCanChlErrorV2, ZCanChlStatus},
     frame::{ZCanHdrInfo, ZCanFrameV1, ZCanFrameV2, ZCanFrameV3, ZCanFdFrameV1, ZCanFdFrameV2},
     message::CanMessage
 };
@@ -48,7 +47,7 @@ fn frame_new<T: NewZCanFrame<Error = ZCanError>>(
            msg.channel(),
            msg.data(),
            info,
-           fix_device_time(timestamp)
+           timestamp
     )
 }
 
@@ -59,9 +58,9 @@ impl TryFrom<CanMessage, u64> for ZCanFrameV1 {
     }
 }
 
-impl TryFrom<ZCanFrameV1, u64> for CanMessage {
+impl TryFrom<ZCanFrameV1, &mut ClockSync> for CanMessage {
     type Error = ZCanError;
-    fn try_from(value: ZCanFrameV1, timestamp: u64) -> Result<Self, Self::Error> {
+    fn try_from(value: ZCanFrameV1, clock: &mut ClockSync) -> Result<Self, Self::Error> {
         let id = if value.ext_flag > 0 {
             Id::Extended(value.can_id)
         }
@@ -80,7 +79,8 @@ impl TryFrom<ZCanFrameV1, u64> for CanMessage {
         }?;
 
         message.set_direct(Direct::Receive)
-            .set_timestamp(Some(fix_system_time(value.timestamp as u64, timestamp)))
+            .set_timestamp(Some(clock.synchronize(value.timestamp as u64, Instant::now())))
+            .set_time_base(TimeBase::Monotonic)
             .set_channel(value.channel);
 
         Ok(message)
@@ -96,11 +96,11 @@ impl TryFromIterator<CanMessage, u64> for Vec<ZCanFrameV1> {
     }
 }
 
-impl TryFromIterator<ZCanFrameV1, u64> for Vec<CanMessage> {
+impl TryFromIterator<ZCanFrameV1, &mut ClockSync> for Vec<CanMessage> {
     type Error = ZCanError;
-    fn try_from_iter<T: IntoIterator<Item=ZCanFrameV1>>(iter: T, timestamp: u64) -> Result<Self, Self::Error> {
+    fn try_from_iter<T: IntoIterator<Item=ZCanFrameV1>>(iter: T, clock: &mut ClockSync) -> Result<Self, Self::Error> {
         iter.into_iter()
-            .map(|v| <CanMessage as TryFrom<ZCanFrameV1, u64>>::try_from(v, timestamp))
+            .map(|v| <CanMessage as TryFrom<ZCanFrameV1, &mut ClockSync>>::try_from(v, clock))
             .collect()
     }
 }
@@ -112,9 +112,9 @@ impl TryFrom<CanMessage, u64> for ZCanFrameV2 {
     }
 }
 
-impl TryFrom<ZCanFrameV2, u64> for CanMessage {
+impl TryFrom<ZCanFrameV2, &mut ClockSync> for CanMessage {
     type Error = ZCanError;
-    fn try_from(value: ZCanFrameV2, timestamp: u64) -> Result<Self, Self::Error> {
+    fn try_from(value: ZCanFrameV2, clock: &mut ClockSync) -> Result<Self, Self::Error> {
         let hdr = value.hdr;
         let info = hdr.info;
 
@@ -136,7 +136,8 @@ impl TryFrom<ZCanFrameV2, u64> for CanMessage {
         }?;
 
         message.set_direct(Direct::Receive)
-            .set_timestamp(Some(fix_system_time(value.hdr.timestamp as u64, timestamp)))
+            .set_timestamp(Some(clock.synchronize(value.hdr.timestamp as u64, Instant::now())))
+            .set_time_base(TimeBase::Monotonic)
             .set_channel(hdr.channel)
             .set_error_frame(info.get_field(ZCanHdrInfoField::IsErrorFrame) > 0);
 
@@ -153,11 +154,11 @@ impl TryFromIterator<CanMessage, u64> for Vec<ZCanFrameV2> {
     }
 }
 
-impl TryFromIterator<ZCanFrameV2, u64> for Vec<CanMessage> {
+impl TryFromIterator<ZCanFrameV2, &mut ClockSync> for Vec<CanMessage> {
     type Error = ZCanError;
-    fn try_from_iter<T: IntoIterator<Item=ZCanFrameV2>>(iter: T, timestamp: u64) -> Result<Self, Self::Error> {
+    fn try_from_iter<T: IntoIterator<Item=ZCanFrameV2>>(iter: T, clock: &mut ClockSync) -> Result<Self, Self::Error> {
         iter.into_iter()
-            .map(|v| <CanMessage as TryFrom<ZCanFrameV2, u64>>::try_from(v, timestamp))
+            .map(|v| <CanMessage as TryFrom<ZCanFrameV2, &mut ClockSync>>::try_from(v, clock))
             .collect()
     }
 }
@@ -169,9 +170,9 @@ impl TryFrom<CanMessage, u64> for ZCanFrameV3 {
     }
 }
 
-impl TryFrom<ZCanFrameV3, u64> for CanMessage {
+impl TryFrom<ZCanFrameV3, &mut ClockSync> for CanMessage {
     type Error = ZCanError;
-    fn try_from(value: ZCanFrameV3, timestamp: u64) -> Result<Self, Self::Error> {
+    fn try_from(value: ZCanFrameV3, clock: &mut ClockSync) -> Result<Self, Self::Error> {
         let hdr = value.hdr;
         let can_id = hdr.can_id;
 
@@ -193,7 +194,8 @@ impl TryFrom<ZCanFrameV3, u64> for CanMessage {
         }?;
 
         message.set_direct(Direct::Receive)
-            .set_timestamp(Some(fix_system_time(value.ts_or_mode as u64, timestamp)))
+            .set_timestamp(Some(clock.synchronize(value.ts_or_mode as u64, Instant::now())))
+            .set_time_base(TimeBase::Monotonic)
             .set_channel(hdr.__res0)
             .set_error_frame((can_id & IdentifierFlags::ERROR.bits()) > 0);
 
@@ -210,11 +212,11 @@ impl TryFromIterator<CanMessage, u64> for Vec<ZCanFrameV3> {
     }
 }
 
-impl TryFromIterator<ZCanFrameV3, u64> for Vec<CanMessage> {
+impl TryFromIterator<ZCanFrameV3, &mut ClockSync> for Vec<CanMessage> {
     type Error = ZCanError;
-    fn try_from_iter<T: IntoIterator<Item=ZCanFrameV3>>(iter: T, timestamp: u64) -> Result<Self, Self::Error> {
+    fn try_from_iter<T: IntoIterator<Item=ZCanFrameV3>>(iter: T, clock: &mut ClockSync) -> Result<Self, Self::Error> {
         iter.into_iter()
-            .map(|v| <CanMessage as TryFrom<ZCanFrameV3, u64>>::try_from(v, timestamp))
+            .map(|v| <CanMessage as TryFrom<ZCanFrameV3, &mut ClockSync>>::try_from(v, clock))
             .collect()
     }
 }
@@ -227,9 +229,9 @@ impl TryFrom<CanMessage, u64> for ZCanFdFrameV1 {
     }
 }
 
-impl TryFrom<ZCanFdFrameV1, u64> for CanMessage {
+impl TryFrom<ZCanFdFrameV1, &mut ClockSync> for CanMessage {
     type Error = ZCanError;
-    fn try_from(value: ZCanFdFrameV1, timestamp: u64) -> Result<Self, Self::Error> {
+    fn try_from(value: ZCanFdFrameV1, clock: &mut ClockSync) -> Result<Self, Self::Error> {
         let hdr = value.hdr;
         let info = hdr.info;
         let can_id = hdr.can_id;
@@ -253,7 +255,8 @@ impl TryFrom<ZCanFdFrameV1, u64> for CanMessage {
 
         message.set_direct(Direct::Receive)
             .set_can_fd(true)
-            .set_timestamp(Some(fix_system_time(hdr.timestamp as u64, timestamp)))
+            .set_timestamp(Some(clock.synchronize(hdr.timestamp as u64, Instant::now())))
+            .set_time_base(TimeBase::Monotonic)
             .set_channel(hdr.channel)
             .set_error_frame((can_id & IdentifierFlags::ERROR.bits()) > 0)
             .set_bitrate_switch(info.get_field(ZCanHdrInfoField::IsBitrateSwitch) > 0)
@@ -272,11 +275,11 @@ impl TryFromIterator<CanMessage, u64> for Vec<ZCanFdFrameV1> {
     }
 }
 
-impl TryFromIterator<ZCanFdFrameV1, u64> for Vec<CanMessage> {
+impl TryFromIterator<ZCanFdFrameV1, &mut ClockSync> for Vec<CanMessage> {
     type Error = ZCanError;
-    fn try_from_iter<T: IntoIterator<Item=ZCanFdFrameV1>>(iter: T, timestamp: u64) -> Result<Self, Self::Error> {
+    fn try_from_iter<T: IntoIterator<Item=ZCanFdFrameV1>>(iter: T, clock: &mut ClockSync) -> Result<Self, Self::Error> {
         iter.into_iter()
-            .map(|v| <CanMessage as TryFrom<ZCanFdFrameV1, u64>>::try_from(v, timestamp))
+            .map(|v| <CanMessage as TryFrom<ZCanFdFrameV1, &mut ClockSync>>::try_from(v, clock))
             .collect()
     }
 }
@@ -288,9 +291,9 @@ impl TryFrom<CanMessage, u64> for ZCanFdFrameV2 {
     }
 }
 
-impl TryFrom<ZCanFdFrameV2, u64> for CanMessage {
+impl TryFrom<ZCanFdFrameV2, &mut ClockSync> for CanMessage {
     type Error = ZCanError;
-    fn try_from(value: ZCanFdFrameV2, timestamp: u64) -> Result<Self, Self::Error> {
+    fn try_from(value: ZCanFdFrameV2, clock: &mut ClockSync) -> Result<Self, Self::Error> {
         let hdr = value.hdr;
         let can_id = hdr.can_id;
         let flag = hdr.flag;
@@ -314,7 +317,8 @@ impl TryFrom<ZCanFdFrameV2, u64> for CanMessage {
 
         message.set_direct(Direct::Receive)
             .set_can_fd(true)
-            .set_timestamp(Some(fix_system_time(value.ts_or_mode as u64, timestamp)))
+            .set_timestamp(Some(clock.synchronize(value.ts_or_mode as u64, Instant::now())))
+            .set_time_base(TimeBase::Monotonic)
             .set_channel(hdr.__res0)
             .set_error_frame(can_id & IdentifierFlags::ERROR.bits() > 0)
             .set_bitrate_switch(flag & CANFD_BRS > 0)
@@ -333,46 +337,184 @@ impl TryFromIterator<CanMessage, u64> for Vec<ZCanFdFrameV2> {
     }
 }
 
-impl TryFromIterator<ZCanFdFrameV2, u64> for Vec<CanMessage> {
+impl TryFromIterator<ZCanFdFrameV2, &mut ClockSync> for Vec<CanMessage> {
     type Error = ZCanError;
-    fn try_from_iter<T: IntoIterator<Item=ZCanFdFrameV2>>(iter: T, timestamp: u64) -> Result<Self, Self::Error> {
+    fn try_from_iter<T: IntoIterator<Item=ZCanFdFrameV2>>(iter: T, clock: &mut ClockSync) -> Result<Self, Self::Error> {
         iter.into_iter()
-            .map(|v| <CanMessage as TryFrom<ZCanFdFrameV2, u64>>::try_from(v, timestamp))
+            .map(|v| <CanMessage as TryFrom<ZCanFdFrameV2, &mut ClockSync>>::try_from(v, clock))
             .collect()
     }
 }
 
-impl TryFrom<ZCanChlErrorV1, u64> for CanMessage {
+impl TryFrom<ZCanChlErrorV1, &mut ClockSync> for ErrorEvent<u8> {
     type Error = ZCanError;
-    fn try_from(value: ZCanChlErrorV1, timestamp: u64) -> Result<Self, Self::Error> {
+    fn try_from(value: ZCanChlErrorV1, clock: &mut ClockSync) -> Result<Self, Self::Error> {
         let hdr = value.hdr;
-        let info = hdr.info;
-
-        let id = if info.get_field(ZCanHdrInfoField::IsExtendFrame) > 0 {
-            Id::Extended(hdr.can_id)
+        let data = value.data;
+
+        let mut event = ErrorEvent::new(hdr.channel, clock.synchronize(hdr.timestamp as u64, Instant::now()));
+        match (data[0], data[1]) {
+            (ZCAN_ERR_TYPE_BUS_ERR, ZCAN_BUS_ERR_ARBITRATION_LOST) => {
+                event.add(ErrorKind::ArbitrationLost(Some(data[5] & 0x1F)));
+            },
+            (ZCAN_ERR_TYPE_BUS_ERR, subtype) => {
+                let error = match subtype {
+                    ZCAN_BUS_ERR_BIT_ERR => Some(ProtocolError::Bit),
+                    ZCAN_BUS_ERR_ACK_ERR => Some(ProtocolError::Ack),
+                    ZCAN_BUS_ERR_CRC_ERR => Some(ProtocolError::Crc),
+                    ZCAN_BUS_ERR_FORM_ERR => Some(ProtocolError::Form),
+                    ZCAN_BUS_ERR_STUFF_ERR => Some(ProtocolError::Stuff),
+                    ZCAN_BUS_ERR_OVERLOAD_ERR => Some(ProtocolError::Overload),
+                    _ => None,  // node state changed only
+                };
+                if let Some(error) = error {
+                    // the direction is not reported, only the transmitter detects ACK error
+                    event.add(ErrorKind::Protocol { error, transmitting: error == ProtocolError::Ack });
+                }
+            },
+            (ZCAN_ERR_TYPE_CONTROLLER_ERR, ZCAN_CONTROLLER_RX_FIFO_OVERFLOW)
+            | (ZCAN_ERR_TYPE_CONTROLLER_ERR, ZCAN_CONTROLLER_DRIVER_RX_BUFFER_OVERFLOW)
+            | (ZCAN_ERR_TYPE_DEVICE_ERR, ZCAN_DEVICE_APP_RX_BUFFER_OVERFLOW) => {
+                event.add(ErrorKind::Overrun { transmitting: false });
+            },
+            (ZCAN_ERR_TYPE_CONTROLLER_ERR, ZCAN_CONTROLLER_DRIVER_TX_BUFFER_OVERFLOW)
+            | (ZCAN_ERR_TYPE_DEVICE_ERR, ZCAN_DEVICE_APP_TX_BUFFER_OVERFLOW) => {
+                event.add(ErrorKind::Overrun { transmitting: true });
+            },
+            (ZCAN_ERR_TYPE_DEVICE_ERR, ZCAN_DEVICE_APP_AUTO_SEND_FAILED) => {
+                event.add(ErrorKind::TransmitTimeout);
+            },
+            _ => {},
         }
-        else {
-            Id::Standard(hdr.can_id  as u16)
+
+        let state = match data[2] {
+            ZCAN_NODE_STATE_ACTIVE => Some(BusState::ErrorActive),
+            ZCAN_NODE_STATE_WARNING => Some(BusState::ErrorWarning),
+            ZCAN_NODE_STATE_PASSIVE => Some(BusState::ErrorPassive),
+            ZCAN_NODE_STATE_BUSOFF => Some(BusState::BusOff),
+            _ => None,
         };
-        let mut data = value.data.to_vec();
-        data_resize(&mut data, hdr.len as usize);
-        let mut message = CanMessage::new(id, data.as_slice())
-            .ok_or(ZCanError::Other("invalid data length".to_string()))?;
+        if let Some(state) = state {
+            event.add(ErrorKind::State(state));
+        }
+        event.set_counters(Some(ErrorCounters::new(data[4], data[3])));
 
-        message.set_direct(Direct::Receive)
-            .set_timestamp(Some(fix_system_time(hdr.timestamp as u64, timestamp)))
-            .set_channel(hdr.channel)
-            .set_error_frame(true);
+        Ok(event)
+    }
+}
 
-        Ok(message)
+impl TryFrom<ZCanChlErrorV1, &mut ClockSync> for CanMessage {
+    type Error = ZCanError;
+    fn try_from(value: ZCanChlErrorV1, clock: &mut ClockSync) -> Result<Self, Self::Error> {
+        let event = <ErrorEvent<u8> as TryFrom<ZCanChlErrorV1, &mut ClockSync>>::try_from(value, clock)?;
+        Ok(CanMessage::from(&event))
     }
 }
 
-#[allow(unused_variables)]
-impl TryFrom<ZCanChlErrorV2, ()> for CanMessage {
+/// The parameter is the channel and the timestamp, the `ZCanChlErrorV2` has neither.
+impl TryFrom<ZCanChlErrorV2, (u8, u64)> for ErrorEvent<u8> {
     type Error = ZCanError;
-    fn try_from(value: ZCanChlErrorV2, _: ()) -> Result<Self, Self::Error> {
-        todo!()
+    fn try_from(value: ZCanChlErrorV2, (channel, timestamp): (u8, u64)) -> Result<Self, Self::Error> {
+        let code = value.error_code;
+        // the error code capture register(ECC) of SJA1000, RX error counter and TX error counter
+        let [ecc, rx, tx] = value.passive_ErrData;
+
+        let mut event = ErrorEvent::new(channel, timestamp);
+        if code & (ZCAN_ERROR_CAN_OVERFLOW | ZCAN_ERROR_CAN_BUFFER_OVERFLOW) > 0 {
+            event.add(ErrorKind::Overrun { transmitting: false });
+        }
+        if code & ZCAN_ERROR_CAN_LOSE > 0 {
+            event.add(ErrorKind::ArbitrationLost(Some(value.arLost_ErrData & 0x1F)));
+        }
+        if code & ZCAN_ERROR_CAN_BUSERR > 0 {
+            let error = match (ecc >> 6, ecc & 0x1F) {
+                (0b00, _) => ProtocolError::Bit,
+                (0b01, _) => ProtocolError::Form,
+                (0b10, _) => ProtocolError::Stuff,
+                (_, 0x08) | (_, 0x18) => ProtocolError::Crc,    // CRC sequence and delimiter
+                (_, 0x19) | (_, 0x1B) => ProtocolError::Ack,    // ACK slot and delimiter
+                _ => ProtocolError::Other,
+            };
+            event.add(ErrorKind::Protocol { error, transmitting: ecc & 0x20 == 0 });
+        }
+
+        if code & ZCAN_ERROR_CAN_BUSOFF > 0 {
+            event.add(ErrorKind::State(BusState::BusOff));
+        }
+        else if code & ZCAN_ERROR_CAN_PASSIVE > 0 {
+            event.add(ErrorKind::State(BusState::ErrorPassive))
+                .set_counters(Some(ErrorCounters::new(tx, rx)));
+        }
+        else if code & ZCAN_ERROR_CAN_ERRALARM > 0 {
+            event.add(ErrorKind::State(BusState::ErrorWarning));
+        }
+
+        Ok(event)
+    }
+}
+
+impl TryFrom<ZCanChlErrorV2, (u8, u64)> for CanMessage {
+    type Error = ZCanError;
+    fn try_from(value: ZCanChlErrorV2, param: (u8, u64)) -> Result<Self, Self::Error> {
+        let event = <ErrorEvent<u8> as TryFrom<ZCanChlErrorV2, (u8, u64)>>::try_from(value, param)?;
+        Ok(CanMessage::from(&event))
     }
 }
 
+/// The parameter is the channel and the timestamp.
+impl TryFrom<ZCanChlStatus, (u8, u64)> for ErrorEvent<u8> {
+    type Error = ZCanError;
+    fn try_from(value: ZCanChlStatus, (channel, timestamp): (u8, u64)) -> Result<Self, Self::Error> {
+        let counters = ErrorCounters::new(value.regTECounter, value.regRECounter);
+        let mut event = ErrorEvent::new(channel, timestamp);
+        event.add(ErrorKind::State(BusState::from_counters(&counters)))
+            .set_counters(Some(counters));
+
+        Ok(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use rs_can::{clock_sync::ClockSync, error_frame::{BusState, ErrorCounters, ErrorEvent, ErrorKind, ProtocolError}};
+    use crate::TryFrom;
+    use crate::can::{constant::*, channel::{ZCanChlErrorV1, ZCanChlErrorV2, ZCanChlStatus}};
+
+    #[test]
+    fn test_error_event() -> anyhow::Result<()> {
+        // stuff error while receiving, the node is error passive
+        let error = ZCanChlErrorV2 {
+            error_code: ZCAN_ERROR_CAN_BUSERR | ZCAN_ERROR_CAN_PASSIVE,
+            passive_ErrData: [0b1010_0000, 130, 8],
+            arLost_ErrData: 0,
+        };
+        let event = <ErrorEvent<u8> as TryFrom<_, _>>::try_from(error, (1, 100))?;
+        assert_eq!(event.kinds(), [
+            ErrorKind::Protocol { error: ProtocolError::Stuff, transmitting: false },
+            ErrorKind::State(BusState::ErrorPassive),
+        ]);
+        assert_eq!(event.counters(), Some(ErrorCounters::new(8, 130)));
+
+        let error = ZCanChlErrorV2 { error_code: ZCAN_ERROR_CAN_LOSE | ZCAN_ERROR_CAN_BUSOFF, passive_ErrData: [0; 3], arLost_ErrData: 0x05 };
+        let event = <ErrorEvent<u8> as TryFrom<_, _>>::try_from(error, (0, 0))?;
+        assert_eq!(event.kinds(), [ErrorKind::ArbitrationLost(Some(5)), ErrorKind::State(BusState::BusOff)]);
+
+        let mut error = ZCanChlErrorV1::default();
+        error.hdr.channel = 1;
+        error.data = [ZCAN_ERR_TYPE_BUS_ERR, ZCAN_BUS_ERR_ACK_ERR, ZCAN_NODE_STATE_WARNING, 0, 100, 0, 0, 0];
+        let event = <ErrorEvent<u8> as TryFrom<_, _>>::try_from(error, &mut ClockSync::new(Duration::from_micros(1)))?;
+        assert_eq!(*event.channel(), 1);
+        assert_eq!(event.kinds(), [
+            ErrorKind::Protocol { error: ProtocolError::Ack, transmitting: true },
+            ErrorKind::State(BusState::ErrorWarning),
+        ]);
+        assert_eq!(event.counters(), Some(ErrorCounters::new(100, 0)));
+
+        let status = ZCanChlStatus { regRECounter: 0, regTECounter: 97, ..Default::default() };
+        let event = <ErrorEvent<u8> as TryFrom<_, _>>::try_from(status, (0, 0))?;
+        assert_eq!(event.state(), Some(BusState::ErrorWarning));
+
+        Ok(())
+    }
+}
@@ -1,7 +1,10 @@
 use std::collections::HashMap;
 use std::ffi::{c_uchar, c_ushort, CString};
 use std::fmt::{Display, Formatter};
-use rs_can::utils::system_timestamp;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use rs_can::CanFilter;
+use rs_can::clock_sync::ClockSync;
 use crate::device::{DeriveInfo, ZCanDeviceType};
 use crate::error::ZCanError;
 
@@ -152,18 +155,27 @@ impl ZDeviceContext {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct ZChannelContext {
     device: ZDeviceContext,
     channel: u8,
     chl_hdl: Option<u32>,
-    timestamp: u64,
+    clock: Arc<Mutex<ClockSync>>,
+    filters: Vec<CanFilter>,
 }
 
 impl ZChannelContext {
     #[inline]
     pub fn new(device: ZDeviceContext, channel: u8, chl_hdl: Option<u32>) -> Self {
-        Self { device, channel, chl_hdl, timestamp: Default::default() }
+        // the timestamp of `ZCanFrameV1` is 100us, and others are 1us.
+        let resolution = match device.dev_type {
+            #[cfg(target_os = "linux")]
+            ZCanDeviceType::ZCAN_USBCAN1 | ZCanDeviceType::ZCAN_USBCAN2 => Duration::from_micros(100),
+            _ => Duration::from_micros(1),
+        };
+        let mut clock = ClockSync::new(resolution);
+        clock.set_bits(Some(u32::BITS));
+        Self { device, channel, chl_hdl, clock: Arc::new(Mutex::new(clock)), filters: Default::default() }
     }
     #[inline]
     pub fn device_context(&self) -> &ZDeviceContext {
@@ -191,12 +203,29 @@ impl ZChannelContext {
     }
     #[inline]
     pub fn set_channel_handler(&mut self, handler: Option<u32>) {
-        self.timestamp = system_timestamp();
+        if let Ok(mut clock) = self.clock.lock() {
+            clock.reset();
+        }
         self.chl_hdl = handler;
     }
+    /// The filters matched in software, the hardware accepts the merged one only.
+    #[inline]
+    pub fn filters(&self) -> &[CanFilter] {
+        &self.filters
+    }
     #[inline]
-    pub fn timestamp(&self) -> u64 {
-        self.timestamp
+    pub fn set_filters(&mut self, filters: Vec<CanFilter>) {
+        self.filters = filters;
+    }
+    /// Synchronize the device clock of channel to host.
+    #[inline]
+    pub fn clock_sync<C, T>(&self, callback: C) -> Result<T, ZCanError>
+        where
+            C: FnOnce(&mut ClockSync) -> Result<T, ZCanError> {
+        match self.clock.lock() {
+            Ok(mut clock) => callback(&mut clock),
+            Err(e) => Err(ZCanError::Other(e.to_string())),
+        }
     }
 }
 
@@ -244,6 +273,10 @@ impl Handler {
         self.cans.get(&channel)
     }
     #[inline(always)]
+    pub fn find_can_mut(&mut self, channel: u8) -> Option<&mut ZChannelContext> {
+        self.cans.get_mut(&channel)
+    }
+    #[inline(always)]
     pub fn remove_can(&mut self, channel: u8) {
         self.cans.remove(&channel);
     }
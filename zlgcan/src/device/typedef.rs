@@ -181,6 +181,92 @@ impl TryFrom<u32> for ZCanDeviceType {
     }
 }
 
+impl std::str::FromStr for ZCanDeviceType {
+    type Err = ZCanError;
+    /// Parse the device type by name, the prefix `ZCAN_` is optional, such as `USBCANFD_200U`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.to_uppercase();
+        let name = if name.starts_with("ZCAN_") { name } else { format!("ZCAN_{}", name) };
+        match name.as_str() {
+            "ZCAN_PCI5121" => Ok(Self::ZCAN_PCI5121),
+            "ZCAN_PCI9810" => Ok(Self::ZCAN_PCI9810),
+            "ZCAN_USBCAN1" => Ok(Self::ZCAN_USBCAN1),
+            "ZCAN_USBCAN2" => Ok(Self::ZCAN_USBCAN2),
+            "ZCAN_PCI9820" => Ok(Self::ZCAN_PCI9820),
+            "ZCAN_CAN232" => Ok(Self::ZCAN_CAN232),
+            "ZCAN_PCI5110" => Ok(Self::ZCAN_PCI5110),
+            "ZCAN_CANLITE" => Ok(Self::ZCAN_CANLITE),
+            "ZCAN_ISA9620" => Ok(Self::ZCAN_ISA9620),
+            "ZCAN_ISA5420" => Ok(Self::ZCAN_ISA5420),
+            "ZCAN_PC104CAN" => Ok(Self::ZCAN_PC104CAN),
+            "ZCAN_CANETUDP" => Ok(Self::ZCAN_CANETUDP),
+            "ZCAN_DNP9810" => Ok(Self::ZCAN_DNP9810),
+            "ZCAN_PCI9840" => Ok(Self::ZCAN_PCI9840),
+            "ZCAN_PC104CAN2" => Ok(Self::ZCAN_PC104CAN2),
+            "ZCAN_PCI9820I" => Ok(Self::ZCAN_PCI9820I),
+            "ZCAN_CANETTCP" => Ok(Self::ZCAN_CANETTCP),
+            "ZCAN_PCIE_9220" => Ok(Self::ZCAN_PCIE_9220),
+            "ZCAN_PCI5010U" => Ok(Self::ZCAN_PCI5010U),
+            "ZCAN_USBCAN_E_U" => Ok(Self::ZCAN_USBCAN_E_U),
+            "ZCAN_USBCAN_2E_U" => Ok(Self::ZCAN_USBCAN_2E_U),
+            "ZCAN_PCI5020U" => Ok(Self::ZCAN_PCI5020U),
+            "ZCAN_EG20T_CAN" => Ok(Self::ZCAN_EG20T_CAN),
+            "ZCAN_PCIE9221" => Ok(Self::ZCAN_PCIE9221),
+            "ZCAN_WIFICAN_TCP" => Ok(Self::ZCAN_WIFICAN_TCP),
+            "ZCAN_WIFICAN_UDP" => Ok(Self::ZCAN_WIFICAN_UDP),
+            "ZCAN_PCIe9120" => Ok(Self::ZCAN_PCIe9120),
+            "ZCAN_PCIe9110" => Ok(Self::ZCAN_PCIe9110),
+            "ZCAN_PCIe9140" => Ok(Self::ZCAN_PCIe9140),
+            "ZCAN_USBCAN_4E_U" => Ok(Self::ZCAN_USBCAN_4E_U),
+            "ZCAN_CANDTU_200UR" => Ok(Self::ZCAN_CANDTU_200UR),
+            "ZCAN_CANDTU_MINI" => Ok(Self::ZCAN_CANDTU_MINI),
+            "ZCAN_USBCAN_8E_U" => Ok(Self::ZCAN_USBCAN_8E_U),
+            "ZCAN_CANREPLAY" => Ok(Self::ZCAN_CANREPLAY),
+            "ZCAN_CANDTU_NET" => Ok(Self::ZCAN_CANDTU_NET),
+            "ZCAN_CANDTU_100UR" => Ok(Self::ZCAN_CANDTU_100UR),
+            "ZCAN_PCIE_CANFD_100U" => Ok(Self::ZCAN_PCIE_CANFD_100U),
+            "ZCAN_PCIE_CANFD_200U" => Ok(Self::ZCAN_PCIE_CANFD_200U),
+            "ZCAN_PCIE_CANFD_400U" => Ok(Self::ZCAN_PCIE_CANFD_400U),
+            "ZCAN_USBCANFD_200U" => Ok(Self::ZCAN_USBCANFD_200U),
+            "ZCAN_USBCANFD_100U" => Ok(Self::ZCAN_USBCANFD_100U),
+            "ZCAN_USBCANFD_MINI" => Ok(Self::ZCAN_USBCANFD_MINI),
+            "ZCAN_CANFDCOM_100IE" => Ok(Self::ZCAN_CANFDCOM_100IE),
+            "ZCAN_CANSCOPE" => Ok(Self::ZCAN_CANSCOPE),
+            "ZCAN_CLOUD" => Ok(Self::ZCAN_CLOUD),
+            "ZCAN_CANDTU_NET_400" => Ok(Self::ZCAN_CANDTU_NET_400),
+            "ZCAN_CANFDNET_200U_TCP" => Ok(Self::ZCAN_CANFDNET_200U_TCP),
+            "ZCAN_CANFDNET_200U_UDP" => Ok(Self::ZCAN_CANFDNET_200U_UDP),
+            "ZCAN_CANFDWIFI_100U_TCP" => Ok(Self::ZCAN_CANFDWIFI_100U_TCP),
+            "ZCAN_CANFDWIFI_100U_UDP" => Ok(Self::ZCAN_CANFDWIFI_100U_UDP),
+            "ZCAN_CANFDNET_400U_TCP" => Ok(Self::ZCAN_CANFDNET_400U_TCP),
+            "ZCAN_CANFDNET_400U_UDP" => Ok(Self::ZCAN_CANFDNET_400U_UDP),
+            "ZCAN_CANFDBLUE_200U" => Ok(Self::ZCAN_CANFDBLUE_200U),
+            "ZCAN_CANFDNET_100U_TCP" => Ok(Self::ZCAN_CANFDNET_100U_TCP),
+            "ZCAN_CANFDNET_100U_UDP" => Ok(Self::ZCAN_CANFDNET_100U_UDP),
+            "ZCAN_CANFDNET_800U_TCP" => Ok(Self::ZCAN_CANFDNET_800U_TCP),
+            "ZCAN_CANFDNET_800U_UDP" => Ok(Self::ZCAN_CANFDNET_800U_UDP),
+            "ZCAN_USBCANFD_800U" => Ok(Self::ZCAN_USBCANFD_800U),
+            "ZCAN_PCIE_CANFD_100U_EX" => Ok(Self::ZCAN_PCIE_CANFD_100U_EX),
+            "ZCAN_PCIE_CANFD_400U_EX" => Ok(Self::ZCAN_PCIE_CANFD_400U_EX),
+            "ZCAN_PCIE_CANFD_200U_MINI" => Ok(Self::ZCAN_PCIE_CANFD_200U_MINI),
+            "ZCAN_PCIE_CANFD_200U_M2" => Ok(Self::ZCAN_PCIE_CANFD_200U_M2),
+            "ZCAN_CANFDDTU_400_TCP" => Ok(Self::ZCAN_CANFDDTU_400_TCP),
+            "ZCAN_CANFDDTU_400_UDP" => Ok(Self::ZCAN_CANFDDTU_400_UDP),
+            "ZCAN_CANFDWIFI_200U_TCP" => Ok(Self::ZCAN_CANFDWIFI_200U_TCP),
+            "ZCAN_CANFDWIFI_200U_UDP" => Ok(Self::ZCAN_CANFDWIFI_200U_UDP),
+            "ZCAN_CANFDDTU_800ER_TCP" => Ok(Self::ZCAN_CANFDDTU_800ER_TCP),
+            "ZCAN_CANFDDTU_800ER_UDP" => Ok(Self::ZCAN_CANFDDTU_800ER_UDP),
+            "ZCAN_CANFDDTU_800EWGR_TCP" => Ok(Self::ZCAN_CANFDDTU_800EWGR_TCP),
+            "ZCAN_CANFDDTU_800EWGR_UDP" => Ok(Self::ZCAN_CANFDDTU_800EWGR_UDP),
+            "ZCAN_CANFDDTU_600EWGR_TCP" => Ok(Self::ZCAN_CANFDDTU_600EWGR_TCP),
+            "ZCAN_CANFDDTU_600EWGR_UDP" => Ok(Self::ZCAN_CANFDDTU_600EWGR_UDP),
+            "ZCAN_OFFLINE_DEVICE" => Ok(Self::ZCAN_OFFLINE_DEVICE),
+            "ZCAN_VIRTUAL_DEVICE" => Ok(Self::ZCAN_VIRTUAL_DEVICE),
+            _ => Err(ZCanError::InvalidDeviceType),
+        }
+    }
+}
+
 impl std::fmt::Display for ZCanDeviceType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}", *self)
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 use dlopen2::symbor::{Container};
-use crate::can::{CanChlCfg, CanMessage, ZCanChlError, ZCanChlStatus, ZCanFdFrameV1, ZCanFdFrameV2, ZCanFrameType, ZCanFrameV1, ZCanFrameV2, ZCanFrameV3, ZCAN_VAR, ZCAN_ENV, ZCAN_PATH_DEFAULT};
+use crate::can::{CanChlCfg, CanChlCfgFactory, CanMessage, ZCanChlError, ZCanChlStatus, ZCanFdFrameV1, ZCanFdFrameV2, ZCanFrameType, ZCanFrameV1, ZCanFrameV2, ZCanFrameV3, ZCAN_VAR, ZCAN_ENV, ZCAN_PATH_DEFAULT};
 use crate::device::{DeriveInfo, Handler, ZCanDeviceType, ZChannelContext, ZDeviceContext, ZDeviceInfo};
 use crate::lin::{ZLinChlCfg, ZLinDataType, ZLinFrame, ZLinFrameDataUnion, ZLinPublish, ZLinSubscribe};
 use crate::TryFromIterator;
@@ -28,6 +28,8 @@ pub struct ZCanDriver {
     pub(crate) dev_type:          ZCanDeviceType,
     pub(crate) dev_idx:           u32,
     pub(crate) derive:            Option<DeriveInfo>,
+    /// The bitrate table loaded once and shared by channel initialization.
+    pub(crate) factory:           CanChlCfgFactory,
 }
 
 impl ZDevice for ZCanDriver {
@@ -55,6 +57,7 @@ impl ZDevice for ZCanDriver {
             dev_type,
             dev_idx,
             derive,
+            factory: CanChlCfgFactory::new()?,
         })
     }
 
@@ -185,7 +188,7 @@ impl ZDevice for ZCanDriver {
         self.derive.is_some()
     }
 
-    fn init_can_chl(&mut self, cfg: Vec<CanChlCfg>) -> Result<(), ZCanError> {
+    fn init_can_chl_indexed(&mut self, cfg: Vec<(u8, CanChlCfg)>) -> Result<(), ZCanError> {
         match &mut self.handler {
             Some(dev_hdl) => {
                 let dev_info = dev_hdl.device_info();
@@ -195,11 +198,11 @@ impl ZDevice for ZCanDriver {
                     return self.usbcan_4e_api.init_can_chl_ex(dev_hdl, channels, &cfg);
                 }
 
-                for (idx, cfg) in cfg.iter().enumerate() {
-                    let idx = idx as u8;
+                for (idx, cfg) in cfg.iter() {
+                    let idx = *idx;
                     if idx >= channels {
-                        log::warn!("ZLGCAN - the length of CAN channel configuration is out of channels!");
-                        break;
+                        log::warn!("ZLGCAN - the CAN channel: {} is out of channels!", idx);
+                        continue;
                     }
 
                     let mut context = ZChannelContext::new(dev_hdl.device_context().clone(), idx, None);
@@ -437,7 +440,7 @@ impl ZDevice for ZCanDriver {
                     })
                 })?;
 
-                Vec::try_from_iter(results, self.timestamp(channel)?)
+                self.clock_sync(channel, |clock| Vec::try_from_iter(results, clock))
             },
             ZCanDeviceType::ZCAN_USBCAN_4E_U => {
                 let results = self.can_handler(channel, |context| {
@@ -446,7 +449,7 @@ impl ZDevice for ZCanDriver {
                     })
                 })?;
 
-                Vec::try_from_iter(results, self.timestamp(channel)?)
+                self.clock_sync(channel, |clock| Vec::try_from_iter(results, clock))
             },
             ZCanDeviceType::ZCAN_USBCAN_8E_U => {
                 let results = self.can_handler(channel, |context| {
@@ -455,7 +458,7 @@ impl ZDevice for ZCanDriver {
                     })
                 })?;
 
-                Vec::try_from_iter(results, self.timestamp(channel)?)
+                self.clock_sync(channel, |clock| Vec::try_from_iter(results, clock))
             },
             ZCanDeviceType::ZCAN_USBCANFD_MINI
             | ZCanDeviceType::ZCAN_USBCANFD_100U
@@ -466,7 +469,7 @@ impl ZDevice for ZCanDriver {
                     })
                 })?;
 
-                Vec::try_from_iter(results, self.timestamp(channel)?)
+                self.clock_sync(channel, |clock| Vec::try_from_iter(results, clock))
             },
             ZCanDeviceType::ZCAN_USBCANFD_800U => {
                 let results = self.can_handler(channel, |context| {
@@ -475,7 +478,7 @@ impl ZDevice for ZCanDriver {
                     })
                 })?;
 
-                Vec::try_from_iter(results, self.timestamp(channel)?)
+                self.clock_sync(channel, |clock| Vec::try_from_iter(results, clock))
             },
             _ => Err(ZCanError::DeviceNotSupported),
         }
@@ -485,19 +488,19 @@ impl ZDevice for ZCanDriver {
         match self.dev_type {
             ZCanDeviceType::ZCAN_USBCAN1
             | ZCanDeviceType::ZCAN_USBCAN2 => {
-                let frames = Vec::try_from_iter(frames, self.timestamp(channel)?)?;
+                let frames = Vec::try_from_iter(frames, self.device_time(channel)?)?;
                 self.can_handler(channel, |context| {
                     self.usbcan_api.transmit_can(context, frames)
                 })
             },
             ZCanDeviceType::ZCAN_USBCAN_4E_U => {
-                let frames = Vec::try_from_iter(frames, self.timestamp(channel)?)?;
+                let frames = Vec::try_from_iter(frames, self.device_time(channel)?)?;
                 self.can_handler(channel, |context| {
                     self.usbcan_4e_api.transmit_can(context, frames)
                 })
             },
             ZCanDeviceType::ZCAN_USBCAN_8E_U => {
-                let frames = Vec::try_from_iter(frames, self.timestamp(channel)?)?;
+                let frames = Vec::try_from_iter(frames, self.device_time(channel)?)?;
                 self.can_handler(channel, |context| {
                     self.usbcan_8e_api.transmit_can(context, frames)
                 })
@@ -505,13 +508,13 @@ impl ZDevice for ZCanDriver {
             ZCanDeviceType::ZCAN_USBCANFD_MINI
             | ZCanDeviceType::ZCAN_USBCANFD_100U
             | ZCanDeviceType::ZCAN_USBCANFD_200U => {
-                let frames = Vec::try_from_iter(frames, self.timestamp(channel)?)?;
+                let frames = Vec::try_from_iter(frames, self.device_time(channel)?)?;
                 self.can_handler(channel, |context| {
                     self.usbcanfd_api.transmit_can(context, frames)
                 })
             },
             ZCanDeviceType::ZCAN_USBCANFD_800U => {
-                let frames = Vec::try_from_iter(frames, self.timestamp(channel)?)?;
+                let frames = Vec::try_from_iter(frames, self.device_time(channel)?)?;
                 self.can_handler(channel, |context| {
                     self.usbcanfd_800u_api.transmit_can(context, frames)
                 })
@@ -532,7 +535,7 @@ impl ZDevice for ZCanDriver {
                     })
                 })?;
 
-                Vec::try_from_iter(results, self.timestamp(channel)?)
+                self.clock_sync(channel, |clock| Vec::try_from_iter(results, clock))
             },
             ZCanDeviceType::ZCAN_USBCANFD_800U => {
                 let results = self.can_handler(channel, |context| {
@@ -541,7 +544,7 @@ impl ZDevice for ZCanDriver {
                     })
                 })?;
 
-                Vec::try_from_iter(results, self.timestamp(channel)?)
+                self.clock_sync(channel, |clock| Vec::try_from_iter(results, clock))
             },
             _ => Err(ZCanError::DeviceNotSupported),
         }
@@ -550,13 +553,13 @@ impl ZDevice for ZCanDriver {
     fn transmit_canfd(&self, channel: u8, frames: Vec<CanMessage>) -> Result<u32, ZCanError> {
         match self.dev_type {
             ZCanDeviceType::ZCAN_USBCANFD_MINI | ZCanDeviceType::ZCAN_USBCANFD_100U | ZCanDeviceType::ZCAN_USBCANFD_200U => {
-                let frames = Vec::try_from_iter(frames, self.timestamp(channel)?)?;
+                let frames = Vec::try_from_iter(frames, self.device_time(channel)?)?;
                 self.can_handler(channel, |context| {
                     self.usbcanfd_api.transmit_canfd(context, frames)
                 })
             },
             ZCanDeviceType::ZCAN_USBCANFD_800U => {
-                let frames = Vec::try_from_iter(frames, self.timestamp(channel)?)?;
+                let frames = Vec::try_from_iter(frames, self.device_time(channel)?)?;
                 self.can_handler(channel, |context| {
                     self.usbcanfd_800u_api.transmit_canfd(context, frames)
                 })
@@ -731,11 +734,6 @@ impl ZDevice for ZCanDriver {
         }
     }
 
-    #[inline]
-    fn timestamp(&self, channel: u8) -> Result<u64, ZCanError> {
-        self.can_handler(channel, |context| Ok(context.timestamp()))
-    }
-
     fn device_handler<C, T>(&self, callback: C) -> Result<T, ZCanError>
         where
             C: FnOnce(&Handler) -> Result<T, ZCanError> {
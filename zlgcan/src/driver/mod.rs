@@ -1,6 +1,9 @@
 use isotp_rs::can::frame::Frame;
 use isotp_rs::device::Driver;
-use crate::can::{CanChlCfg, CanMessage, ZCanChlError, ZCanChlStatus, ZCanFrameType};
+use std::time::{Duration, Instant};
+use rs_can::{clock_sync::ClockSync, error_frame::ErrorEvent, filter, utils::monotonic_timestamp, CanDevice, Capability, ChannelConfig};
+use rs_can::periodic::AutoSend;
+use crate::can::{CanChlCfg, CanMessage, ZCanChlError, ZCanChlErrorV2, ZCanChlStatus, ZCanFrameType};
 use crate::cloud::{ZCloudGpsFrame, ZCloudServerInfo, ZCloudUserData};
 use crate::device::{DeriveInfo, Handler, ZCanDeviceType, ZChannelContext, ZDeviceInfo};
 use crate::lin::{ZLinChlCfg, ZLinFrame, ZLinPublish, ZLinPublishEx, ZLinSubscribe};
@@ -16,6 +19,10 @@ mod linux;
 #[cfg(target_os = "linux")]
 pub use linux::ZCanDriver;
 
+/// The async ZLGCAN driver, the blocking calls run on dedicated threads.
+#[cfg(feature = "async")]
+pub type AsyncZCanDriver = rs_can::asyncio::AsyncCan<ZCanDriver>;
+
 impl Driver for ZCanDriver {
     type Error = ZCanError;
     type C = u8;
@@ -70,7 +77,10 @@ impl Driver for ZCanDriver {
             }
         }
 
-        Ok(results)
+        self.can_handler(channel, |context| {
+            results.retain(|msg| filter::is_matched(context.filters(), msg));
+            Ok(results)
+        })
     }
 
     #[inline]
@@ -79,6 +89,67 @@ impl Driver for ZCanDriver {
     }
 }
 
+impl CanDevice for ZCanDriver {
+    #[inline]
+    fn open_device(&mut self) -> Result<(), Self::Error> {
+        self.open()
+    }
+
+    fn init_channel(&mut self, channel: Self::C, cfg: &ChannelConfig) -> Result<(), Self::Error> {
+        let chl_cfg = self.factory.from_channel_config(self.device_type() as u32, cfg)?;
+        self.init_can_chl_indexed(vec![(channel, chl_cfg), ])?;
+
+        // the filters are merged into acceptance code and mask, and matched exactly when receiving
+        if let Some(context) = self.handler.as_mut().and_then(|hdl| hdl.find_can_mut(channel)) {
+            context.set_filters(cfg.filters.clone());
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    fn reset_channel(&mut self, channel: Self::C) -> Result<(), Self::Error> {
+        self.reset_can_chl(channel)
+    }
+
+    #[inline]
+    fn close_device(&mut self) -> Result<(), Self::Error> {
+        self.close();
+        Ok(())
+    }
+
+    fn capability(&self) -> Result<Capability, Self::Error> {
+        let dev_type = self.device_type();
+        let dev_info = self.device_info()?;
+        Ok(Capability {
+            channels: Some(dev_info.can_channels() as usize),
+            canfd: dev_type.canfd_support(),
+            resistance: dev_type.has_resistance(),
+            hardware_filter: true,
+            filter_count: Some(1),
+            // the cyclic transmission is only implemented by the windows library
+            auto_send: cfg!(target_os = "windows") && dev_type.auto_send_support(),
+            listen_only: true,
+        })
+    }
+}
+
+impl AutoSend for ZCanDriver {
+    fn start_auto_send(&self, index: u32, msg: &Self::F, period: Duration) -> Result<(), Self::Error> {
+        let interval = period.as_millis();
+        if interval == 0 || interval > u32::MAX as u128 {
+            return Err(ZCanError::ParamNotSupported);
+        }
+        let index = u16::try_from(index).map_err(|_| ZCanError::ParamNotSupported)?;
+        self.set_auto_send(msg.channel(), index, interval as u32, Some(msg.clone()))
+    }
+
+    fn stop_auto_send(&self, channel: Self::C, index: u32) -> Result<(), Self::Error> {
+        let index = u16::try_from(index).map_err(|_| ZCanError::ParamNotSupported)?;
+        self.set_auto_send(channel, index, 0, None)
+    }
+}
+
 #[allow(unused_variables)]
 pub trait ZDevice {
     fn new(dev_type: u32, dev_idx: u32, derive: Option<DeriveInfo>) -> Result<Self, ZCanError>
@@ -92,11 +163,31 @@ pub trait ZDevice {
     fn is_online(&self) -> Result<bool, ZCanError> {
         Err(ZCanError::MethodNotSupported)
     }
-    fn init_can_chl(&mut self, cfg: Vec<CanChlCfg>) -> Result<(), ZCanError>;
+    /// Initialize the channels by the index of configuration.
+    fn init_can_chl(&mut self, cfg: Vec<CanChlCfg>) -> Result<(), ZCanError> {
+        self.init_can_chl_indexed(
+            cfg.into_iter()
+                .enumerate()
+                .map(|(idx, cfg)| (idx as u8, cfg))
+                .collect()
+        )
+    }
+    /// Initialize the given channels only, the others are kept.
+    fn init_can_chl_indexed(&mut self, cfg: Vec<(u8, CanChlCfg)>) -> Result<(), ZCanError>;
     fn reset_can_chl(&mut self, channel: u8) -> Result<(), ZCanError>;
     // fn resistance_state(&self, dev_idx: u32, channel: u8) -> Result<(), ZCanError>;
     fn read_can_chl_status(&self, channel: u8) -> Result<ZCanChlStatus, ZCanError>;
     fn read_can_chl_error(&self, channel: u8) -> Result<ZCanChlError, ZCanError>;
+    /// The error event of the last error information, the timestamp is when it is read.
+    fn read_error_event(&self, channel: u8) -> Result<ErrorEvent<u8>, ZCanError> {
+        let error = ZCanChlErrorV2::from(&self.read_can_chl_error(channel)?);
+        <ErrorEvent<u8> as crate::TryFrom<ZCanChlErrorV2, (u8, u64)>>::try_from(error, (channel, monotonic_timestamp()))
+    }
+    /// The bus state event derived from the error counters of channel status.
+    fn read_status_event(&self, channel: u8) -> Result<ErrorEvent<u8>, ZCanError> {
+        let status = self.read_can_chl_status(channel)?;
+        <ErrorEvent<u8> as crate::TryFrom<ZCanChlStatus, (u8, u64)>>::try_from(status, (channel, monotonic_timestamp()))
+    }
     fn clear_can_buffer(&self, channel: u8) -> Result<(), ZCanError>;
     fn get_can_num(&self, channel: u8, can_type: ZCanFrameType) -> Result<u32, ZCanError>;
     fn receive_can(&self, channel: u8, size: u32, timeout: Option<u32>) -> Result<Vec<CanMessage>, ZCanError>;
@@ -107,6 +198,10 @@ pub trait ZDevice {
     fn transmit_canfd(&self, channel: u8, frames: Vec<CanMessage>) -> Result<u32, ZCanError> {
         Err(ZCanError::MethodNotSupported)
     }
+    /// Set the `index` slot of hardware cyclic transmission, the slot is disabled when `msg` is `None`.
+    fn set_auto_send(&self, channel: u8, index: u16, interval: u32, msg: Option<CanMessage>) -> Result<(), ZCanError> {
+        Err(ZCanError::MethodNotSupported)
+    }
     fn init_lin_chl(&mut self, cfg: Vec<ZLinChlCfg>) -> Result<(), ZCanError> {
         Err(ZCanError::MethodNotSupported)
     }
@@ -163,7 +258,6 @@ pub trait ZDevice {
     fn receive_gps(&self, size: u32, timeout: Option<u32>) -> Result<Vec<ZCloudGpsFrame>, ZCanError> {
         Err(ZCanError::MethodNotSupported)
     }
-    fn timestamp(&self, channel: u8) -> Result<u64, ZCanError>;
     fn device_handler<C, T>(&self, callback: C) -> Result<T, ZCanError>
         where
             C: FnOnce(&Handler) -> Result<T, ZCanError>;
@@ -179,6 +273,19 @@ pub trait ZDevice {
         })
     }
 
+    #[inline(always)]
+    fn clock_sync<C, T>(&self, channel: u8, callback: C) -> Result<T, ZCanError>
+        where
+            C: FnOnce(&mut ClockSync) -> Result<T, ZCanError> {
+        self.can_handler(channel, |context| context.clock_sync(callback))
+    }
+
+    /// The current device time(ticks) of channel, used by the frames to be transmitted.
+    #[inline(always)]
+    fn device_time(&self, channel: u8) -> Result<u64, ZCanError> {
+        self.clock_sync(channel, |clock| Ok(clock.to_device(Instant::now())))
+    }
+
     #[inline(always)]
     fn lin_handler<C, T>(&self, channel: u8, callback: C) -> Result<T, ZCanError>
         where
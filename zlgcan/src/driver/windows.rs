@@ -1,6 +1,8 @@
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use dlopen2::symbor::Container;
-use crate::can::{CanChlCfg, CanMessage, ZCanChlError, ZCanChlStatus, ZCanFdFrameV1, ZCanFdFrameV2, ZCanFrameType, ZCanFrameV1, ZCanFrameV2, ZCanFrameV3, ZCAN_VAR, ZCAN_ENV, ZCAN_PATH_DEFAULT};
+use isotp_rs::can::frame::Frame;
+use crate::can::{CanChlCfg, CanChlCfgFactory, CanMessage, ZCanAutoTransmitObj, ZCanChlError, ZCanChlStatus, ZCanFdFrameV1, ZCanFdFrameV2, ZCanFrameType, ZCanFrameV1, ZCanFrameV2, ZCanFrameV3, ZCAN_VAR, ZCAN_ENV, ZCAN_PATH_DEFAULT};
 use crate::cloud::{ZCloudGpsFrame, ZCloudServerInfo, ZCloudUserData};
 use crate::device::{DeriveInfo, Handler, ZCanDeviceType, ZChannelContext, ZDeviceContext, ZDeviceInfo};
 use crate::lin::{ZLinChlCfg, ZLinDataType, ZLinFrame, ZLinFrameDataUnion, ZLinPublish, ZLinPublishEx, ZLinSubscribe};
@@ -22,6 +24,10 @@ pub struct ZCanDriver {
     pub(crate) dev_type:   ZCanDeviceType,
     pub(crate) dev_idx:    u32,
     pub(crate) derive:     Option<DeriveInfo>,
+    /// The started auto-send slots of `(channel, index)`, the value is true for CAN-FD.
+    pub(crate) auto_send_slots: Arc<Mutex<HashMap<(u8, u16), bool>>>,
+    /// The bitrate table loaded once and shared by channel initialization.
+    pub(crate) factory:    CanChlCfgFactory,
 }
 
 impl ZDevice for ZCanDriver {
@@ -38,7 +44,8 @@ impl ZDevice for ZCanDriver {
                 .map_err(|_| ZCanError::LibraryLoadFailed(libpath))
         }?);
         let dev_type = ZCanDeviceType::try_from(dev_type)?;
-        Ok(Self { handler: Default::default(), api, dev_type, dev_idx, derive })
+        let factory = CanChlCfgFactory::new()?;
+        Ok(Self { handler: Default::default(), api, dev_type, dev_idx, derive, auto_send_slots: Default::default(), factory })
     }
 
     fn device_type(&self) -> ZCanDeviceType {
@@ -75,6 +82,9 @@ impl ZDevice for ZCanDriver {
             }
 
             self.api.close(handler.device_context()).unwrap_or_else(|e| log::warn!("{}", e));
+            if let Ok(mut v) = self.auto_send_slots.lock() {
+                v.clear();
+            }
             self.handler = None
         }
     }
@@ -96,16 +106,16 @@ impl ZDevice for ZCanDriver {
         })
     }
 
-    fn init_can_chl(&mut self, cfg: Vec<CanChlCfg>) -> Result<(), ZCanError> {
+    fn init_can_chl_indexed(&mut self, cfg: Vec<(u8, CanChlCfg)>) -> Result<(), ZCanError> {
         match &mut self.handler {
             Some(dev_hdl) => {
                 let dev_info = dev_hdl.device_info();
                 let channels = dev_info.can_channels();
-                for (idx, cfg) in cfg.iter().enumerate() {
-                    let idx = idx as u8;
+                for (idx, cfg) in cfg.iter() {
+                    let idx = *idx;
                     if idx >= channels {
-                        log::warn!("ZLGCAN - the length of CAN channel configuration is out of channels!");
-                        break;
+                        log::warn!("ZLGCAN - the CAN channel: {} is out of channels!", idx);
+                        continue;
                     }
 
                     if let Some(v) = dev_hdl.find_can(idx) {
@@ -172,11 +182,11 @@ impl ZDevice for ZCanDriver {
             })
         })?;
 
-        Vec::try_from_iter(frames, self.timestamp(channel)?)
+        self.clock_sync(channel, |clock| Vec::try_from_iter(frames, clock))
     }
 
     fn transmit_can(&self, channel: u8, frames: Vec<CanMessage>) -> Result<u32, ZCanError> {
-        let frames = Vec::try_from_iter(frames, self.timestamp(channel)?)?;
+        let frames = Vec::try_from_iter(frames, self.device_time(channel)?)?;
         self.can_handler(channel, |context| {
             self.api.transmit_can(context, frames)
         })
@@ -190,16 +200,56 @@ impl ZDevice for ZCanDriver {
             })
         })?;
 
-        Vec::try_from_iter(frames, self.timestamp(channel)?)
+        self.clock_sync(channel, |clock| Vec::try_from_iter(frames, clock))
     }
 
     fn transmit_canfd(&self, channel: u8, frames: Vec<CanMessage>) -> Result<u32, ZCanError> {
-        let frames = Vec::try_from_iter(frames, self.timestamp(channel)?)?;
+        let frames = Vec::try_from_iter(frames, self.device_time(channel)?)?;
         self.can_handler(channel, |context| {
             self.api.transmit_canfd(context, frames)
         })
     }
 
+    fn set_auto_send(&self, channel: u8, index: u16, interval: u32, msg: Option<CanMessage>) -> Result<(), ZCanError> {
+        if !self.dev_type.auto_send_support() {
+            return Err(ZCanError::MethodNotSupported);
+        }
+        let timestamp = self.device_time(channel)?;
+        let mut slots = self.auto_send_slots.lock()
+            .map_err(|e| ZCanError::Other(e.to_string()))?;
+        self.can_handler(channel, |context| {
+            // the slot is stopped by the API(CAN or CAN-FD) it was started with
+            let stop = |is_fd: bool| if is_fd {
+                self.api.set_auto_send_canfd(context, &ZCanAutoTransmitObj::new(index, 0, Default::default()))
+            }
+            else {
+                self.api.set_auto_send(context, &ZCanAutoTransmitObj::new(index, 0, Default::default()))
+            };
+            let previous = slots.remove(&(channel, index));
+            match msg {
+                Some(msg) => {
+                    let is_fd = msg.is_can_fd();
+                    if let Some(v) = previous.filter(|v| *v != is_fd) {
+                        stop(v)?;
+                    }
+                    if is_fd {
+                        let frame = <ZCanFdFrameV2 as crate::TryFrom<CanMessage, u64>>::try_from(msg, timestamp)?;
+                        self.api.set_auto_send_canfd(context, &ZCanAutoTransmitObj::new(index, interval, frame))?;
+                    }
+                    else {
+                        let frame = <ZCanFrameV3 as crate::TryFrom<CanMessage, u64>>::try_from(msg, timestamp)?;
+                        self.api.set_auto_send(context, &ZCanAutoTransmitObj::new(index, interval, frame))?;
+                    }
+                    if interval > 0 {
+                        slots.insert((channel, index), is_fd);
+                    }
+                },
+                None => stop(previous.unwrap_or_default())?,
+            }
+            self.api.apply_auto_send(context)
+        })
+    }
+
     fn init_lin_chl(&mut self, cfg: Vec<ZLinChlCfg>) -> Result<(), ZCanError> {
         if !self.dev_type.lin_support() {
             return Err(ZCanError::MethodNotSupported);
@@ -383,11 +433,6 @@ impl ZDevice for ZCanDriver {
         })
     }
 
-    #[inline]
-    fn timestamp(&self, channel: u8) -> Result<u64, ZCanError> {
-        self.can_handler(channel, |context| Ok(context.timestamp()))
-    }
-
     fn device_handler<C, T>(&self, callback: C) -> Result<T, ZCanError>
         where
             C: FnOnce(&Handler) -> Result<T, ZCanError> {
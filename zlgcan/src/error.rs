@@ -33,3 +33,16 @@ pub enum ZCanError {
     #[error("ZLGCAN - No message received!")]
     NoMessageReceived,
 }
+
+impl From<ZCanError> for rs_can::error::CanError {
+    fn from(value: ZCanError) -> Self {
+        match value {
+            ZCanError::DeviceNotSupported
+            | ZCanError::MethodNotSupported => Self::DeviceNotSupported,
+            ZCanError::DeviceNotOpened => Self::DeviceOpenFailed,
+            ZCanError::ConfigurationError(_) => Self::DeviceConfigFailed,
+            ZCanError::MessageConvertFailed => Self::FrameConvertFailed(value.to_string()),
+            _ => Self::OperationError(value.to_string()),
+        }
+    }
+}
@@ -0,0 +1,39 @@
+//! Register `zlgcan://<device type>/<device index>?channel=0,1&bitrate=500000&dbitrate=2000000`
+//! to the factory of `rs-can`, the device type is the name of [`ZCanDeviceType`] such as `USBCANFD_200U`.
+
+use std::str::FromStr;
+use rs_can::CanDevice;
+use rs_can::error::CanError;
+use rs_can::factory::{self, AnyBus, BusAdapter, BusUrl};
+use rs_can::filter::FilteredDevice;
+use crate::device::ZCanDeviceType;
+use crate::driver::{ZCanDriver, ZDevice};
+
+pub const SCHEME: &str = "zlgcan";
+
+/// Register the ZLGCAN backend to factory.
+#[inline]
+pub fn register() {
+    factory::register(SCHEME, open);
+}
+
+/// Open the device and initialize the channels by descriptor.
+pub fn open(url: &BusUrl) -> Result<AnyBus, CanError> {
+    let dev_type = ZCanDeviceType::from_str(url.host.as_str())?;
+    let dev_idx = match url.path.first() {
+        Some(v) => v.parse::<u32>()
+            .map_err(|_| CanError::OtherError(format!("invalid device index: `{}`", v)))?,
+        None => 0,
+    };
+    // the filters are pushed down to acceptance code, and the rest are applied in software.
+    let mut device = FilteredDevice::new(ZCanDriver::new(dev_type as u32, dev_idx, None)?);
+    device.open_device()?;
+    for channel in url.channels("0") {
+        let cfg = url.channel_config_of(&channel)?;
+        let channel = channel.parse::<u8>()
+            .map_err(|_| CanError::ChannelNotOpened(channel))?;
+        device.init_channel(channel, &cfg)?;
+    }
+
+    Ok(Box::new(BusAdapter(device)))
+}
@@ -9,6 +9,7 @@
 //! `device` module defined the struct for device.
 //! `lin` module defined the LIN struct.
 //! The `error.rs` defined the only error struct.
+//! The `factory.rs` registered the device to the bus factory of `rs-can`.
 //! The `util.rs` defined utility functions.
 pub mod can;
 pub mod cloud;
@@ -16,6 +17,7 @@ pub mod device;
 pub mod driver;
 pub mod error;
 // pub mod extends;
+pub mod factory;
 pub mod lin;
 pub mod utils;
 
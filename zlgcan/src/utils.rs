@@ -1,5 +1,4 @@
 use std::ffi::{c_char, CStr};
-use rs_can::utils::system_timestamp;
 use crate::error::ZCanError;
 
 #[inline]
@@ -14,14 +13,3 @@ pub fn c_str_to_string(src: *const c_char) -> Result<String, ZCanError> {
         Ok(value)
     }
 }
-
-#[inline]
-pub fn fix_system_time(frame_timestamp: u64, fix_timestamp: u64) -> u64 {
-    frame_timestamp + fix_timestamp
-}
-
-#[inline]
-pub fn fix_device_time(fix_timestamp: u64) -> u64 {
-    system_timestamp() - fix_timestamp
-}
-